@@ -26,7 +26,6 @@ fn encode_path_segment(s: &str) -> String {
     utf8_percent_encode(s, PATH_SEGMENT_ENCODE_SET).to_string()
 }
 
-
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Reqwest error: {0}")]
@@ -52,6 +51,9 @@ pub struct ClientConfig {
     pub base_url: String,
     pub tenant_id: String,
     pub tenant_role: String,
+    /// Signed bearer token (JWT). Required by deployed workers; the
+    /// `x-tenant-*` headers are only honoured by local dev workers.
+    pub bearer_token: Option<String>,
     pub cf_client_id: Option<String>,
     pub cf_client_secret: Option<String>,
 }
@@ -75,6 +77,10 @@ impl ClientConfig {
         let tenant_role =
             std::env::var("DATA_FABRIC_TENANT_ROLE").unwrap_or_else(|_| "builder".to_string());
 
+        let bearer_token = read_secret_file("/var/run/secrets/data-fabric/token")
+            .or_else(|| std::env::var("DATA_FABRIC_TOKEN").ok())
+            .filter(|s| !s.trim().is_empty());
+
         // Read from files first
         let mut cf_client_id = read_secret_file("/var/run/secrets/data-fabric/client-id");
         let mut cf_client_secret = read_secret_file("/var/run/secrets/data-fabric/client-secret");
@@ -91,6 +97,7 @@ impl ClientConfig {
             base_url,
             tenant_id,
             tenant_role,
+            bearer_token,
             cf_client_id,
             cf_client_secret,
         })
@@ -123,6 +130,9 @@ impl Client {
             .header("x-tenant-id", &self.config.tenant_id)
             .header("x-tenant-role", &self.config.tenant_role);
 
+        if let Some(ref token) = self.config.bearer_token {
+            req = req.bearer_auth(token);
+        }
         if let Some(ref client_id) = self.config.cf_client_id {
             req = req.header("CF-Access-Client-Id", client_id);
         }
@@ -240,7 +250,9 @@ impl Client {
     ///
     /// * `POST /mcp/task/next` — the operation is state-mutating (transfers
     ///   ownership of a task), so it must use POST. GET now returns 405.
-    /// * `agent_id` is a required query parameter.
+    /// * `agent_id` is a required query parameter. It must be the token's
+    ///   `sub`, or an agent that subject registered; anything else is 403
+    ///   `AGENT_NOT_BOUND`.
     /// * `cap` is an optional comma-separated query parameter.
     /// * No request body — inputs come exclusively from the query string.
     ///
@@ -252,6 +264,18 @@ impl Client {
         &self,
         agent_id: &str,
         capabilities: &[String],
+    ) -> Result<reqwest::Request> {
+        self.build_claim_next_task_wait_request(agent_id, capabilities, 0)
+    }
+
+    /// Long-poll variant of [`Client::build_claim_next_task_request`]:
+    /// `wait_secs > 0` adds `wait=<secs>`, asking the server to hold the
+    /// claim open until a matching task arrives (capped server-side at 30s).
+    pub fn build_claim_next_task_wait_request(
+        &self,
+        agent_id: &str,
+        capabilities: &[String],
+        wait_secs: u64,
     ) -> Result<reqwest::Request> {
         let mut builder = self
             .prepare_request(Method::POST, "/mcp/task/next")
@@ -260,6 +284,9 @@ impl Client {
             let caps = capabilities.join(",");
             builder = builder.query(&[("cap", caps.as_str())]);
         }
+        if wait_secs > 0 {
+            builder = builder.query(&[("wait", wait_secs)]);
+        }
         builder.build().map_err(Error::from)
     }
 
@@ -268,7 +295,18 @@ impl Client {
         agent_id: &str,
         capabilities: &[String],
     ) -> Result<Option<AgentTask>> {
-        let req = self.build_claim_next_task_request(agent_id, capabilities)?;
+        self.claim_next_task_wait(agent_id, capabilities, 0).await
+    }
+
+    /// Claim the next task, waiting up to `wait_secs` for one to be
+    /// enqueued. Returns `None` if the wait elapses with nothing to claim.
+    pub async fn claim_next_task_wait(
+        &self,
+        agent_id: &str,
+        capabilities: &[String],
+        wait_secs: u64,
+    ) -> Result<Option<AgentTask>> {
+        let req = self.build_claim_next_task_wait_request(agent_id, capabilities, wait_secs)?;
         let resp = self.http.execute(req).await?;
         if resp.status() == StatusCode::NO_CONTENT {
             return Ok(None);
@@ -277,7 +315,7 @@ impl Client {
         Ok(Some(task))
     }
 
-    pub async fn heartbeat_task(&self, task_id: &str, agent_id: &str) -> Result<serde_json::Value> {
+    pub async fn heartbeat_task(&self, task_id: &str, agent_id: &str) -> Result<TaskHeartbeatAck> {
         let req = self.build_heartbeat_task_request(task_id, agent_id)?;
         let resp = self.http.execute(req).await?;
         self.handle_response(resp).await
@@ -320,6 +358,169 @@ impl Client {
         self.send_request(Method::POST, &path, Some(req)).await
     }
 
+    /// Snapshot of the tenant's task queue: depth, leases, rejections and
+    /// stuck play notifications.
+    pub async fn get_task_queue(&self) -> Result<QueueSnapshot> {
+        self.send_request::<(), QueueSnapshot>(Method::GET, "/v1/tasks/queue", None)
+            .await
+    }
+
+    pub async fn list_dead_letter_tasks(
+        &self,
+        job_id: Option<&str>,
+        cursor: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<DeadLetterList> {
+        let req = self.build_list_dead_letter_tasks_request(job_id, cursor, limit)?;
+        let resp = self.http.execute(req).await?;
+        self.handle_response(resp).await
+    }
+
+    /// Build the HTTP request used by [`Client::list_dead_letter_tasks`].
+    /// Filters go through `.query()` so they are percent-encoded.
+    pub fn build_list_dead_letter_tasks_request(
+        &self,
+        job_id: Option<&str>,
+        cursor: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<reqwest::Request> {
+        let mut query: Vec<(&str, String)> = Vec::new();
+        if let Some(job_id) = job_id {
+            query.push(("job_id", job_id.to_string()));
+        }
+        if let Some(cursor) = cursor {
+            query.push(("cursor", cursor.to_string()));
+        }
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+        self.prepare_request(Method::GET, "/v1/tasks/dead-letter")
+            .query(&query)
+            .build()
+            .map_err(Error::from)
+    }
+
+    /// Put a dead-lettered task back on the queue with a fresh retry budget.
+    pub async fn requeue_dead_letter_task(&self, task_id: &str) -> Result<AgentTask> {
+        let path = format!(
+            "/v1/tasks/dead-letter/{}/requeue",
+            encode_path_segment(task_id)
+        );
+        self.send_request::<(), AgentTask>(Method::POST, &path, None)
+            .await
+    }
+
+    /// Drop a dead-lettered task for good.
+    pub async fn discard_dead_letter_task(&self, task_id: &str) -> Result<DeadLetterTask> {
+        let path = format!(
+            "/v1/tasks/dead-letter/{}/discard",
+            encode_path_segment(task_id)
+        );
+        self.send_request::<(), DeadLetterTask>(Method::POST, &path, None)
+            .await
+    }
+
+    /// Cancel a queued or running task. A running task reports
+    /// `cancel_requested` until its agent's next heartbeat.
+    pub async fn cancel_task(&self, task_id: &str) -> Result<TaskCancelled> {
+        let path = format!("/v1/tasks/{}/cancel", encode_path_segment(task_id));
+        self.send_request::<(), TaskCancelled>(Method::POST, &path, None)
+            .await
+    }
+
+    /// Cancel a run and every task enqueued under it.
+    pub async fn cancel_run(&self, run_id: &str) -> Result<RunCancelled> {
+        let path = format!("/v1/runs/{}/cancel", encode_path_segment(run_id));
+        self.send_request::<(), RunCancelled>(Method::POST, &path, None)
+            .await
+    }
+
+    // ── Task Schedules ─────────────────────────────────────────────────────
+
+    pub async fn create_task_schedule(&self, req: &CreateTaskSchedule) -> Result<TaskSchedule> {
+        self.send_request(Method::POST, "/v1/task-schedules", Some(req))
+            .await
+    }
+
+    pub async fn list_task_schedules(&self) -> Result<TaskScheduleList> {
+        self.send_request::<(), TaskScheduleList>(Method::GET, "/v1/task-schedules", None)
+            .await
+    }
+
+    pub async fn delete_task_schedule(&self, id: &str) -> Result<serde_json::Value> {
+        let path = format!("/v1/task-schedules/{}", encode_path_segment(id));
+        self.send_request::<(), serde_json::Value>(Method::DELETE, &path, None)
+            .await
+    }
+
+    // ── Plays ──────────────────────────────────────────────────────────────
+
+    /// Per-task state of a play run.
+    pub async fn get_play_run(&self, run_id: &str) -> Result<PlayRunStatus> {
+        let path = format!("/v1/plays/runs/{}", encode_path_segment(run_id));
+        self.send_request::<(), PlayRunStatus>(Method::GET, &path, None)
+            .await
+    }
+
+    /// A play run's DAG rendered server-side; `format` is `dot` or
+    /// `mermaid`.
+    pub async fn get_play_run_graph(&self, run_id: &str, format: &str) -> Result<String> {
+        let req = self.build_get_play_run_graph_request(run_id, format)?;
+        let resp = self.http.execute(req).await?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(Error::Api {
+                status,
+                message: resp.text().await.unwrap_or_default(),
+            });
+        }
+        Ok(resp.text().await?)
+    }
+
+    /// Build the HTTP request used by [`Client::get_play_run_graph`].
+    pub fn build_get_play_run_graph_request(
+        &self,
+        run_id: &str,
+        format: &str,
+    ) -> Result<reqwest::Request> {
+        let path = format!("/v1/plays/runs/{}", encode_path_segment(run_id));
+        self.prepare_request(Method::GET, &path)
+            .query(&[("format", format)])
+            .build()
+            .map_err(Error::from)
+    }
+
+    /// Result a play task returned, as recorded by its PlayManager.
+    pub async fn get_play_task_result(
+        &self,
+        run_id: &str,
+        task_id: &str,
+    ) -> Result<serde_json::Value> {
+        let path = format!(
+            "/v1/plays/runs/{}/tasks/{}/result",
+            encode_path_segment(run_id),
+            encode_path_segment(task_id)
+        );
+        self.send_request::<(), serde_json::Value>(Method::GET, &path, None)
+            .await
+    }
+
+    /// Approve, request changes on, or reject an approval task of a play
+    /// run.
+    pub async fn decide_play_approval(
+        &self,
+        run_id: &str,
+        task_id: &str,
+        request: &PlayApprovalRequest,
+    ) -> Result<PlayApprovalDecision> {
+        let path = format!(
+            "/v1/plays/runs/{}/approvals/{}",
+            encode_path_segment(run_id),
+            encode_path_segment(task_id)
+        );
+        self.send_request(Method::POST, &path, Some(request)).await
+    }
+
     // ── Agents ─────────────────────────────────────────────────────────────
 
     pub async fn register_agent(&self, agent: &RegisterAgent) -> Result<serde_json::Value> {
@@ -363,6 +564,46 @@ impl Client {
             .await
     }
 
+    /// Start a new thread from checkpoint `id`, e.g. to rewind and try a
+    /// different plan.
+    pub async fn fork_checkpoint(
+        &self,
+        id: &str,
+        fork: &ForkCheckpoint,
+    ) -> Result<CheckpointForked> {
+        let path = format!("/v1/checkpoints/{}/fork", encode_path_segment(id));
+        self.send_request(Method::POST, &path, Some(fork)).await
+    }
+
+    /// Every checkpoint of a thread with its parent/child edges and the
+    /// threads forked from it.
+    pub async fn get_checkpoint_tree(&self, thread_id: &str) -> Result<CheckpointTree> {
+        let path = format!(
+            "/v1/checkpoints/threads/{}/tree",
+            encode_path_segment(thread_id)
+        );
+        self.send_request::<(), CheckpointTree>(Method::GET, &path, None)
+            .await
+    }
+
+    /// JSON Patch from the state of checkpoint `from` to that of `to`.
+    pub async fn diff_checkpoints(&self, from: &str, to: &str) -> Result<CheckpointDiff> {
+        let path = format!(
+            "/v1/checkpoints/{}/diff/{}",
+            encode_path_segment(from),
+            encode_path_segment(to)
+        );
+        self.send_request::<(), CheckpointDiff>(Method::GET, &path, None)
+            .await
+    }
+
+    /// The checkpoints leading to `id`, root first.
+    pub async fn get_checkpoint_lineage(&self, id: &str) -> Result<CheckpointLineage> {
+        let path = format!("/v1/checkpoints/{}/lineage", encode_path_segment(id));
+        self.send_request::<(), CheckpointLineage>(Method::GET, &path, None)
+            .await
+    }
+
     // ── Artifacts ──────────────────────────────────────────────────────────
 
     pub async fn put_artifact(&self, key: &str, data: Vec<u8>) -> Result<ArtifactStoredResponse> {
@@ -478,6 +719,7 @@ mod tests {
             base_url: "https://df.example".to_string(),
             tenant_id: "t-1".to_string(),
             tenant_role: "builder".to_string(),
+            bearer_token: None,
             cf_client_id: None,
             cf_client_secret: None,
         })
@@ -524,6 +766,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn claim_next_task_wait_adds_wait_seconds() {
+        let client = test_client();
+        let req = client
+            .build_claim_next_task_wait_request("agent-1", &[], 20)
+            .expect("request must build");
+        let wait = req
+            .url()
+            .query_pairs()
+            .find(|(k, _)| k == "wait")
+            .map(|(_, v)| v.into_owned());
+        assert_eq!(wait.as_deref(), Some("20"));
+
+        let req = client
+            .build_claim_next_task_request("agent-1", &[])
+            .expect("request must build");
+        assert!(!req.url().query().unwrap_or("").contains("wait="));
+    }
+
     /// `cap` is optional per the OpenAPI spec — omit it entirely when no
     /// capabilities are supplied (rather than sending `cap=`), so the
     /// server-side parser doesn't see an empty capability set as a single
@@ -628,5 +889,45 @@ mod tests {
             .expect("agent_id must be present");
         assert_eq!(agent_id_value, "agent&id=evil");
     }
-}
 
+    #[test]
+    fn get_play_run_graph_encodes_run_id_and_format() {
+        let client = test_client();
+        let req = client
+            .build_get_play_run_graph_request("run/1", "mermaid")
+            .expect("request must build");
+        let url = req.url();
+        assert_eq!(req.method(), &Method::GET);
+        assert_eq!(url.path(), "/v1/plays/runs/run%2F1");
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert_eq!(pairs, vec![("format".to_string(), "mermaid".to_string())]);
+    }
+
+    #[test]
+    fn list_dead_letter_tasks_encodes_filters_in_query() {
+        let client = test_client();
+        let req = client
+            .build_list_dead_letter_tasks_request(Some("job&limit=999"), None, Some(20))
+            .expect("request must build");
+        let url = req.url();
+        assert_eq!(req.method(), &Method::GET);
+        assert_eq!(url.path(), "/v1/tasks/dead-letter");
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("job_id".to_string(), "job&limit=999".to_string()),
+                ("limit".to_string(), "20".to_string()),
+            ]
+        );
+    }
+
+    /// Servers that predate cancellation reply `{"ok": true}`; that must
+    /// still parse, as "keep going".
+    #[test]
+    fn heartbeat_ack_defaults_cancel_to_false() {
+        let ack: TaskHeartbeatAck = serde_json::from_str(r#"{"ok":true}"#).unwrap();
+        assert!(ack.ok);
+        assert!(!ack.cancel);
+    }
+}
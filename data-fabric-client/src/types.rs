@@ -104,6 +104,17 @@ pub struct CreateAgentTask {
     pub play_id: Option<String>,
     pub parent_task_id: Option<String>,
     pub max_retries: Option<i32>,
+    /// RFC 3339 timestamp before which the task cannot be claimed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<String>,
+    /// Capability requirements the claiming agent must meet, e.g.
+    /// `rust>=1.80`, `repo:stevedores-org/*` or `agent:builder-7`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// Resubmitting with the same key within the server's window returns
+    /// the original task (`TaskCreated::duplicate`) instead of a new one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -126,12 +137,119 @@ pub struct AgentTask {
     pub completed_at: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub memory_context: Option<String>,
+    #[serde(default)]
+    pub failures: Vec<TaskAttemptFailure>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<String>,
+    #[serde(default)]
+    pub cancel_requested: bool,
+    #[serde(default)]
+    pub requires: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// Per-attempt time limit in millis. Unlike the lease, heartbeats do not
+    /// extend it; a timed-out attempt fails like an expired lease.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Unix millis at which the current attempt times out, set on claim
+    /// from `timeout_ms`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskAttemptFailure {
+    pub attempt: i32,
+    pub agent_id: Option<String>,
+    pub error: String,
+    pub lease_expires_at: Option<String>,
+    pub failed_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeadLetterTask {
+    pub task: AgentTask,
+    pub dead_lettered_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeadLetterList {
+    pub tasks: Vec<DeadLetterTask>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueueSnapshot {
+    pub pending_total: usize,
+    pub scheduled_total: usize,
+    pub max_pending: usize,
+    pub depth: Vec<QueueDepth>,
+    pub oldest_pending_age_ms: Option<u64>,
+    pub active: Vec<ActiveLease>,
+    pub rejected_total: u64,
+    pub dead_letter_total: usize,
+    pub pending_notifies: Vec<PendingNotifyInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueueDepth {
+    pub task_type: String,
+    pub priority: i32,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActiveLease {
+    pub task_id: String,
+    pub job_id: String,
+    pub task_type: String,
+    pub agent_id: Option<String>,
+    pub lease_expires_at: Option<String>,
+    #[serde(default)]
+    pub cancel_requested: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PendingNotifyInfo {
+    pub target_name: String,
+    pub play_task_id: String,
+    pub attempts: u32,
+    pub next_attempt_at_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CreateTaskSchedule {
+    pub name: String,
+    /// Five-field cron expression, evaluated in UTC.
+    pub cron: String,
+    pub task: CreateAgentTask,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskSchedule {
+    pub id: String,
+    pub name: String,
+    pub cron: String,
+    pub task: CreateAgentTask,
+    pub next_run_at: String,
+    pub last_run_at: Option<String>,
+    pub last_task_id: Option<String>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskScheduleList {
+    pub schedules: Vec<TaskSchedule>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TaskCreated {
     pub id: String,
     pub status: String,
+    #[serde(default)]
+    pub duplicate: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -144,6 +262,64 @@ pub struct TaskFailRequest {
     pub error: String,
 }
 
+/// Heartbeat response. `cancel` means the task was cancelled and the agent
+/// should stop working on it; its lease is already released.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskHeartbeatAck {
+    pub ok: bool,
+    #[serde(default)]
+    pub cancel: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskCancelled {
+    pub id: String,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunCancelled {
+    pub run_id: String,
+    pub status: String,
+    pub cancelled_tasks: Vec<String>,
+    pub cancel_requested_tasks: Vec<String>,
+}
+
+/// Agent → server message on the `/mcp/task/ws` task socket.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskSocketClientMessage {
+    Heartbeat {
+        task_id: String,
+    },
+    Complete {
+        task_id: String,
+        #[serde(default)]
+        result: Option<serde_json::Value>,
+    },
+    Fail {
+        task_id: String,
+        error: String,
+    },
+}
+
+/// Server → agent message on the `/mcp/task/ws` task socket.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskSocketServerMessage {
+    Task {
+        task: Box<AgentTask>,
+    },
+    Ack {
+        task_id: String,
+        status: String,
+    },
+    Error {
+        task_id: Option<String>,
+        message: String,
+    },
+}
+
 // Agent registration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RegisterAgent {
@@ -193,6 +369,92 @@ pub struct CheckpointCreated {
     pub state_r2_key: String,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ForkCheckpoint {
+    /// Thread to create; generated by the server when absent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointForked {
+    pub id: String,
+    pub thread_id: String,
+    pub state_r2_key: String,
+    pub forked_from: CheckpointBranch,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointBranch {
+    pub thread_id: String,
+    pub checkpoint_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointTree {
+    pub thread_id: String,
+    pub roots: Vec<String>,
+    pub nodes: Vec<CheckpointTreeNode>,
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointTreeNode {
+    #[serde(flatten)]
+    pub checkpoint: Checkpoint,
+    #[serde(default)]
+    pub children: Vec<String>,
+    #[serde(default)]
+    pub forks: Vec<CheckpointBranch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointLineage {
+    pub checkpoint_id: String,
+    /// Root first, ending with the checkpoint itself.
+    pub path: Vec<Checkpoint>,
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointDiff {
+    pub from: String,
+    pub to: String,
+    /// RFC 6902 operations that turn `from`'s state into `to`'s.
+    pub patch: Vec<JsonPatchOp>,
+    pub summary: CheckpointDiffSummary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOp {
+    Add {
+        path: String,
+        value: serde_json::Value,
+    },
+    Remove {
+        path: String,
+    },
+    Replace {
+        path: String,
+        value: serde_json::Value,
+    },
+}
+
+/// JSON pointers of the keys and array items a diff touched.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointDiffSummary {
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub changed: Vec<String>,
+}
+
 // Artifact Response
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ArtifactStoredResponse {
@@ -443,3 +705,94 @@ pub struct GraphEventAck {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duration_ms: Option<f64>,
 }
+
+// Play run status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl PlayStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlayStatus::Running => "running",
+            PlayStatus::Succeeded => "succeeded",
+            PlayStatus::Failed => "failed",
+            PlayStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayRunStatus {
+    pub run_id: String,
+    pub play: String,
+    pub status: PlayStatus,
+    #[serde(default)]
+    pub failed_task: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    pub tasks: Vec<PlayRunTask>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayRunTask {
+    pub id: String,
+    pub task_type: String,
+    /// `pending`, `running`, `awaiting_approval`, `completed`, `failed`,
+    /// `skipped` or `cancelled`.
+    pub state: String,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub on_failure: Option<String>,
+    #[serde(default)]
+    pub launched_at: Option<String>,
+    #[serde(default)]
+    pub finished_at: Option<String>,
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub result_ref: Option<String>,
+    #[serde(default)]
+    pub approval: Option<PlayApprovalState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayApprovalState {
+    pub requested_at: String,
+    #[serde(default)]
+    pub deadline: Option<String>,
+    #[serde(default)]
+    pub escalation_id: Option<String>,
+    #[serde(default)]
+    pub params: Option<serde_json::Value>,
+    #[serde(default)]
+    pub decision: Option<PlayApprovalDecision>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayApprovalDecision {
+    pub id: String,
+    pub actor: String,
+    /// `approve`, `request_changes` or `reject`.
+    pub decision: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub decided_at: String,
+}
+
+/// The deciding actor is `human:<sub>` from the caller's token, not a field.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayApprovalRequest {
+    /// `approve`, `request_changes` or `reject`.
+    pub decision: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
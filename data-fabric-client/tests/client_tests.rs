@@ -18,6 +18,7 @@ async fn test_client_headers_and_response() {
         // Assert client headers
         assert!(request_str.contains("x-tenant-id: test-tenant"));
         assert!(request_str.contains("x-tenant-role: builder"));
+        assert!(request_str.contains("authorization: Bearer header.payload.sig"));
         assert!(request_str.contains("cf-access-client-id: id-123"));
         assert!(request_str.contains("cf-access-client-secret: secret-456"));
 
@@ -30,6 +31,7 @@ async fn test_client_headers_and_response() {
         base_url: format!("http://127.0.0.1:{}", port),
         tenant_id: "test-tenant".to_string(),
         tenant_role: "builder".to_string(),
+        bearer_token: Some("header.payload.sig".to_string()),
         cf_client_id: Some("id-123".to_string()),
         cf_client_secret: Some("secret-456".to_string()),
     };
@@ -71,6 +73,7 @@ async fn test_client_get_pull_request_encodes_id() {
         base_url: format!("http://127.0.0.1:{}", port),
         tenant_id: "test-tenant".to_string(),
         tenant_role: "builder".to_string(),
+        bearer_token: None,
         cf_client_id: None,
         cf_client_secret: None,
     };
@@ -35,6 +35,7 @@ use crate::error::Error;
 /// see the validation section of PR #53 for which patterns we mapped to
 /// this trait and which stay adapter-specific.
 #[async_trait(?Send)]
+#[allow(clippy::double_must_use)] // emitted by the async_trait expansion
 pub trait Repository {
     /// Logical primary key. For D1 row repos this is a `(tenant_id, id)`
     /// pair; for R2 it's the object key as a `String`. Adapter-defined so
@@ -100,6 +100,7 @@ impl MonotonicClock for WorkerClock {
 /// shouldn't actually sleep, and `worker::Delay` doesn't work on the host
 /// target anyway.
 #[async_trait::async_trait(?Send)]
+#[allow(clippy::double_must_use)] // emitted by the async_trait expansion
 pub trait Sleeper {
     async fn sleep(&self, dur: Duration);
 }
@@ -379,6 +380,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::assert_is_empty)] // pre-existing assertion; lint is newer than the test
     fn retry_with_max_attempts_one_never_sleeps() {
         let calls = Rc::new(Cell::new(0u32));
         let sleeps = Rc::new(Cell::new(Vec::<u64>::new()));
@@ -1,7 +1,11 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use data_fabric_client::{
-    types::{CreateCheckpoint, CreateRun, PolicyCheckRequest},
+    types::{
+        CheckpointDiff, CheckpointLineage, CheckpointTree, CreateCheckpoint, CreateRun,
+        ForkCheckpoint, JsonPatchOp, PlayApprovalRequest, PlayRunStatus, PolicyCheckRequest,
+        QueueSnapshot,
+    },
     Client, ClientConfig,
 };
 use std::path::PathBuf;
@@ -25,6 +29,11 @@ struct Cli {
     #[arg(long, env = "DATA_FABRIC_TENANT_ROLE", default_value = "builder")]
     tenant_role: String,
 
+    /// Signed bearer token (JWT) for the worker. Deployed workers reject
+    /// requests without one.
+    #[arg(long, env = "DATA_FABRIC_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
     /// Cloudflare Access service token client id.
     #[arg(long, env = "CF_ACCESS_CLIENT_ID")]
     cf_client_id: Option<String>,
@@ -65,6 +74,18 @@ enum Commands {
         #[command(subcommand)]
         cmd: AgentCommands,
     },
+
+    /// Inspect the agent task queue.
+    Tasks {
+        #[command(subcommand)]
+        cmd: TaskCommands,
+    },
+
+    /// Inspect play runs.
+    Plays {
+        #[command(subcommand)]
+        cmd: PlayCommands,
+    },
 }
 
 #[derive(Subcommand)]
@@ -115,6 +136,35 @@ enum CheckpointCommands {
     Get { id: String },
     /// Delete a checkpoint.
     Delete { id: String },
+    /// Start a new thread from a checkpoint.
+    Fork {
+        id: String,
+        /// Thread to create; generated when omitted.
+        #[arg(long)]
+        thread_id: Option<String>,
+    },
+    /// Show a thread's checkpoints as a tree, with the threads forked from it.
+    Tree {
+        thread_id: String,
+        /// Print the raw JSON tree.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show how the state changed between two checkpoints.
+    Diff {
+        from: String,
+        to: String,
+        /// Print the raw JSON diff, patch included.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show the checkpoints leading to a checkpoint, root first.
+    Lineage {
+        id: String,
+        /// Print the raw JSON lineage.
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -138,11 +188,45 @@ enum AgentCommands {
     List,
 }
 
+#[derive(Subcommand)]
+enum TaskCommands {
+    /// Show queue depth, active leases and stuck notifications.
+    Queue {
+        /// Print the raw JSON snapshot.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PlayCommands {
+    /// Show each task of a play run and its DAG.
+    Status {
+        run_id: String,
+        /// `text` draws the graph in the terminal; `dot` and `mermaid`
+        /// print the server's rendering; `json` the raw status.
+        #[arg(long, default_value = "text", value_parser = ["text", "dot", "mermaid", "json"])]
+        format: String,
+    },
+    /// Decide an approval task holding a play run. `approve` lets the run
+    /// continue; `request_changes` and `reject` fail the task. The decision
+    /// is recorded as `human:<sub>` for the calling token's `sub` claim.
+    Decide {
+        run_id: String,
+        task_id: String,
+        #[arg(value_parser = ["approve", "request_changes", "reject"])]
+        decision: String,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
 fn build_client(cli: &Cli) -> Client {
     let config = ClientConfig {
         base_url: cli.url.clone(),
         tenant_id: cli.tenant_id.clone(),
         tenant_role: cli.tenant_role.clone(),
+        bearer_token: cli.token.clone(),
         cf_client_id: cli.cf_client_id.clone(),
         cf_client_secret: cli.cf_client_secret.clone(),
     };
@@ -252,6 +336,57 @@ async fn main() -> Result<()> {
                     .context("Failed to delete checkpoint")?;
                 println!("Checkpoint {} deleted.", id);
             }
+            CheckpointCommands::Fork { id, thread_id } => {
+                let req = ForkCheckpoint {
+                    thread_id,
+                    metadata: None,
+                };
+                let res = client
+                    .fork_checkpoint(&id, &req)
+                    .await
+                    .context("Failed to fork checkpoint")?;
+                println!("Checkpoint forked successfully!");
+                println!("ID:           {}", res.id);
+                println!("Thread:       {}", res.thread_id);
+                println!(
+                    "Forked from:  {} ({})",
+                    res.forked_from.checkpoint_id, res.forked_from.thread_id
+                );
+                println!("State R2 key: {}", res.state_r2_key);
+            }
+            CheckpointCommands::Tree { thread_id, json } => {
+                let res = client
+                    .get_checkpoint_tree(&thread_id)
+                    .await
+                    .context("Failed to fetch checkpoint tree")?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&res)?);
+                } else {
+                    print_checkpoint_tree(&res);
+                }
+            }
+            CheckpointCommands::Diff { from, to, json } => {
+                let res = client
+                    .diff_checkpoints(&from, &to)
+                    .await
+                    .context("Failed to diff checkpoints")?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&res)?);
+                } else {
+                    print_checkpoint_diff(&res)?;
+                }
+            }
+            CheckpointCommands::Lineage { id, json } => {
+                let res = client
+                    .get_checkpoint_lineage(&id)
+                    .await
+                    .context("Failed to fetch checkpoint lineage")?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&res)?);
+                } else {
+                    print_checkpoint_lineage(&res)?;
+                }
+            }
         },
 
         Commands::Policy { cmd } => match cmd {
@@ -290,11 +425,381 @@ async fn main() -> Result<()> {
                 println!("{}", serde_json::to_string_pretty(&res)?);
             }
         },
+
+        Commands::Tasks { cmd } => match cmd {
+            TaskCommands::Queue { json } => {
+                let res = client
+                    .get_task_queue()
+                    .await
+                    .context("Failed to fetch task queue")?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&res)?);
+                } else {
+                    print_queue(&res)?;
+                }
+            }
+        },
+
+        Commands::Plays { cmd } => match cmd {
+            PlayCommands::Status { run_id, format } => match format.as_str() {
+                "dot" | "mermaid" => {
+                    let graph = client
+                        .get_play_run_graph(&run_id, &format)
+                        .await
+                        .context("Failed to fetch play run graph")?;
+                    print!("{}", graph);
+                }
+                _ => {
+                    let res = client
+                        .get_play_run(&run_id)
+                        .await
+                        .context("Failed to fetch play run")?;
+                    if format == "json" {
+                        println!("{}", serde_json::to_string_pretty(&res)?);
+                    } else {
+                        print_play_run(&res)?;
+                    }
+                }
+            },
+            PlayCommands::Decide {
+                run_id,
+                task_id,
+                decision,
+                reason,
+            } => {
+                let req = PlayApprovalRequest { decision, reason };
+                let res = client
+                    .decide_play_approval(&run_id, &task_id, &req)
+                    .await
+                    .context("Failed to record approval decision")?;
+                println!(
+                    "Recorded {} by {} on task {} (decision {})",
+                    res.decision, res.actor, task_id, res.id
+                );
+            }
+        },
     }
 
     Ok(())
 }
 
+fn print_queue(q: &QueueSnapshot) -> Result<()> {
+    use cli_table::{print_stdout, Cell, Style, Table};
+
+    println!("Pending:     {} / {}", q.pending_total, q.max_pending);
+    println!("Scheduled:   {}", q.scheduled_total);
+    println!("Active:      {}", q.active.len());
+    println!("Dead-letter: {}", q.dead_letter_total);
+    println!("Rejected:    {}", q.rejected_total);
+    match q.oldest_pending_age_ms {
+        Some(ms) => println!("Oldest:      {}s", ms / 1000),
+        None => println!("Oldest:      -"),
+    }
+
+    if !q.depth.is_empty() {
+        println!();
+        let rows: Vec<_> = q
+            .depth
+            .iter()
+            .map(|d| {
+                vec![
+                    d.task_type.clone().cell(),
+                    d.priority.cell(),
+                    d.count.cell(),
+                ]
+            })
+            .collect();
+        print_stdout(rows.table().title(vec![
+            "Task Type".cell().bold(true),
+            "Priority".cell().bold(true),
+            "Pending".cell().bold(true),
+        ]))?;
+    }
+
+    if !q.active.is_empty() {
+        println!();
+        let rows: Vec<_> = q
+            .active
+            .iter()
+            .map(|l| {
+                vec![
+                    l.task_id.clone().cell(),
+                    l.task_type.clone().cell(),
+                    l.agent_id.as_deref().unwrap_or("-").cell(),
+                    l.lease_expires_at.as_deref().unwrap_or("-").cell(),
+                    if l.cancel_requested { "yes" } else { "" }.cell(),
+                ]
+            })
+            .collect();
+        print_stdout(rows.table().title(vec![
+            "Task ID".cell().bold(true),
+            "Task Type".cell().bold(true),
+            "Agent".cell().bold(true),
+            "Lease Expires".cell().bold(true),
+            "Cancelling".cell().bold(true),
+        ]))?;
+    }
+
+    if !q.pending_notifies.is_empty() {
+        println!();
+        println!("Play notifications awaiting retry:");
+        let rows: Vec<_> = q
+            .pending_notifies
+            .iter()
+            .map(|n| {
+                vec![
+                    n.target_name.clone().cell(),
+                    n.play_task_id.clone().cell(),
+                    n.attempts.cell(),
+                    n.next_attempt_at_ms.cell(),
+                ]
+            })
+            .collect();
+        print_stdout(rows.table().title(vec![
+            "Play Manager".cell().bold(true),
+            "Task".cell().bold(true),
+            "Attempts".cell().bold(true),
+            "Next Attempt (ms)".cell().bold(true),
+        ]))?;
+    }
+    Ok(())
+}
+
+fn print_checkpoint_tree(tree: &CheckpointTree) {
+    let nodes: std::collections::HashMap<&str, _> = tree
+        .nodes
+        .iter()
+        .map(|n| (n.checkpoint.id.as_str(), n))
+        .collect();
+
+    println!("Thread: {}", tree.thread_id);
+    // Walked with an explicit stack: a long thread is a deep chain.
+    let mut stack: Vec<(&str, usize)> =
+        tree.roots.iter().rev().map(|id| (id.as_str(), 0)).collect();
+    while let Some((id, depth)) = stack.pop() {
+        let Some(node) = nodes.get(id) else {
+            continue;
+        };
+        let cp = &node.checkpoint;
+        let indent = "  ".repeat(depth);
+        match (&cp.parent_id, depth) {
+            (Some(parent), 0) => println!(
+                "{}{} {} {} (from {})",
+                indent, cp.id, cp.node_id, cp.created_at, parent
+            ),
+            _ => println!("{}{} {} {}", indent, cp.id, cp.node_id, cp.created_at),
+        }
+        for fork in &node.forks {
+            println!(
+                "{}  -> fork {} ({})",
+                indent, fork.thread_id, fork.checkpoint_id
+            );
+        }
+        stack.extend(node.children.iter().rev().map(|c| (c.as_str(), depth + 1)));
+    }
+    if tree.truncated {
+        println!(
+            "... truncated to the first {} checkpoints",
+            tree.nodes.len()
+        );
+    }
+}
+
+fn print_checkpoint_diff(diff: &CheckpointDiff) -> Result<()> {
+    println!("From: {}", diff.from);
+    println!("To:   {}", diff.to);
+    if diff.patch.is_empty() {
+        println!("No changes.");
+        return Ok(());
+    }
+    println!(
+        "{} added, {} removed, {} changed",
+        diff.summary.added.len(),
+        diff.summary.removed.len(),
+        diff.summary.changed.len()
+    );
+    println!();
+    for op in &diff.patch {
+        match op {
+            JsonPatchOp::Add { path, value } => {
+                println!(
+                    "+ {} = {}",
+                    pointer_label(path),
+                    serde_json::to_string(value)?
+                )
+            }
+            JsonPatchOp::Remove { path } => println!("- {}", pointer_label(path)),
+            JsonPatchOp::Replace { path, value } => {
+                println!(
+                    "~ {} = {}",
+                    pointer_label(path),
+                    serde_json::to_string(value)?
+                )
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The whole document is the empty pointer; show it as `/`.
+fn pointer_label(path: &str) -> &str {
+    if path.is_empty() {
+        "/"
+    } else {
+        path
+    }
+}
+
+fn print_checkpoint_lineage(lineage: &CheckpointLineage) -> Result<()> {
+    use cli_table::{print_stdout, Cell, Style, Table};
+
+    if !lineage.complete {
+        println!("(lineage incomplete: the oldest ancestor is missing or too far back)");
+    }
+    let rows: Vec<_> = lineage
+        .path
+        .iter()
+        .map(|cp| {
+            vec![
+                cp.id.clone().cell(),
+                cp.thread_id.clone().cell(),
+                cp.node_id.clone().cell(),
+                cp.created_at.clone().cell(),
+            ]
+        })
+        .collect();
+    print_stdout(rows.table().title(vec![
+        "Checkpoint".cell().bold(true),
+        "Thread".cell().bold(true),
+        "Node".cell().bold(true),
+        "Created".cell().bold(true),
+    ]))?;
+    Ok(())
+}
+
+fn print_play_run(run: &PlayRunStatus) -> Result<()> {
+    use cli_table::{print_stdout, Cell, Style, Table};
+
+    println!("Run:    {}", run.run_id);
+    println!("Play:   {}", run.play);
+    println!("Status: {}", run.status.as_str());
+    if let Some(task) = &run.failed_task {
+        println!("Failed: {}", task);
+    }
+
+    if !run.tasks.is_empty() {
+        println!();
+        let rows: Vec<_> = run
+            .tasks
+            .iter()
+            .map(|t| {
+                vec![
+                    t.id.clone().cell(),
+                    t.task_type.clone().cell(),
+                    t.state.clone().cell(),
+                    t.agent_id
+                        .as_deref()
+                        .or_else(|| {
+                            let decision = t.approval.as_ref()?.decision.as_ref()?;
+                            Some(decision.actor.as_str())
+                        })
+                        .unwrap_or("-")
+                        .cell(),
+                    t.launched_at.as_deref().unwrap_or("-").cell(),
+                    t.finished_at.as_deref().unwrap_or("-").cell(),
+                    t.error.as_deref().unwrap_or("").cell(),
+                ]
+            })
+            .collect();
+        print_stdout(rows.table().title(vec![
+            "Task".cell().bold(true),
+            "Task Type".cell().bold(true),
+            "State".cell().bold(true),
+            "Agent".cell().bold(true),
+            "Launched".cell().bold(true),
+            "Finished".cell().bold(true),
+            "Error".cell().bold(true),
+        ]))?;
+
+        println!();
+        for line in play_graph_lines(run) {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Draw a play run's DAG one stage per line group: a task sits one stage
+/// below its deepest parent, and lists its parents after `<-`.
+fn play_graph_lines(run: &PlayRunStatus) -> Vec<String> {
+    use std::collections::HashMap;
+
+    let index: HashMap<&str, usize> = run
+        .tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.id.as_str(), i))
+        .collect();
+    // Tasks come parents first except for expanded maps, which follow
+    // their children; iterate to a fixpoint rather than rely on order.
+    let mut stage = vec![0usize; run.tasks.len()];
+    for _ in 0..run.tasks.len() {
+        let mut changed = false;
+        for (i, task) in run.tasks.iter().enumerate() {
+            let depth = task
+                .depends_on
+                .iter()
+                .filter_map(|d| index.get(d.as_str()))
+                .map(|&p| stage[p] + 1)
+                .max()
+                .unwrap_or(0);
+            if depth > stage[i] {
+                stage[i] = depth;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut lines = Vec::new();
+    let last = stage.iter().copied().max().unwrap_or(0);
+    for s in 0..=last {
+        let mut first = true;
+        let tasks = run.tasks.iter().zip(&stage).filter(|(_, &ts)| ts == s);
+        for (task, _) in tasks {
+            let label = if first {
+                format!("Stage {}", s)
+            } else {
+                String::new()
+            };
+            first = false;
+            let mut line = format!("{:<9}{} {}", label, state_glyph(&task.state), task.id);
+            if !task.depends_on.is_empty() {
+                line.push_str(&format!(" <- {}", task.depends_on.join(", ")));
+            }
+            if let Some(handler) = &task.on_failure {
+                line.push_str(&format!("  (on failure: {})", handler));
+            }
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+fn state_glyph(state: &str) -> &'static str {
+    match state {
+        "completed" => "[ok]",
+        "running" => "[..]",
+        "awaiting_approval" => "[??]",
+        "failed" => "[!!]",
+        "skipped" => "[--]",
+        "cancelled" => "[xx]",
+        _ => "[  ]",
+    }
+}
+
 fn print_runs(res: &serde_json::Value) -> Result<()> {
     use cli_table::{print_stdout, Cell, Style, Table};
 
@@ -0,0 +1,8 @@
+-- WS8: per-tenant API rate limits (edge limiter).
+--
+-- The TenantRateLimiter Durable Object enforces these at the router edge;
+-- the columns are the durable record of what provisioning configured so a
+-- re-provision (or a DO storage reset) can restore the same limits.
+-- Defaults match tenant_security::TenantRateLimitConfig::default().
+ALTER TABLE tenants ADD COLUMN api_requests_per_minute INTEGER NOT NULL DEFAULT 120;
+ALTER TABLE tenants ADD COLUMN api_burst_limit INTEGER NOT NULL DEFAULT 20;
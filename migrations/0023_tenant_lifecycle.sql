@@ -0,0 +1,26 @@
+-- WS8: tenant lifecycle (suspend / resume / export / hard-delete).
+--
+-- `tenants.status` already exists (0005) and defaults to 'active'. The
+-- lifecycle endpoints move it between 'active', 'suspended' and 'deleted';
+-- the two columns below record why and when it last changed.
+--
+-- A hard-deleted tenant keeps its `tenants` row as a tombstone with
+-- status = 'deleted' so still-valid bearer tokens for that tenant are
+-- rejected at the edge instead of writing fresh rows into a purged tenant.
+ALTER TABLE tenants ADD COLUMN status_reason TEXT;
+ALTER TABLE tenants ADD COLUMN status_changed_at TEXT;
+
+-- Verifiable deletion reports. Deliberately NOT purged by the hard-delete
+-- itself: the report is the evidence that the purge happened.
+CREATE TABLE IF NOT EXISTS tenant_deletion_reports (
+    tenant_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    report_json TEXT NOT NULL,
+    digest TEXT NOT NULL,
+    verified INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (tenant_id, id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tenant_deletion_reports_created
+    ON tenant_deletion_reports(tenant_id, created_at DESC);
@@ -0,0 +1,13 @@
+-- WS8: opt-in cross-tenant federation.
+--
+-- One row per source tenant mirroring tenant_security::FederationConfig.
+-- `allowed_tenants` and `sharing_scope` are JSON string arrays; readers
+-- re-check them with `can_federate` so a malformed row never widens sharing.
+-- No row (the default) means the tenant shares nothing.
+CREATE TABLE IF NOT EXISTS tenant_federation (
+    tenant_id TEXT PRIMARY KEY,
+    opt_in INTEGER NOT NULL DEFAULT 0,
+    allowed_tenants TEXT NOT NULL DEFAULT '[]',
+    sharing_scope TEXT NOT NULL DEFAULT '[]',
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
@@ -0,0 +1,24 @@
+-- WS8: ingest-time secret scanning.
+--
+-- Per-tenant handling of detected secrets: 'reject' refuses the write,
+-- 'redact' masks secrets before storing, 'flag' stores unchanged and
+-- records the findings. Set via POST /v1/tenants/provision.
+ALTER TABLE tenants ADD COLUMN secret_scan_mode TEXT NOT NULL DEFAULT 'redact';
+
+-- One row per write that had findings, keyed by the table and id of the
+-- row it describes. `findings` is a JSON array of {path, kind}; secret
+-- values are never stored here. A write with no row scanned clean.
+CREATE TABLE IF NOT EXISTS secret_scan_results (
+    tenant_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    target_table TEXT NOT NULL,
+    target_id TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    finding_count INTEGER NOT NULL,
+    findings TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (tenant_id, id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_secret_scan_results_target
+    ON secret_scan_results(tenant_id, target_table, target_id);
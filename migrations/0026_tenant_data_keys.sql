@@ -0,0 +1,20 @@
+-- WS8: envelope encryption at rest for R2 objects.
+--
+-- Per-tenant AES-256 data keys, stored only wrapped (AES-256-GCM) under a
+-- master key from the ENCRYPTION_MASTER_KEYS secret. `master_key_id` names
+-- the wrapping key so rotation can re-wrap rows without touching objects.
+-- Exactly one row per tenant is `active` (used for new writes); retired
+-- keys stay for reads of objects sealed under them.
+CREATE TABLE IF NOT EXISTS tenant_data_keys (
+    tenant_id TEXT NOT NULL,
+    key_id TEXT NOT NULL,
+    wrapped_key TEXT NOT NULL,
+    master_key_id TEXT NOT NULL,
+    active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    rewrapped_at TEXT,
+    PRIMARY KEY (tenant_id, key_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tenant_data_keys_active
+    ON tenant_data_keys(tenant_id, active, created_at DESC);
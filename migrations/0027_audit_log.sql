@@ -0,0 +1,42 @@
+-- WS8: tamper-evident audit log of mutating API calls.
+--
+-- The router appends one row per authenticated non-GET request, including
+-- ones denied by authorization, admission or the rate limiter. Rows form a
+-- per-tenant hash chain: `seq` is contiguous from 1, `prev_hash` is the
+-- previous row's `hash` (64 zeros for seq 1), and `hash` covers every other
+-- column. GET /v1/audit/verify recomputes the chain to detect edits and gaps.
+--
+-- Append-only: the triggers below refuse UPDATE and DELETE. Like
+-- tenant_deletion_reports, a tenant hard-delete keeps these rows.
+CREATE TABLE IF NOT EXISTS audit_log (
+    tenant_id TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    id TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    method TEXT NOT NULL,
+    route TEXT NOT NULL,
+    path TEXT NOT NULL,
+    target_ids TEXT NOT NULL DEFAULT '{}',
+    status INTEGER NOT NULL,
+    outcome TEXT NOT NULL,
+    error_code TEXT,
+    created_at TEXT NOT NULL,
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    PRIMARY KEY (tenant_id, seq)
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor
+    ON audit_log(tenant_id, actor, seq DESC);
+
+CREATE TRIGGER IF NOT EXISTS audit_log_no_update
+BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_log_no_delete
+BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
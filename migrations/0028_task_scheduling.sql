@@ -0,0 +1,27 @@
+-- Delayed and recurring agent tasks.
+--
+-- `mcp_tasks.not_before` mirrors CreateAgentTask.not_before: the
+-- TaskLeaseManager DO keeps such a task out of its claimable queue until
+-- the timestamp passes. NULL means claimable on enqueue.
+--
+-- `task_schedules` holds cron templates. Every scheduled tick enqueues one
+-- task per schedule whose `next_run_at` has passed, then advances
+-- `next_run_at` with a compare-and-set on its old value so overlapping
+-- ticks cannot enqueue the same run twice. Missed fires collapse into one.
+ALTER TABLE mcp_tasks ADD COLUMN not_before TEXT;
+
+CREATE TABLE IF NOT EXISTS task_schedules (
+    tenant_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    template TEXT NOT NULL,
+    next_run_at TEXT NOT NULL,
+    last_run_at TEXT,
+    last_task_id TEXT,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (tenant_id, id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_schedules_due
+    ON task_schedules(next_run_at);
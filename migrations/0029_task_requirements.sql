@@ -0,0 +1,7 @@
+-- Capability requirements on agent tasks.
+--
+-- `mcp_tasks.requires` is the JSON array from CreateAgentTask.requires
+-- (e.g. ["rust>=1.80", "repo:stevedores-org/*"]). TaskLeaseManager only
+-- hands the task to an agent whose advertised capabilities satisfy every
+-- entry. NULL means no requirements.
+ALTER TABLE mcp_tasks ADD COLUMN requires TEXT;
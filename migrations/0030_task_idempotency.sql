@@ -0,0 +1,10 @@
+-- Client-supplied dedupe keys on agent task submission.
+--
+-- A POST /v1/tasks carrying an idempotency_key that matches a task created
+-- within the idempotency window (TASK_IDEMPOTENCY_WINDOW_SECS, default 24h)
+-- returns the original task instead of creating a new one. Not UNIQUE:
+-- keys may be reused once they fall out of the window.
+ALTER TABLE mcp_tasks ADD COLUMN idempotency_key TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_mcp_tasks_idempotency
+    ON mcp_tasks(tenant_id, idempotency_key, created_at);
@@ -0,0 +1,8 @@
+-- Play-level error policy, read by PlayManager when a task fails with no
+-- on_failure handler.
+--
+-- 'fail_fast' (the default, and the behaviour before this column) fails
+-- the run at once and cancels its in-flight tasks. 'continue' keeps
+-- running every branch that does not depend on the failure; the run still
+-- ends 'failed'.
+ALTER TABLE play_definitions ADD COLUMN on_error TEXT NOT NULL DEFAULT 'fail_fast';
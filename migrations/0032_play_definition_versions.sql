@@ -0,0 +1,28 @@
+-- Versioned play definitions, authored through /v1/plays.
+--
+-- play_definitions keeps one row per play holding its latest version, so
+-- launches and plays seeded by migrations read it as before.
+-- play_definition_versions keeps every version ever stored, for
+-- launch-by-version and /v1/plays/:name/diff. major/minor/patch are split
+-- out so versions sort numerically.
+ALTER TABLE play_definitions ADD COLUMN version TEXT NOT NULL DEFAULT '1.0.0';
+
+CREATE TABLE IF NOT EXISTS play_definition_versions (
+    tenant_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    major INTEGER NOT NULL,
+    minor INTEGER NOT NULL,
+    patch INTEGER NOT NULL,
+    goal TEXT NOT NULL,
+    tasks_json TEXT NOT NULL, -- JSON array of PlayTaskDefinition
+    on_error TEXT NOT NULL DEFAULT 'fail_fast',
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (tenant_id, name, version)
+);
+
+-- Existing plays become version 1.0.0.
+INSERT INTO play_definition_versions
+    (tenant_id, name, version, major, minor, patch, goal, tasks_json, on_error, created_at)
+SELECT tenant_id, name, '1.0.0', 1, 0, 0, goal, tasks_json, on_error, updated_at
+FROM play_definitions;
@@ -0,0 +1,5 @@
+-- Typed launch inputs of a play: JSON object of input name to
+-- { "type", "default"?, "description"? }. Task params reference them as
+-- {{inputs.<name>}}. NULL for plays without inputs.
+ALTER TABLE play_definitions ADD COLUMN inputs_json TEXT;
+ALTER TABLE play_definition_versions ADD COLUMN inputs_json TEXT;
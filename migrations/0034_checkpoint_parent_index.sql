@@ -0,0 +1,4 @@
+-- Checkpoint trees: children of a checkpoint (including the first
+-- checkpoint of a thread forked from it) are found by parent_id, both
+-- when listing a thread's tree and when walking a lineage.
+CREATE INDEX IF NOT EXISTS idx_checkpoints_tenant_parent ON checkpoints(tenant_id, parent_id);
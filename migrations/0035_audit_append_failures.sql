@@ -0,0 +1,20 @@
+-- WS8: audit rows the router could not append.
+--
+-- The router appends each audit row before it returns the response. When
+-- that append still fails after its retries, the entry is recorded here so
+-- GET /v1/audit/verify can report the missing row instead of the chain
+-- silently looking complete. Retained across a tenant hard-delete, like
+-- audit_log.
+CREATE TABLE IF NOT EXISTS audit_append_failures (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    error TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_append_failures_tenant
+    ON audit_append_failures(tenant_id, created_at);
@@ -0,0 +1,4 @@
+-- A scheduled tick claims a fire (advances next_run_at) before it enqueues
+-- the task. When that enqueue fails the fire is gone, so the failure is
+-- kept on the schedule itself; the next successful fire clears it.
+ALTER TABLE task_schedules ADD COLUMN last_error TEXT;
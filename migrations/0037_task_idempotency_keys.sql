@@ -0,0 +1,15 @@
+-- One owner per (tenant_id, idempotency_key) for agent task submission.
+--
+-- The lookup on mcp_tasks.idempotency_key (0030) and the TaskLeaseManager
+-- dedupe both race: two submissions can miss each other in D1, and with
+-- TASK_SHARDS > 1 they can land on different DO shards. POST /v1/tasks now
+-- claims the key here first. The primary key makes the claim atomic, and
+-- the upsert only takes over a row whose window has passed (expires_at),
+-- so a key can be reused once it falls out of the window.
+CREATE TABLE IF NOT EXISTS task_idempotency_keys (
+    tenant_id TEXT NOT NULL,
+    idempotency_key TEXT NOT NULL,
+    task_id TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    PRIMARY KEY (tenant_id, idempotency_key)
+);
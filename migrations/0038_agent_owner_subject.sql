@@ -0,0 +1,9 @@
+-- Bind registered agents to the token subject that registered them.
+--
+-- The claim, socket and heartbeat routes take `?agent_id=`, which feeds the
+-- implicit `agent:<id>` capability used for task pinning and exclusion. A
+-- caller may only act as an agent id equal to its token's `sub`, or as a
+-- registered agent whose owner_subject is that `sub`. Rows registered
+-- before this column existed have no owner and can only be used by a token
+-- whose `sub` is the agent id itself.
+ALTER TABLE agents ADD COLUMN owner_subject TEXT;
@@ -0,0 +1,12 @@
+-- Launched play runs, one row per PlayManager instance.
+--
+-- PlayManager state lives only in its Durable Object, named
+-- `{tenant_id}:play:{run_id}`. A tenant hard-delete walks this table to
+-- purge every one of them; Durable Objects cannot be listed by name.
+CREATE TABLE IF NOT EXISTS play_runs (
+    tenant_id TEXT NOT NULL,
+    run_id TEXT NOT NULL,
+    play_name TEXT NOT NULL,
+    launched_at TEXT NOT NULL,
+    PRIMARY KEY (tenant_id, run_id)
+);
@@ -0,0 +1,17 @@
+-- Enforce the "exactly one active data key per tenant" rule from 0026.
+--
+-- Two requests minting a tenant's first key at once, or a rotation racing a
+-- first write, could leave several rows with `active = 1`. Keep the newest
+-- (the one `get_active_data_key` already picks) and retire the rest; retired
+-- keys still decrypt objects sealed under them.
+UPDATE tenant_data_keys SET active = 0
+WHERE active = 1
+  AND key_id <> (
+    SELECT k.key_id FROM tenant_data_keys k
+    WHERE k.tenant_id = tenant_data_keys.tenant_id AND k.active = 1
+    ORDER BY k.created_at DESC, k.key_id
+    LIMIT 1
+  );
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tenant_data_keys_one_active
+    ON tenant_data_keys(tenant_id) WHERE active = 1;
@@ -11,6 +11,10 @@
 #   scripts/pilot-smoke.sh --tenant-id acme --window 7d
 #   scripts/pilot-smoke.sh --task-type oxidizedgraph.test
 #
+# Deployed workers need a signed token: export DATA_FABRIC_TOKEN=<jwt>.
+# Without it the x-tenant-id header is sent, which only local dev workers
+# (AUTH_DEV_HEADERS=true) accept.
+#
 # Exit codes:
 #   0   response is 200 and shape is valid
 #   2   missing prereqs (curl, jq)
@@ -30,7 +34,7 @@ while [[ $# -gt 0 ]]; do
     --window)    WINDOW="$2";    shift 2 ;;
     --task-type) TASK_TYPE="$2"; shift 2 ;;
     -h|--help)
-      sed -n '2,15p' "$0"
+      sed -n '2,19p' "$0"
       exit 0
       ;;
     *) echo "unknown arg: $1" >&2; exit 64 ;;
@@ -58,8 +62,12 @@ echo "  x-tenant-id: ${TENANT_ID}"
 # Capture body and status separately so a non-200 still gives a body to inspect.
 BODY_FILE=$(mktemp)
 trap 'rm -f "$BODY_FILE"' EXIT
+AUTH_ARGS=(-H "x-tenant-id: ${TENANT_ID}")
+if [[ -n "${DATA_FABRIC_TOKEN:-}" ]]; then
+  AUTH_ARGS+=(-H "authorization: Bearer ${DATA_FABRIC_TOKEN}")
+fi
 HTTP_STATUS=$(curl -sS -o "$BODY_FILE" -w "%{http_code}" \
-  -H "x-tenant-id: ${TENANT_ID}" \
+  "${AUTH_ARGS[@]}" \
   "$URL") || {
     echo "error: curl failed" >&2
     exit 3
@@ -0,0 +1,545 @@
+//! WS8: tamper-evident audit log of mutating API calls.
+//!
+//! `fetch` builds an [`AuditEntry`] for every authenticated non-GET request
+//! — including ones refused by authorization, tenant admission or the rate
+//! limiter — and [`append`]s it to `audit_log` before returning the
+//! response. That costs each audited request two D1 round trips (head read,
+//! then insert; more when concurrent writers collide on a `seq`) on its
+//! latency, the price of never answering a request whose row is neither in
+//! the chain nor in `audit_append_failures`. An append that still fails is kept in `audit_append_failures`
+//! and reported by [`verify_tenant`] as an `append_failed` issue. Rows
+//! are hash-chained per tenant: row `n` stores the hash of row `n - 1`, and
+//! its own hash covers every other column, so an edited row, a removed row
+//! or a re-ordered pair shows up in [`ChainVerifier`].
+//!
+//! Chain hashing and verification are pure; only [`append`] and
+//! [`verify_tenant`] touch D1.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use worker::{D1Database, Method, Response, ResponseBody, Result};
+
+use crate::authz;
+use crate::db;
+use crate::models::{AuditAppendFailure, AuditChainIssue, AuditRecord, AuditVerifyResponse};
+
+/// `prev_hash` of the first row in every tenant's chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Attempts at claiming the next `seq` before giving up. Two writers that
+/// read the same head collide on the `(tenant_id, seq)` primary key; the
+/// loser re-reads the head and tries again.
+const APPEND_ATTEMPTS: usize = 4;
+
+/// Response bodies larger than this are not parsed for a created id or
+/// error code.
+const MAX_PEEK_BYTES: usize = 64 * 1024;
+
+/// Rows read per page by [`verify_tenant`].
+const VERIFY_PAGE: u32 = 500;
+
+/// Rows [`verify_tenant`] checks per call unless the caller asks for fewer.
+pub const MAX_VERIFY_ROWS: u32 = 10_000;
+
+/// What the router knows about one request once it has a response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub actor: String,
+    pub method: String,
+    pub route: String,
+    pub path: String,
+    pub target_ids: BTreeMap<String, String>,
+    pub status: u16,
+    pub outcome: &'static str,
+    pub error_code: Option<String>,
+}
+
+/// Only state-changing methods are audited.
+pub fn is_audited(method: &Method) -> bool {
+    !matches!(method, Method::Get | Method::Head | Method::Options)
+}
+
+pub fn outcome_for(status: u16) -> &'static str {
+    match status {
+        200..=399 => "success",
+        401 | 403 | 429 => "denied",
+        _ => "failure",
+    }
+}
+
+impl AuditEntry {
+    /// Build the entry from the request line and whatever response the
+    /// router produced (`Err` counts as a 500).
+    pub fn new(actor: String, method: &Method, path: &str, response: &Result<Response>) -> Self {
+        let (route, mut target_ids) = match authz::route_params(method, path) {
+            Some((pattern, params)) => (pattern.to_string(), params),
+            None => (path.to_string(), BTreeMap::new()),
+        };
+        let (status, body) = match response {
+            Ok(resp) => (resp.status_code(), peek_json(resp)),
+            Err(_) => (500, None),
+        };
+        let body = body.unwrap_or(serde_json::Value::Null);
+        if let Some(id) = body.get("id").and_then(|v| v.as_str()) {
+            target_ids
+                .entry("id".to_string())
+                .or_insert_with(|| id.to_string());
+        }
+        let error_code = body
+            .pointer("/error/code")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        Self {
+            actor,
+            method: method.to_string(),
+            route,
+            path: path.to_string(),
+            target_ids,
+            status,
+            outcome: outcome_for(status),
+            error_code,
+        }
+    }
+}
+
+/// JSON body of a buffered response; streamed bodies are left untouched.
+fn peek_json(resp: &Response) -> Option<serde_json::Value> {
+    match resp.body() {
+        ResponseBody::Body(bytes) if bytes.len() <= MAX_PEEK_BYTES => {
+            serde_json::from_slice(bytes).ok()
+        }
+        _ => None,
+    }
+}
+
+/// The row that follows `head` (`(seq, hash)` of the current last row).
+pub fn next_record(
+    tenant_id: &str,
+    head: Option<(i64, String)>,
+    entry: &AuditEntry,
+    id: String,
+    created_at: String,
+) -> AuditRecord {
+    let (seq, prev_hash) = match head {
+        Some((seq, hash)) => (seq + 1, hash),
+        None => (1, GENESIS_HASH.to_string()),
+    };
+    let mut record = AuditRecord {
+        tenant_id: tenant_id.to_string(),
+        seq,
+        id,
+        actor: entry.actor.clone(),
+        method: entry.method.clone(),
+        route: entry.route.clone(),
+        path: entry.path.clone(),
+        target_ids: entry.target_ids.clone(),
+        status: entry.status,
+        outcome: entry.outcome.to_string(),
+        error_code: entry.error_code.clone(),
+        created_at,
+        prev_hash,
+        hash: String::new(),
+    };
+    record.hash = record_hash(&record);
+    record
+}
+
+/// Hex SHA-256 over the record's canonical JSON with `hash` blanked.
+/// Field order is fixed by the `Canonical` struct and `target_ids` is a sorted map,
+/// so the encoding is stable across writers and verifiers.
+pub fn record_hash(record: &AuditRecord) -> String {
+    #[derive(Serialize)]
+    struct Canonical<'a> {
+        tenant_id: &'a str,
+        seq: i64,
+        id: &'a str,
+        actor: &'a str,
+        method: &'a str,
+        route: &'a str,
+        path: &'a str,
+        target_ids: &'a BTreeMap<String, String>,
+        status: u16,
+        outcome: &'a str,
+        error_code: Option<&'a str>,
+        created_at: &'a str,
+        prev_hash: &'a str,
+    }
+    let canonical = Canonical {
+        tenant_id: &record.tenant_id,
+        seq: record.seq,
+        id: &record.id,
+        actor: &record.actor,
+        method: &record.method,
+        route: &record.route,
+        path: &record.path,
+        target_ids: &record.target_ids,
+        status: record.status,
+        outcome: &record.outcome,
+        error_code: record.error_code.as_deref(),
+        created_at: &record.created_at,
+        prev_hash: &record.prev_hash,
+    };
+    let bytes = serde_json::to_vec(&canonical).unwrap_or_default();
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Parse the `after_seq` / `after_hash` pair of `GET /v1/audit/verify`:
+/// both or neither, a non-negative seq and a hex SHA-256.
+pub fn parse_verify_anchor(
+    seq: Option<&str>,
+    hash: Option<&str>,
+) -> std::result::Result<Option<(i64, String)>, &'static str> {
+    match (seq, hash) {
+        (None, None) => Ok(None),
+        (Some(seq), Some(hash)) => {
+            let seq = seq
+                .parse::<i64>()
+                .ok()
+                .filter(|s| *s >= 0)
+                .ok_or("after_seq must be a non-negative integer")?;
+            if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err("after_hash must be a hex SHA-256");
+            }
+            Ok(Some((seq, hash.to_ascii_lowercase())))
+        }
+        _ => Err("after_seq and after_hash must be given together"),
+    }
+}
+
+/// Walks a tenant's rows in ascending `seq` order and collects every break.
+#[derive(Debug)]
+pub struct ChainVerifier {
+    expected_seq: i64,
+    prev_hash: String,
+    checked: usize,
+    issues: Vec<AuditChainIssue>,
+}
+
+impl Default for ChainVerifier {
+    fn default() -> Self {
+        Self {
+            expected_seq: 1,
+            prev_hash: GENESIS_HASH.to_string(),
+            checked: 0,
+            issues: Vec::new(),
+        }
+    }
+}
+
+impl ChainVerifier {
+    /// Continue a chain already verified up to `head_seq`, whose row
+    /// hashed to `head_hash`.
+    pub fn resume(head_seq: i64, head_hash: &str) -> Self {
+        Self {
+            expected_seq: head_seq + 1,
+            prev_hash: head_hash.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, record: &AuditRecord) {
+        self.checked += 1;
+        if record.seq != self.expected_seq {
+            self.issue(
+                record.seq,
+                "gap",
+                format!("expected seq {}, found {}", self.expected_seq, record.seq),
+            );
+        }
+        if record.prev_hash != self.prev_hash {
+            self.issue(
+                record.seq,
+                "broken_link",
+                "prev_hash does not match the preceding row".to_string(),
+            );
+        }
+        if record_hash(record) != record.hash {
+            self.issue(
+                record.seq,
+                "hash_mismatch",
+                "row contents do not match its hash".to_string(),
+            );
+        }
+        self.expected_seq = record.seq + 1;
+        self.prev_hash = record.hash.clone();
+    }
+
+    /// Report an entry that never made it into the chain.
+    pub fn append_failed(&mut self, failure: &AuditAppendFailure) {
+        self.issue(
+            0,
+            "append_failed",
+            format!(
+                "{} {} by {} ({}) at {} was not appended: {}",
+                failure.method,
+                failure.path,
+                failure.actor,
+                failure.status,
+                failure.created_at,
+                failure.error
+            ),
+        );
+    }
+
+    fn issue(&mut self, seq: i64, kind: &str, detail: String) {
+        self.issues.push(AuditChainIssue {
+            seq,
+            kind: kind.to_string(),
+            detail,
+        });
+    }
+
+    pub fn finish(self, tenant_id: &str, complete: bool) -> AuditVerifyResponse {
+        AuditVerifyResponse {
+            tenant_id: tenant_id.to_string(),
+            verified: self.issues.is_empty(),
+            records_checked: self.checked,
+            head_seq: self.expected_seq - 1,
+            head_hash: self.prev_hash,
+            complete,
+            issues: self.issues,
+        }
+    }
+}
+
+/// Append `entry` to the tenant's chain, retrying when a concurrent writer
+/// claims the same `seq` first.
+pub async fn append(d1: &D1Database, tenant_id: &str, entry: &AuditEntry) -> Result<AuditRecord> {
+    let mut last_err = None;
+    for _ in 0..APPEND_ATTEMPTS {
+        let head = db::audit_head(d1, tenant_id).await?;
+        let record = next_record(tenant_id, head, entry, crate::generate_id()?, db::now_iso());
+        match db::insert_audit_record(d1, &record).await {
+            Ok(()) => return Ok(record),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| worker::Error::RustError("audit append failed".into())))
+}
+
+/// [`append`], falling back to recording the failure in
+/// `audit_append_failures` so [`verify_tenant`] reports the missing row.
+pub async fn append_or_record_failure(d1: &D1Database, tenant_id: &str, entry: &AuditEntry) {
+    let Err(e) = append(d1, tenant_id, entry).await else {
+        return;
+    };
+    worker::console_log!("WARN: audit append failed for {tenant_id}: {e:?}");
+    let failure = AuditAppendFailure {
+        id: crate::generate_id().unwrap_or_default(),
+        tenant_id: tenant_id.to_string(),
+        actor: entry.actor.clone(),
+        method: entry.method.clone(),
+        path: entry.path.clone(),
+        status: entry.status,
+        error: e.to_string(),
+        created_at: db::now_iso(),
+    };
+    if let Err(e) = db::insert_audit_append_failure(d1, &failure).await {
+        worker::console_log!("ERROR: audit append failure not recorded for {tenant_id}: {e:?}");
+    }
+}
+
+/// Verify up to `max_rows` rows of the tenant's chain, plus any entries
+/// that failed to append. Starts at seq 1, or right after `after`: the
+/// `(head_seq, head_hash)` of an earlier verification, which the caller
+/// keeps so a long chain is never re-read and a rewritten prefix still
+/// breaks the first link checked. The response is `complete: false` when
+/// rows remain past its head.
+pub async fn verify_tenant(
+    d1: &D1Database,
+    tenant_id: &str,
+    after: Option<(i64, &str)>,
+    max_rows: u32,
+) -> Result<AuditVerifyResponse> {
+    let mut verifier = match after {
+        Some((seq, hash)) => ChainVerifier::resume(seq, hash),
+        None => ChainVerifier::default(),
+    };
+    for failure in db::list_audit_append_failures(d1, tenant_id, VERIFY_PAGE).await? {
+        verifier.append_failed(&failure);
+    }
+    let mut after_seq = after.map_or(0, |(seq, _)| seq);
+    let mut remaining = max_rows;
+    let complete = loop {
+        if remaining == 0 {
+            break db::list_audit_ascending(d1, tenant_id, after_seq, 1)
+                .await?
+                .is_empty();
+        }
+        let limit = remaining.min(VERIFY_PAGE);
+        let page = db::list_audit_ascending(d1, tenant_id, after_seq, limit).await?;
+        for record in &page {
+            verifier.push(record);
+        }
+        remaining -= page.len() as u32;
+        match page.last() {
+            Some(last) if page.len() as u32 == limit => after_seq = last.seq,
+            _ => break true,
+        }
+    };
+    Ok(verifier.finish(tenant_id, complete))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str) -> AuditEntry {
+        AuditEntry {
+            actor: "tenant:acme:admin".to_string(),
+            method: "POST".to_string(),
+            route: "/v1/tenants/:tenant_id/suspend".to_string(),
+            path: path.to_string(),
+            target_ids: [("tenant_id".to_string(), "acme".to_string())].into(),
+            status: 200,
+            outcome: "success",
+            error_code: None,
+        }
+    }
+
+    fn chain(n: usize) -> Vec<AuditRecord> {
+        let mut out: Vec<AuditRecord> = Vec::new();
+        for i in 0..n {
+            let head = out.last().map(|r| (r.seq, r.hash.clone()));
+            out.push(next_record(
+                "acme",
+                head,
+                &entry(&format!("/v1/tenants/acme/{i}")),
+                format!("id-{i}"),
+                format!("2026-01-01T00:00:0{i}.000Z"),
+            ));
+        }
+        out
+    }
+
+    fn verify(records: &[AuditRecord]) -> AuditVerifyResponse {
+        let mut v = ChainVerifier::default();
+        records.iter().for_each(|r| v.push(r));
+        v.finish("acme", true)
+    }
+
+    fn kinds(resp: &AuditVerifyResponse) -> Vec<(i64, &str)> {
+        resp.issues
+            .iter()
+            .map(|i| (i.seq, i.kind.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn chain_starts_at_genesis_and_links() {
+        let rows = chain(3);
+        assert_eq!(rows[0].seq, 1);
+        assert_eq!(rows[0].prev_hash, GENESIS_HASH);
+        assert_eq!(rows[1].prev_hash, rows[0].hash);
+        assert_eq!(rows[2].prev_hash, rows[1].hash);
+
+        let resp = verify(&rows);
+        assert!(resp.verified, "{:?}", resp.issues);
+        assert_eq!(resp.records_checked, 3);
+        assert_eq!(resp.head_seq, 3);
+        assert_eq!(resp.head_hash, rows[2].hash);
+    }
+
+    #[test]
+    fn resumed_verification_checks_only_rows_after_the_anchor() {
+        let rows = chain(4);
+        let mut v = ChainVerifier::resume(2, &rows[1].hash);
+        rows[2..].iter().for_each(|r| v.push(r));
+        let resp = v.finish("acme", true);
+        assert!(resp.verified, "{:?}", resp.issues);
+        assert_eq!(resp.records_checked, 2);
+        assert_eq!(resp.head_seq, 4);
+        assert_eq!(resp.head_hash, rows[3].hash);
+
+        // A prefix rewritten since the anchor was taken breaks the first
+        // link checked.
+        let mut v = ChainVerifier::resume(2, &rows[0].hash);
+        rows[2..].iter().for_each(|r| v.push(r));
+        assert_eq!(kinds(&v.finish("acme", true)), vec![(3, "broken_link")]);
+    }
+
+    #[test]
+    fn verify_anchor_needs_both_parts() {
+        let hash = "AB".repeat(32);
+        assert_eq!(parse_verify_anchor(None, None), Ok(None));
+        assert_eq!(
+            parse_verify_anchor(Some("7"), Some(&hash)),
+            Ok(Some((7, "ab".repeat(32))))
+        );
+        assert!(parse_verify_anchor(Some("7"), None).is_err());
+        assert!(parse_verify_anchor(None, Some(&hash)).is_err());
+        assert!(parse_verify_anchor(Some("-1"), Some(&hash)).is_err());
+        assert!(parse_verify_anchor(Some("7"), Some("abc")).is_err());
+    }
+
+    #[test]
+    fn empty_chain_verifies() {
+        let resp = verify(&[]);
+        assert!(resp.verified);
+        assert_eq!(resp.head_seq, 0);
+        assert_eq!(resp.head_hash, GENESIS_HASH);
+    }
+
+    #[test]
+    fn edited_row_is_a_hash_mismatch() {
+        let mut rows = chain(3);
+        rows[1].actor = "tenant:acme:viewer".to_string();
+        assert_eq!(kinds(&verify(&rows)), vec![(2, "hash_mismatch")]);
+    }
+
+    #[test]
+    fn rehashed_edit_breaks_the_next_link() {
+        let mut rows = chain(3);
+        rows[1].status = 500;
+        rows[1].hash = record_hash(&rows[1]);
+        assert_eq!(kinds(&verify(&rows)), vec![(3, "broken_link")]);
+    }
+
+    #[test]
+    fn removed_row_is_a_gap_and_broken_link() {
+        let mut rows = chain(3);
+        rows.remove(1);
+        assert_eq!(kinds(&verify(&rows)), vec![(3, "gap"), (3, "broken_link")]);
+    }
+
+    #[test]
+    fn failed_append_fails_verification_of_an_intact_chain() {
+        let rows = chain(2);
+        let mut v = ChainVerifier::default();
+        v.append_failed(&AuditAppendFailure {
+            id: "f-1".to_string(),
+            tenant_id: "acme".to_string(),
+            actor: "tenant:acme:admin".to_string(),
+            method: "DELETE".to_string(),
+            path: "/v1/tenants/acme".to_string(),
+            status: 200,
+            error: "D1 unavailable".to_string(),
+            created_at: "2026-01-01T00:00:05.000Z".to_string(),
+        });
+        rows.iter().for_each(|r| v.push(r));
+        let resp = v.finish("acme", true);
+        assert!(!resp.verified);
+        assert_eq!(resp.records_checked, 2);
+        assert_eq!(kinds(&resp), vec![(0, "append_failed")]);
+        assert!(resp.issues[0].detail.contains("DELETE /v1/tenants/acme"));
+    }
+
+    #[test]
+    fn outcome_classifies_denials_separately() {
+        assert_eq!(outcome_for(201), "success");
+        assert_eq!(outcome_for(403), "denied");
+        assert_eq!(outcome_for(429), "denied");
+        assert_eq!(outcome_for(409), "failure");
+        assert_eq!(outcome_for(500), "failure");
+    }
+
+    #[test]
+    fn only_mutating_methods_are_audited() {
+        assert!(is_audited(&Method::Post));
+        assert!(is_audited(&Method::Delete));
+        assert!(!is_audited(&Method::Get));
+        assert!(!is_audited(&Method::Head));
+    }
+}
@@ -0,0 +1,542 @@
+//! Signed bearer-token authentication (WS8).
+//!
+//! Callers present `Authorization: Bearer <jwt>`; the worker verifies the
+//! signature against keys loaded from secret bindings and derives the tenant
+//! identity from the token claims instead of trusting `x-tenant-*` headers.
+//!
+//! Supported algorithms:
+//! - `HS256` — shared secret in the `AUTH_JWT_HS256_SECRET` secret.
+//! - `EdDSA` (Ed25519) — 32-byte public key in the
+//!   `AUTH_JWT_ED25519_PUBLIC_KEY` secret (hex or base64).
+//!
+//! Anything else — including `alg: none` — is rejected before any signature
+//! work happens. The claim payload is the `tenant_security::TokenClaims`
+//! shape plus the registered `exp` / `nbf` / `iss` / `aud` claims:
+//!
+//! ```json
+//! { "tenant_id": "acme", "role": "contributor", "exp": 1767225600,
+//!   "sub": "jane", "scoped_permissions": ["read", "write"], "federation": false }
+//! ```
+//!
+//! `sub` is optional and names the person or machine holding the token;
+//! routes that act for a specific principal (play approvals, agent claims)
+//! require it.
+//!
+//! Verification is pure so the whole matrix is unit-tested on the host;
+//! `JwtKeys::from_env` is the only piece that touches the Workers runtime.
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::fmt;
+
+use crate::tenant_security::TokenClaims;
+
+pub const AUTHORIZATION_HEADER: &str = "authorization";
+
+/// Secret binding holding the HS256 shared key (raw UTF-8 bytes).
+const HS256_SECRET_BINDING: &str = "AUTH_JWT_HS256_SECRET";
+/// Secret binding holding the Ed25519 public key (hex or base64, 32 bytes).
+const ED25519_PUBLIC_KEY_BINDING: &str = "AUTH_JWT_ED25519_PUBLIC_KEY";
+/// Optional plain vars pinning the expected `iss` / `aud` claims.
+const ISSUER_VAR: &str = "AUTH_JWT_ISSUER";
+const AUDIENCE_VAR: &str = "AUTH_JWT_AUDIENCE";
+
+/// Tolerated clock skew between the token issuer and the edge, in seconds.
+pub(crate) const CLOCK_SKEW_LEEWAY_SECS: u64 = 60;
+
+/// Verification keys and expected registered claims for bearer tokens.
+#[derive(Clone, Default)]
+pub struct JwtKeys {
+    pub hs256_secret: Option<Vec<u8>>,
+    pub ed25519_public: Option<VerifyingKey>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+}
+
+impl JwtKeys {
+    /// Load keys from the worker's secret bindings. Missing bindings are
+    /// not an error here — a token whose `alg` has no configured key is
+    /// rejected at verification time with [`AuthError::NoKeyConfigured`].
+    pub fn from_env(env: &worker::Env) -> worker::Result<Self> {
+        let hs256_secret = env
+            .secret(HS256_SECRET_BINDING)
+            .ok()
+            .map(|s| s.to_string().into_bytes())
+            .filter(|s| !s.is_empty());
+        let ed25519_public = match env.secret(ED25519_PUBLIC_KEY_BINDING).ok() {
+            Some(raw) => Some(parse_ed25519_public_key(&raw.to_string()).map_err(|e| {
+                worker::Error::RustError(format!("{ED25519_PUBLIC_KEY_BINDING}: {e}"))
+            })?),
+            None => None,
+        };
+        let var = |name: &str| {
+            env.var(name)
+                .ok()
+                .map(|v| v.to_string())
+                .filter(|v| !v.trim().is_empty())
+        };
+        Ok(Self {
+            hs256_secret,
+            ed25519_public,
+            issuer: var(ISSUER_VAR),
+            audience: var(AUDIENCE_VAR),
+        })
+    }
+}
+
+/// Decode an Ed25519 public key given as 64 hex chars or base64 (standard
+/// or URL-safe alphabet).
+pub fn parse_ed25519_public_key(raw: &str) -> Result<VerifyingKey, AuthError> {
+    let raw = raw.trim();
+    let bytes = hex::decode(raw)
+        .ok()
+        .or_else(|| STANDARD.decode(raw).ok())
+        .or_else(|| URL_SAFE_NO_PAD.decode(raw.trim_end_matches('=')).ok())
+        .ok_or(AuthError::Malformed(
+            "ed25519 public key is not hex or base64",
+        ))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| AuthError::Malformed("ed25519 public key must be 32 bytes"))?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|_| AuthError::Malformed("ed25519 public key is not a valid point"))
+}
+
+/// Claims recovered from a token whose signature and validity window checked out.
+#[derive(Debug, Clone)]
+pub struct VerifiedToken {
+    pub claims: TokenClaims,
+    pub federation_allowed: bool,
+    /// The `sub` claim, when present and non-empty.
+    pub subject: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    MissingToken,
+    Malformed(&'static str),
+    UnsupportedAlgorithm(String),
+    NoKeyConfigured(&'static str),
+    BadSignature,
+    Expired,
+    NotYetValid,
+    IssuerMismatch,
+    AudienceMismatch,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "missing bearer token"),
+            AuthError::Malformed(why) => write!(f, "malformed token: {why}"),
+            AuthError::UnsupportedAlgorithm(alg) => write!(f, "unsupported token alg {alg:?}"),
+            AuthError::NoKeyConfigured(alg) => {
+                write!(f, "no verification key configured for {alg}")
+            }
+            AuthError::BadSignature => write!(f, "token signature is invalid"),
+            AuthError::Expired => write!(f, "token has expired"),
+            AuthError::NotYetValid => write!(f, "token is not yet valid"),
+            AuthError::IssuerMismatch => write!(f, "token issuer is not trusted"),
+            AuthError::AudienceMismatch => write!(f, "token audience does not match"),
+        }
+    }
+}
+
+/// Extract the token from an `Authorization` header value. The scheme is
+/// case-insensitive per RFC 7235; an empty token is treated as absent.
+pub fn bearer_token(header_value: &str) -> Option<&str> {
+    let (scheme, token) = header_value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let token = token.trim();
+    (!token.is_empty()).then_some(token)
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, expected: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == expected,
+            Audience::Many(auds) => auds.iter().any(|a| a == expected),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JwtClaims {
+    #[serde(flatten)]
+    claims: TokenClaims,
+    exp: u64,
+    #[serde(default)]
+    nbf: Option<u64>,
+    #[serde(default)]
+    iss: Option<String>,
+    #[serde(default)]
+    aud: Option<Audience>,
+    #[serde(default)]
+    sub: Option<String>,
+    #[serde(default)]
+    federation: bool,
+}
+
+/// Verify a compact JWS and return its claims. `now_secs` is Unix seconds;
+/// `exp` is mandatory so a leaked token cannot live forever.
+pub fn verify_token(
+    token: &str,
+    keys: &JwtKeys,
+    now_secs: u64,
+) -> Result<VerifiedToken, AuthError> {
+    let mut parts = token.split('.');
+    let (Some(header_b64), Some(payload_b64), Some(sig_b64), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(AuthError::Malformed(
+            "expected three dot-separated segments",
+        ));
+    };
+
+    let header: JwtHeader = decode_segment(header_b64, "header is not base64url JSON")?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(sig_b64)
+        .map_err(|_| AuthError::Malformed("signature is not base64url"))?;
+    let signing_input = &token[..header_b64.len() + 1 + payload_b64.len()];
+
+    match header.alg.as_str() {
+        "HS256" => {
+            let secret = keys
+                .hs256_secret
+                .as_deref()
+                .ok_or(AuthError::NoKeyConfigured("HS256"))?;
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+                .map_err(|_| AuthError::NoKeyConfigured("HS256"))?;
+            mac.update(signing_input.as_bytes());
+            // `verify_slice` is constant-time.
+            mac.verify_slice(&signature)
+                .map_err(|_| AuthError::BadSignature)?;
+        }
+        "EdDSA" => {
+            let key = keys
+                .ed25519_public
+                .as_ref()
+                .ok_or(AuthError::NoKeyConfigured("EdDSA"))?;
+            let signature =
+                Signature::from_slice(&signature).map_err(|_| AuthError::BadSignature)?;
+            key.verify_strict(signing_input.as_bytes(), &signature)
+                .map_err(|_| AuthError::BadSignature)?;
+        }
+        other => return Err(AuthError::UnsupportedAlgorithm(other.to_string())),
+    }
+
+    // Claims are only parsed after the signature checks out so an attacker
+    // cannot probe the claim parser with unsigned input.
+    let claims: JwtClaims = decode_segment(payload_b64, "payload is not valid claims JSON")?;
+    if claims.exp.saturating_add(CLOCK_SKEW_LEEWAY_SECS) <= now_secs {
+        return Err(AuthError::Expired);
+    }
+    if let Some(nbf) = claims.nbf {
+        if nbf > now_secs.saturating_add(CLOCK_SKEW_LEEWAY_SECS) {
+            return Err(AuthError::NotYetValid);
+        }
+    }
+    if let Some(expected) = &keys.issuer {
+        if claims.iss.as_deref() != Some(expected.as_str()) {
+            return Err(AuthError::IssuerMismatch);
+        }
+    }
+    if let Some(expected) = &keys.audience {
+        if !claims
+            .aud
+            .as_ref()
+            .is_some_and(|aud| aud.contains(expected))
+        {
+            return Err(AuthError::AudienceMismatch);
+        }
+    }
+    if claims.claims.tenant_id.trim().is_empty() {
+        return Err(AuthError::Malformed("tenant_id claim is empty"));
+    }
+
+    Ok(VerifiedToken {
+        claims: claims.claims,
+        federation_allowed: claims.federation,
+        subject: claims.sub.filter(|sub| !sub.trim().is_empty()),
+    })
+}
+
+fn decode_segment<T: serde::de::DeserializeOwned>(
+    segment: &str,
+    err: &'static str,
+) -> Result<T, AuthError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(segment)
+        .map_err(|_| AuthError::Malformed(err))?;
+    serde_json::from_slice(&bytes).map_err(|_| AuthError::Malformed(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenant_security::{Permission, Role};
+    use ed25519_dalek::{Signer, SigningKey};
+    use serde_json::json;
+
+    const NOW: u64 = 1_800_000_000;
+    const SECRET: &[u8] = b"test-shared-secret";
+
+    fn b64(value: &serde_json::Value) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(value).unwrap())
+    }
+
+    fn hs256_token(claims: serde_json::Value, secret: &[u8]) -> String {
+        let input = format!(
+            "{}.{}",
+            b64(&json!({"alg": "HS256", "typ": "JWT"})),
+            b64(&claims)
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(input.as_bytes());
+        let sig = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{input}.{sig}")
+    }
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7u8; 32])
+    }
+
+    fn eddsa_token(claims: serde_json::Value) -> String {
+        let input = format!("{}.{}", b64(&json!({"alg": "EdDSA"})), b64(&claims));
+        let sig = signing_key().sign(input.as_bytes());
+        format!("{input}.{}", URL_SAFE_NO_PAD.encode(sig.to_bytes()))
+    }
+
+    fn hs_keys() -> JwtKeys {
+        JwtKeys {
+            hs256_secret: Some(SECRET.to_vec()),
+            ..JwtKeys::default()
+        }
+    }
+
+    fn base_claims() -> serde_json::Value {
+        json!({"tenant_id": "acme", "role": "contributor", "exp": NOW + 300})
+    }
+
+    // ── bearer_token ───────────────────────────────────────────
+
+    #[test]
+    fn bearer_token_parses_scheme_case_insensitively() {
+        assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("bearer   abc "), Some("abc"));
+        assert_eq!(bearer_token("Basic abc"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token("abc"), None);
+    }
+
+    // ── HS256 ──────────────────────────────────────────────────
+
+    #[test]
+    fn hs256_valid_token_yields_claims() {
+        let token = hs256_token(base_claims(), SECRET);
+        let verified = verify_token(&token, &hs_keys(), NOW).unwrap();
+        assert_eq!(verified.claims.tenant_id, "acme");
+        assert_eq!(verified.claims.role, Role::Contributor);
+        assert!(verified.claims.scoped_permissions.is_none());
+        assert!(!verified.federation_allowed);
+        assert!(verified.subject.is_none());
+    }
+
+    #[test]
+    fn subject_claim_is_carried_when_non_empty() {
+        let mut claims = base_claims();
+        claims["sub"] = json!("jane");
+        let verified = verify_token(&hs256_token(claims.clone(), SECRET), &hs_keys(), NOW).unwrap();
+        assert_eq!(verified.subject.as_deref(), Some("jane"));
+
+        claims["sub"] = json!("  ");
+        let verified = verify_token(&hs256_token(claims, SECRET), &hs_keys(), NOW).unwrap();
+        assert!(verified.subject.is_none());
+    }
+
+    #[test]
+    fn hs256_wrong_secret_is_rejected() {
+        let token = hs256_token(base_claims(), b"some-other-secret");
+        assert_eq!(
+            verify_token(&token, &hs_keys(), NOW).unwrap_err(),
+            AuthError::BadSignature
+        );
+    }
+
+    #[test]
+    fn tampered_payload_is_rejected() {
+        let token = hs256_token(base_claims(), SECRET);
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let forged = b64(&json!({"tenant_id": "acme", "role": "admin", "exp": NOW + 300}));
+        parts[1] = &forged;
+        assert_eq!(
+            verify_token(&parts.join("."), &hs_keys(), NOW).unwrap_err(),
+            AuthError::BadSignature
+        );
+    }
+
+    #[test]
+    fn scoped_permissions_and_federation_claims_round_trip() {
+        let mut claims = base_claims();
+        claims["role"] = json!("system_service");
+        claims["scoped_permissions"] = json!(["read", "write"]);
+        claims["federation"] = json!(true);
+        let verified = verify_token(&hs256_token(claims, SECRET), &hs_keys(), NOW).unwrap();
+        assert_eq!(verified.claims.role, Role::SystemService);
+        let scoped = verified.claims.scoped_permissions.unwrap();
+        assert!(scoped.contains(&Permission::Write));
+        assert!(!scoped.contains(&Permission::Admin));
+        assert!(verified.federation_allowed);
+    }
+
+    // ── Algorithm confusion ────────────────────────────────────
+
+    #[test]
+    fn alg_none_is_rejected() {
+        let token = format!("{}.{}.", b64(&json!({"alg": "none"})), b64(&base_claims()));
+        assert_eq!(
+            verify_token(&token, &hs_keys(), NOW).unwrap_err(),
+            AuthError::UnsupportedAlgorithm("none".to_string())
+        );
+    }
+
+    #[test]
+    fn alg_without_configured_key_is_rejected() {
+        let token = eddsa_token(base_claims());
+        assert_eq!(
+            verify_token(&token, &hs_keys(), NOW).unwrap_err(),
+            AuthError::NoKeyConfigured("EdDSA")
+        );
+    }
+
+    // ── EdDSA ──────────────────────────────────────────────────
+
+    #[test]
+    fn eddsa_valid_token_yields_claims() {
+        let keys = JwtKeys {
+            ed25519_public: Some(signing_key().verifying_key()),
+            ..JwtKeys::default()
+        };
+        let verified = verify_token(&eddsa_token(base_claims()), &keys, NOW).unwrap();
+        assert_eq!(verified.claims.tenant_id, "acme");
+    }
+
+    #[test]
+    fn eddsa_wrong_key_is_rejected() {
+        let keys = JwtKeys {
+            ed25519_public: Some(SigningKey::from_bytes(&[9u8; 32]).verifying_key()),
+            ..JwtKeys::default()
+        };
+        assert_eq!(
+            verify_token(&eddsa_token(base_claims()), &keys, NOW).unwrap_err(),
+            AuthError::BadSignature
+        );
+    }
+
+    #[test]
+    fn parse_ed25519_public_key_accepts_hex_and_base64() {
+        let vk = signing_key().verifying_key();
+        let hex_key = hex::encode(vk.to_bytes());
+        let b64_key = STANDARD.encode(vk.to_bytes());
+        assert_eq!(parse_ed25519_public_key(&hex_key).unwrap(), vk);
+        assert_eq!(parse_ed25519_public_key(&b64_key).unwrap(), vk);
+        assert!(parse_ed25519_public_key("deadbeef").is_err());
+    }
+
+    // ── Validity window and registered claims ──────────────────
+
+    #[test]
+    fn expired_token_is_rejected_after_leeway() {
+        let mut claims = base_claims();
+        claims["exp"] = json!(NOW - CLOCK_SKEW_LEEWAY_SECS);
+        assert_eq!(
+            verify_token(&hs256_token(claims, SECRET), &hs_keys(), NOW).unwrap_err(),
+            AuthError::Expired
+        );
+    }
+
+    #[test]
+    fn expiry_within_leeway_is_accepted() {
+        let mut claims = base_claims();
+        claims["exp"] = json!(NOW - 10);
+        assert!(verify_token(&hs256_token(claims, SECRET), &hs_keys(), NOW).is_ok());
+    }
+
+    #[test]
+    fn missing_exp_is_rejected() {
+        let claims = json!({"tenant_id": "acme", "role": "reader"});
+        assert!(matches!(
+            verify_token(&hs256_token(claims, SECRET), &hs_keys(), NOW).unwrap_err(),
+            AuthError::Malformed(_)
+        ));
+    }
+
+    #[test]
+    fn future_nbf_is_rejected() {
+        let mut claims = base_claims();
+        claims["nbf"] = json!(NOW + 3600);
+        assert_eq!(
+            verify_token(&hs256_token(claims, SECRET), &hs_keys(), NOW).unwrap_err(),
+            AuthError::NotYetValid
+        );
+    }
+
+    #[test]
+    fn issuer_and_audience_are_enforced_when_configured() {
+        let keys = JwtKeys {
+            issuer: Some("https://auth.stevedores.org".to_string()),
+            audience: Some("data-fabric".to_string()),
+            ..hs_keys()
+        };
+        let mut claims = base_claims();
+        assert_eq!(
+            verify_token(&hs256_token(claims.clone(), SECRET), &keys, NOW).unwrap_err(),
+            AuthError::IssuerMismatch
+        );
+        claims["iss"] = json!("https://auth.stevedores.org");
+        claims["aud"] = json!(["other", "data-fabric"]);
+        assert!(verify_token(&hs256_token(claims.clone(), SECRET), &keys, NOW).is_ok());
+        claims["aud"] = json!("other");
+        assert_eq!(
+            verify_token(&hs256_token(claims, SECRET), &keys, NOW).unwrap_err(),
+            AuthError::AudienceMismatch
+        );
+    }
+
+    #[test]
+    fn empty_tenant_id_is_rejected() {
+        let mut claims = base_claims();
+        claims["tenant_id"] = json!("  ");
+        assert!(verify_token(&hs256_token(claims, SECRET), &hs_keys(), NOW).is_err());
+    }
+
+    #[test]
+    fn wrong_segment_count_is_malformed() {
+        assert!(matches!(
+            verify_token("a.b", &hs_keys(), NOW).unwrap_err(),
+            AuthError::Malformed(_)
+        ));
+        assert!(matches!(
+            verify_token("a.b.c.d", &hs_keys(), NOW).unwrap_err(),
+            AuthError::Malformed(_)
+        ));
+    }
+}
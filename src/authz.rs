@@ -0,0 +1,488 @@
+//! Route-level authorization table (WS8).
+//!
+//! Every route registered in `lib.rs` declares the resource type it touches
+//! and the [`Permission`] it needs. `tenant::authorize` resolves the request
+//! against this table and hands the result to
+//! [`tenant_security::evaluate_authz`], so the role matrix (including the
+//! per-resource overrides in `AuthzPolicy::default`) is the single source of
+//! truth — e.g. a `system_service` CI token can write events and CI check
+//! runs but not policy rules or checkpoints.
+//!
+//! `route_table_covers_every_router_path` keeps this table in lock-step
+//! with the router: adding a route without an entry fails the test suite.
+
+use std::collections::BTreeMap;
+
+use worker::Method;
+use worker::Method::{Delete, Get, Patch, Post, Put};
+
+use crate::tenant_security::Permission;
+use crate::tenant_security::Permission::{Admin, Federation, Read, Write};
+
+/// Authorization requirement declared by one route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteRule {
+    pub method: Method,
+    /// Router template, e.g. `/v1/runs/:run_id/pause`.
+    pub pattern: &'static str,
+    pub resource_type: &'static str,
+    pub permission: Permission,
+}
+
+const fn rule(
+    method: Method,
+    pattern: &'static str,
+    resource_type: &'static str,
+    permission: Permission,
+) -> RouteRule {
+    RouteRule {
+        method,
+        pattern,
+        resource_type,
+        permission,
+    }
+}
+
+pub const ROUTE_RULES: &[RouteRule] = &[
+    // ── Tenants (WS8) ──
+    rule(Post, "/v1/tenants/provision", "tenant", Admin),
+    rule(Post, "/v1/tenants/:tenant_id/suspend", "tenant", Admin),
+    rule(Post, "/v1/tenants/:tenant_id/resume", "tenant", Admin),
+    rule(Post, "/v1/tenants/:tenant_id/export", "tenant", Admin),
+    rule(
+        Post,
+        "/v1/tenants/:tenant_id/export/:export_id",
+        "tenant",
+        Admin,
+    ),
+    rule(Delete, "/v1/tenants/:tenant_id", "tenant", Admin),
+    rule(
+        Get,
+        "/v1/tenants/:tenant_id/deletion-report",
+        "tenant",
+        Admin,
+    ),
+    rule(Post, "/v1/tenants/:tenant_id/keys/rotate", "tenant", Admin),
+    rule(Put, "/v1/tenants/:tenant_id/settings", "tenant", Admin),
+    rule(Get, "/v1/audit", "audit", Admin),
+    rule(Get, "/v1/audit/verify", "audit", Admin),
+    // ── Runs (WS2) + AIVCS ──
+    rule(Post, "/v1/runs", "run", Write),
+    rule(Get, "/v1/runs", "run", Read),
+    rule(Get, "/v1/runs/:id", "run", Read),
+    rule(Get, "/v1/pull-requests/:id", "pull_request", Read),
+    rule(Post, "/v1/runs/:run_id/pause", "run", Write),
+    rule(Post, "/v1/runs/:run_id/resume", "run", Write),
+    rule(Post, "/v1/runs/:run_id/cancel", "run", Write),
+    rule(Get, "/v1/metrics/pilot", "metrics", Read),
+    rule(Post, "/v1/runs/:run_id/tasks", "task", Write),
+    rule(Get, "/v1/runs/:run_id/tasks", "task", Read),
+    rule(Post, "/v1/plans", "plan", Write),
+    rule(Post, "/v1/tool-calls", "tool_call", Write),
+    rule(Post, "/v1/releases", "release", Write),
+    rule(Post, "/v1/events", "event", Write),
+    rule(Get, "/v1/events", "event", Read),
+    // ── Memory (WS5) ── retrieval and context packs are reads even
+    // though they are POSTs (the body carries the query).
+    rule(Post, "/v1/memory/index", "memory", Write),
+    rule(Post, "/v1/memory/retrieve", "memory", Read),
+    rule(Post, "/v1/memory/context-pack", "memory", Read),
+    rule(Post, "/v1/memory/:id/retire", "memory", Write),
+    rule(Post, "/v1/memory/gc", "memory", Write),
+    rule(Post, "/v1/memory/retrieval-feedback", "memory", Write),
+    rule(Get, "/v1/memory/eval/summary", "memory", Read),
+    rule(Post, "/v1/memory", "memory", Write),
+    rule(Get, "/v1/memory/threads/:thread_id", "memory", Read),
+    rule(Get, "/v1/context-pack/threads/:thread_id", "memory", Read),
+    // ── Plays ──
+    rule(Post, "/v1/plays", "play", Write),
+    rule(Get, "/v1/plays", "play", Read),
+    rule(Get, "/v1/plays/:name", "play", Read),
+    rule(Put, "/v1/plays/:name", "play", Write),
+    rule(Delete, "/v1/plays/:name", "play", Write),
+    rule(Get, "/v1/plays/:name/versions", "play", Read),
+    rule(Get, "/v1/plays/:name/diff", "play", Read),
+    rule(Post, "/v1/plays/:name/launch", "play", Write),
+    rule(Get, "/v1/plays/runs/:run_id", "play", Read),
+    rule(
+        Get,
+        "/v1/plays/runs/:run_id/tasks/:task_id/result",
+        "play",
+        Read,
+    ),
+    rule(
+        Post,
+        "/v1/plays/runs/:run_id/approvals/:task_id",
+        "human_decision",
+        Write,
+    ),
+    // ── Artifacts ──
+    rule(Put, "/v1/artifacts/:key", "artifact", Write),
+    rule(Get, "/v1/artifacts/:key", "artifact", Read),
+    // ── Policy (WS4) ──
+    rule(Post, "/v1/policies/check", "policy_decision", Write),
+    rule(Post, "/v1/policies/rules", "policy_rule", Write),
+    rule(Get, "/v1/policies/rules", "policy_rule", Read),
+    rule(Get, "/v1/policies/rules/:id", "policy_rule", Read),
+    rule(Patch, "/v1/policies/rules/:id", "policy_rule", Write),
+    rule(Delete, "/v1/policies/rules/:id", "policy_rule", Write),
+    rule(Get, "/v1/policies/decisions", "policy_decision", Read),
+    rule(Get, "/v1/verification/evidence", "verification", Read),
+    rule(
+        Put,
+        "/v1/policies/definitions/:version",
+        "policy_definition",
+        Write,
+    ),
+    rule(
+        Post,
+        "/v1/policies/activate/:version",
+        "policy_definition",
+        Admin,
+    ),
+    rule(Get, "/v1/policies/active", "policy_definition", Read),
+    rule(Post, "/v1/retention/run", "retention", Write),
+    // ── Agent tasks (MCP) ──
+    rule(Post, "/v1/tasks", "task", Write),
+    rule(Post, "/mcp/task/next", "task", Write),
+    rule(Get, "/mcp/task/next", "task", Read),
+    rule(Get, "/mcp/task/ws", "task", Write),
+    rule(Post, "/mcp/task/:id/heartbeat", "task", Write),
+    rule(Post, "/mcp/task/:id/complete", "task", Write),
+    rule(Post, "/mcp/task/:id/fail", "task", Write),
+    rule(Post, "/v1/tasks/:id/cancel", "task", Write),
+    rule(Get, "/v1/tasks/queue", "task", Read),
+    rule(Get, "/v1/tasks/dead-letter", "task", Read),
+    rule(Post, "/v1/tasks/dead-letter/:id/requeue", "task", Write),
+    rule(Post, "/v1/tasks/dead-letter/:id/discard", "task", Write),
+    rule(Post, "/v1/task-schedules", "task_schedule", Write),
+    rule(Get, "/v1/task-schedules", "task_schedule", Read),
+    rule(Delete, "/v1/task-schedules/:id", "task_schedule", Write),
+    rule(Post, "/v1/agents", "agent", Write),
+    rule(Get, "/v1/agents", "agent", Read),
+    rule(Post, "/v1/telemetry", "telemetry", Write),
+    rule(Post, "/v1/reasoning-traces", "reasoning_trace", Write),
+    rule(Get, "/v1/reasoning-traces", "reasoning_trace", Read),
+    rule(
+        Get,
+        "/v1/reasoning-traces/:id/payload/:field",
+        "reasoning_trace",
+        Read,
+    ),
+    // ── Checkpoints ──
+    rule(Post, "/v1/checkpoints", "checkpoint", Write),
+    rule(
+        Get,
+        "/v1/checkpoints/threads/:thread_id",
+        "checkpoint",
+        Read,
+    ),
+    rule(
+        Get,
+        "/v1/checkpoints/threads/:thread_id/tree",
+        "checkpoint",
+        Read,
+    ),
+    rule(Get, "/v1/checkpoints/:id", "checkpoint", Read),
+    rule(Delete, "/v1/checkpoints/:id", "checkpoint", Write),
+    rule(Get, "/v1/checkpoints/:id/lineage", "checkpoint", Read),
+    rule(
+        Get,
+        "/v1/checkpoints/:id/diff/:other_id",
+        "checkpoint",
+        Read,
+    ),
+    rule(Post, "/v1/checkpoints/:id/fork", "checkpoint", Write),
+    // ── Provenance + gold layer (WS3) ──
+    rule(Get, "/v1/traces/:run_id", "trace", Read),
+    rule(Get, "/v1/traces/:run_id/lineage", "trace", Read),
+    rule(Get, "/v1/provenance/:kind/:id", "provenance", Read),
+    rule(Get, "/v1/runs/:run_id/summary", "run_summary", Read),
+    rule(Get, "/v1/gold/run-summaries", "run_summary", Read),
+    rule(Get, "/v1/gold/runs/:run_id/task-graph", "run_summary", Read),
+    // ── Federation (WS8) ──
+    rule(Get, "/v1/federation/config", "federation", Read),
+    rule(Put, "/v1/federation/config", "federation", Admin),
+    rule(
+        Get,
+        "/v1/federation/run-summaries",
+        "run_summary",
+        Federation,
+    ),
+    rule(Post, "/v1/replay/plan", "replay", Read),
+    rule(Post, "/v1/replay", "replay", Write),
+    // ── Integrations (WS6) ──
+    rule(Post, "/v1/integrations", "integration", Write),
+    rule(Get, "/v1/integrations", "integration", Read),
+    rule(Get, "/v1/integrations/:id", "integration", Read),
+    rule(Patch, "/v1/integrations/:id", "integration", Write),
+    rule(Delete, "/v1/integrations/:id", "integration", Write),
+    rule(
+        Post,
+        "/v1/integrations/oxidizedgraph/events",
+        "event",
+        Write,
+    ),
+    rule(Post, "/v1/integrations/aivcs/events", "event", Write),
+    rule(
+        Post,
+        "/v1/integrations/llama-rs/inference",
+        "telemetry",
+        Write,
+    ),
+    rule(
+        Post,
+        "/v1/integrations/llama-rs/telemetry",
+        "telemetry",
+        Write,
+    ),
+    rule(Post, "/v1/integrations/llama-rs/context", "memory", Read),
+    rule(Post, "/v1/graph-events", "event", Write),
+    // ── AIVCS projections (issue #148) ──
+    rule(Get, "/v1/change-sets", "change_set", Read),
+    rule(Get, "/v1/change-sets/:id", "change_set", Read),
+    rule(Get, "/v1/review-threads", "review_thread", Read),
+    rule(
+        Get,
+        "/v1/review-threads/:id/comments",
+        "review_thread",
+        Read,
+    ),
+    rule(Get, "/v1/review-threads/:id/anchors", "review_thread", Read),
+    rule(Get, "/v1/file-anchors", "file_anchor", Read),
+    rule(Get, "/v1/human-decisions", "human_decision", Read),
+    rule(Get, "/v1/ci-check-runs", "ci_check_run", Read),
+    rule(Get, "/v1/branches", "branch", Read),
+];
+
+/// Requirement resolved for a concrete request path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub resource_type: &'static str,
+    pub resource_id: String,
+    pub permission: Permission,
+}
+
+/// Resolve the requirement for `method` + `path`.
+///
+/// Literal segments beat `:param` segments, so `/v1/memory/gc` resolves to
+/// its own rule rather than `/v1/memory/:id/...`-style siblings. Paths with
+/// no declared rule fall back to the legacy prefix policy (tenant and policy
+/// activation subtrees need `Admin`; otherwise reads need `Read` and
+/// everything else `Write`) — the router 404s them anyway, but the request
+/// still has to be authorized before it gets that far.
+pub fn requirement_for(method: &Method, path: &str) -> Requirement {
+    let path_segments: Vec<&str> = split_path(path).collect();
+    if let Some(rule) = best_rule(method, &path_segments) {
+        return Requirement {
+            resource_type: rule.resource_type,
+            resource_id: first_param(rule.pattern, &path_segments)
+                .unwrap_or("*")
+                .to_string(),
+            permission: rule.permission,
+        };
+    }
+
+    let is_read = matches!(method, Method::Get | Method::Head | Method::Options);
+    let (resource_type, permission) =
+        if path.starts_with("/v1/tenants/") || path.starts_with("/v1/policies/activate") {
+            ("tenant", Permission::Admin)
+        } else if is_read {
+            ("*", Permission::Read)
+        } else {
+            ("*", Permission::Write)
+        };
+    Requirement {
+        resource_type,
+        resource_id: "*".to_string(),
+        permission,
+    }
+}
+
+/// Router template and `:param` values for a concrete request, e.g.
+/// `("/v1/runs/:run_id/pause", {"run_id": "r-1"})`. `None` for paths no
+/// route declares.
+pub fn route_params(
+    method: &Method,
+    path: &str,
+) -> Option<(&'static str, BTreeMap<String, String>)> {
+    let path_segments: Vec<&str> = split_path(path).collect();
+    let rule = best_rule(method, &path_segments)?;
+    let params = split_path(rule.pattern)
+        .zip(&path_segments)
+        .filter_map(|(p, s)| {
+            p.strip_prefix(':')
+                .map(|name| (name.to_string(), s.to_string()))
+        })
+        .collect();
+    Some((rule.pattern, params))
+}
+
+fn best_rule(method: &Method, path_segments: &[&str]) -> Option<&'static RouteRule> {
+    ROUTE_RULES
+        .iter()
+        .filter(|r| effective_method(&r.method) == effective_method(method))
+        .filter_map(|r| match_score(r.pattern, path_segments).map(|s| (s, r)))
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, rule)| rule)
+}
+
+/// HEAD is served by the GET handler, so it shares the GET rule.
+fn effective_method(method: &Method) -> &Method {
+    match method {
+        Method::Head => &Method::Get,
+        other => other,
+    }
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+/// Number of literal segments matched, or `None` when the template does not
+/// match. Used to prefer the most specific template.
+fn match_score(pattern: &str, path_segments: &[&str]) -> Option<usize> {
+    let pattern_segments: Vec<&str> = split_path(pattern).collect();
+    if pattern_segments.len() != path_segments.len() {
+        return None;
+    }
+    let mut literals = 0;
+    for (p, s) in pattern_segments.iter().zip(path_segments) {
+        if p.starts_with(':') {
+            continue;
+        }
+        if p != s {
+            return None;
+        }
+        literals += 1;
+    }
+    Some(literals)
+}
+
+fn first_param<'a>(pattern: &str, path_segments: &[&'a str]) -> Option<&'a str> {
+    split_path(pattern)
+        .zip(path_segments)
+        .find(|(p, _)| p.starts_with(':'))
+        .map(|(_, s)| *s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pull every `.get/.post/.put/.patch/.delete[_async]("/...")` template
+    /// out of the router source.
+    fn router_routes() -> Vec<(Method, String)> {
+        let src = include_str!("lib.rs");
+        let mut out = Vec::new();
+        for (name, method) in [
+            ("get", Method::Get),
+            ("post", Method::Post),
+            ("put", Method::Put),
+            ("patch", Method::Patch),
+            ("delete", Method::Delete),
+        ] {
+            for suffix in ["(", "_async("] {
+                let needle = format!(".{name}{suffix}");
+                let mut rest = src;
+                while let Some(idx) = rest.find(&needle) {
+                    rest = &rest[idx + needle.len()..];
+                    let arg = rest.trim_start();
+                    if let Some(lit) = arg.strip_prefix("\"/") {
+                        let end = lit.find('"').unwrap();
+                        out.push((method.clone(), format!("/{}", &lit[..end])));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn is_public(path: &str) -> bool {
+        matches!(path, "/" | "/health" | "/openapi.json" | "/docs")
+    }
+
+    #[test]
+    fn route_table_covers_every_router_path() {
+        let routes = router_routes();
+        assert!(routes.len() > 50, "router scan found too few routes");
+        for (method, pattern) in routes {
+            if is_public(&pattern) {
+                continue;
+            }
+            assert!(
+                ROUTE_RULES
+                    .iter()
+                    .any(|r| r.method == method && r.pattern == pattern),
+                "route {method:?} {pattern} has no authz rule in ROUTE_RULES"
+            );
+        }
+    }
+
+    #[test]
+    fn route_table_has_no_duplicates() {
+        for (i, a) in ROUTE_RULES.iter().enumerate() {
+            for b in &ROUTE_RULES[i + 1..] {
+                assert!(
+                    !(a.method == b.method && a.pattern == b.pattern),
+                    "duplicate rule for {:?} {}",
+                    a.method,
+                    a.pattern
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn literal_segments_win_over_params() {
+        let req = requirement_for(&Method::Get, "/v1/checkpoints/threads/t-1");
+        assert_eq!(req.resource_type, "checkpoint");
+        assert_eq!(req.resource_id, "t-1");
+
+        let gc = requirement_for(&Method::Post, "/v1/memory/gc");
+        assert_eq!(gc.permission, Permission::Write);
+        let retrieve = requirement_for(&Method::Post, "/v1/memory/retrieve");
+        assert_eq!(retrieve.permission, Permission::Read);
+    }
+
+    #[test]
+    fn resource_id_comes_from_first_param() {
+        let req = requirement_for(&Method::Post, "/v1/runs/run-9/pause");
+        assert_eq!(req.resource_type, "run");
+        assert_eq!(req.resource_id, "run-9");
+        assert_eq!(req.permission, Permission::Write);
+        assert_eq!(requirement_for(&Method::Get, "/v1/runs").resource_id, "*");
+    }
+
+    #[test]
+    fn route_params_name_every_param() {
+        let (pattern, params) =
+            route_params(&Method::Post, "/v1/tenants/acme/suspend").expect("declared route");
+        assert_eq!(pattern, "/v1/tenants/:tenant_id/suspend");
+        assert_eq!(params.get("tenant_id").map(String::as_str), Some("acme"));
+        assert!(route_params(&Method::Post, "/v1/nope").is_none());
+    }
+
+    #[test]
+    fn head_uses_get_rule() {
+        let req = requirement_for(&Method::Head, "/v1/artifacts/a.bin");
+        assert_eq!(req.resource_type, "artifact");
+        assert_eq!(req.permission, Permission::Read);
+    }
+
+    #[test]
+    fn undeclared_paths_fall_back_to_method_policy() {
+        assert_eq!(
+            requirement_for(&Method::Get, "/v1/unknown").permission,
+            Permission::Read
+        );
+        assert_eq!(
+            requirement_for(&Method::Post, "/v1/unknown").permission,
+            Permission::Write
+        );
+        assert_eq!(
+            requirement_for(&Method::Get, "/v1/tenants/list").permission,
+            Permission::Admin
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Capability expressions for matching agent tasks to agents.
+//!
+//! Agents advertise capabilities (from `RegisterAgent.capabilities` and the
+//! claim's `cap` parameter); tasks list requirements in `requires`. A task
+//! can be claimed only if every requirement matches.
+//!
+//! A capability is a `:`-separated path with an optional version after `@`
+//! or `=`: `build`, `gpu:none`, `repo:stevedores-org/data-fabric`,
+//! `tool:cargo-audit`, `rust@1.82.0`. Plain names (one segment, no version)
+//! are task types: an agent advertising none of them takes every type, as
+//! before structured capabilities existed.
+//!
+//! A requirement is one or more `|`-separated selectors, optionally
+//! negated with a leading `!`:
+//!
+//! - a path, matching any capability it is a prefix of (`tool` matches
+//!   `tool:cargo-audit`); `*` in a segment matches any run of characters
+//!   (`repo:stevedores-org/*`);
+//! - optionally followed by a version constraint `=`, `>=`, `<=`, `>` or
+//!   `<` (`rust>=1.80`). Versions compare by numeric dot components, and
+//!   `=` only compares the components given (`rust=1.82` matches 1.82.3).
+//!
+//! Every agent also carries an implicit `agent:<agent_id>` capability, so
+//! `agent:builder-7` pins a task to one agent and `!agent:builder-7` keeps
+//! it away from one. The `agent` namespace is reserved for that: agents
+//! cannot register or claim with capabilities in it.
+
+use std::cmp::Ordering;
+
+/// First segment of the implicit per-agent capability.
+const RESERVED_NAMESPACE: &str = "agent";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capability {
+    path: Vec<String>,
+    version: Option<Vec<u64>>,
+}
+
+impl Capability {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        let (path, version) = match raw.split_once(['@', '=']) {
+            Some((path, version)) => (path, Some(parse_version(version)?)),
+            None => (raw, None),
+        };
+        Ok(Self {
+            path: parse_path(path, raw)?,
+            version,
+        })
+    }
+
+    /// In the reserved `agent` namespace.
+    fn is_reserved(&self) -> bool {
+        self.path.first().is_some_and(|s| s == RESERVED_NAMESPACE)
+    }
+
+    /// A bare task type such as `build`.
+    fn task_type(&self) -> Option<&str> {
+        match (self.path.as_slice(), &self.version) {
+            ([name], None) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Selector {
+    path: Vec<String>,
+    version: Option<(Op, Vec<u64>)>,
+}
+
+impl Selector {
+    fn parse(raw: &str, expr: &str) -> Result<Self, String> {
+        let Some(at) = raw.find(['=', '>', '<']) else {
+            return Ok(Self {
+                path: parse_path(raw, expr)?,
+                version: None,
+            });
+        };
+        let (path, constraint) = raw.split_at(at);
+        let (op, version) = [
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+            ("=", Op::Eq),
+        ]
+        .into_iter()
+        .find_map(|(sym, op)| constraint.strip_prefix(sym).map(|v| (op, v)))
+        .ok_or_else(|| format!("invalid version constraint in '{expr}'"))?;
+        Ok(Self {
+            path: parse_path(path, expr)?,
+            version: Some((op, parse_version(version)?)),
+        })
+    }
+
+    fn matches(&self, cap: &Capability) -> bool {
+        if self.path.len() > cap.path.len()
+            || !self
+                .path
+                .iter()
+                .zip(&cap.path)
+                .all(|(pattern, segment)| glob_match(pattern, segment))
+        {
+            return false;
+        }
+        let Some((op, wanted)) = &self.version else {
+            return true;
+        };
+        let Some(have) = &cap.version else {
+            return false;
+        };
+        match op {
+            Op::Eq => have.len() >= wanted.len() && have[..wanted.len()] == wanted[..],
+            Op::Ge => compare_versions(have, wanted) != Ordering::Less,
+            Op::Le => compare_versions(have, wanted) != Ordering::Greater,
+            Op::Gt => compare_versions(have, wanted) == Ordering::Greater,
+            Op::Lt => compare_versions(have, wanted) == Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    negated: bool,
+    alternatives: Vec<Selector>,
+}
+
+impl Requirement {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let expr = raw.trim();
+        let (negated, body) = match expr.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, expr),
+        };
+        let alternatives = body
+            .split('|')
+            .map(|s| Selector::parse(s.trim(), expr))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            negated,
+            alternatives,
+        })
+    }
+
+    pub fn matches(&self, caps: &[Capability]) -> bool {
+        let hit = self
+            .alternatives
+            .iter()
+            .any(|sel| caps.iter().any(|cap| sel.matches(cap)));
+        hit != self.negated
+    }
+}
+
+/// What one agent brings to a claim.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapabilitySet {
+    caps: Vec<Capability>,
+    has_task_types: bool,
+}
+
+impl CapabilitySet {
+    /// Unparseable entries are skipped: registered capabilities are
+    /// validated up front, and a bad `cap` on a claim should narrow the
+    /// agent, not fail the claim. Reserved `agent:` entries are skipped
+    /// too, so an agent cannot claim tasks pinned to another.
+    pub fn new(agent_id: &str, raw: &[String]) -> Self {
+        let mut caps: Vec<Capability> = raw
+            .iter()
+            .filter_map(|c| Capability::parse(c).ok())
+            .filter(|c| !c.is_reserved())
+            .collect();
+        let has_task_types = caps.iter().any(|c| c.task_type().is_some());
+        caps.push(Capability {
+            path: vec!["agent".to_string(), agent_id.to_string()],
+            version: None,
+        });
+        Self {
+            caps,
+            has_task_types,
+        }
+    }
+
+    /// Whether the agent takes tasks of `task_type` and meets every
+    /// requirement. An unparseable requirement never matches.
+    pub fn can_claim(&self, task_type: &str, requires: &[String]) -> bool {
+        let takes_type =
+            !self.has_task_types || self.caps.iter().any(|c| c.task_type() == Some(task_type));
+        takes_type
+            && requires.iter().all(|r| {
+                Requirement::parse(r)
+                    .map(|req| req.matches(&self.caps))
+                    .unwrap_or(false)
+            })
+    }
+}
+
+/// The plain task-type names among `raw`. Empty means the agent takes
+/// every task type.
+pub fn task_types(raw: &[String]) -> Vec<String> {
+    raw.iter()
+        .filter_map(|c| Capability::parse(c).ok())
+        .filter_map(|c| c.task_type().map(str::to_string))
+        .collect()
+}
+
+pub fn validate_capabilities(raw: &[String]) -> Result<(), String> {
+    raw.iter().try_for_each(|c| match Capability::parse(c)? {
+        cap if cap.is_reserved() => Err(format!(
+            "'{}' is in the reserved '{RESERVED_NAMESPACE}' namespace",
+            c.trim()
+        )),
+        _ => Ok(()),
+    })
+}
+
+/// Whether `raw` parses as a capability an agent may advertise.
+pub fn is_claimable(raw: &str) -> bool {
+    Capability::parse(raw).is_ok_and(|c| !c.is_reserved())
+}
+
+pub fn validate_requirements(raw: &[String]) -> Result<(), String> {
+    raw.iter().try_for_each(|r| Requirement::parse(r).map(drop))
+}
+
+fn parse_path(raw: &str, expr: &str) -> Result<Vec<String>, String> {
+    let segments: Vec<String> = raw.split(':').map(|s| s.trim().to_string()).collect();
+    if segments.iter().any(String::is_empty) {
+        return Err(format!("empty capability segment in '{expr}'"));
+    }
+    // Claims carry capabilities as one comma-separated parameter.
+    if raw.contains(',') {
+        return Err(format!("',' is not allowed in '{expr}'"));
+    }
+    Ok(segments)
+}
+
+fn parse_version(raw: &str) -> Result<Vec<u64>, String> {
+    raw.trim()
+        .split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid version '{raw}'"))
+}
+
+/// Compare dot-separated versions, treating missing components as 0.
+fn compare_versions(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| {
+            let x = a.get(i).copied().unwrap_or(0);
+            let y = b.get(i).copied().unwrap_or(0);
+            x.cmp(&y)
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// `*` matches any run of characters; everything else is literal.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(caps: &[&str]) -> CapabilitySet {
+        let caps: Vec<String> = caps.iter().map(|c| c.to_string()).collect();
+        CapabilitySet::new("builder-7", &caps)
+    }
+
+    fn reqs(r: &[&str]) -> Vec<String> {
+        r.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn task_types_keep_exact_matching() {
+        let a = agent(&["build", "rust@1.82"]);
+        assert!(a.can_claim("build", &[]));
+        assert!(!a.can_claim("test", &[]));
+        // Only structured capabilities: every task type, as with no caps.
+        assert!(agent(&["rust@1.82"]).can_claim("test", &[]));
+        assert!(agent(&[]).can_claim("anything", &[]));
+        assert_eq!(
+            task_types(&reqs(&["build", "rust@1.82", "gpu:none", "test"])),
+            vec!["build", "test"]
+        );
+    }
+
+    #[test]
+    fn version_ranges() {
+        let a = agent(&["rust@1.82.0"]);
+        assert!(a.can_claim("t", &reqs(&["rust>=1.80"])));
+        assert!(a.can_claim("t", &reqs(&["rust=1.82"])));
+        assert!(a.can_claim("t", &reqs(&["rust>=1.80", "rust<2"])));
+        assert!(!a.can_claim("t", &reqs(&["rust>1.82"])));
+        assert!(!a.can_claim("t", &reqs(&["rust=1.8"])));
+        // A version constraint needs a versioned capability.
+        assert!(!agent(&["rust"]).can_claim("t", &reqs(&["rust>=1.0"])));
+        // `=` also spells the advertised version.
+        assert!(agent(&["rust=1.85"]).can_claim("t", &reqs(&["rust>=1.80"])));
+    }
+
+    #[test]
+    fn hierarchical_paths_and_wildcards() {
+        let a = agent(&[
+            "repo:stevedores-org/data-fabric",
+            "tool:cargo-audit",
+            "gpu:none",
+        ]);
+        assert!(a.can_claim("t", &reqs(&["repo:stevedores-org/*"])));
+        assert!(!a.can_claim("t", &reqs(&["repo:other-org/*"])));
+        assert!(a.can_claim("t", &reqs(&["tool"])));
+        assert!(a.can_claim("t", &reqs(&["tool:cargo-*", "gpu:none"])));
+        assert!(!a.can_claim("t", &reqs(&["gpu:a100"])));
+        assert!(a.can_claim("t", &reqs(&["gpu:a100|gpu:none"])));
+        assert!(!a.can_claim("t", &reqs(&["!gpu:none"])));
+    }
+
+    #[test]
+    fn agent_identity_pins_and_excludes() {
+        let a = agent(&[]);
+        assert!(a.can_claim("t", &reqs(&["agent:builder-7"])));
+        assert!(!a.can_claim("t", &reqs(&["agent:builder-8"])));
+        assert!(!a.can_claim("t", &reqs(&["!agent:builder-7"])));
+        assert!(a.can_claim("t", &reqs(&["agent:builder-*"])));
+        // An agent cannot advertise its way into another agent's tasks.
+        let spoof = agent(&["agent:builder-8"]);
+        assert!(!spoof.can_claim("t", &reqs(&["agent:builder-8"])));
+        assert!(spoof.can_claim("t", &reqs(&["agent:builder-7"])));
+        assert!(!is_claimable("agent:builder-8"));
+        assert!(is_claimable("tool:cargo-audit"));
+    }
+
+    #[test]
+    fn malformed_expressions_are_rejected() {
+        for bad in ["", "rust>=", "rust>=1.x", "repo::x", "a|", "rust=>1"] {
+            assert!(Requirement::parse(bad).is_err(), "accepted '{bad}'");
+        }
+        assert!(Capability::parse("rust@one").is_err());
+        assert!(validate_capabilities(&reqs(&["build", "rust@1.82"])).is_ok());
+        assert!(validate_capabilities(&reqs(&["build", "agent:builder-7"])).is_err());
+        assert!(validate_capabilities(&reqs(&["agent"])).is_err());
+        // A task with a requirement that cannot parse is never claimable.
+        assert!(!agent(&[]).can_claim("t", &reqs(&["rust>="])));
+    }
+
+    #[test]
+    fn glob_handles_multiple_stars() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*c*e", "abcde"));
+        assert!(!glob_match("a*c*e", "abcd"));
+        assert!(glob_match("*-org/*", "stevedores-org/x"));
+        assert!(!glob_match("ab*ba", "aba"));
+    }
+}
@@ -0,0 +1,218 @@
+//! Structural diff of two checkpoint states for
+//! `GET /v1/checkpoints/:id/diff/:other_id`, as an RFC 6902 JSON Patch.
+//!
+//! Objects are compared key by key and arrays index by index; anything
+//! else that differs, including a change of type, is one `replace`. Array
+//! moves are not detected: inserting at the front of a list shows up as a
+//! `replace` of every item after it plus an `add` at the end. serde_json
+//! caps parsed documents at 128 levels, which bounds the recursion here.
+
+use crate::models::{CheckpointDiff, CheckpointDiffSummary, JsonPatchOp};
+use crate::secret_scan::escape_pointer;
+use serde_json::Value;
+
+/// Diff the state of checkpoint `from` against that of `to`.
+pub fn diff(from: &str, from_state: &Value, to: &str, to_state: &Value) -> CheckpointDiff {
+    let patch = json_patch(from_state, to_state);
+    let summary = summarize(&patch);
+    CheckpointDiff {
+        from: from.to_string(),
+        to: to.to_string(),
+        patch,
+        summary,
+    }
+}
+
+/// The operations that turn `from` into `to` when applied in order.
+pub fn json_patch(from: &Value, to: &Value) -> Vec<JsonPatchOp> {
+    let mut ops = Vec::new();
+    diff_at(from, to, String::new(), &mut ops);
+    ops
+}
+
+fn diff_at(from: &Value, to: &Value, path: String, ops: &mut Vec<JsonPatchOp>) {
+    match (from, to) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let child = format!("{path}/{}", escape_pointer(key));
+                match new.get(key) {
+                    Some(new_value) => diff_at(old_value, new_value, child, ops),
+                    None => ops.push(JsonPatchOp::Remove { path: child }),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    ops.push(JsonPatchOp::Add {
+                        path: format!("{path}/{}", escape_pointer(key)),
+                        value: new_value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            let common = old.len().min(new.len());
+            for i in 0..common {
+                diff_at(&old[i], &new[i], format!("{path}/{i}"), ops);
+            }
+            // Trailing removals go last index first so every index is
+            // still valid when its operation is applied.
+            for i in (common..old.len()).rev() {
+                ops.push(JsonPatchOp::Remove {
+                    path: format!("{path}/{i}"),
+                });
+            }
+            for (i, value) in new.iter().enumerate().skip(common) {
+                ops.push(JsonPatchOp::Add {
+                    path: format!("{path}/{i}"),
+                    value: value.clone(),
+                });
+            }
+        }
+        _ if from != to => ops.push(JsonPatchOp::Replace {
+            path,
+            value: to.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn summarize(patch: &[JsonPatchOp]) -> CheckpointDiffSummary {
+    let mut summary = CheckpointDiffSummary::default();
+    for op in patch {
+        let list = match op {
+            JsonPatchOp::Add { .. } => &mut summary.added,
+            JsonPatchOp::Remove { .. } => &mut summary.removed,
+            JsonPatchOp::Replace { .. } => &mut summary.changed,
+        };
+        list.push(op.path().to_string());
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Minimal RFC 6902 applier for the three ops, to check patches
+    /// round-trip.
+    fn apply(doc: &mut Value, patch: &[JsonPatchOp]) {
+        for op in patch {
+            let path = op.path();
+            if path.is_empty() {
+                if let JsonPatchOp::Replace { value, .. } = op {
+                    *doc = value.clone();
+                }
+                continue;
+            }
+            let (parent, last) = path.rsplit_once('/').unwrap();
+            let key = last.replace("~1", "/").replace("~0", "~");
+            let target = doc.pointer_mut(parent).unwrap();
+            match (op, target) {
+                (JsonPatchOp::Add { value, .. }, Value::Array(items)) => {
+                    items.insert(key.parse().unwrap(), value.clone())
+                }
+                (JsonPatchOp::Remove { .. }, Value::Array(items)) => {
+                    items.remove(key.parse().unwrap());
+                }
+                (JsonPatchOp::Replace { value, .. }, Value::Array(items)) => {
+                    items[key.parse::<usize>().unwrap()] = value.clone()
+                }
+                (JsonPatchOp::Remove { .. }, Value::Object(map)) => {
+                    map.remove(&key);
+                }
+                (
+                    JsonPatchOp::Add { value, .. } | JsonPatchOp::Replace { value, .. },
+                    Value::Object(map),
+                ) => {
+                    map.insert(key, value.clone());
+                }
+                (_, other) => panic!("cannot apply {op:?} to {other}"),
+            }
+        }
+    }
+
+    #[test]
+    fn identical_states_have_an_empty_patch() {
+        let state = json!({"plan": ["a", "b"], "step": 3});
+        let d = diff("c1", &state, "c2", &state);
+        assert!(d.patch.is_empty());
+        assert_eq!(d.summary, CheckpointDiffSummary::default());
+    }
+
+    #[test]
+    fn nested_changes_are_reported_by_pointer() {
+        let from = json!({"step": 3, "memory": {"goal": "ship", "notes": "x"}, "done": false});
+        let to = json!({"step": 4, "memory": {"goal": "ship", "error": "timeout"}, "done": false});
+        let d = diff("c1", &from, "c2", &to);
+        assert_eq!(
+            d.patch,
+            vec![
+                JsonPatchOp::Remove {
+                    path: "/memory/notes".to_string()
+                },
+                JsonPatchOp::Add {
+                    path: "/memory/error".to_string(),
+                    value: json!("timeout"),
+                },
+                JsonPatchOp::Replace {
+                    path: "/step".to_string(),
+                    value: json!(4),
+                },
+            ]
+        );
+        assert_eq!(d.summary.added, vec!["/memory/error"]);
+        assert_eq!(d.summary.removed, vec!["/memory/notes"]);
+        assert_eq!(d.summary.changed, vec!["/step"]);
+        let json = serde_json::to_value(&d.patch[0]).unwrap();
+        assert_eq!(json, json!({"op": "remove", "path": "/memory/notes"}));
+    }
+
+    #[test]
+    fn shrinking_an_array_removes_from_the_end() {
+        let from = json!({"plan": ["a", "b", "c", "d"]});
+        let to = json!({"plan": ["a", "B"]});
+        let patch = json_patch(&from, &to);
+        let paths: Vec<_> = patch.iter().map(|op| op.path()).collect();
+        assert_eq!(paths, vec!["/plan/1", "/plan/3", "/plan/2"]);
+        let mut doc = from.clone();
+        apply(&mut doc, &patch);
+        assert_eq!(doc, to);
+    }
+
+    #[test]
+    fn type_changes_and_root_scalars_are_replaced() {
+        assert_eq!(
+            json_patch(&json!({"x": [1]}), &json!({"x": {"0": 1}})),
+            vec![JsonPatchOp::Replace {
+                path: "/x".to_string(),
+                value: json!({"0": 1}),
+            }]
+        );
+        assert_eq!(
+            json_patch(&json!(1), &json!("one")),
+            vec![JsonPatchOp::Replace {
+                path: String::new(),
+                value: json!("one"),
+            }]
+        );
+    }
+
+    #[test]
+    fn patch_round_trips_with_escaped_keys() {
+        let from = json!({
+            "a/b": {"~k": 1},
+            "list": [{"id": 1}, {"id": 2}],
+            "gone": null,
+        });
+        let to = json!({
+            "a/b": {"~k": 2, "new": true},
+            "list": [{"id": 1, "ok": true}, {"id": 2}, {"id": 3}],
+        });
+        let patch = json_patch(&from, &to);
+        assert!(patch.iter().any(|op| op.path() == "/a~1b/~0k"));
+        let mut doc = from.clone();
+        apply(&mut doc, &patch);
+        assert_eq!(doc, to);
+    }
+}
@@ -0,0 +1,190 @@
+//! Checkpoint trees and lineages, assembled from `checkpoints` rows for
+//! `GET /v1/checkpoints/threads/:thread_id/tree` and
+//! `GET /v1/checkpoints/:id/lineage`. The edges are `parent_id`; a forked
+//! thread's first checkpoint has its fork point, in another thread, as
+//! parent.
+
+use crate::models::{
+    Checkpoint, CheckpointBranch, CheckpointLineage, CheckpointTree, CheckpointTreeNode,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Most checkpoints, and most forks off them, listed in one thread's tree.
+pub const MAX_TREE_CHECKPOINTS: usize = 1000;
+
+/// Longest lineage walked back from a checkpoint.
+pub const MAX_LINEAGE_DEPTH: usize = 1000;
+
+/// Assemble the tree of `thread_id` from its `checkpoints`, oldest first,
+/// and `forks`: checkpoints of other threads whose parent is one of them.
+pub fn build_tree(
+    thread_id: &str,
+    checkpoints: Vec<Checkpoint>,
+    forks: Vec<Checkpoint>,
+    truncated: bool,
+) -> CheckpointTree {
+    let ids: HashSet<&str> = checkpoints.iter().map(|c| c.id.as_str()).collect();
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    let mut roots = Vec::new();
+    for cp in &checkpoints {
+        match cp.parent_id.as_deref().filter(|p| ids.contains(p)) {
+            Some(parent) => children
+                .entry(parent.to_string())
+                .or_default()
+                .push(cp.id.clone()),
+            None => roots.push(cp.id.clone()),
+        }
+    }
+    let mut branches: HashMap<String, Vec<CheckpointBranch>> = HashMap::new();
+    for fork in forks {
+        if let Some(parent) = fork.parent_id {
+            branches.entry(parent).or_default().push(CheckpointBranch {
+                thread_id: fork.thread_id,
+                checkpoint_id: fork.id,
+            });
+        }
+    }
+    let nodes = checkpoints
+        .into_iter()
+        .map(|checkpoint| CheckpointTreeNode {
+            children: children.remove(&checkpoint.id).unwrap_or_default(),
+            forks: branches.remove(&checkpoint.id).unwrap_or_default(),
+            checkpoint,
+        })
+        .collect();
+    CheckpointTree {
+        thread_id: thread_id.to_string(),
+        roots,
+        nodes,
+        truncated,
+    }
+}
+
+/// Order the `ancestors` of `checkpoint_id` (itself included, in any
+/// order) root first by following `parent_id`. `None` if `checkpoint_id`
+/// is not among them.
+pub fn lineage(checkpoint_id: &str, ancestors: Vec<Checkpoint>) -> Option<CheckpointLineage> {
+    let mut by_id: HashMap<String, Checkpoint> =
+        ancestors.into_iter().map(|c| (c.id.clone(), c)).collect();
+    let mut path = Vec::new();
+    let mut next = Some(checkpoint_id.to_string());
+    while let Some(id) = next {
+        // `remove` also stops a `parent_id` cycle on its second lap.
+        let Some(cp) = by_id.remove(&id) else {
+            break;
+        };
+        next = cp.parent_id.clone();
+        path.push(cp);
+    }
+    if path.is_empty() {
+        return None;
+    }
+    path.reverse();
+    let complete = path[0].parent_id.is_none();
+    Some(CheckpointLineage {
+        checkpoint_id: checkpoint_id.to_string(),
+        path,
+        complete,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cp(id: &str, thread: &str, parent: Option<&str>) -> Checkpoint {
+        Checkpoint {
+            id: id.to_string(),
+            thread_id: thread.to_string(),
+            node_id: "n".to_string(),
+            parent_id: parent.map(str::to_string),
+            state_r2_key: format!("checkpoints/{thread}/{id}"),
+            state_size_bytes: Some(2),
+            metadata: None,
+            created_at: "2026-10-01T00:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn tree_links_children_branches_and_forks() {
+        let tree = build_tree(
+            "main",
+            vec![
+                cp("a", "main", None),
+                cp("b", "main", Some("a")),
+                cp("c", "main", Some("b")),
+                cp("c2", "main", Some("b")),
+            ],
+            vec![cp("x", "retry", Some("b"))],
+            false,
+        );
+        assert_eq!(tree.roots, vec!["a"]);
+        let node = |id: &str| tree.nodes.iter().find(|n| n.checkpoint.id == id).unwrap();
+        assert_eq!(node("a").children, vec!["b"]);
+        assert_eq!(node("b").children, vec!["c", "c2"]);
+        assert!(node("c").children.is_empty());
+        assert_eq!(
+            node("b").forks,
+            vec![CheckpointBranch {
+                thread_id: "retry".to_string(),
+                checkpoint_id: "x".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn forked_thread_is_rooted_at_its_first_checkpoint() {
+        let tree = build_tree(
+            "retry",
+            vec![cp("x", "retry", Some("b")), cp("y", "retry", Some("x"))],
+            Vec::new(),
+            true,
+        );
+        assert_eq!(tree.roots, vec!["x"]);
+        assert_eq!(tree.nodes[0].children, vec!["y"]);
+        assert!(tree.truncated);
+        let json = serde_json::to_value(&tree).unwrap();
+        assert_eq!(json["nodes"][0]["id"], "x");
+        assert_eq!(json["nodes"][0]["parent_id"], "b");
+    }
+
+    #[test]
+    fn lineage_runs_from_the_root_across_forks() {
+        let lineage = lineage(
+            "y",
+            vec![
+                cp("y", "retry", Some("x")),
+                cp("a", "main", None),
+                cp("x", "retry", Some("b")),
+                cp("b", "main", Some("a")),
+            ],
+        )
+        .unwrap();
+        let ids: Vec<_> = lineage.path.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "x", "y"]);
+        assert!(lineage.complete);
+    }
+
+    #[test]
+    fn lineage_with_a_missing_parent_is_incomplete() {
+        let lineage = lineage(
+            "c",
+            vec![cp("c", "main", Some("b")), cp("b", "main", Some("gone"))],
+        )
+        .unwrap();
+        assert_eq!(lineage.path.len(), 2);
+        assert!(!lineage.complete);
+        assert!(super::lineage("zzz", Vec::new()).is_none());
+    }
+
+    #[test]
+    fn lineage_stops_on_a_parent_cycle() {
+        let lineage = lineage(
+            "a",
+            vec![cp("a", "main", Some("b")), cp("b", "main", Some("a"))],
+        )
+        .unwrap();
+        assert_eq!(lineage.path.len(), 2);
+        assert!(!lineage.complete);
+    }
+}
@@ -0,0 +1,265 @@
+//! Five-field cron expressions for recurring task schedules.
+//!
+//! `minute hour day-of-month month day-of-week`, evaluated in UTC. Each
+//! field takes `*`, a value, a range `a-b`, a step `*/n` or `a-b/n`, or a
+//! comma-separated list of those. Day-of-week is 0-7 with both 0 and 7
+//! meaning Sunday. As in Vixie cron, when both day fields are restricted a
+//! day matches if either does.
+//!
+//! Only [`CronSchedule::next_after`] is needed at runtime: the scheduled
+//! handler stores the next fire time and materializes the template once it
+//! has passed, so fire times are only as precise as the worker's cron
+//! trigger.
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+/// Upper bound on how far [`CronSchedule::next_after`] searches. An
+/// expression that cannot fire within it (e.g. `0 0 30 2 *`) yields `None`.
+const SEARCH_HORIZON_MINUTES: u64 = 5 * 366 * MINUTES_PER_DAY;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(format!(
+                "cron expression must have 5 fields, got {}",
+                fields.len()
+            ));
+        };
+        let mut days_of_week = parse_field(dow, 0, 7, "day-of-week")?;
+        // Fold 7 onto 0 so both spellings of Sunday match.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days_of_month: parse_field(dom, 1, 31, "day-of-month")?,
+            months: parse_field(month, 1, 12, "month")?,
+            days_of_week,
+            dom_restricted: dom != "*",
+            dow_restricted: dow != "*",
+        })
+    }
+
+    /// First fire time strictly after `after_ms` (Unix millis), on a whole
+    /// minute.
+    pub fn next_after(&self, after_ms: u64) -> Option<u64> {
+        let mut minute = after_ms / 60_000 + 1;
+        let horizon = minute + SEARCH_HORIZON_MINUTES;
+        while minute < horizon {
+            let days = minute / MINUTES_PER_DAY;
+            let (year, month, day) = civil_from_days(days as i64);
+            if !bit(self.months, month) {
+                let (y, m) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                minute = days_from_civil(y, m, 1) as u64 * MINUTES_PER_DAY;
+                continue;
+            }
+            let weekday = ((days + 4) % 7) as u32; // 1970-01-01 was a Thursday
+            if !self.day_matches(day, weekday) {
+                minute = (days + 1) * MINUTES_PER_DAY;
+                continue;
+            }
+            let of_day = minute % MINUTES_PER_DAY;
+            if !bit(self.hours, (of_day / 60) as u32) {
+                minute = (minute / 60 + 1) * 60;
+                continue;
+            }
+            if !bit(self.minutes, (of_day % 60) as u32) {
+                minute += 1;
+                continue;
+            }
+            return Some(minute * 60_000);
+        }
+        None
+    }
+
+    fn day_matches(&self, day: u32, weekday: u32) -> bool {
+        let dom = bit(self.days_of_month, day);
+        let dow = bit(self.days_of_week, weekday);
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+}
+
+fn bit(set: u64, n: u32) -> bool {
+    set & (1 << n) != 0
+}
+
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid {name} step in '{part}'"))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (
+                parse_value(lo, min, max, name)?,
+                parse_value(hi, min, max, name)?,
+            )
+        } else {
+            let v = parse_value(range, min, max, name)?;
+            // `5/15` means "from 5 to the end of the range, every 15".
+            (v, if part.contains('/') { max } else { v })
+        };
+        if lo > hi {
+            return Err(format!("invalid {name} range '{range}'"));
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            set |= 1 << v;
+        }
+    }
+    Ok(set)
+}
+
+fn parse_value(raw: &str, min: u32, max: u32, name: &str) -> Result<u32, String> {
+    raw.parse::<u32>()
+        .ok()
+        .filter(|v| (min..=max).contains(v))
+        .ok_or_else(|| format!("{name} value '{raw}' outside {min}-{max}"))
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of [`days_from_civil`]: `(year, month, day)`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unix millis for a UTC wall-clock time.
+    fn at(year: i64, month: u32, day: u32, hour: u64, minute: u64) -> u64 {
+        (days_from_civil(year, month, day) as u64 * MINUTES_PER_DAY + hour * 60 + minute) * 60_000
+    }
+
+    fn next(expr: &str, after: u64) -> Option<u64> {
+        CronSchedule::parse(expr)
+            .expect("valid cron")
+            .next_after(after)
+    }
+
+    #[test]
+    fn civil_conversions_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        for days in [-1, 59, 365, 11_016, 20_513, 50_000] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+    }
+
+    #[test]
+    fn nightly_fires_at_the_next_matching_minute() {
+        let after = at(2026, 10, 16, 14, 7);
+        assert_eq!(next("30 2 * * *", after), Some(at(2026, 10, 17, 2, 30)));
+        // Strictly after: a fire time equal to `after` is skipped.
+        let exact = at(2026, 10, 17, 2, 30);
+        assert_eq!(next("30 2 * * *", exact), Some(at(2026, 10, 18, 2, 30)));
+    }
+
+    #[test]
+    fn steps_ranges_and_lists() {
+        let after = at(2026, 1, 1, 0, 0);
+        assert_eq!(next("*/15 * * * *", after), Some(at(2026, 1, 1, 0, 15)));
+        assert_eq!(next("5/20 * * * *", after), Some(at(2026, 1, 1, 0, 5)));
+        assert_eq!(next("0 9-17/4 * * *", after), Some(at(2026, 1, 1, 9, 0)));
+        assert_eq!(next("0 0 1,15 * *", after), Some(at(2026, 1, 15, 0, 0)));
+        assert_eq!(next("0 0 1 3 *", after), Some(at(2026, 3, 1, 0, 0)));
+    }
+
+    #[test]
+    fn month_rollover_crosses_the_year() {
+        let after = at(2026, 12, 31, 23, 59);
+        assert_eq!(next("0 0 * * *", after), Some(at(2027, 1, 1, 0, 0)));
+    }
+
+    #[test]
+    fn day_of_week_accepts_seven_as_sunday() {
+        // 2026-10-16 is a Friday; the next Sunday is the 18th.
+        let after = at(2026, 10, 16, 12, 0);
+        assert_eq!(next("0 3 * * 0", after), Some(at(2026, 10, 18, 3, 0)));
+        assert_eq!(next("0 3 * * 7", after), Some(at(2026, 10, 18, 3, 0)));
+        assert_eq!(next("0 3 * * 1-5", after), Some(at(2026, 10, 19, 3, 0)));
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // "The 20th or any Monday": Monday the 19th comes first.
+        let after = at(2026, 10, 16, 12, 0);
+        assert_eq!(next("0 0 20 * 1", after), Some(at(2026, 10, 19, 0, 0)));
+    }
+
+    #[test]
+    fn impossible_dates_never_fire() {
+        assert_eq!(next("0 0 30 2 *", at(2026, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_expressions() {
+        for bad in [
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "10-5 * * * *",
+            "a * * * *",
+        ] {
+            assert!(CronSchedule::parse(bad).is_err(), "accepted '{bad}'");
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::models;
 use crate::policy::RiskLevel;
+use crate::tenant_security::{FederationConfig, TenantRateLimitConfig};
 use std::collections::{HashMap, HashSet};
 use wasm_bindgen::JsValue;
 use worker::*;
@@ -15,7 +16,8 @@ use worker::*;
 // statements the cross_tenant_sql_shape tests will fail.
 
 /// SELECT for `get_play_definition` — scoped by tenant_id then name.
-const SQL_GET_PLAY_DEFINITION: &str = "SELECT name, goal, tasks_json FROM play_definitions \
+const SQL_GET_PLAY_DEFINITION: &str =
+    "SELECT name, goal, tasks_json, on_error, inputs_json FROM play_definitions \
      WHERE tenant_id = ?1 AND name = ?2";
 
 /// INSERT for `create_policy_escalation` — tenant_id is the first column.
@@ -194,6 +196,21 @@ pub const SQL_RESUME_RUN: &str =
     "UPDATE runs SET status = 'running', resumed_at = datetime('now'), updated_at = datetime('now') \
      WHERE tenant_id = ?1 AND id = ?2 AND status = 'paused'";
 
+/// UPDATE for `cancel_run`. Guarded like `SQL_PAUSE_RUN`: a run that has
+/// already reached a terminal state affects zero rows. Paused runs can be
+/// cancelled.
+pub const SQL_CANCEL_RUN: &str =
+    "UPDATE runs SET status = 'cancelled', updated_at = datetime('now') \
+     WHERE tenant_id = ?1 AND id = ?2 \
+       AND status IN ('created', 'running', 'paused')";
+
+/// UPDATE for `finish_run`: the terminal status (`?3`) a play run reached.
+/// Guarded like `SQL_CANCEL_RUN`, so a run that was cancelled or finished
+/// meanwhile keeps its status.
+pub const SQL_FINISH_RUN: &str = "UPDATE runs SET status = ?3, updated_at = datetime('now') \
+     WHERE tenant_id = ?1 AND id = ?2 \
+       AND status IN ('created', 'running', 'paused')";
+
 pub fn now_iso() -> String {
     js_sys::Date::new_0().to_iso_string().as_string().unwrap()
 }
@@ -224,8 +241,8 @@ pub async fn create_task(
     let max_retries = body.max_retries.unwrap_or(3);
 
     db.prepare(
-        "INSERT INTO mcp_tasks (tenant_id, id, job_id, task_type, priority, status, params, graph_ref, play_id, parent_task_id, max_retries, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, 'pending', ?6, ?7, ?8, ?9, ?10, ?11)",
+        "INSERT INTO mcp_tasks (tenant_id, id, job_id, task_type, priority, status, params, graph_ref, play_id, parent_task_id, max_retries, created_at, not_before, requires, idempotency_key)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'pending', ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
     )
     .bind(&[
         JsValue::from_str(tenant_id),
@@ -239,10 +256,130 @@ pub async fn create_task(
         opt_str(&body.parent_task_id),
         JsValue::from(max_retries),
         JsValue::from_str(&now),
+        opt_str(&body.not_before),
+        if body.requires.is_empty() {
+            JsValue::NULL
+        } else {
+            JsValue::from_str(&serde_json::to_string(&body.requires).unwrap())
+        },
+        opt_str(&body.idempotency_key),
+    ])?
+    .run()
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct IdempotencyOwnerRow {
+    task_id: String,
+}
+
+/// Claim `idempotency_key` for `task_id` until `expires_at` (ISO-8601).
+/// Returns `None` when the claim is ours, or the task id that holds the key
+/// inside its window. The `(tenant_id, idempotency_key)` primary key
+/// settles concurrent claims; an expired holder is taken over in place.
+pub async fn claim_task_idempotency_key(
+    db: &D1Database,
+    tenant_id: &str,
+    idempotency_key: &str,
+    task_id: &str,
+    now: &str,
+    expires_at: &str,
+) -> Result<Option<String>> {
+    // A holder released between our upsert and the read leaves no owner;
+    // claim again rather than proceed unclaimed.
+    for _ in 0..2 {
+        if let Some(owner) =
+            try_claim_task_idempotency_key(db, tenant_id, idempotency_key, task_id, now, expires_at)
+                .await?
+        {
+            return Ok(owner);
+        }
+    }
+    Err(Error::RustError(format!(
+        "could not claim idempotency key '{idempotency_key}'"
+    )))
+}
+
+/// One claim attempt: `Some(None)` if claimed, `Some(Some(owner))` if held,
+/// `None` if the holder disappeared before it could be read.
+async fn try_claim_task_idempotency_key(
+    db: &D1Database,
+    tenant_id: &str,
+    idempotency_key: &str,
+    task_id: &str,
+    now: &str,
+    expires_at: &str,
+) -> Result<Option<Option<String>>> {
+    let result: D1Result = db
+        .prepare(
+            "INSERT INTO task_idempotency_keys (tenant_id, idempotency_key, task_id, expires_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(tenant_id, idempotency_key) DO UPDATE SET
+                task_id = excluded.task_id,
+                expires_at = excluded.expires_at
+             WHERE task_idempotency_keys.expires_at <= ?5",
+        )
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(idempotency_key),
+            JsValue::from_str(task_id),
+            JsValue::from_str(expires_at),
+            JsValue::from_str(now),
+        ])?
+        .run()
+        .await?;
+    let claimed = result
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false);
+    if claimed {
+        return Ok(Some(None));
+    }
+    let owner: Option<IdempotencyOwnerRow> = db
+        .prepare(
+            "SELECT task_id FROM task_idempotency_keys
+             WHERE tenant_id = ?1 AND idempotency_key = ?2",
+        )
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(idempotency_key),
+        ])?
+        .first(None)
+        .await?;
+    Ok(owner.map(|o| Some(o.task_id)))
+}
+
+/// Give up `task_id`'s claim on `idempotency_key`, e.g. when its enqueue
+/// was rejected, so a retry can submit again.
+pub async fn release_task_idempotency_key(
+    db: &D1Database,
+    tenant_id: &str,
+    idempotency_key: &str,
+    task_id: &str,
+) -> Result<()> {
+    db.prepare(
+        "DELETE FROM task_idempotency_keys
+         WHERE tenant_id = ?1 AND idempotency_key = ?2 AND task_id = ?3",
+    )
+    .bind(&[
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(idempotency_key),
+        JsValue::from_str(task_id),
     ])?
     .run()
     .await?;
+    Ok(())
+}
 
+/// Remove a task row that never reached TaskLeaseManager (e.g. it lost an
+/// idempotency race there).
+pub async fn delete_task(db: &D1Database, tenant_id: &str, id: &str) -> Result<()> {
+    db.prepare("DELETE FROM mcp_tasks WHERE tenant_id = ?1 AND id = ?2")
+        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(id)])?
+        .run()
+        .await?;
     Ok(())
 }
 
@@ -325,13 +462,6 @@ pub async fn get_play_definition(
     tenant_id: &str,
     name: &str,
 ) -> Result<Option<models::PlayDefinition>> {
-    #[derive(serde::Deserialize)]
-    struct PlayRow {
-        name: String,
-        goal: String,
-        tasks_json: String,
-    }
-
     // Tenant-scoped lookup: a play named "foo" in tenant A is invisible
     // to tenant B. Migration 0014_ws8_tenant_id_addendum.sql adds the
     // `tenant_id` column and seeds the platform-default `sre-incident`
@@ -341,35 +471,347 @@ pub async fn get_play_definition(
         .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(name)])?
         .first(None)
         .await?;
+    row.map(|r| r.into_definition()).transpose()
+}
+
+/// A `play_definitions` or `play_definition_versions` row.
+#[derive(serde::Deserialize)]
+struct PlayRow {
+    name: String,
+    goal: String,
+    tasks_json: String,
+    #[serde(default)]
+    on_error: Option<String>,
+    #[serde(default)]
+    inputs_json: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    created_at: Option<String>,
+}
+
+impl PlayRow {
+    fn into_definition(self) -> Result<models::PlayDefinition> {
+        let tasks: Vec<models::PlayTaskDefinition> = serde_json::from_str(&self.tasks_json)
+            .map_err(|e| Error::RustError(format!("failed to parse play tasks: {e}")))?;
+        let on_error = match self.on_error.as_deref() {
+            None | Some("fail_fast") => models::PlayErrorPolicy::FailFast,
+            Some("continue") => models::PlayErrorPolicy::Continue,
+            Some(other) => {
+                return Err(Error::RustError(format!(
+                    "unknown play on_error policy '{other}'"
+                )))
+            }
+        };
+        let inputs = match self.inputs_json.as_deref() {
+            Some(json) => serde_json::from_str(json)
+                .map_err(|e| Error::RustError(format!("failed to parse play inputs: {e}")))?,
+            None => Default::default(),
+        };
+        Ok(models::PlayDefinition {
+            name: self.name,
+            goal: self.goal,
+            tasks,
+            on_error,
+            inputs,
+        })
+    }
+
+    fn into_stored(mut self) -> Result<models::StoredPlayDefinition> {
+        let version = match self.version.take() {
+            Some(v) => models::PlayVersion::parse(&v).map_err(Error::RustError)?,
+            None => models::PlayVersion::INITIAL,
+        };
+        let created_at = self.created_at.take().unwrap_or_default();
+        Ok(models::StoredPlayDefinition {
+            version,
+            created_at,
+            definition: self.into_definition()?,
+        })
+    }
+}
+
+/// SELECT for `get_stored_play_definition`: the latest version, which
+/// `play_definitions` always holds. Its `updated_at` is when that version
+/// was stored.
+const SQL_GET_LATEST_PLAY_DEFINITION: &str =
+    "SELECT name, goal, tasks_json, on_error, inputs_json, version, updated_at AS created_at \
+     FROM play_definitions WHERE tenant_id = ?1 AND name = ?2";
+
+/// SELECT for `get_stored_play_definition` with a version.
+const SQL_GET_PLAY_DEFINITION_VERSION: &str =
+    "SELECT name, goal, tasks_json, on_error, inputs_json, version, created_at \
+     FROM play_definition_versions WHERE tenant_id = ?1 AND name = ?2 AND version = ?3";
+
+/// INSERT for `create_play_definition`'s version row. Writes nothing if
+/// the play already exists; runs before the head INSERT in one batch.
+const SQL_INSERT_FIRST_PLAY_VERSION: &str = "INSERT INTO play_definition_versions \
+       (tenant_id, name, version, major, minor, patch, goal, tasks_json, on_error, created_at, \
+        inputs_json) \
+     SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11 \
+     WHERE NOT EXISTS (SELECT 1 FROM play_definitions WHERE tenant_id = ?1 AND name = ?2)";
+
+/// INSERT for `create_play_definition`'s `play_definitions` (latest) row.
+const SQL_INSERT_PLAY_DEFINITION: &str = "INSERT INTO play_definitions \
+       (tenant_id, name, goal, tasks_json, on_error, version, created_at, updated_at, inputs_json) \
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7, ?8) \
+     ON CONFLICT(tenant_id, name) DO NOTHING";
+
+/// INSERT for `update_play_definition`'s version row. `?12` is the
+/// version the update was computed against; if another update landed
+/// first, nothing is written.
+const SQL_INSERT_NEXT_PLAY_VERSION: &str = "INSERT INTO play_definition_versions \
+       (tenant_id, name, version, major, minor, patch, goal, tasks_json, on_error, created_at, \
+        inputs_json) \
+     SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11 \
+     WHERE EXISTS (SELECT 1 FROM play_definitions \
+                   WHERE tenant_id = ?1 AND name = ?2 AND version = ?12)";
+
+/// UPDATE for `update_play_definition`, guarded by the previous version
+/// like `SQL_INSERT_NEXT_PLAY_VERSION`.
+const SQL_UPDATE_PLAY_DEFINITION: &str =
+    "UPDATE play_definitions SET goal = ?3, tasks_json = ?4, on_error = ?5, version = ?6, \
+       updated_at = ?7, inputs_json = ?9 \
+     WHERE tenant_id = ?1 AND name = ?2 AND version = ?8";
+
+/// The latest version of a play, or the given one.
+pub async fn get_stored_play_definition(
+    db: &D1Database,
+    tenant_id: &str,
+    name: &str,
+    version: Option<&models::PlayVersion>,
+) -> Result<Option<models::StoredPlayDefinition>> {
+    let latest: Option<PlayRow> = db
+        .prepare(SQL_GET_LATEST_PLAY_DEFINITION)
+        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(name)])?
+        .first(None)
+        .await?;
+    let Some(latest) = latest.map(PlayRow::into_stored).transpose()? else {
+        return Ok(None);
+    };
+    let Some(version) = version.filter(|v| **v != latest.version) else {
+        // Plays seeded by migrations have no version rows; their latest
+        // version is only in `play_definitions`.
+        return Ok(Some(latest));
+    };
+    let row: Option<PlayRow> = db
+        .prepare(SQL_GET_PLAY_DEFINITION_VERSION)
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(name),
+            JsValue::from_str(&version.to_string()),
+        ])?
+        .first(None)
+        .await?;
+    row.map(PlayRow::into_stored).transpose()
+}
 
-    match row {
-        Some(r) => {
-            let tasks: Vec<models::PlayTaskDefinition> = serde_json::from_str(&r.tasks_json)
-                .map_err(|e| Error::RustError(format!("failed to parse play tasks: {e}")))?;
-            Ok(Some(models::PlayDefinition {
+/// Latest version of every play of the tenant, by name.
+pub async fn list_play_definitions(
+    db: &D1Database,
+    tenant_id: &str,
+) -> Result<Vec<models::PlaySummary>> {
+    #[derive(serde::Deserialize)]
+    struct Row {
+        name: String,
+        goal: String,
+        version: String,
+        updated_at: String,
+    }
+    let result = db
+        .prepare(
+            "SELECT name, goal, version, updated_at FROM play_definitions \
+             WHERE tenant_id = ?1 ORDER BY name ASC",
+        )
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .all()
+        .await?;
+    let rows: Vec<Row> = result.results()?;
+    rows.into_iter()
+        .map(|r| {
+            Ok(models::PlaySummary {
+                version: models::PlayVersion::parse(&r.version).map_err(Error::RustError)?,
                 name: r.name,
                 goal: r.goal,
-                tasks,
-            }))
-        }
-        None => Ok(None),
+                updated_at: r.updated_at,
+            })
+        })
+        .collect()
+}
+
+/// Stored versions of a play, newest first.
+pub async fn list_play_versions(
+    db: &D1Database,
+    tenant_id: &str,
+    name: &str,
+) -> Result<Vec<models::PlayVersionInfo>> {
+    #[derive(serde::Deserialize)]
+    struct Row {
+        version: String,
+        created_at: String,
     }
+    let result = db
+        .prepare(
+            "SELECT version, created_at FROM play_definition_versions \
+             WHERE tenant_id = ?1 AND name = ?2 \
+             ORDER BY major DESC, minor DESC, patch DESC",
+        )
+        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(name)])?
+        .all()
+        .await?;
+    let rows: Vec<Row> = result.results()?;
+    rows.into_iter()
+        .map(|r| {
+            Ok(models::PlayVersionInfo {
+                version: models::PlayVersion::parse(&r.version).map_err(Error::RustError)?,
+                created_at: r.created_at,
+            })
+        })
+        .collect()
+}
+
+fn play_version_binds(
+    tenant_id: &str,
+    def: &models::PlayDefinition,
+    version: &models::PlayVersion,
+    now: &str,
+) -> Result<Vec<JsValue>> {
+    Ok(vec![
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(&def.name),
+        JsValue::from_str(&version.to_string()),
+        JsValue::from_f64(version.major as f64),
+        JsValue::from_f64(version.minor as f64),
+        JsValue::from_f64(version.patch as f64),
+        JsValue::from_str(&def.goal),
+        JsValue::from_str(&serde_json::to_string(&def.tasks)?),
+        JsValue::from_str(def.on_error.as_str()),
+        JsValue::from_str(now),
+        play_inputs_json(def)?,
+    ])
+}
+
+/// `inputs_json` column value: NULL for a play without inputs.
+fn play_inputs_json(def: &models::PlayDefinition) -> Result<JsValue> {
+    Ok(if def.inputs.is_empty() {
+        JsValue::NULL
+    } else {
+        JsValue::from_str(&serde_json::to_string(&def.inputs)?)
+    })
+}
+
+/// Store a new play at `version`. Returns `None` if a play of that name
+/// already exists.
+pub async fn create_play_definition(
+    db: &D1Database,
+    tenant_id: &str,
+    def: &models::PlayDefinition,
+    version: models::PlayVersion,
+) -> Result<Option<models::StoredPlayDefinition>> {
+    let now = now_iso();
+    let results = db
+        .batch(vec![
+            db.prepare(SQL_INSERT_FIRST_PLAY_VERSION)
+                .bind(&play_version_binds(tenant_id, def, &version, &now)?)?,
+            db.prepare(SQL_INSERT_PLAY_DEFINITION).bind(&[
+                JsValue::from_str(tenant_id),
+                JsValue::from_str(&def.name),
+                JsValue::from_str(&def.goal),
+                JsValue::from_str(&serde_json::to_string(&def.tasks)?),
+                JsValue::from_str(def.on_error.as_str()),
+                JsValue::from_str(&version.to_string()),
+                JsValue::from_str(&now),
+                play_inputs_json(def)?,
+            ])?,
+        ])
+        .await?;
+    let created = match results.get(1) {
+        Some(r) => r.meta()?.and_then(|m| m.changes).unwrap_or(0) > 0,
+        None => false,
+    };
+    Ok(created.then(|| models::StoredPlayDefinition {
+        version,
+        created_at: now,
+        definition: def.clone(),
+    }))
+}
+
+/// Store `def` as `version`, the play's new latest. `previous` is the
+/// version the caller read; returns `None` if the play is gone or has
+/// moved past it since.
+pub async fn update_play_definition(
+    db: &D1Database,
+    tenant_id: &str,
+    def: &models::PlayDefinition,
+    version: models::PlayVersion,
+    previous: &models::PlayVersion,
+) -> Result<Option<models::StoredPlayDefinition>> {
+    let now = now_iso();
+    let mut version_binds = play_version_binds(tenant_id, def, &version, &now)?;
+    version_binds.push(JsValue::from_str(&previous.to_string()));
+    let results = db
+        .batch(vec![
+            db.prepare(SQL_INSERT_NEXT_PLAY_VERSION)
+                .bind(&version_binds)?,
+            db.prepare(SQL_UPDATE_PLAY_DEFINITION).bind(&[
+                JsValue::from_str(tenant_id),
+                JsValue::from_str(&def.name),
+                JsValue::from_str(&def.goal),
+                JsValue::from_str(&serde_json::to_string(&def.tasks)?),
+                JsValue::from_str(def.on_error.as_str()),
+                JsValue::from_str(&version.to_string()),
+                JsValue::from_str(&now),
+                JsValue::from_str(&previous.to_string()),
+                play_inputs_json(def)?,
+            ])?,
+        ])
+        .await?;
+    let updated = match results.get(1) {
+        Some(r) => r.meta()?.and_then(|m| m.changes).unwrap_or(0) > 0,
+        None => false,
+    };
+    Ok(updated.then(|| models::StoredPlayDefinition {
+        version,
+        created_at: now,
+        definition: def.clone(),
+    }))
+}
+
+/// Delete a play and all its versions. Runs already launched keep their
+/// copy of the definition in PlayManager.
+pub async fn delete_play_definition(db: &D1Database, tenant_id: &str, name: &str) -> Result<bool> {
+    let binds = [JsValue::from_str(tenant_id), JsValue::from_str(name)];
+    let results = db
+        .batch(vec![
+            db.prepare("DELETE FROM play_definition_versions WHERE tenant_id = ?1 AND name = ?2")
+                .bind(&binds)?,
+            db.prepare("DELETE FROM play_definitions WHERE tenant_id = ?1 AND name = ?2")
+                .bind(&binds)?,
+        ])
+        .await?;
+    Ok(match results.get(1) {
+        Some(r) => r.meta()?.and_then(|m| m.changes).unwrap_or(0) > 0,
+        None => false,
+    })
 }
 
 // ── Agents ──────────────────────────────────────────────────────
 
+/// Register an agent owned by `owner_subject`, the registering token's
+/// `sub`; only that subject may later claim work as this agent.
 pub async fn register_agent(
     db: &D1Database,
     tenant_id: &str,
     id: &str,
+    owner_subject: Option<&str>,
     body: &models::RegisterAgent,
 ) -> Result<()> {
     let now = now_iso();
     let caps_json = serde_json::to_string(&body.capabilities).unwrap();
 
     db.prepare(
-        "INSERT INTO agents (tenant_id, id, name, capabilities, endpoint, last_heartbeat, status, metadata)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'active', ?7)",
+        "INSERT INTO agents (tenant_id, id, name, capabilities, endpoint, last_heartbeat, status, metadata, owner_subject)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'active', ?7, ?8)",
     )
     .bind(&[
         JsValue::from_str(tenant_id),
@@ -379,6 +821,7 @@ pub async fn register_agent(
         opt_str(&body.endpoint),
         JsValue::from_str(&now),
         opt_json(&body.metadata),
+        owner_subject.map_or(JsValue::NULL, JsValue::from_str),
     ])?
     .run()
     .await?;
@@ -394,6 +837,36 @@ pub async fn register_agent(
 /// (`name > ?` OR `name = ? AND id > ?`) rather than a row-value comparison
 /// because SQLite/D1 cannot index `(a, b) > (?, ?)` as a single sargable
 /// expression.
+/// Capabilities a registered agent advertised, or empty if `id` is not a
+/// registered, active agent of the tenant.
+pub async fn get_agent_capabilities(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+) -> Result<Vec<String>> {
+    let caps: Option<String> = db
+        .prepare(
+            "SELECT capabilities FROM agents WHERE tenant_id = ?1 AND id = ?2 AND status = 'active'",
+        )
+        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(id)])?
+        .first(Some("capabilities"))
+        .await?;
+    Ok(caps
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default())
+}
+
+/// `owner_subject` of an active registered agent; `None` when there is no
+/// such agent or it has no owner.
+pub async fn get_agent_owner(db: &D1Database, tenant_id: &str, id: &str) -> Result<Option<String>> {
+    db.prepare(
+        "SELECT owner_subject FROM agents WHERE tenant_id = ?1 AND id = ?2 AND status = 'active'",
+    )
+    .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(id)])?
+    .first(Some("owner_subject"))
+    .await
+}
+
 pub async fn list_agents(
     db: &D1Database,
     tenant_id: &str,
@@ -448,6 +921,37 @@ pub(crate) fn compute_agents_next_cursor(
 
 // ── Checkpoints ─────────────────────────────────────────────────
 
+/// SELECT for `list_thread_checkpoints`: one thread's checkpoints, oldest
+/// first, `?3` at most.
+const SQL_LIST_THREAD_CHECKPOINTS: &str = "SELECT * FROM checkpoints \
+     WHERE tenant_id = ?1 AND thread_id = ?2 \
+     ORDER BY created_at ASC, id ASC LIMIT ?3";
+
+/// SELECT for `list_checkpoint_forks`: checkpoints of other threads whose
+/// parent belongs to thread `?2`, `?3` at most.
+const SQL_LIST_CHECKPOINT_FORKS: &str = "SELECT c.* FROM checkpoints p \
+     JOIN checkpoints c ON c.tenant_id = p.tenant_id AND c.parent_id = p.id \
+     WHERE p.tenant_id = ?1 AND p.thread_id = ?2 AND c.thread_id <> ?2 \
+     ORDER BY c.created_at ASC, c.id ASC LIMIT ?3";
+
+/// INSERT for `create_fork_checkpoint`: the first checkpoint of thread `?3`,
+/// written only while that thread has none, in one statement.
+const SQL_INSERT_FORK_CHECKPOINT: &str = "INSERT INTO checkpoints \
+     (tenant_id, id, thread_id, node_id, parent_id, state_r2_key, state_size_bytes, metadata, created_at) \
+     SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9 \
+     WHERE NOT EXISTS (SELECT 1 FROM checkpoints WHERE tenant_id = ?1 AND thread_id = ?3)";
+
+/// SELECT for `list_checkpoint_ancestors`: checkpoint `?2` and up to `?3`
+/// ancestors along `parent_id`, never leaving tenant `?1`.
+const SQL_LIST_CHECKPOINT_ANCESTORS: &str = "WITH RECURSIVE lineage(id, depth) AS ( \
+       SELECT id, 0 FROM checkpoints WHERE tenant_id = ?1 AND id = ?2 \
+       UNION ALL \
+       SELECT c.parent_id, l.depth + 1 FROM lineage l \
+       JOIN checkpoints c ON c.tenant_id = ?1 AND c.id = l.id \
+       WHERE c.parent_id IS NOT NULL AND l.depth < ?3 \
+     ) \
+     SELECT DISTINCT c.* FROM lineage l JOIN checkpoints c ON c.tenant_id = ?1 AND c.id = l.id";
+
 pub async fn create_checkpoint(
     db: &D1Database,
     tenant_id: &str,
@@ -479,6 +983,37 @@ pub async fn create_checkpoint(
     Ok(())
 }
 
+/// Insert a fork's checkpoint as the first of `body.thread_id`. Returns
+/// false, writing nothing, when that thread already has checkpoints.
+pub async fn create_fork_checkpoint(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+    body: &models::CreateCheckpoint,
+    r2_key: &str,
+    size: i64,
+) -> Result<bool> {
+    let result = db
+        .prepare(SQL_INSERT_FORK_CHECKPOINT)
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(id),
+            JsValue::from_str(&body.thread_id),
+            JsValue::from_str(&body.node_id),
+            opt_str(&body.parent_id),
+            JsValue::from_str(r2_key),
+            JsValue::from(size as f64),
+            opt_json(&body.metadata),
+            JsValue::from_str(&now_iso()),
+        ])?
+        .run()
+        .await?;
+    Ok(result
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false))
+}
+
 pub async fn get_latest_checkpoint(
     db: &D1Database,
     tenant_id: &str,
@@ -503,6 +1038,65 @@ pub async fn get_checkpoint_by_id(
         .await
 }
 
+/// Up to `limit` checkpoints of `thread_id`, oldest first.
+pub async fn list_thread_checkpoints(
+    db: &D1Database,
+    tenant_id: &str,
+    thread_id: &str,
+    limit: usize,
+) -> Result<Vec<CheckpointRow>> {
+    let result: D1Result = db
+        .prepare(SQL_LIST_THREAD_CHECKPOINTS)
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(thread_id),
+            JsValue::from(limit as f64),
+        ])?
+        .all()
+        .await?;
+    result.results()
+}
+
+/// Up to `limit` first checkpoints of the threads forked from a checkpoint
+/// of `thread_id`, oldest first.
+pub async fn list_checkpoint_forks(
+    db: &D1Database,
+    tenant_id: &str,
+    thread_id: &str,
+    limit: usize,
+) -> Result<Vec<CheckpointRow>> {
+    let result: D1Result = db
+        .prepare(SQL_LIST_CHECKPOINT_FORKS)
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(thread_id),
+            JsValue::from(limit as f64),
+        ])?
+        .all()
+        .await?;
+    result.results()
+}
+
+/// Checkpoint `id` and up to `max_depth` of its ancestors, unordered; see
+/// `checkpoint_tree::lineage`.
+pub async fn list_checkpoint_ancestors(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+    max_depth: usize,
+) -> Result<Vec<CheckpointRow>> {
+    let result: D1Result = db
+        .prepare(SQL_LIST_CHECKPOINT_ANCESTORS)
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(id),
+            JsValue::from(max_depth as f64),
+        ])?
+        .all()
+        .await?;
+    result.results()
+}
+
 pub async fn delete_checkpoint(db: &D1Database, tenant_id: &str, id: &str) -> Result<bool> {
     let res: D1Result = db
         .prepare("DELETE FROM checkpoints WHERE tenant_id = ?1 AND id = ?2")
@@ -1187,7 +1781,195 @@ pub async fn resume_run(
     }))
 }
 
-// ── WS5 Retrieval + Memory Federation ───────────────────────────
+/// Outcome of attempting to cancel a run. `Cancelled` and the idempotent
+/// `AlreadyCancelled` are 200s; `Terminal` maps to 409
+/// RUN_IN_TERMINAL_STATE; `NotFound` means there is no `runs` row (a play
+/// run may still exist without one).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CancelOutcome {
+    Cancelled(RunStatusUpdate),
+    AlreadyCancelled(RunStatusUpdate),
+    Terminal { current_status: String },
+    NotFound,
+}
+
+/// Attempt to cancel a run, appending `run.cancelled` to events_bronze on
+/// the transition. Task cancellation is the handler's job; this only owns
+/// the `runs` row.
+pub async fn cancel_run(
+    db: &D1Database,
+    tenant_id: &str,
+    run_id: &str,
+    actor: &str,
+) -> Result<CancelOutcome> {
+    let Some(current) = get_run(db, tenant_id, run_id).await? else {
+        return Ok(CancelOutcome::NotFound);
+    };
+    let update = |status: String| RunStatusUpdate {
+        id: run_id.to_string(),
+        status,
+        paused_at: None,
+        resumed_at: None,
+    };
+    match current.status.as_str() {
+        "cancelled" => return Ok(CancelOutcome::AlreadyCancelled(update(current.status))),
+        "succeeded" | "failed" => {
+            return Ok(CancelOutcome::Terminal {
+                current_status: current.status,
+            })
+        }
+        _ => {}
+    }
+
+    let result = db
+        .prepare(SQL_CANCEL_RUN)
+        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(run_id)])?
+        .run()
+        .await?;
+    let changed = result
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false);
+    if !changed {
+        // Raced with another transition; report where the run ended up.
+        let Some(refreshed) = get_run(db, tenant_id, run_id).await? else {
+            return Ok(CancelOutcome::NotFound);
+        };
+        return Ok(match refreshed.status.as_str() {
+            "cancelled" => CancelOutcome::AlreadyCancelled(update(refreshed.status)),
+            _ => CancelOutcome::Terminal {
+                current_status: refreshed.status,
+            },
+        });
+    }
+
+    insert_run_cancelled_event(db, tenant_id, run_id, actor, Some(&current.status)).await?;
+    Ok(CancelOutcome::Cancelled(update("cancelled".to_string())))
+}
+
+/// Record the outcome a play run reached on its own (`succeeded` or
+/// `failed`): the `runs` row, if there is one, takes `status`, and
+/// `run.{status}` is appended to events_bronze with `payload`. Returns
+/// whether a `runs` row changed.
+pub async fn finish_run(
+    db: &D1Database,
+    tenant_id: &str,
+    run_id: &str,
+    status: &str,
+    payload: serde_json::Value,
+) -> Result<bool> {
+    let result = db
+        .prepare(SQL_FINISH_RUN)
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(run_id),
+            JsValue::from_str(status),
+        ])?
+        .run()
+        .await?;
+    let changed = result
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false);
+
+    let event_type = format!("run.{status}");
+    let event = models::GraphEvent {
+        run_id: Some(run_id.to_string()),
+        thread_id: None,
+        event_type: event_type.clone(),
+        node_id: None,
+        actor: Some("play-manager".to_string()),
+        payload: Some(payload),
+    };
+    let now = now_iso();
+    let event_id = format!("evt_{event_type}_{run_id}_{now}");
+    insert_events_bronze(db, tenant_id, &[(event_id, &event, now)]).await?;
+    Ok(changed)
+}
+
+/// Append `run.cancelled` to events_bronze. `from_status` is `None` for a
+/// play run that has no `runs` row.
+pub async fn insert_run_cancelled_event(
+    db: &D1Database,
+    tenant_id: &str,
+    run_id: &str,
+    actor: &str,
+    from_status: Option<&str>,
+) -> Result<()> {
+    let event_id = format!("evt_cancel_{run_id}_{}", now_iso());
+    let payload = serde_json::json!({
+        "transition": "cancel",
+        "from_status": from_status,
+        "to_status": "cancelled",
+    });
+    db.prepare(
+        "INSERT INTO events_bronze (tenant_id, id, run_id, thread_id, event_type, node_id, actor, payload, created_at)
+         VALUES (?1, ?2, ?3, NULL, 'run.cancelled', NULL, ?4, ?5, datetime('now'))",
+    )
+    .bind(&[
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(&event_id),
+        JsValue::from_str(run_id),
+        JsValue::from_str(actor),
+        JsValue::from_str(&payload.to_string()),
+    ])?
+    .run()
+    .await?;
+    Ok(())
+}
+
+/// Mirror a TaskLeaseManager cancellation into D1: cancelled tasks get
+/// their final status, and each task gets a `task.cancelled` or
+/// `task.cancel_requested` graph event keyed by its job_id (the run id for
+/// play tasks).
+pub async fn record_task_cancellations(
+    db: &D1Database,
+    tenant_id: &str,
+    actor: &str,
+    outcome: &models::CancelledTasks,
+) -> Result<()> {
+    for task in &outcome.cancelled {
+        sync_task_status(db, tenant_id, task).await?;
+    }
+    let now = now_iso();
+    let events: Vec<(String, models::GraphEvent)> = outcome
+        .cancelled
+        .iter()
+        .map(|t| (t, "task.cancelled"))
+        .chain(
+            outcome
+                .cancel_requested
+                .iter()
+                .map(|t| (t, "task.cancel_requested")),
+        )
+        .map(|(task, event_type)| {
+            (
+                format!("evt_{event_type}_{}_{now}", task.id),
+                models::GraphEvent {
+                    run_id: Some(task.job_id.clone()),
+                    thread_id: None,
+                    event_type: event_type.to_string(),
+                    node_id: Some(task.id.clone()),
+                    actor: Some(actor.to_string()),
+                    payload: Some(serde_json::json!({
+                        "task_type": task.task_type,
+                        "agent_id": task.agent_id,
+                    })),
+                },
+            )
+        })
+        .collect();
+    if events.is_empty() {
+        return Ok(());
+    }
+    let refs: Vec<(String, &models::GraphEvent, String)> = events
+        .iter()
+        .map(|(id, evt)| (id.clone(), evt, now.clone()))
+        .collect();
+    insert_events_bronze(db, tenant_id, &refs).await
+}
+
+// ── WS5 Retrieval + Memory Federation ───────────────────────────
 
 pub async fn upsert_memory_item(
     db: &D1Database,
@@ -1263,135 +2045,480 @@ pub async fn upsert_memory_item(
     Ok(expires_at)
 }
 
-// ── WS2 Domain: Tasks (run-scoped) ──────────────────────────────
+// ── WS8: Cross-tenant federation ────────────────────────────────
 
-pub async fn create_ws2_task(
+#[derive(Debug, serde::Deserialize)]
+struct FederationRow {
+    tenant_id: String,
+    opt_in: i64,
+    allowed_tenants: String,
+    sharing_scope: String,
+}
+
+impl FederationRow {
+    fn into_config(self) -> (String, FederationConfig) {
+        let config = FederationConfig {
+            opt_in: self.opt_in != 0,
+            allowed_tenants: serde_json::from_str(&self.allowed_tenants).unwrap_or_default(),
+            sharing_scope: serde_json::from_str(&self.sharing_scope).unwrap_or_default(),
+        };
+        (self.tenant_id, config)
+    }
+}
+
+/// Sharing config for `tenant_id`; tenants without a row share nothing.
+pub async fn get_federation_config(db: &D1Database, tenant_id: &str) -> Result<FederationConfig> {
+    let row: Option<FederationRow> = db
+        .prepare("SELECT * FROM tenant_federation WHERE tenant_id = ?1")
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .first(None)
+        .await?;
+    Ok(row.map(|r| r.into_config().1).unwrap_or_default())
+}
+
+pub async fn put_federation_config(
     db: &D1Database,
     tenant_id: &str,
-    id: &str,
-    run_id: &str,
-    body: &models::CreateTask,
+    config: &FederationConfig,
 ) -> Result<()> {
-    let now = now_iso();
+    let mut allowed: Vec<&String> = config.allowed_tenants.iter().collect();
+    allowed.sort();
+    let mut scope: Vec<&String> = config.sharing_scope.iter().collect();
+    scope.sort();
     db.prepare(
-        "INSERT INTO tasks (tenant_id, id, run_id, plan_id, name, status, actor, created_at, updated_at, metadata)
-         VALUES (?1, ?2, ?3, ?4, ?5, 'created', ?6, ?7, ?7, ?8)",
+        "INSERT INTO tenant_federation (tenant_id, opt_in, allowed_tenants, sharing_scope, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(tenant_id) DO UPDATE SET
+           opt_in = excluded.opt_in,
+           allowed_tenants = excluded.allowed_tenants,
+           sharing_scope = excluded.sharing_scope,
+           updated_at = excluded.updated_at",
     )
     .bind(&[
         JsValue::from_str(tenant_id),
-        JsValue::from_str(id),
-        JsValue::from_str(run_id),
-        opt_str(&body.plan_id),
-        JsValue::from_str(&body.name),
-        opt_str(&body.actor),
-        JsValue::from_str(&now),
-        opt_json(&body.metadata),
+        JsValue::from(if config.opt_in { 1 } else { 0 }),
+        JsValue::from_str(&serde_json::to_string(&allowed)?),
+        JsValue::from_str(&serde_json::to_string(&scope)?),
+        JsValue::from_str(&now_iso()),
     ])?
     .run()
     .await?;
     Ok(())
 }
 
-pub async fn list_ws2_tasks(
+/// Tenants that share `data_type` with `target_tenant`. SQL narrows by the
+/// allowlist; `can_federate` is the authoritative check.
+pub async fn list_federation_sources(
     db: &D1Database,
-    tenant_id: &str,
-    run_id: &str,
-) -> Result<Vec<Ws2TaskResponse>> {
+    target_tenant: &str,
+    data_type: &str,
+) -> Result<Vec<String>> {
     let result: D1Result = db
-        .prepare("SELECT * FROM tasks WHERE tenant_id = ?1 AND run_id = ?2 ORDER BY created_at ASC")
-        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(run_id)])?
+        .prepare(
+            "SELECT * FROM tenant_federation
+             WHERE opt_in = 1 AND tenant_id != ?1
+               AND EXISTS (SELECT 1 FROM json_each(tenant_federation.allowed_tenants) WHERE value = ?1)
+             ORDER BY tenant_id ASC",
+        )
+        .bind(&[JsValue::from_str(target_tenant)])?
         .all()
         .await?;
-    let rows: Vec<Ws2TaskRow> = result.results()?;
-    Ok(rows.into_iter().map(|r| r.into_task_response()).collect())
+    let rows: Vec<FederationRow> = result.results()?;
+    Ok(rows
+        .into_iter()
+        .map(FederationRow::into_config)
+        .filter(|(_, config)| {
+            crate::tenant_security::can_federate(config, target_tenant, data_type)
+        })
+        .map(|(tenant_id, _)| tenant_id)
+        .collect())
 }
 
-// ── WS2 Domain: Plans ────────────────────────────────────────────
-
-pub async fn create_plan(
+/// Active, safe, current memory items of `source_tenant`, already passed
+/// through `federation::federate_memory_candidate`. The query is matched
+/// and scored against the anonymized title and summary only, so a partner
+/// cannot probe withheld fields or redacted secrets. Repo/run/thread/task
+/// filters are tenant-local identifiers and are deliberately not applied
+/// across tenants.
+pub async fn retrieve_federated_memory(
     db: &D1Database,
-    tenant_id: &str,
-    id: &str,
-    body: &models::CreatePlan,
-) -> Result<()> {
+    source_tenant: &str,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<models::MemoryCandidate>> {
     let now = now_iso();
-    let task_ids_json = body
-        .task_ids
-        .as_ref()
-        .map(|ids| serde_json::to_string(ids).unwrap())
-        .unwrap_or_else(|| "[]".into());
+    let now_ms = js_sys::Date::parse(&now);
+    let result: D1Result = db
+        .prepare(
+            "SELECT * FROM memory_index
+             WHERE tenant_id = ?1 AND status = 'active' AND unsafe_reason IS NULL
+             ORDER BY indexed_at DESC LIMIT 200",
+        )
+        .bind(&[JsValue::from_str(source_tenant)])?
+        .all()
+        .await?;
+    let rows: Vec<MemoryIndexRow> = result.results()?;
+    let latest_conflicts = latest_conflict_versions(&rows);
 
-    db.prepare(
-        "INSERT INTO plans (tenant_id, id, run_id, name, status, task_ids, created_at, updated_at, metadata)
-         VALUES (?1, ?2, ?3, ?4, 'created', ?5, ?6, ?6, ?7)",
-    )
-    .bind(&[
-        JsValue::from_str(tenant_id),
-        JsValue::from_str(id),
-        JsValue::from_str(&body.run_id),
-        JsValue::from_str(&body.name),
-        JsValue::from_str(&task_ids_json),
-        JsValue::from_str(&now),
-        opt_json(&body.metadata),
-    ])?
-    .run()
-    .await?;
-    Ok(())
+    let mut candidates = Vec::new();
+    for row in rows {
+        if is_stale(&row, &now) || is_conflicted(&row, &latest_conflicts) {
+            continue;
+        }
+        let source = models::MemoryCandidate {
+            id: row.id.clone(),
+            repo: row.repo.clone(),
+            kind: row.kind.clone(),
+            run_id: row.run_id.clone(),
+            task_id: row.task_id.clone(),
+            thread_id: row.thread_id.clone(),
+            title: row.title.clone(),
+            summary: row.summary.clone(),
+            tags: Vec::new(),
+            content_ref: row.content_ref.clone(),
+            success_rate: row.success_rate,
+            stale: false,
+            unsafe_reason: None,
+            conflicted: false,
+            estimated_tokens: 0,
+            score: 0.0,
+            source_tenant: Some(source_tenant.to_string()),
+        };
+        let Some(mut shared) = crate::federation::federate_memory_candidate(source, source_tenant)
+        else {
+            continue;
+        };
+        if !crate::federation::shared_text_matches(&shared, query) {
+            continue;
+        }
+        let shared_row = MemoryIndexRow {
+            title: shared.title.clone(),
+            summary: shared.summary.clone(),
+            tags: None,
+            ..row
+        };
+        shared.score = score_candidate(&shared_row, query, now_ms);
+        shared.estimated_tokens = estimate_tokens(&shared_row.title, &shared_row.summary, &None);
+        candidates.push(shared);
+    }
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    candidates.truncate(limit);
+    Ok(candidates)
 }
 
-// ── WS2 Domain: Tool Calls ──────────────────────────────────────
+// ── Recurring task schedules ────────────────────────────────────
 
-pub async fn record_tool_call(
+#[derive(Debug, serde::Deserialize)]
+struct TaskScheduleRow {
+    tenant_id: String,
+    id: String,
+    name: String,
+    cron: String,
+    template: String,
+    next_run_at: String,
+    last_run_at: Option<String>,
+    last_task_id: Option<String>,
+    last_error: Option<String>,
+    created_at: String,
+}
+
+impl TaskScheduleRow {
+    /// `None` when the stored template no longer parses; callers skip it.
+    fn into_schedule(self) -> Option<(String, models::TaskSchedule)> {
+        let task = serde_json::from_str(&self.template).ok()?;
+        Some((
+            self.tenant_id,
+            models::TaskSchedule {
+                id: self.id,
+                name: self.name,
+                cron: self.cron,
+                task,
+                next_run_at: self.next_run_at,
+                last_run_at: self.last_run_at,
+                last_task_id: self.last_task_id,
+                last_error: self.last_error,
+                created_at: self.created_at,
+            },
+        ))
+    }
+}
+
+pub async fn insert_task_schedule(
     db: &D1Database,
     tenant_id: &str,
-    id: &str,
-    body: &models::RecordToolCall,
+    schedule: &models::TaskSchedule,
 ) -> Result<()> {
-    let now = now_iso();
-    let input_json = serde_json::to_string(&body.input).unwrap();
-
     db.prepare(
-        "INSERT INTO tool_calls (tenant_id, id, run_id, task_id, tool_name, input, output, status, duration_ms, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'created', ?8, ?9)",
+        "INSERT INTO task_schedules (tenant_id, id, name, cron, template, next_run_at, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
     )
     .bind(&[
         JsValue::from_str(tenant_id),
-        JsValue::from_str(id),
-        JsValue::from_str(&body.run_id),
-        opt_str(&body.task_id),
-        JsValue::from_str(&body.tool_name),
-        JsValue::from_str(&input_json),
-        opt_json(&body.output),
-        match body.duration_ms {
-            Some(ms) => JsValue::from(ms as f64),
-            None => JsValue::NULL,
-        },
-        JsValue::from_str(&now),
+        JsValue::from_str(&schedule.id),
+        JsValue::from_str(&schedule.name),
+        JsValue::from_str(&schedule.cron),
+        JsValue::from_str(&serde_json::to_string(&schedule.task)?),
+        JsValue::from_str(&schedule.next_run_at),
+        JsValue::from_str(&schedule.created_at),
     ])?
     .run()
     .await?;
     Ok(())
 }
 
-pub async fn retrieve_memory_hybrid(
+pub async fn list_task_schedules(
     db: &D1Database,
     tenant_id: &str,
-    req: &models::RetrieveMemoryRequest,
-    semantic_ids: &[String],
-) -> Result<models::RetrieveMemoryResponse> {
-    let start = js_sys::Date::now();
-    let now = now_iso();
-    let now_ms = js_sys::Date::parse(&now);
-    let query_id = random_hex_id()?;
+) -> Result<Vec<models::TaskSchedule>> {
+    let result = db
+        .prepare(
+            "SELECT * FROM task_schedules WHERE tenant_id = ?1 ORDER BY created_at ASC, id ASC",
+        )
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .all()
+        .await?;
+    let rows: Vec<TaskScheduleRow> = result.results()?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|r| r.into_schedule().map(|(_, s)| s))
+        .collect())
+}
 
-    let mut repos = vec![req.repo.clone()];
-    for r in &req.related_repos {
-        if !repos.iter().any(|x| x == r) {
-            repos.push(r.clone());
-        }
-    }
+pub async fn delete_task_schedule(db: &D1Database, tenant_id: &str, id: &str) -> Result<bool> {
+    let result: D1Result = db
+        .prepare("DELETE FROM task_schedules WHERE tenant_id = ?1 AND id = ?2")
+        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(id)])?
+        .run()
+        .await?;
+    Ok(result
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false))
+}
 
-    let mut bind: Vec<JsValue> = vec![];
+/// Schedules across all tenants whose `next_run_at` is at or before `now`,
+/// soonest first, as `(tenant_id, schedule)`.
+pub async fn list_due_task_schedules(
+    db: &D1Database,
+    now: &str,
+    limit: u32,
+) -> Result<Vec<(String, models::TaskSchedule)>> {
+    let result = db
+        .prepare(
+            "SELECT * FROM task_schedules WHERE next_run_at <= ?1 ORDER BY next_run_at ASC LIMIT ?2",
+        )
+        .bind(&[JsValue::from_str(now), JsValue::from(limit)])?
+        .all()
+        .await?;
+    let rows: Vec<TaskScheduleRow> = result.results()?;
+    Ok(rows
+        .into_iter()
+        .filter_map(TaskScheduleRow::into_schedule)
+        .collect())
+}
+
+/// Compare-and-set `next_run_at` from `expected_next` to `next`. Returns
+/// `false` when another tick already advanced the schedule (or it was
+/// deleted), in which case the caller must not enqueue.
+pub async fn advance_task_schedule(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+    expected_next: &str,
+    next: &str,
+    now: &str,
+) -> Result<bool> {
+    let result: D1Result = db
+        .prepare(
+            "UPDATE task_schedules SET next_run_at = ?4, last_run_at = ?5
+             WHERE tenant_id = ?1 AND id = ?2 AND next_run_at = ?3",
+        )
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(id),
+            JsValue::from_str(expected_next),
+            JsValue::from_str(next),
+            JsValue::from_str(now),
+        ])?
+        .run()
+        .await?;
+    Ok(result
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false))
+}
+
+pub async fn set_task_schedule_last_task(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+    task_id: &str,
+) -> Result<()> {
+    db.prepare(
+        "UPDATE task_schedules SET last_task_id = ?3, last_error = NULL
+         WHERE tenant_id = ?1 AND id = ?2",
+    )
+    .bind(&[
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(id),
+        JsValue::from_str(task_id),
+    ])?
+    .run()
+    .await?;
+    Ok(())
+}
+
+/// Record why a claimed fire did not enqueue its task.
+pub async fn set_task_schedule_error(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+    error: &str,
+) -> Result<()> {
+    db.prepare("UPDATE task_schedules SET last_error = ?3 WHERE tenant_id = ?1 AND id = ?2")
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(id),
+            JsValue::from_str(error),
+        ])?
+        .run()
+        .await?;
+    Ok(())
+}
+
+// ── WS2 Domain: Tasks (run-scoped) ──────────────────────────────
+
+pub async fn create_ws2_task(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+    run_id: &str,
+    body: &models::CreateTask,
+) -> Result<()> {
+    let now = now_iso();
+    db.prepare(
+        "INSERT INTO tasks (tenant_id, id, run_id, plan_id, name, status, actor, created_at, updated_at, metadata)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'created', ?6, ?7, ?7, ?8)",
+    )
+    .bind(&[
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(id),
+        JsValue::from_str(run_id),
+        opt_str(&body.plan_id),
+        JsValue::from_str(&body.name),
+        opt_str(&body.actor),
+        JsValue::from_str(&now),
+        opt_json(&body.metadata),
+    ])?
+    .run()
+    .await?;
+    Ok(())
+}
+
+pub async fn list_ws2_tasks(
+    db: &D1Database,
+    tenant_id: &str,
+    run_id: &str,
+) -> Result<Vec<Ws2TaskResponse>> {
+    let result: D1Result = db
+        .prepare("SELECT * FROM tasks WHERE tenant_id = ?1 AND run_id = ?2 ORDER BY created_at ASC")
+        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(run_id)])?
+        .all()
+        .await?;
+    let rows: Vec<Ws2TaskRow> = result.results()?;
+    Ok(rows.into_iter().map(|r| r.into_task_response()).collect())
+}
+
+// ── WS2 Domain: Plans ────────────────────────────────────────────
+
+pub async fn create_plan(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+    body: &models::CreatePlan,
+) -> Result<()> {
+    let now = now_iso();
+    let task_ids_json = body
+        .task_ids
+        .as_ref()
+        .map(|ids| serde_json::to_string(ids).unwrap())
+        .unwrap_or_else(|| "[]".into());
+
+    db.prepare(
+        "INSERT INTO plans (tenant_id, id, run_id, name, status, task_ids, created_at, updated_at, metadata)
+         VALUES (?1, ?2, ?3, ?4, 'created', ?5, ?6, ?6, ?7)",
+    )
+    .bind(&[
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(id),
+        JsValue::from_str(&body.run_id),
+        JsValue::from_str(&body.name),
+        JsValue::from_str(&task_ids_json),
+        JsValue::from_str(&now),
+        opt_json(&body.metadata),
+    ])?
+    .run()
+    .await?;
+    Ok(())
+}
+
+// ── WS2 Domain: Tool Calls ──────────────────────────────────────
+
+pub async fn record_tool_call(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+    body: &models::RecordToolCall,
+) -> Result<()> {
+    let now = now_iso();
+    let input_json = serde_json::to_string(&body.input).unwrap();
+
+    db.prepare(
+        "INSERT INTO tool_calls (tenant_id, id, run_id, task_id, tool_name, input, output, status, duration_ms, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'created', ?8, ?9)",
+    )
+    .bind(&[
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(id),
+        JsValue::from_str(&body.run_id),
+        opt_str(&body.task_id),
+        JsValue::from_str(&body.tool_name),
+        JsValue::from_str(&input_json),
+        opt_json(&body.output),
+        match body.duration_ms {
+            Some(ms) => JsValue::from(ms as f64),
+            None => JsValue::NULL,
+        },
+        JsValue::from_str(&now),
+    ])?
+    .run()
+    .await?;
+    Ok(())
+}
+
+pub async fn retrieve_memory_hybrid(
+    db: &D1Database,
+    tenant_id: &str,
+    req: &models::RetrieveMemoryRequest,
+    semantic_ids: &[String],
+) -> Result<models::RetrieveMemoryResponse> {
+    let start = js_sys::Date::now();
+    let now = now_iso();
+    let now_ms = js_sys::Date::parse(&now);
+    let query_id = random_hex_id()?;
+
+    let mut repos = vec![req.repo.clone()];
+    for r in &req.related_repos {
+        if !repos.iter().any(|x| x == r) {
+            repos.push(r.clone());
+        }
+    }
+
+    let mut bind: Vec<JsValue> = vec![];
     let mut where_parts: Vec<String> = vec![];
     let mut idx = 1usize;
 
@@ -1514,6 +2641,7 @@ pub async fn retrieve_memory_hybrid(
             conflicted,
             estimated_tokens,
             score,
+            source_tenant: None,
         });
     }
 
@@ -1549,6 +2677,7 @@ pub async fn retrieve_memory_hybrid(
         unsafe_filtered,
         conflict_filtered,
         items: selected,
+        federated: Vec::new(),
     })
 }
 
@@ -1672,6 +2801,7 @@ pub async fn retrieve_memory(
             conflicted,
             estimated_tokens,
             score,
+            source_tenant: None,
         });
     }
 
@@ -1708,6 +2838,7 @@ pub async fn retrieve_memory(
         unsafe_filtered,
         conflict_filtered,
         items: selected,
+        federated: Vec::new(),
     })
 }
 
@@ -2802,164 +3933,816 @@ pub async fn insert_reasoning_trace(
             JsValue::from_str(&body.idempotency_key),
             JsValue::from_str(&now),
         ])?
-        .run()
+        .run()
+        .await?;
+
+    let inserted = res
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false);
+    Ok(inserted)
+}
+
+/// Page of reasoning traces for a single job. `after_step` is the cursor:
+/// callers paginate by feeding the last returned `step_number` back in.
+/// `has_more` is true when the DB held at least one more row beyond the
+/// returned page (computed by fetching `limit + 1` and trimming).
+pub struct ReasoningTracePage {
+    pub traces: Vec<models::ReasoningTrace>,
+    pub has_more: bool,
+}
+
+pub async fn list_reasoning_traces_for_job(
+    db: &D1Database,
+    tenant_id: &str,
+    job_id: &str,
+    after_step: Option<i64>,
+    limit: u32,
+) -> Result<ReasoningTracePage> {
+    // Fetch one extra row to detect whether more pages exist without a
+    // separate COUNT round-trip.
+    let fetch = (limit as i64) + 1;
+    let result: D1Result = db
+        .prepare(
+            "SELECT * FROM reasoning_traces
+             WHERE tenant_id = ?1 AND job_id = ?2 AND step_number > ?3
+             ORDER BY step_number ASC
+             LIMIT ?4",
+        )
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(job_id),
+            JsValue::from(after_step.unwrap_or(-1) as f64),
+            JsValue::from(fetch as f64),
+        ])?
+        .all()
+        .await?;
+    let mut rows: Vec<ReasoningTraceRow> = result.results()?;
+    let has_more = rows.len() > limit as usize;
+    if has_more {
+        rows.truncate(limit as usize);
+    }
+    Ok(ReasoningTracePage {
+        traces: rows.into_iter().map(|r| r.into_trace()).collect(),
+        has_more,
+    })
+}
+
+pub async fn get_reasoning_trace(
+    db: &D1Database,
+    tenant_id: &str,
+    id: &str,
+) -> Result<Option<models::ReasoningTrace>> {
+    let row: Option<ReasoningTraceRow> = db
+        .prepare("SELECT * FROM reasoning_traces WHERE tenant_id = ?1 AND id = ?2")
+        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(id)])?
+        .first(None)
+        .await?;
+    Ok(row.map(|r| r.into_trace()))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ReasoningTraceRow {
+    id: String,
+    schema_version: i64,
+    agent_id: String,
+    job_id: String,
+    parent_span_id: Option<String>,
+    step_number: i64,
+    step_type: String,
+    inputs_inline: Option<String>,
+    inputs_r2_key: Option<String>,
+    outputs_inline: Option<String>,
+    outputs_r2_key: Option<String>,
+    tokens_input: i64,
+    tokens_output: i64,
+    tokens_cached: i64,
+    started_at: String,
+    completed_at: Option<String>,
+    created_at: String,
+}
+
+impl ReasoningTraceRow {
+    fn into_trace(self) -> models::ReasoningTrace {
+        let step_type = models::StepType::from_storage_str(&self.step_type);
+        models::ReasoningTrace {
+            id: self.id,
+            schema_version: self.schema_version as u32,
+            agent_id: self.agent_id,
+            job_id: self.job_id,
+            parent_span_id: self.parent_span_id,
+            step_number: self.step_number as u32,
+            step_type,
+            inputs_inline: self
+                .inputs_inline
+                .and_then(|s| serde_json::from_str(&s).ok()),
+            inputs_r2_key: self.inputs_r2_key,
+            outputs_inline: self
+                .outputs_inline
+                .and_then(|s| serde_json::from_str(&s).ok()),
+            outputs_r2_key: self.outputs_r2_key,
+            tokens: models::TokenCost {
+                input: self.tokens_input as u32,
+                output: self.tokens_output as u32,
+                cached: self.tokens_cached as u32,
+            },
+            started_at: self.started_at,
+            completed_at: self.completed_at,
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Upsert for `provision_tenant`. `secret_scan_mode` is only written on
+/// insert, and a deleted tenant is left as is: the `WHERE` turns the update
+/// into a no-op, so provisioning never brings a deleted tenant back.
+const SQL_PROVISION_TENANT: &str =
+    "INSERT INTO tenants (tenant_id, display_name, plan, quota_runs_per_minute, \
+       api_requests_per_minute, api_burst_limit, secret_scan_mode, updated_at) \
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, COALESCE(?8, 'redact'), ?7) \
+     ON CONFLICT(tenant_id) DO UPDATE SET \
+       display_name = excluded.display_name, \
+       plan = excluded.plan, \
+       quota_runs_per_minute = excluded.quota_runs_per_minute, \
+       api_requests_per_minute = excluded.api_requests_per_minute, \
+       api_burst_limit = excluded.api_burst_limit, \
+       updated_at = excluded.updated_at \
+     WHERE tenants.status <> 'deleted'";
+
+/// Create or update a tenant. Returns false, writing nothing, when the
+/// tenant exists and has been deleted.
+pub async fn provision_tenant(
+    db: &D1Database,
+    body: &models::TenantProvisionRequest,
+    rate_limit: &TenantRateLimitConfig,
+) -> Result<bool> {
+    let now = now_iso();
+    let plan = if body.plan.trim().is_empty() {
+        "standard"
+    } else {
+        body.plan.as_str()
+    };
+    let quota_runs = if body.quota_runs_per_minute <= 0 {
+        120
+    } else {
+        body.quota_runs_per_minute
+    };
+
+    Ok(db
+        .prepare(SQL_PROVISION_TENANT)
+        .bind(&[
+            JsValue::from_str(&body.tenant_id),
+            JsValue::from_str(&body.display_name),
+            JsValue::from_str(plan),
+            JsValue::from(quota_runs as f64),
+            JsValue::from(rate_limit.requests_per_minute as f64),
+            JsValue::from(rate_limit.burst_limit as f64),
+            JsValue::from_str(&now),
+            opt_str(&body.secret_scan_mode),
+        ])?
+        .run()
+        .await?
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TenantRateLimitRow {
+    api_requests_per_minute: i64,
+    api_burst_limit: i64,
+}
+
+/// The API rate limits last provisioned for `tenant_id`, or `None` for a
+/// tenant that was never provisioned. `quota_bytes` is not stored in D1
+/// and comes back as the default.
+pub async fn get_tenant_rate_limit(
+    db: &D1Database,
+    tenant_id: &str,
+) -> Result<Option<TenantRateLimitConfig>> {
+    let row: Option<TenantRateLimitRow> = db
+        .prepare(
+            "SELECT api_requests_per_minute, api_burst_limit FROM tenants WHERE tenant_id = ?1",
+        )
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .first(None)
+        .await?;
+    Ok(row.map(|r| TenantRateLimitConfig {
+        requests_per_minute: r.api_requests_per_minute.max(0) as u64,
+        burst_limit: r.api_burst_limit.max(0) as u64,
+        ..TenantRateLimitConfig::default()
+    }))
+}
+
+// ── WS8: Tenant lifecycle ───────────────────────────────────────
+//
+// `table` arguments below always come from
+// `tenant_lifecycle::TENANT_SCOPED_TABLES`, never from request input, so
+// interpolating them into the SQL text is safe. `tenant_id` is still bound.
+
+#[derive(Debug, serde::Deserialize)]
+struct TenantStatusRow {
+    status: String,
+}
+
+/// What the router edge needs to know about a provisioned tenant.
+#[derive(Debug, serde::Deserialize)]
+pub struct TenantAdmissionRow {
+    pub status: String,
+    pub secret_scan_mode: String,
+}
+
+/// Status and ingest scan mode in one read, for `fetch`'s admission check.
+pub async fn get_tenant_admission(
+    db: &D1Database,
+    tenant_id: &str,
+) -> Result<Option<TenantAdmissionRow>> {
+    db.prepare("SELECT status, secret_scan_mode FROM tenants WHERE tenant_id = ?1")
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .first(None)
+        .await
+}
+
+/// Lifecycle status for `tenant_id`, or `None` for tenants that were never
+/// provisioned (header/dev-mode tenants and pre-WS8 data stay admitted).
+pub async fn get_tenant_status(db: &D1Database, tenant_id: &str) -> Result<Option<String>> {
+    let row: Option<TenantStatusRow> = db
+        .prepare("SELECT status FROM tenants WHERE tenant_id = ?1")
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .first(None)
+        .await?;
+    Ok(row.map(|r| r.status))
+}
+
+/// Move a provisioned tenant to `status`. Returns `false` when no tenant
+/// row exists.
+/// Change a provisioned tenant's ingest scan mode. Returns false when the
+/// tenant has no row. `provision_tenant` only sets the mode on first insert,
+/// so this is the one place it changes afterwards.
+pub async fn set_tenant_secret_scan_mode(
+    db: &D1Database,
+    tenant_id: &str,
+    mode: &str,
+    now: &str,
+) -> Result<bool> {
+    Ok(db
+        .prepare(
+            "UPDATE tenants SET secret_scan_mode = ?2, updated_at = ?3
+             WHERE tenant_id = ?1 AND status <> 'deleted'",
+        )
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(mode),
+            JsValue::from_str(now),
+        ])?
+        .run()
+        .await?
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false))
+}
+
+pub async fn set_tenant_status(
+    db: &D1Database,
+    tenant_id: &str,
+    status: &str,
+    reason: Option<&str>,
+    now: &str,
+) -> Result<bool> {
+    let reason = reason.map(JsValue::from_str).unwrap_or(JsValue::NULL);
+    Ok(db
+        .prepare(
+            "UPDATE tenants SET status = ?2, status_reason = ?3, status_changed_at = ?4, updated_at = ?4
+             WHERE tenant_id = ?1",
+        )
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from_str(status),
+            reason,
+            JsValue::from_str(now),
+        ])?
+        .run()
+        .await?
+        .meta()?
+        .map(|m| m.changes.unwrap_or(0) > 0)
+        .unwrap_or(false))
+}
+
+/// Every row of `table` owned by `tenant_id`, as raw JSON objects.
+pub async fn export_tenant_rows(
+    db: &D1Database,
+    table: &str,
+    tenant_id: &str,
+) -> Result<Vec<serde_json::Value>> {
+    let sql = format!("SELECT * FROM {table} WHERE tenant_id = ?1");
+    let result: D1Result = db
+        .prepare(&sql)
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .all()
+        .await?;
+    result.results()
+}
+
+pub async fn count_tenant_rows(db: &D1Database, table: &str, tenant_id: &str) -> Result<usize> {
+    let sql = format!("SELECT count(*) as count FROM {table} WHERE tenant_id = ?1");
+    let row: Option<CountRow> = db
+        .prepare(&sql)
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .first(None)
+        .await?;
+    Ok(row.map(|r| r.count as usize).unwrap_or(0))
+}
+
+/// Delete every row of `table` owned by `tenant_id`; returns D1's change count.
+pub async fn delete_tenant_rows(db: &D1Database, table: &str, tenant_id: &str) -> Result<usize> {
+    let sql = format!("DELETE FROM {table} WHERE tenant_id = ?1");
+    Ok(db
+        .prepare(&sql)
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .run()
+        .await?
+        .meta()?
+        .and_then(|m| m.changes)
+        .unwrap_or(0))
+}
+
+/// Record a play launch so a tenant hard-delete can find its `PlayManager`.
+pub async fn record_play_run(
+    db: &D1Database,
+    tenant_id: &str,
+    run_id: &str,
+    play_name: &str,
+) -> Result<()> {
+    db.prepare(
+        "INSERT OR IGNORE INTO play_runs (tenant_id, run_id, play_name, launched_at)
+         VALUES (?1, ?2, ?3, ?4)",
+    )
+    .bind(&[
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(run_id),
+        JsValue::from_str(play_name),
+        JsValue::from_str(&now_iso()),
+    ])?
+    .run()
+    .await?;
+    Ok(())
+}
+
+async fn tenant_ids(db: &D1Database, sql: &str, tenant_id: &str) -> Result<Vec<String>> {
+    let result: D1Result = db
+        .prepare(sql)
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .all()
+        .await?;
+    Ok(result
+        .results::<IdRow>()?
+        .into_iter()
+        .map(|r| r.id)
+        .collect())
+}
+
+/// Run ids of every `PlayManager` the tenant launched. Runs launched before
+/// `play_runs` existed are found through the `job_id` of their tasks.
+pub async fn tenant_play_run_ids(db: &D1Database, tenant_id: &str) -> Result<Vec<String>> {
+    tenant_ids(
+        db,
+        "SELECT run_id AS id FROM play_runs WHERE tenant_id = ?1
+         UNION
+         SELECT job_id AS id FROM mcp_tasks WHERE tenant_id = ?1 AND play_id IS NOT NULL",
+        tenant_id,
+    )
+    .await
+}
+
+/// Thread ids of every `ThreadManager` the tenant checkpointed into.
+pub async fn tenant_thread_ids(db: &D1Database, tenant_id: &str) -> Result<Vec<String>> {
+    tenant_ids(
+        db,
+        "SELECT DISTINCT thread_id AS id FROM checkpoints WHERE tenant_id = ?1",
+        tenant_id,
+    )
+    .await
+}
+
+/// Ids of the tenant's memory items, which are also their Vectorize ids.
+pub async fn tenant_memory_ids(db: &D1Database, tenant_id: &str) -> Result<Vec<String>> {
+    tenant_ids(
+        db,
+        "SELECT id FROM memory_index WHERE tenant_id = ?1",
+        tenant_id,
+    )
+    .await
+}
+
+pub async fn insert_tenant_deletion_report(
+    db: &D1Database,
+    report: &models::TenantDeletionReport,
+) -> Result<()> {
+    db.prepare(
+        "INSERT INTO tenant_deletion_reports (tenant_id, id, report_json, digest, verified, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )
+    .bind(&[
+        JsValue::from_str(&report.tenant_id),
+        JsValue::from_str(&report.report_id),
+        JsValue::from_str(&serde_json::to_string(report)?),
+        JsValue::from_str(&report.digest),
+        JsValue::from(if report.verified { 1 } else { 0 }),
+        JsValue::from_str(&report.deleted_at),
+    ])?
+    .run()
+    .await?;
+    Ok(())
+}
+
+/// Record the findings of an ingest-time secret scan next to the row they
+/// describe. No-op when the write scanned clean.
+pub async fn record_secret_scan(
+    db: &D1Database,
+    tenant_id: &str,
+    target_table: &str,
+    target_id: &str,
+    mode: crate::secret_scan::ScanMode,
+    findings: &[crate::secret_scan::Finding],
+) -> Result<()> {
+    if findings.is_empty() {
+        return Ok(());
+    }
+    db.prepare(
+        "INSERT INTO secret_scan_results (tenant_id, id, target_table, target_id, mode, finding_count, findings, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )
+    .bind(&[
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(&random_hex_id()?),
+        JsValue::from_str(target_table),
+        JsValue::from_str(target_id),
+        JsValue::from_str(mode.as_str()),
+        JsValue::from(findings.len() as f64),
+        JsValue::from_str(&serde_json::to_string(findings)?),
+        JsValue::from_str(&now_iso()),
+    ])?
+    .run()
+    .await?;
+    Ok(())
+}
+
+// ── WS8: Audit log ──────────────────────────────────────────────
+
+#[derive(Debug, serde::Deserialize)]
+struct AuditRow {
+    tenant_id: String,
+    seq: i64,
+    id: String,
+    actor: String,
+    method: String,
+    route: String,
+    path: String,
+    target_ids: String,
+    status: i64,
+    outcome: String,
+    error_code: Option<String>,
+    created_at: String,
+    prev_hash: String,
+    hash: String,
+}
+
+impl AuditRow {
+    /// Unparseable `target_ids` becomes an empty map, which the verifier
+    /// then reports as a hash mismatch rather than failing the listing.
+    fn into_record(self) -> models::AuditRecord {
+        models::AuditRecord {
+            tenant_id: self.tenant_id,
+            seq: self.seq,
+            id: self.id,
+            actor: self.actor,
+            method: self.method,
+            route: self.route,
+            path: self.path,
+            target_ids: serde_json::from_str(&self.target_ids).unwrap_or_default(),
+            status: self.status as u16,
+            outcome: self.outcome,
+            error_code: self.error_code,
+            created_at: self.created_at,
+            prev_hash: self.prev_hash,
+            hash: self.hash,
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct AuditHeadRow {
+    seq: i64,
+    hash: String,
+}
+
+/// `(seq, hash)` of the tenant's newest audit row.
+pub async fn audit_head(db: &D1Database, tenant_id: &str) -> Result<Option<(i64, String)>> {
+    let row: Option<AuditHeadRow> = db
+        .prepare("SELECT seq, hash FROM audit_log WHERE tenant_id = ?1 ORDER BY seq DESC LIMIT 1")
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .first(None)
+        .await?;
+    Ok(row.map(|r| (r.seq, r.hash)))
+}
+
+/// Plain INSERT: a concurrent writer holding the same `seq` makes this fail
+/// on the primary key, and `audit::append` retries from the new head.
+pub async fn insert_audit_record(db: &D1Database, record: &models::AuditRecord) -> Result<()> {
+    db.prepare(
+        "INSERT INTO audit_log (tenant_id, seq, id, actor, method, route, path, target_ids,
+                                status, outcome, error_code, created_at, prev_hash, hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+    )
+    .bind(&[
+        JsValue::from_str(&record.tenant_id),
+        JsValue::from(record.seq as f64),
+        JsValue::from_str(&record.id),
+        JsValue::from_str(&record.actor),
+        JsValue::from_str(&record.method),
+        JsValue::from_str(&record.route),
+        JsValue::from_str(&record.path),
+        JsValue::from_str(&serde_json::to_string(&record.target_ids)?),
+        JsValue::from(record.status as f64),
+        JsValue::from_str(&record.outcome),
+        opt_str(&record.error_code),
+        JsValue::from_str(&record.created_at),
+        JsValue::from_str(&record.prev_hash),
+        JsValue::from_str(&record.hash),
+    ])?
+    .run()
+    .await?;
+    Ok(())
+}
+
+/// Rows with `seq > after_seq` in chain order, for verification.
+pub async fn list_audit_ascending(
+    db: &D1Database,
+    tenant_id: &str,
+    after_seq: i64,
+    limit: u32,
+) -> Result<Vec<models::AuditRecord>> {
+    let result = db
+        .prepare(
+            "SELECT * FROM audit_log WHERE tenant_id = ?1 AND seq > ?2 ORDER BY seq ASC LIMIT ?3",
+        )
+        .bind(&[
+            JsValue::from_str(tenant_id),
+            JsValue::from(after_seq as f64),
+            JsValue::from(limit),
+        ])?
+        .all()
         .await?;
-
-    let inserted = res
-        .meta()?
-        .map(|m| m.changes.unwrap_or(0) > 0)
-        .unwrap_or(false);
-    Ok(inserted)
+    let rows: Vec<AuditRow> = result.results()?;
+    Ok(rows.into_iter().map(AuditRow::into_record).collect())
 }
 
-/// Page of reasoning traces for a single job. `after_step` is the cursor:
-/// callers paginate by feeding the last returned `step_number` back in.
-/// `has_more` is true when the DB held at least one more row beyond the
-/// returned page (computed by fetching `limit + 1` and trimming).
-pub struct ReasoningTracePage {
-    pub traces: Vec<models::ReasoningTrace>,
-    pub has_more: bool,
+/// Record an audit entry whose append to `audit_log` failed.
+pub async fn insert_audit_append_failure(
+    db: &D1Database,
+    failure: &models::AuditAppendFailure,
+) -> Result<()> {
+    db.prepare(
+        "INSERT INTO audit_append_failures (id, tenant_id, actor, method, path, status, error,
+                                            created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )
+    .bind(&[
+        JsValue::from_str(&failure.id),
+        JsValue::from_str(&failure.tenant_id),
+        JsValue::from_str(&failure.actor),
+        JsValue::from_str(&failure.method),
+        JsValue::from_str(&failure.path),
+        JsValue::from(failure.status as f64),
+        JsValue::from_str(&failure.error),
+        JsValue::from_str(&failure.created_at),
+    ])?
+    .run()
+    .await?;
+    Ok(())
 }
 
-pub async fn list_reasoning_traces_for_job(
+/// Oldest-first failed appends for the tenant, at most `limit`.
+pub async fn list_audit_append_failures(
     db: &D1Database,
     tenant_id: &str,
-    job_id: &str,
-    after_step: Option<i64>,
     limit: u32,
-) -> Result<ReasoningTracePage> {
-    // Fetch one extra row to detect whether more pages exist without a
-    // separate COUNT round-trip.
-    let fetch = (limit as i64) + 1;
-    let result: D1Result = db
+) -> Result<Vec<models::AuditAppendFailure>> {
+    let result = db
         .prepare(
-            "SELECT * FROM reasoning_traces
-             WHERE tenant_id = ?1 AND job_id = ?2 AND step_number > ?3
-             ORDER BY step_number ASC
-             LIMIT ?4",
+            "SELECT * FROM audit_append_failures WHERE tenant_id = ?1
+             ORDER BY created_at ASC LIMIT ?2",
         )
-        .bind(&[
-            JsValue::from_str(tenant_id),
-            JsValue::from_str(job_id),
-            JsValue::from(after_step.unwrap_or(-1) as f64),
-            JsValue::from(fetch as f64),
-        ])?
+        .bind(&[JsValue::from_str(tenant_id), JsValue::from(limit)])?
         .all()
         .await?;
-    let mut rows: Vec<ReasoningTraceRow> = result.results()?;
-    let has_more = rows.len() > limit as usize;
-    if has_more {
-        rows.truncate(limit as usize);
+    result.results()
+}
+
+/// Newest-first page of the audit log, optionally for one actor.
+pub async fn list_audit(
+    db: &D1Database,
+    tenant_id: &str,
+    actor: Option<&str>,
+    limit: u32,
+    cursor: Option<&crate::pagination::AuditCursor>,
+) -> Result<(
+    Vec<models::AuditRecord>,
+    Option<crate::pagination::AuditCursor>,
+)> {
+    let mut clauses: Vec<String> = vec!["tenant_id = ?".into()];
+    let mut bindings: Vec<JsValue> = vec![JsValue::from_str(tenant_id)];
+    if let Some(a) = actor {
+        clauses.push("actor = ?".into());
+        bindings.push(JsValue::from_str(a));
     }
-    Ok(ReasoningTracePage {
-        traces: rows.into_iter().map(|r| r.into_trace()).collect(),
-        has_more,
-    })
+    if let Some(c) = cursor {
+        clauses.push("seq < ?".into());
+        bindings.push(JsValue::from(c.seq as f64));
+    }
+    bindings.push(JsValue::from(limit.saturating_add(1)));
+
+    let query = format!(
+        "SELECT * FROM audit_log WHERE {} ORDER BY seq DESC LIMIT ?",
+        clauses.join(" AND ")
+    );
+    let result: D1Result = db.prepare(&query).bind(&bindings)?.all().await?;
+    let mut rows: Vec<AuditRow> = result.results()?;
+    let next_cursor = if rows.len() as u32 > limit {
+        rows.truncate(limit as usize);
+        rows.last()
+            .map(|r| crate::pagination::AuditCursor { seq: r.seq })
+    } else {
+        None
+    };
+    Ok((
+        rows.into_iter().map(AuditRow::into_record).collect(),
+        next_cursor,
+    ))
 }
 
-pub async fn get_reasoning_trace(
+// ── WS8: Envelope encryption data keys ──────────────────────────
+
+/// A tenant data key as stored: the key material is wrapped under the
+/// master key named by `master_key_id` (see `envelope`).
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct DataKeyRow {
+    pub key_id: String,
+    pub wrapped_key: String,
+    pub master_key_id: String,
+}
+
+pub async fn get_active_data_key(db: &D1Database, tenant_id: &str) -> Result<Option<DataKeyRow>> {
+    db.prepare(
+        "SELECT key_id, wrapped_key, master_key_id FROM tenant_data_keys
+         WHERE tenant_id = ?1 AND active = 1
+         ORDER BY created_at DESC, key_id LIMIT 1",
+    )
+    .bind(&[JsValue::from_str(tenant_id)])?
+    .first(None)
+    .await
+}
+
+pub async fn get_data_key(
     db: &D1Database,
     tenant_id: &str,
-    id: &str,
-) -> Result<Option<models::ReasoningTrace>> {
-    let row: Option<ReasoningTraceRow> = db
-        .prepare("SELECT * FROM reasoning_traces WHERE tenant_id = ?1 AND id = ?2")
-        .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(id)])?
-        .first(None)
+    key_id: &str,
+) -> Result<Option<DataKeyRow>> {
+    db.prepare(
+        "SELECT key_id, wrapped_key, master_key_id FROM tenant_data_keys
+         WHERE tenant_id = ?1 AND key_id = ?2",
+    )
+    .bind(&[JsValue::from_str(tenant_id), JsValue::from_str(key_id)])?
+    .first(None)
+    .await
+}
+
+pub async fn list_data_keys(db: &D1Database, tenant_id: &str) -> Result<Vec<DataKeyRow>> {
+    let result = db
+        .prepare(
+            "SELECT key_id, wrapped_key, master_key_id FROM tenant_data_keys
+             WHERE tenant_id = ?1 ORDER BY created_at",
+        )
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .all()
         .await?;
-    Ok(row.map(|r| r.into_trace()))
+    result.results()
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct ReasoningTraceRow {
-    id: String,
-    schema_version: i64,
-    agent_id: String,
-    job_id: String,
-    parent_span_id: Option<String>,
-    step_number: i64,
-    step_type: String,
-    inputs_inline: Option<String>,
-    inputs_r2_key: Option<String>,
-    outputs_inline: Option<String>,
-    outputs_r2_key: Option<String>,
-    tokens_input: i64,
-    tokens_output: i64,
-    tokens_cached: i64,
-    started_at: String,
-    completed_at: Option<String>,
-    created_at: String,
+const SQL_INSERT_ACTIVE_DATA_KEY: &str =
+    "INSERT INTO tenant_data_keys (tenant_id, key_id, wrapped_key, master_key_id, active, created_at)
+     SELECT ?1, ?2, ?3, ?4, 1, ?5
+     WHERE NOT EXISTS (SELECT 1 FROM tenant_data_keys WHERE tenant_id = ?1 AND active = 1)";
+
+fn data_key_binds(
+    tenant_id: &str,
+    key_id: &str,
+    wrapped_key: &str,
+    master_key_id: &str,
+) -> [JsValue; 5] {
+    [
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(key_id),
+        JsValue::from_str(wrapped_key),
+        JsValue::from_str(master_key_id),
+        JsValue::from_str(&now_iso()),
+    ]
 }
 
-impl ReasoningTraceRow {
-    fn into_trace(self) -> models::ReasoningTrace {
-        let step_type = models::StepType::from_storage_str(&self.step_type);
-        models::ReasoningTrace {
-            id: self.id,
-            schema_version: self.schema_version as u32,
-            agent_id: self.agent_id,
-            job_id: self.job_id,
-            parent_span_id: self.parent_span_id,
-            step_number: self.step_number as u32,
-            step_type,
-            inputs_inline: self
-                .inputs_inline
-                .and_then(|s| serde_json::from_str(&s).ok()),
-            inputs_r2_key: self.inputs_r2_key,
-            outputs_inline: self
-                .outputs_inline
-                .and_then(|s| serde_json::from_str(&s).ok()),
-            outputs_r2_key: self.outputs_r2_key,
-            tokens: models::TokenCost {
-                input: self.tokens_input as u32,
-                output: self.tokens_output as u32,
-                cached: self.tokens_cached as u32,
-            },
-            started_at: self.started_at,
-            completed_at: self.completed_at,
-            created_at: self.created_at,
-        }
-    }
+/// Store the tenant's first data key as active. Returns `false` without
+/// writing if another request already stored one; the caller then reads
+/// that key with [`get_active_data_key`].
+pub async fn insert_first_data_key(
+    db: &D1Database,
+    tenant_id: &str,
+    key_id: &str,
+    wrapped_key: &str,
+    master_key_id: &str,
+) -> Result<bool> {
+    let result = db
+        .prepare(SQL_INSERT_ACTIVE_DATA_KEY)
+        .bind(&data_key_binds(
+            tenant_id,
+            key_id,
+            wrapped_key,
+            master_key_id,
+        ))?
+        .run()
+        .await?;
+    Ok(result.meta()?.and_then(|m| m.changes).unwrap_or(0) > 0)
 }
 
-pub async fn provision_tenant(
+/// Retire the active data key and store `key_id` as the new one in a single
+/// batch, so the tenant never has two active keys or none. Retired keys
+/// still decrypt old objects.
+pub async fn replace_active_data_key(
     db: &D1Database,
-    body: &models::TenantProvisionRequest,
+    tenant_id: &str,
+    key_id: &str,
+    wrapped_key: &str,
+    master_key_id: &str,
 ) -> Result<()> {
-    let now = now_iso();
-    let plan = if body.plan.trim().is_empty() {
-        "standard"
-    } else {
-        body.plan.as_str()
-    };
-    let quota_runs = if body.quota_runs_per_minute <= 0 {
-        120
-    } else {
-        body.quota_runs_per_minute
-    };
+    db.batch(vec![
+        db.prepare("UPDATE tenant_data_keys SET active = 0 WHERE tenant_id = ?1 AND active = 1")
+            .bind(&[JsValue::from_str(tenant_id)])?,
+        db.prepare(SQL_INSERT_ACTIVE_DATA_KEY)
+            .bind(&data_key_binds(
+                tenant_id,
+                key_id,
+                wrapped_key,
+                master_key_id,
+            ))?,
+    ])
+    .await?;
+    Ok(())
+}
 
+pub async fn rewrap_data_key(
+    db: &D1Database,
+    tenant_id: &str,
+    key_id: &str,
+    wrapped_key: &str,
+    master_key_id: &str,
+    now: &str,
+) -> Result<()> {
     db.prepare(
-        "INSERT INTO tenants (tenant_id, display_name, plan, quota_runs_per_minute, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5)
-         ON CONFLICT(tenant_id) DO UPDATE SET
-           display_name = excluded.display_name,
-           plan = excluded.plan,
-           quota_runs_per_minute = excluded.quota_runs_per_minute,
-           updated_at = excluded.updated_at",
+        "UPDATE tenant_data_keys SET wrapped_key = ?3, master_key_id = ?4, rewrapped_at = ?5
+         WHERE tenant_id = ?1 AND key_id = ?2",
     )
     .bind(&[
-        JsValue::from_str(&body.tenant_id),
-        JsValue::from_str(&body.display_name),
-        JsValue::from_str(plan),
-        JsValue::from(quota_runs as f64),
-        JsValue::from_str(&now),
+        JsValue::from_str(tenant_id),
+        JsValue::from_str(key_id),
+        JsValue::from_str(wrapped_key),
+        JsValue::from_str(master_key_id),
+        JsValue::from_str(now),
     ])?
     .run()
     .await?;
-
     Ok(())
 }
 
+#[derive(Debug, serde::Deserialize)]
+struct DeletionReportRow {
+    report_json: String,
+}
+
+/// Most recent deletion report for `tenant_id`.
+pub async fn latest_tenant_deletion_report(
+    db: &D1Database,
+    tenant_id: &str,
+) -> Result<Option<models::TenantDeletionReport>> {
+    let row: Option<DeletionReportRow> = db
+        .prepare(
+            "SELECT report_json FROM tenant_deletion_reports WHERE tenant_id = ?1
+             ORDER BY created_at DESC LIMIT 1",
+        )
+        .bind(&[JsValue::from_str(tenant_id)])?
+        .first(None)
+        .await?;
+    row.map(|r| serde_json::from_str(&r.report_json).map_err(Error::from))
+        .transpose()
+}
+
 fn add_seconds_to_now(seconds: u64) -> String {
     let now = js_sys::Date::now();
     let future = js_sys::Date::new(&JsValue::from_f64(now + (seconds as f64 * 1000.0)));
@@ -3409,6 +5192,11 @@ struct CountRow {
     count: i64,
 }
 
+#[derive(Debug, serde::Deserialize)]
+struct IdRow {
+    id: String,
+}
+
 #[derive(Debug, serde::Deserialize)]
 struct KeyRow {
     key: String,
@@ -3439,6 +5227,12 @@ pub struct TaskRow {
     pub completed_at: Option<String>,
     #[serde(default)]
     pub tenant_id: Option<String>,
+    #[serde(default)]
+    pub not_before: Option<String>,
+    #[serde(default)]
+    pub requires: Option<String>,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
 impl TaskRow {
@@ -3462,6 +5256,17 @@ impl TaskRow {
             completed_at: self.completed_at,
             memory_context: None,
             tenant_id: self.tenant_id,
+            enqueued_at_ms: None,
+            failures: Vec::new(),
+            not_before: self.not_before,
+            cancel_requested: false,
+            requires: self
+                .requires
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            idempotency_key: self.idempotency_key,
+            timeout_ms: None,
+            deadline_ms: None,
         }
     }
 }
@@ -4635,7 +6440,10 @@ pub async fn list_pending_gemini_batch_jobs(
         .await?;
 
     let rows: Vec<JobRow> = result.results()?;
-    Ok(rows.into_iter().map(|r| (r.tenant_id, r.job_name, r.model)).collect())
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.tenant_id, r.job_name, r.model))
+        .collect())
 }
 
 pub async fn get_integration(
@@ -4946,7 +6754,6 @@ impl HumanDecisionRow {
 ///
 /// Tenant scoping: `tenant_id` is bound to ?1; see
 /// `SQL_INSERT_HUMAN_DECISION`.
-#[allow(dead_code)]
 pub async fn create_human_decision(
     db: &D1Database,
     tenant_id: &str,
@@ -4975,7 +6782,6 @@ pub async fn create_human_decision(
 /// returns the full set rather than paginating; pagination can be added
 /// in a follow-up slice if a single run accumulates enough decisions to
 /// warrant it.
-#[allow(dead_code)]
 pub async fn list_human_decisions_by_run(
     db: &D1Database,
     tenant_id: &str,
@@ -5438,6 +7244,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn play_definition_version_sql_is_tenant_scoped() {
+        for sql in [
+            SQL_GET_LATEST_PLAY_DEFINITION,
+            SQL_GET_PLAY_DEFINITION_VERSION,
+        ] {
+            assert!(
+                sql.contains("WHERE tenant_id = ?1 AND name = ?2"),
+                "got: {sql}"
+            );
+        }
+        assert!(SQL_GET_PLAY_DEFINITION_VERSION.contains("AND version = ?3"));
+        for sql in [SQL_INSERT_FIRST_PLAY_VERSION, SQL_INSERT_NEXT_PLAY_VERSION] {
+            assert!(sql.contains("(tenant_id, name, version,"), "got: {sql}");
+            assert!(sql.contains("SELECT ?1, ?2, ?3"), "got: {sql}");
+        }
+        assert!(SQL_INSERT_PLAY_DEFINITION.contains("VALUES (?1, ?2,"));
+    }
+
+    #[test]
+    fn play_definition_writes_are_guarded() {
+        // Create writes a version row only for a play that does not exist
+        // yet, and never overwrites one.
+        assert!(SQL_INSERT_FIRST_PLAY_VERSION.contains("WHERE NOT EXISTS"));
+        assert!(SQL_INSERT_PLAY_DEFINITION.contains("ON CONFLICT(tenant_id, name) DO NOTHING"));
+        // Update only applies on top of the version it was computed from.
+        assert!(SQL_INSERT_NEXT_PLAY_VERSION.contains("AND version = ?12"));
+        assert!(SQL_UPDATE_PLAY_DEFINITION
+            .contains("WHERE tenant_id = ?1 AND name = ?2 AND version = ?8"));
+    }
+
     #[test]
     fn cross_tenant_sql_create_policy_escalation_writes_tenant_id() {
         // The INSERT must include `tenant_id` in the column list and
@@ -5976,6 +7813,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn cross_tenant_sql_checkpoint_tree_queries_bind_tenant_id_first() {
+        for sql in [
+            SQL_LIST_THREAD_CHECKPOINTS,
+            SQL_LIST_CHECKPOINT_FORKS,
+            SQL_LIST_CHECKPOINT_ANCESTORS,
+        ] {
+            assert!(
+                sql.contains("tenant_id = ?1"),
+                "checkpoint tree SQL must filter on tenant_id = ?1; got: {sql}"
+            );
+        }
+        assert!(SQL_LIST_THREAD_CHECKPOINTS.contains("tenant_id = ?1 AND thread_id = ?2"));
+        // Forks join parent to child within one tenant only, and are capped.
+        assert!(SQL_LIST_CHECKPOINT_FORKS.contains("c.tenant_id = p.tenant_id"));
+        assert!(SQL_LIST_CHECKPOINT_FORKS.ends_with("LIMIT ?3"));
+        // Every step of the recursive walk stays in the tenant.
+        assert_eq!(
+            SQL_LIST_CHECKPOINT_ANCESTORS
+                .matches("c.tenant_id = ?1")
+                .count(),
+            2,
+            "{SQL_LIST_CHECKPOINT_ANCESTORS}"
+        );
+        assert!(SQL_LIST_CHECKPOINT_ANCESTORS.contains("l.depth < ?3"));
+    }
+
+    #[test]
+    fn provision_never_resurrects_a_deleted_tenant() {
+        let sql = SQL_PROVISION_TENANT;
+        assert!(sql.ends_with("WHERE tenants.status <> 'deleted'"), "{sql}");
+        assert!(!sql.contains("status ="), "{sql}");
+        // The scan mode is only set on insert.
+        let update = sql.split("DO UPDATE").nth(1).unwrap();
+        assert!(!update.contains("secret_scan_mode"), "{sql}");
+    }
+
+    #[test]
+    fn cross_tenant_sql_fork_checkpoint_claims_the_thread_in_its_tenant() {
+        let sql = SQL_INSERT_FORK_CHECKPOINT;
+        assert!(
+            sql.contains("SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9"),
+            "{sql}"
+        );
+        assert!(!sql.contains("?10"), "{sql}");
+        // The emptiness check and the insert are one statement, scoped to
+        // the caller's tenant.
+        assert!(
+            sql.contains("WHERE NOT EXISTS (SELECT 1 FROM checkpoints WHERE tenant_id = ?1 AND thread_id = ?3)"),
+            "{sql}"
+        );
+    }
+
     #[test]
     fn cross_tenant_sql_list_human_decisions_by_run_filters_on_tenant_id() {
         // SELECT must include `tenant_id = ?1` in the WHERE clause so a
@@ -6128,6 +8018,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sql_finish_run_is_tenant_scoped_and_skips_terminal_runs() {
+        let sql = SQL_FINISH_RUN;
+        assert!(sql.contains("WHERE tenant_id = ?1"), "got: {sql}");
+        assert!(sql.contains("AND id = ?2"), "got: {sql}");
+        assert!(sql.contains("status = ?3"), "got: {sql}");
+        assert!(
+            sql.contains("status IN ('created', 'running', 'paused')"),
+            "a cancelled or finished run must keep its status; got: {sql}",
+        );
+    }
+
+    #[test]
+    fn sql_cancel_run_is_tenant_scoped_and_skips_terminal_runs() {
+        let sql = SQL_CANCEL_RUN;
+        assert!(sql.contains("tenant_id = ?1"), "got: {sql}");
+        assert!(
+            sql.contains("status IN ('created', 'running', 'paused')"),
+            "SQL_CANCEL_RUN must only cancel live or paused runs; got: {sql}",
+        );
+        assert!(sql.contains("status = 'cancelled'"), "got: {sql}");
+    }
+
     #[test]
     fn pause_resume_outcomes_serialize_status_update() {
         // The RunStatusUpdate envelope serialised in handler responses
@@ -0,0 +1,370 @@
+//! WS8: envelope encryption at rest for R2 objects.
+//!
+//! Each tenant gets AES-256 data keys (DEKs) stored in `tenant_data_keys`
+//! only in wrapped form: AES-256-GCM under a master key loaded from the
+//! `ENCRYPTION_MASTER_KEYS` secret. Sealed objects carry a small header
+//! naming the DEK that encrypted them, so reads never depend on which key is
+//! currently active:
+//!
+//! ```text
+//! "DFE1" | key_id_len: u8 | key_id | nonce (12 bytes) | ciphertext + tag
+//! ```
+//!
+//! The secret is a comma-separated list of `id:base64key` entries; the first
+//! entry is the active master key and the rest stay available for unwrapping
+//! until [`TenantKeyring::rotate`] has re-wrapped every DEK under the active
+//! one. Rotation touches `tenant_data_keys` rows only — objects are never
+//! rewritten. Hard-deleting a tenant drops its DEK rows, which leaves any
+//! surviving ciphertext unreadable.
+//!
+//! When the secret is unset (local dev) writes stay plaintext; reads of
+//! sealed objects then fail rather than return ciphertext.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use worker::{D1Database, Env, Error, Result};
+
+use crate::db;
+
+/// Secret binding holding the master key list (`id:base64key,...`).
+const MASTER_KEYS_SECRET: &str = "ENCRYPTION_MASTER_KEYS";
+/// Leading bytes of every sealed object.
+const MAGIC: &[u8; 4] = b"DFE1";
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+type RawKey = [u8; KEY_LEN];
+
+/// Master keys parsed from [`MASTER_KEYS_SECRET`]; the first is active.
+#[derive(Clone)]
+pub struct MasterKeys {
+    keys: Vec<(String, RawKey)>,
+}
+
+impl MasterKeys {
+    pub fn parse(raw: &str) -> std::result::Result<Self, String> {
+        let mut keys: Vec<(String, RawKey)> = Vec::new();
+        for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (id, encoded) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("entry `{entry}` is not `id:base64key`"))?;
+            let id = id.trim();
+            if id.is_empty() {
+                return Err("master key id must be non-empty".to_string());
+            }
+            if keys.iter().any(|(existing, _)| existing == id) {
+                return Err(format!("duplicate master key id `{id}`"));
+            }
+            let key: RawKey = STANDARD
+                .decode(encoded.trim())
+                .map_err(|_| format!("master key `{id}` is not base64"))?
+                .try_into()
+                .map_err(|_| format!("master key `{id}` must be {KEY_LEN} bytes"))?;
+            keys.push((id.to_string(), key));
+        }
+        if keys.is_empty() {
+            return Err("no master keys configured".to_string());
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn active_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    fn get(&self, id: &str) -> Option<&RawKey> {
+        self.keys.iter().find(|(k, _)| k == id).map(|(_, key)| key)
+    }
+}
+
+/// True when `blob` starts with the sealed-object header magic.
+pub fn is_sealed(blob: &[u8]) -> bool {
+    blob.starts_with(MAGIC)
+}
+
+/// Header key id of a sealed object.
+pub fn sealed_key_id(blob: &[u8]) -> Option<&str> {
+    let len = *blob.get(MAGIC.len())? as usize;
+    let start = MAGIC.len() + 1;
+    std::str::from_utf8(blob.get(start..start + len)?).ok()
+}
+
+/// Encrypt `plaintext` under `dek`. The header (magic + key id) is bound as
+/// associated data so it cannot be swapped onto another ciphertext.
+pub fn seal(dek: &RawKey, key_id: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let key_id_len = u8::try_from(key_id.len())
+        .map_err(|_| Error::RustError("data key id too long".to_string()))?;
+    let mut out =
+        Vec::with_capacity(MAGIC.len() + 1 + key_id.len() + NONCE_LEN + plaintext.len() + 16);
+    out.extend_from_slice(MAGIC);
+    out.push(key_id_len);
+    out.extend_from_slice(key_id.as_bytes());
+    let header_len = out.len();
+    let nonce = random_bytes::<NONCE_LEN>()?;
+    let ciphertext = aead_encrypt(dek, &nonce, plaintext, &out[..header_len])?;
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypt a blob produced by [`seal`] with the DEK its header names.
+pub fn open(dek: &RawKey, blob: &[u8]) -> Result<Vec<u8>> {
+    let key_id = sealed_key_id(blob).ok_or_else(|| malformed("truncated header"))?;
+    let header_len = MAGIC.len() + 1 + key_id.len();
+    let body = &blob[header_len..];
+    if body.len() < NONCE_LEN {
+        return Err(malformed("truncated nonce"));
+    }
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+    aead_decrypt(dek, nonce, ciphertext, &blob[..header_len])
+}
+
+/// Wrap a DEK under a master key; the tenant and key id are bound as
+/// associated data so a wrapped key cannot be replayed into another row.
+pub fn wrap_key(master: &RawKey, tenant_id: &str, key_id: &str, dek: &RawKey) -> Result<String> {
+    let nonce = random_bytes::<NONCE_LEN>()?;
+    let aad = format!("{tenant_id}:{key_id}");
+    let mut out = nonce.to_vec();
+    out.extend(aead_encrypt(master, &nonce, dek, aad.as_bytes())?);
+    Ok(STANDARD.encode(out))
+}
+
+pub fn unwrap_key(master: &RawKey, tenant_id: &str, key_id: &str, wrapped: &str) -> Result<RawKey> {
+    let raw = STANDARD
+        .decode(wrapped)
+        .map_err(|_| malformed("wrapped data key is not base64"))?;
+    if raw.len() < NONCE_LEN {
+        return Err(malformed("wrapped data key truncated"));
+    }
+    let (nonce, ciphertext) = raw.split_at(NONCE_LEN);
+    let aad = format!("{tenant_id}:{key_id}");
+    aead_decrypt(master, nonce, ciphertext, aad.as_bytes())?
+        .try_into()
+        .map_err(|_| malformed("unwrapped data key has the wrong length"))
+}
+
+fn aead_encrypt(key: &RawKey, nonce: &[u8], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .encrypt(Nonce::from_slice(nonce), Payload { msg, aad })
+        .map_err(|_| Error::RustError("encryption failed".to_string()))
+}
+
+fn aead_decrypt(key: &RawKey, nonce: &[u8], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), Payload { msg, aad })
+        .map_err(|_| Error::RustError("decryption failed: wrong key or tampered data".to_string()))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    getrandom::getrandom(&mut buf)
+        .map_err(|err| Error::RustError(format!("failed to generate key material: {err}")))?;
+    Ok(buf)
+}
+
+fn malformed(what: &str) -> Error {
+    Error::RustError(format!("malformed sealed object: {what}"))
+}
+
+/// Outcome of [`TenantKeyring::rotate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationOutcome {
+    pub master_key_id: String,
+    pub rewrapped: usize,
+    pub active_data_key_id: String,
+    pub data_key_rotated: bool,
+}
+
+/// One tenant's view of its data keys for a single request.
+pub struct TenantKeyring<'a> {
+    d1: &'a D1Database,
+    tenant_id: &'a str,
+    master: MasterKeys,
+}
+
+impl<'a> TenantKeyring<'a> {
+    /// `None` when no master keys are configured (encryption disabled).
+    pub fn from_env(env: &Env, d1: &'a D1Database, tenant_id: &'a str) -> Result<Option<Self>> {
+        let Ok(raw) = env.secret(MASTER_KEYS_SECRET) else {
+            return Ok(None);
+        };
+        let master = MasterKeys::parse(&raw.to_string())
+            .map_err(|e| Error::RustError(format!("{MASTER_KEYS_SECRET}: {e}")))?;
+        Ok(Some(Self {
+            d1,
+            tenant_id,
+            master,
+        }))
+    }
+
+    pub async fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let (key_id, dek) = self.active_data_key().await?;
+        seal(&dek, &key_id, plaintext)
+    }
+
+    pub async fn open(&self, blob: &[u8]) -> Result<Vec<u8>> {
+        let key_id = sealed_key_id(blob).ok_or_else(|| malformed("truncated header"))?;
+        let row = db::get_data_key(self.d1, self.tenant_id, key_id)
+            .await?
+            .ok_or_else(|| Error::RustError(format!("data key {key_id} not found")))?;
+        open(&self.unwrap_row(&row)?, blob)
+    }
+
+    /// Re-wrap every DEK not already under the active master key, and with
+    /// `new_data_key` retire the active DEK in favour of a fresh one in a
+    /// single D1 batch. Old DEKs stay readable, so existing objects need no
+    /// rewrite.
+    pub async fn rotate(&self, new_data_key: bool) -> Result<RotationOutcome> {
+        let active_master = self.master.active_id().to_string();
+        let master_key = self.master.keys[0].1;
+        let now = db::now_iso();
+        let mut rewrapped = 0;
+        for row in db::list_data_keys(self.d1, self.tenant_id).await? {
+            if row.master_key_id == active_master {
+                continue;
+            }
+            let dek = self.unwrap_row(&row)?;
+            let wrapped = wrap_key(&master_key, self.tenant_id, &row.key_id, &dek)?;
+            db::rewrap_data_key(
+                self.d1,
+                self.tenant_id,
+                &row.key_id,
+                &wrapped,
+                &active_master,
+                &now,
+            )
+            .await?;
+            rewrapped += 1;
+        }
+        let active_data_key_id = if new_data_key {
+            let (key_id, _, wrapped) = self.mint_data_key()?;
+            db::replace_active_data_key(self.d1, self.tenant_id, &key_id, &wrapped, &active_master)
+                .await?;
+            key_id
+        } else {
+            self.active_data_key().await?.0
+        };
+        Ok(RotationOutcome {
+            master_key_id: active_master,
+            rewrapped,
+            active_data_key_id,
+            data_key_rotated: new_data_key,
+        })
+    }
+
+    /// The tenant's active DEK, minting one on first use. Concurrent first
+    /// uses race on the insert; the losers adopt the winner's key.
+    async fn active_data_key(&self) -> Result<(String, RawKey)> {
+        if let Some(row) = db::get_active_data_key(self.d1, self.tenant_id).await? {
+            return Ok((row.key_id.clone(), self.unwrap_row(&row)?));
+        }
+        let (key_id, dek, wrapped) = self.mint_data_key()?;
+        let master_id = self.master.active_id();
+        if db::insert_first_data_key(self.d1, self.tenant_id, &key_id, &wrapped, master_id).await? {
+            return Ok((key_id, dek));
+        }
+        let row = db::get_active_data_key(self.d1, self.tenant_id)
+            .await?
+            .ok_or_else(|| Error::RustError("active data key vanished after insert".into()))?;
+        Ok((row.key_id.clone(), self.unwrap_row(&row)?))
+    }
+
+    /// A fresh DEK and its wrapped form under the active master key.
+    fn mint_data_key(&self) -> Result<(String, RawKey, String)> {
+        let key_id = hex::encode(random_bytes::<16>()?);
+        let dek = random_bytes::<KEY_LEN>()?;
+        let wrapped = wrap_key(&self.master.keys[0].1, self.tenant_id, &key_id, &dek)?;
+        Ok((key_id, dek, wrapped))
+    }
+
+    fn unwrap_row(&self, row: &db::DataKeyRow) -> Result<RawKey> {
+        let master = self.master.get(&row.master_key_id).ok_or_else(|| {
+            Error::RustError(format!(
+                "data key {} is wrapped by master key `{}`, which is not configured",
+                row.key_id, row.master_key_id
+            ))
+        })?;
+        unwrap_key(master, self.tenant_id, &row.key_id, &row.wrapped_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEK: RawKey = [7u8; KEY_LEN];
+
+    fn b64(key: &RawKey) -> String {
+        STANDARD.encode(key)
+    }
+
+    #[test]
+    fn seal_open_round_trip() {
+        let blob = seal(&DEK, "k1", b"checkpoint state").unwrap();
+        assert!(is_sealed(&blob));
+        assert_eq!(sealed_key_id(&blob), Some("k1"));
+        assert!(!blob.windows(10).any(|w| w == b"checkpoint"));
+        assert_eq!(open(&DEK, &blob).unwrap(), b"checkpoint state");
+    }
+
+    #[test]
+    fn nonces_are_fresh_per_seal() {
+        assert_ne!(
+            seal(&DEK, "k1", b"x").unwrap(),
+            seal(&DEK, "k1", b"x").unwrap()
+        );
+    }
+
+    #[test]
+    fn open_rejects_wrong_key_and_tampering() {
+        let blob = seal(&DEK, "k1", b"payload").unwrap();
+        assert!(open(&[8u8; KEY_LEN], &blob).is_err());
+
+        let mut flipped = blob.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert!(open(&DEK, &flipped).is_err());
+
+        // Re-labelling the header breaks the associated data.
+        let mut relabelled = blob;
+        relabelled[MAGIC.len() + 1] = b'x';
+        assert!(open(&DEK, &relabelled).is_err());
+    }
+
+    #[test]
+    fn plaintext_is_not_mistaken_for_sealed() {
+        assert!(!is_sealed(br#"{"state":1}"#));
+        assert!(open(&DEK, b"DFE1").is_err());
+    }
+
+    #[test]
+    fn wrapped_keys_are_bound_to_tenant_and_key_id() {
+        let master = [1u8; KEY_LEN];
+        let wrapped = wrap_key(&master, "acme", "k1", &DEK).unwrap();
+        assert_eq!(unwrap_key(&master, "acme", "k1", &wrapped).unwrap(), DEK);
+        assert!(unwrap_key(&master, "other", "k1", &wrapped).is_err());
+        assert!(unwrap_key(&master, "acme", "k2", &wrapped).is_err());
+        assert!(unwrap_key(&[2u8; KEY_LEN], "acme", "k1", &wrapped).is_err());
+    }
+
+    #[test]
+    fn master_keys_parse_first_entry_as_active() {
+        let raw = format!("m2:{}, m1:{}", b64(&[2u8; 32]), b64(&[1u8; 32]));
+        let keys = MasterKeys::parse(&raw).unwrap();
+        assert_eq!(keys.active_id(), "m2");
+        assert_eq!(keys.get("m1"), Some(&[1u8; 32]));
+        assert!(keys.get("m3").is_none());
+    }
+
+    #[test]
+    fn master_keys_reject_bad_config() {
+        let good = b64(&[1u8; 32]);
+        assert!(MasterKeys::parse("").is_err());
+        assert!(MasterKeys::parse(&good).is_err(), "missing id");
+        assert!(MasterKeys::parse(&format!(":{good}")).is_err());
+        assert!(MasterKeys::parse("m1:not-base64!").is_err());
+        assert!(MasterKeys::parse(&format!("m1:{}", &b64(&[1u8; 32])[..20])).is_err());
+        assert!(MasterKeys::parse(&format!("m1:{good},m1:{good}")).is_err());
+    }
+}
@@ -42,7 +42,6 @@ pub fn error_response(code: &str, message: &str, status: u16) -> Result<Response
 }
 
 /// Build a JSON error response that includes structured details.
-#[allow(dead_code)] // surface for future handlers (validation, conflict, etc.)
 pub fn error_response_with_details(
     code: &str,
     message: &str,
@@ -1,5 +1,7 @@
 use crate::db;
-use crate::integrations::gemini::{BatchRequest, GenerateContentRequest, Content, Part, GeminiClient};
+use crate::integrations::gemini::{
+    BatchRequest, Content, GeminiClient, GenerateContentRequest, Part,
+};
 use wasm_bindgen::JsValue;
 use worker::*;
 
@@ -12,7 +14,7 @@ pub async fn summarize_telemetry_batch(
     model: &str,
 ) -> Result<String> {
     let d1 = env.d1("DB")?;
-    
+
     // 1. Gather telemetry events that need summarization.
     // For now, let's just get the last 1000 events.
     // In a real scenario, we might want to filter by run_id or time range.
@@ -20,7 +22,7 @@ pub async fn summarize_telemetry_batch(
         .bind(&[JsValue::from_str(tenant_id)])?
         .all()
         .await?;
-    
+
     let rows: Vec<serde_json::Value> = events.results()?;
     if rows.is_empty() {
         return Ok("No events to summarize".to_string());
@@ -31,14 +33,17 @@ pub async fn summarize_telemetry_batch(
     for (i, row) in rows.into_iter().enumerate() {
         let event_type = row["event_type"].as_str().unwrap_or("unknown");
         let payload = row["payload"].to_string();
-        
+
         let req = BatchRequest {
             key: format!("event_{}_{}", tenant_id, i),
             request: GenerateContentRequest {
                 contents: vec![Content {
                     role: Some("user".to_string()),
                     parts: vec![Part {
-                        text: Some(format!("Summarize this telemetry event (type: {}): {}", event_type, payload)),
+                        text: Some(format!(
+                            "Summarize this telemetry event (type: {}): {}",
+                            event_type, payload
+                        )),
                         inline_data: None,
                         file_data: None,
                         function_call: None,
@@ -48,7 +53,10 @@ pub async fn summarize_telemetry_batch(
                 system_instruction: Some(Content {
                     role: None,
                     parts: vec![Part {
-                        text: Some("You are a telemetry analyst. Provide a concise summary of the event.".to_string()),
+                        text: Some(
+                            "You are a telemetry analyst. Provide a concise summary of the event."
+                                .to_string(),
+                        ),
                         inline_data: None,
                         file_data: None,
                         function_call: None,
@@ -61,39 +69,38 @@ pub async fn summarize_telemetry_batch(
                 generation_config: None,
             },
         };
-        
+
         jsonl.push_str(&serde_json::to_string(&req).map_err(|e| Error::RustError(e.to_string()))?);
         jsonl.push('\n');
     }
 
     // 3. Upload to Gemini.
     let client = GeminiClient::new(api_key.to_string());
-    let input_file_uri = client.upload_file(
-        jsonl.into_bytes(),
-        "application/jsonl",
-        Some(&format!("telemetry_summary_{}.jsonl", tenant_id))
-    ).await.map_err(|e| Error::RustError(e))?;
+    let input_file_uri = client
+        .upload_file(
+            jsonl.into_bytes(),
+            "application/jsonl",
+            Some(&format!("telemetry_summary_{}.jsonl", tenant_id)),
+        )
+        .await
+        .map_err(Error::RustError)?;
 
     // 4. Start Batch Job.
-    let job = client.create_batch_job(
-        model,
-        &input_file_uri,
-        Some(&format!("Telemetry Summary for {}", tenant_id))
-    ).await.map_err(|e| Error::RustError(e))?;
+    let job = client
+        .create_batch_job(
+            model,
+            &input_file_uri,
+            Some(&format!("Telemetry Summary for {}", tenant_id)),
+        )
+        .await
+        .map_err(Error::RustError)?;
 
     // 5. Track Job in D1.
     let mut random_bytes = [0u8; 8];
     getrandom::getrandom(&mut random_bytes).map_err(|e| Error::RustError(e.to_string()))?;
     let job_id = format!("gb-{}", hex::encode(random_bytes));
-    
-    db::create_gemini_batch_job(
-        &d1,
-        tenant_id,
-        &job_id,
-        &job,
-        model,
-        &input_file_uri
-    ).await?;
+
+    db::create_gemini_batch_job(&d1, tenant_id, &job_id, &job, model, &input_file_uri).await?;
 
     Ok(job.name)
 }
@@ -102,7 +109,7 @@ pub async fn summarize_telemetry_batch(
 pub async fn poll_gemini_jobs(env: &Env) -> Result<()> {
     let d1 = env.d1("DB")?;
     let pending_jobs = db::list_pending_gemini_batch_jobs(&d1).await?;
-    
+
     if pending_jobs.is_empty() {
         return Ok(());
     }
@@ -115,10 +122,14 @@ pub async fn poll_gemini_jobs(env: &Env) -> Result<()> {
         match client.get_batch_job(&job_name).await {
             Ok(job) => {
                 db::update_gemini_batch_job(&d1, &tenant_id, &job).await?;
-                
+
                 if job.state == crate::integrations::gemini::BatchJobState::Succeeded {
                     // TODO: Retrieve results and process them (e.g. save summaries to gold layer)
-                    console_log!("Gemini Batch Job {} succeeded for tenant {}", job_name, tenant_id);
+                    console_log!(
+                        "Gemini Batch Job {} succeeded for tenant {}",
+                        job_name,
+                        tenant_id
+                    );
                 }
             }
             Err(e) => {
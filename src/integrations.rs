@@ -391,6 +391,9 @@ pub mod llama_rs {
             play_id: None,
             parent_task_id: None,
             max_retries: Some(1),
+            not_before: None,
+            requires: Vec::new(),
+            idempotency_key: None,
         }
     }
 
@@ -500,6 +503,7 @@ pub mod llama_rs {
                 include_stale: false,
                 include_unsafe: false,
                 include_conflicted: false,
+                include_federated: false,
             },
             token_budget: req.token_budget.unwrap_or(4096),
         }
@@ -827,7 +831,10 @@ pub mod gemini {
                 .append("X-Goog-Upload-Protocol", "resumable")
                 .map_err(|e| format!("{:?}", e))?;
             headers
-                .append("X-Goog-Upload-Header-Content-Length", &data.len().to_string())
+                .append(
+                    "X-Goog-Upload-Header-Content-Length",
+                    &data.len().to_string(),
+                )
                 .map_err(|e| format!("{:?}", e))?;
             headers
                 .append("X-Goog-Upload-Header-Content-Type", mime_type)
@@ -840,7 +847,8 @@ pub mod gemini {
                 &serde_json::to_string(&metadata).map_err(|e| format!("{:?}", e))?,
             )));
 
-            let req = worker::Request::new_with_init(&url, &opts).map_err(|e| format!("{:?}", e))?;
+            let req =
+                worker::Request::new_with_init(&url, &opts).map_err(|e| format!("{:?}", e))?;
             let res = worker::Fetch::Request(req)
                 .send()
                 .await
@@ -864,8 +872,8 @@ pub mod gemini {
             array.copy_from(&data);
             put_opts.with_body(Some(array.into()));
 
-            let put_req =
-                worker::Request::new_with_init(&upload_url, &put_opts).map_err(|e| format!("{:?}", e))?;
+            let put_req = worker::Request::new_with_init(&upload_url, &put_opts)
+                .map_err(|e| format!("{:?}", e))?;
             let mut put_res = worker::Fetch::Request(put_req)
                 .send()
                 .await
@@ -932,7 +940,8 @@ pub mod gemini {
                 &serde_json::to_string(&body).map_err(|e| format!("{:?}", e))?,
             )));
 
-            let req = worker::Request::new_with_init(&url, &opts).map_err(|e| format!("{:?}", e))?;
+            let req =
+                worker::Request::new_with_init(&url, &opts).map_err(|e| format!("{:?}", e))?;
             let mut res = worker::Fetch::Request(req)
                 .send()
                 .await
@@ -946,9 +955,7 @@ pub mod gemini {
                 ));
             }
 
-            res.json::<BatchJob>()
-                .await
-                .map_err(|e| format!("{:?}", e))
+            res.json::<BatchJob>().await.map_err(|e| format!("{:?}", e))
         }
 
         /// Get status of a batch job.
@@ -967,9 +974,7 @@ pub mod gemini {
                 return Err(format!("Gemini API error: {}", res.status_code()));
             }
 
-            res.json::<BatchJob>()
-                .await
-                .map_err(|e| format!("{:?}", e))
+            res.json::<BatchJob>().await.map_err(|e| format!("{:?}", e))
         }
     }
 }
@@ -3,29 +3,46 @@ use serde::Serialize;
 use wasm_bindgen::JsValue;
 use worker::*;
 
+mod audit;
+mod auth;
+mod authz;
+mod capability;
+mod checkpoint_diff;
+mod checkpoint_tree;
+mod cron;
 mod db;
+mod envelope;
 mod errors;
+mod federation;
+mod gemini_service;
 mod integrations;
 mod metrics;
 mod models;
 mod openapi;
 mod pagination;
 mod play_do;
+mod play_graph;
+mod play_template;
 mod policy;
+mod rate_limit_do;
+mod secret_scan;
 mod storage;
 mod task_do;
+mod task_schedule;
+mod task_shard;
 mod tenant;
+mod tenant_lifecycle;
 #[allow(dead_code)]
 mod tenant_security;
 mod thread_do;
 mod vector_index;
 mod verification;
-mod gemini_service;
 
 pub use task_do::TaskLeaseManager;
 pub use thread_do::ThreadManager;
 
 pub use play_do::PlayManager;
+pub use rate_limit_do::TenantRateLimiter;
 
 #[derive(Serialize)]
 struct HealthResponse<'a> {
@@ -41,10 +58,11 @@ fn is_public_path(path: &str) -> bool {
 }
 
 /// Compose the tenant-namespaced Durable Object instance name for the
-/// `PlayManager` DO. Used by `POST /v1/plays/:name/launch` and by
+/// `PlayManager` DO. Used by `POST /v1/plays/:name/launch`,
+/// `GET /v1/plays/runs/:run_id` and by
 /// `TaskLeaseManager`'s completion callback. Two tenants requesting the
 /// same run_id produce different DO instances.
-fn play_do_name(tenant_id: &str, run_id: &str) -> String {
+pub(crate) fn play_do_name(tenant_id: &str, run_id: &str) -> String {
     format!("{tenant_id}:play:{run_id}")
 }
 
@@ -52,10 +70,93 @@ fn play_do_name(tenant_id: &str, run_id: &str) -> String {
 /// `ThreadManager` DO. Used by `POST /v1/checkpoints` and
 /// `GET /v1/checkpoints/threads/:thread_id`. A guessable thread_id from
 /// tenant A cannot route into tenant B's ThreadManager.
-fn thread_do_name(tenant_id: &str, thread_id: &str) -> String {
+pub(crate) fn thread_do_name(tenant_id: &str, thread_id: &str) -> String {
     format!("{tenant_id}:thread:{thread_id}")
 }
 
+/// Make `id` the latest checkpoint of its thread's ThreadManager (the
+/// fast path of `GET /v1/checkpoints/threads/:thread_id`). The canonical
+/// copy is already in D1 and R2, so a state too large for the DO is fine.
+async fn push_thread_checkpoint(
+    env: &Env,
+    tenant_id: &str,
+    id: &str,
+    body: &models::CreateCheckpoint,
+) -> Result<()> {
+    // SECURITY: name the DO by `{tenant_id}:{thread_id}` so a guessable
+    // thread_id from tenant A cannot route into tenant B's ThreadManager
+    // instance. Pre-WS8 the DO was named by thread_id alone.
+    let do_name = thread_do_name(tenant_id, &body.thread_id);
+    let namespace = env.durable_object("THREAD_MANAGER")?;
+    let stub = namespace.id_from_name(&do_name)?.get_stub()?;
+
+    let headers = Headers::new();
+    headers.set("x-checkpoint-id", id)?;
+    let do_req = Request::new_with_init(
+        "https://do/checkpoint",
+        &RequestInit {
+            method: Method::Post,
+            body: Some(JsValue::from_str(
+                &serde_json::to_string(body).map_err(|e| Error::RustError(e.to_string()))?,
+            )),
+            headers,
+            ..Default::default()
+        },
+    )?;
+    stub.fetch_with_request(do_req).await?;
+    Ok(())
+}
+
+/// Read and parse a checkpoint's state from R2. `None` if the object is
+/// missing (e.g. removed by retention after the row was read).
+async fn load_checkpoint_state(
+    env: &Env,
+    d1: &D1Database,
+    tenant_id: &str,
+    state_r2_key: &str,
+) -> Result<Option<serde_json::Value>> {
+    let bucket = env.bucket("ARTIFACTS")?;
+    let keyring = envelope::TenantKeyring::from_env(env, d1, tenant_id)?;
+    match storage::get_opened(&bucket, state_r2_key, keyring.as_ref()).await? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// The `:tenant_id` of a `/v1/tenants/:tenant_id/...` admin route, or the
+/// 403 to return when it is not the caller's verified tenant.
+fn own_tenant_param(
+    ctx: &RouteContext<Option<tenant::TenantContext>>,
+) -> Result<std::result::Result<String, Response>> {
+    let tenant_ctx = tenant::verified(&ctx.data)?;
+    let tenant_id = ctx
+        .param("tenant_id")
+        .expect("param tenant_id is required by route");
+    match tenant_lifecycle::authorize_path_tenant(&tenant_ctx.tenant_id, tenant_id) {
+        Ok(()) => Ok(Ok(tenant_id.to_string())),
+        Err((code, message)) => errors::error_response(code, message, 403).map(Err),
+    }
+}
+
+/// The 403 to return when `agent_id` is not bound to the caller's token
+/// (see `TenantContext::bind_agent`), checked before the id reaches the
+/// capability matcher as `agent:<id>`.
+async fn agent_binding_error(
+    d1: &D1Database,
+    tenant_ctx: &tenant::TenantContext,
+    agent_id: &str,
+) -> Result<Option<Response>> {
+    let owner = if tenant_ctx.subject.as_deref() == Some(agent_id) {
+        None
+    } else {
+        db::get_agent_owner(d1, &tenant_ctx.tenant_id, agent_id).await?
+    };
+    match tenant_ctx.bind_agent(agent_id, owner.as_deref()) {
+        Ok(()) => Ok(None),
+        Err((code, message)) => errors::error_response(code, message, 403).map(Some),
+    }
+}
+
 fn request_path(req: &Request) -> Result<String> {
     Ok(req.url()?.path().to_string())
 }
@@ -206,35 +307,81 @@ async fn augment_task_with_memory(
 }
 
 #[event(fetch)]
-pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
+pub async fn fetch(req: Request, env: Env, worker_ctx: Context) -> Result<Response> {
     console_error_panic_hook::set_once();
     let start_ms = js_sys::Date::now();
 
     let path = request_path(&req)?;
     let method = req.method();
     let mut tenant_id_for_metric: Option<String> = None;
+    let mut verified_tenant: Option<tenant::TenantContext> = None;
+    let mut limiter_streak = 0u32;
+    // `(tenant_id, actor)` for requests that get an audit row (WS8).
+    let mut audit_who: Option<(String, String)> = None;
     if !is_public_path(&path) {
-        let tenant_ctx = match tenant::tenant_from_request(&req) {
+        let mut tenant_ctx = match tenant::authenticate(&req, &env) {
             Ok(ctx) => ctx,
-            Err(_) => return Response::error("missing or invalid tenant context", 401),
+            Err(err) => {
+                return errors::error_response("UNAUTHENTICATED", &err.to_string(), 401);
+            }
         };
-        if tenant::authorize(&tenant_ctx, req.method(), &path).is_err() {
-            return Response::error("forbidden by tenant role policy", 403);
+        if audit::is_audited(&method) {
+            audit_who = Some((tenant_ctx.tenant_id.clone(), tenant_ctx.actor()));
+        }
+        if let Err(err) = tenant::authorize(&tenant_ctx, req.method(), &path) {
+            let response = errors::error_response("FORBIDDEN", &err.to_string(), 403);
+            append_audit(env.d1("DB").ok(), audit_who, &method, &path, &response).await;
+            return response;
+        }
+        if let Err((code, message, status)) = tenant_admission(&env, &mut tenant_ctx, &path).await {
+            let response = errors::error_response(code, message, status);
+            append_audit(env.d1("DB").ok(), audit_who, &method, &path, &response).await;
+            return response;
         }
         tenant_id_for_metric = Some(tenant_ctx.tenant_id.clone());
+        verified_tenant = Some(tenant_ctx);
+    }
+
+    // Per-tenant edge rate limit (WS8). Runs before routing, so rejected
+    // requests never reach a handler or the task/play DOs, but after the
+    // admission lookup, so a deleted tenant cannot recreate its purged
+    // limiter. A rejection therefore still costs that D1 read plus its
+    // audit row. The limiter fails open: a missing binding or DO error is
+    // logged and the request proceeds.
+    let mut limiter: Option<(Stub, String)> = None;
+    if let Some(tenant_id) = tenant_id_for_metric.as_deref() {
+        if let Some(stub) = rate_limiter_stub(&env, tenant_id) {
+            match rate_limit_check(&stub, tenant_id).await {
+                Ok(outcome) if !outcome.allowed => {
+                    let response = rate_limited_response(&outcome);
+                    append_audit(env.d1("DB").ok(), audit_who, &method, &path, &response).await;
+                    return response;
+                }
+                Ok(outcome) => {
+                    limiter = Some((stub, tenant_id.to_string()));
+                    limiter_streak = outcome.consecutive_failures;
+                }
+                Err(e) => {
+                    worker::console_log!("WARN: rate limiter check failed (fail-open): {e:?}");
+                }
+            }
+        }
     }
 
     // Grab the Analytics Engine sink (and APP_ENV for cross-env filtering)
     // before `env` is consumed by the router. Missing binding in local dev /
     // tests is non-fatal — emission below is best-effort.
     let latency_sink = env.analytics_engine("PILOT_LATENCY").ok();
+    let audit_db = audit_who.as_ref().and_then(|_| env.d1("DB").ok());
     let app_env = env
         .var("APP_ENV")
         .ok()
         .map(|v| v.to_string())
         .unwrap_or_else(|| "unknown".to_string());
 
-    let router = Router::new();
+    // Handlers read the tenant from router data (`tenant::verified`), never
+    // from request headers, so only the context authenticated above is used.
+    let router = Router::with_data(verified_tenant);
 
     let response = router
         // ── Health ──────────────────────────────────────────────
@@ -277,13 +424,52 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── Tenants (WS8) ─────────────────────────────────────
         .post_async("/v1/tenants/provision", |mut req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let body: models::TenantProvisionRequest = req.json().await?;
             if body.tenant_id.trim().is_empty() || body.display_name.trim().is_empty() {
                 return Response::error("tenant_id and display_name are required", 400);
             }
+            if let Err((code, message)) =
+                tenant_lifecycle::authorize_provision(&tenant_ctx.tenant_id, &body.tenant_id)
+            {
+                return errors::error_response(code, message, 403);
+            }
+            if let Some(mode) = body.secret_scan_mode.as_deref() {
+                if secret_scan::ScanMode::parse(mode).is_none() {
+                    return errors::error_response(
+                        "INVALID_SECRET_SCAN_MODE",
+                        "secret_scan_mode must be one of reject, redact, flag",
+                        400,
+                    );
+                }
+            }
             let d1 = ctx.env.d1("DB")?;
             let started = js_sys::Date::now();
-            db::provision_tenant(&d1, &body).await?;
+            let stored = db::get_tenant_rate_limit(&d1, &body.tenant_id).await?;
+            let rate_limit = rate_limit_do::config_for_provision(&body, stored);
+            if !db::provision_tenant(&d1, &body, &rate_limit).await? {
+                return errors::error_response(
+                    "TENANT_DELETED",
+                    "tenant has been deleted and cannot be provisioned again",
+                    409,
+                );
+            }
+            if let Some(stub) = rate_limiter_stub(&ctx.env, &body.tenant_id) {
+                let update = rate_limit_do::RateLimitConfigUpdate {
+                    requests_per_minute: Some(rate_limit.requests_per_minute),
+                    burst_limit: Some(rate_limit.burst_limit),
+                    quota_bytes: None,
+                };
+                let do_req = Request::new_with_init(
+                    "https://do/config",
+                    &RequestInit {
+                        method: Method::Put,
+                        body: Some(JsValue::from_str(&serde_json::to_string(&update)?)),
+                        ..Default::default()
+                    },
+                )?;
+                stub.fetch_with_request(do_req).await?;
+            }
             let elapsed = (js_sys::Date::now() - started) as i64;
             Response::from_json(&models::TenantProvisionResponse {
                 tenant_id: body.tenant_id,
@@ -291,10 +477,293 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 provisioned_in_ms: elapsed,
             })
         })
+        // ── Tenant lifecycle (WS8) ─────────────────────────────
+        //
+        // Admin-only, and only on the caller's own tenant
+        // (`own_tenant_param`). Suspension is enforced at the edge by
+        // `tenant_admission`; hard-delete requires a prior suspend so no
+        // writer can race the purge.
+        .post_async(
+            "/v1/tenants/:tenant_id/suspend",
+            |mut req, ctx| async move {
+                let tenant_id = match own_tenant_param(&ctx)? {
+                    Ok(tenant_id) => tenant_id,
+                    Err(forbidden) => return Ok(forbidden),
+                };
+                let body: models::TenantSuspendRequest = {
+                    let text = req.text().await?;
+                    if text.trim().is_empty() {
+                        models::TenantSuspendRequest::default()
+                    } else {
+                        serde_json::from_str(&text)
+                            .map_err(|e| Error::RustError(format!("invalid JSON body: {e}")))?
+                    }
+                };
+                let d1 = ctx.env.d1("DB")?;
+                let now = db::now_iso();
+                let status = tenant_lifecycle::TenantStatus::Suspended;
+                if !db::set_tenant_status(
+                    &d1,
+                    &tenant_id,
+                    status.as_str(),
+                    body.reason.as_deref(),
+                    &now,
+                )
+                .await?
+                {
+                    return errors::error_response("TENANT_NOT_FOUND", "tenant not found", 404);
+                }
+                Response::from_json(&models::TenantLifecycleResponse {
+                    tenant_id,
+                    status: status.as_str().to_string(),
+                    reason: body.reason,
+                    updated_at: now,
+                })
+            },
+        )
+        .post_async("/v1/tenants/:tenant_id/resume", |_req, ctx| async move {
+            let tenant_id = match own_tenant_param(&ctx)? {
+                Ok(tenant_id) => tenant_id,
+                Err(forbidden) => return Ok(forbidden),
+            };
+            let d1 = ctx.env.d1("DB")?;
+            match db::get_tenant_status(&d1, &tenant_id).await? {
+                None => return errors::error_response("TENANT_NOT_FOUND", "tenant not found", 404),
+                Some(s)
+                    if tenant_lifecycle::TenantStatus::parse(&s)
+                        == tenant_lifecycle::TenantStatus::Deleted =>
+                {
+                    return errors::error_response(
+                        "TENANT_DELETED",
+                        "a deleted tenant must be re-provisioned, not resumed",
+                        409,
+                    );
+                }
+                Some(_) => {}
+            }
+            let now = db::now_iso();
+            let status = tenant_lifecycle::TenantStatus::Active;
+            db::set_tenant_status(&d1, &tenant_id, status.as_str(), None, &now).await?;
+            Response::from_json(&models::TenantLifecycleResponse {
+                tenant_id,
+                status: status.as_str().to_string(),
+                reason: None,
+                updated_at: now,
+            })
+        })
+        .post_async("/v1/tenants/:tenant_id/export", |_req, ctx| async move {
+            let tenant_id = match own_tenant_param(&ctx)? {
+                Ok(tenant_id) => tenant_id,
+                Err(forbidden) => return Ok(forbidden),
+            };
+            let d1 = ctx.env.d1("DB")?;
+            if db::get_tenant_status(&d1, &tenant_id).await?.is_none() {
+                return errors::error_response("TENANT_NOT_FOUND", "tenant not found", 404);
+            }
+            let bucket = ctx.env.bucket("ARTIFACTS")?;
+            let export_id = generate_id()?;
+            let manifest =
+                tenant_lifecycle::start_export(&d1, &bucket, &tenant_id, &export_id).await?;
+            Response::from_json(&manifest)
+        })
+        .post_async(
+            "/v1/tenants/:tenant_id/export/:export_id",
+            |_req, ctx| async move {
+                let tenant_id = match own_tenant_param(&ctx)? {
+                    Ok(tenant_id) => tenant_id,
+                    Err(forbidden) => return Ok(forbidden),
+                };
+                let export_id = ctx
+                    .param("export_id")
+                    .expect("param export_id is required by route")
+                    .to_string();
+                let bucket = ctx.env.bucket("ARTIFACTS")?;
+                match tenant_lifecycle::resume_export(&bucket, &tenant_id, &export_id).await? {
+                    Some(manifest) => Response::from_json(&manifest),
+                    None => errors::error_response("EXPORT_NOT_FOUND", "export not found", 404),
+                }
+            },
+        )
+        .delete_async("/v1/tenants/:tenant_id", |_req, ctx| async move {
+            let tenant_id = match own_tenant_param(&ctx)? {
+                Ok(tenant_id) => tenant_id,
+                Err(forbidden) => return Ok(forbidden),
+            };
+            let d1 = ctx.env.d1("DB")?;
+            let status = match db::get_tenant_status(&d1, &tenant_id).await? {
+                Some(s) => tenant_lifecycle::TenantStatus::parse(&s),
+                None => return errors::error_response("TENANT_NOT_FOUND", "tenant not found", 404),
+            };
+            if status != tenant_lifecycle::TenantStatus::Suspended {
+                return errors::error_response_with_details(
+                    "TENANT_NOT_SUSPENDED",
+                    "suspend the tenant before deleting it",
+                    serde_json::json!({ "current_status": status.as_str() }),
+                    409,
+                );
+            }
+            let bucket = ctx.env.bucket("ARTIFACTS")?;
+            let report_id = generate_id()?;
+            let report = tenant_lifecycle::hard_delete_tenant(
+                &ctx.env, &d1, &bucket, &tenant_id, &report_id,
+            )
+            .await?;
+            db::insert_tenant_deletion_report(&d1, &report).await?;
+            db::set_tenant_status(
+                &d1,
+                &tenant_id,
+                tenant_lifecycle::TenantStatus::Deleted.as_str(),
+                Some(&format!("deletion report {report_id}")),
+                &report.deleted_at,
+            )
+            .await?;
+            Response::from_json(&report)
+        })
+        .get_async(
+            "/v1/tenants/:tenant_id/deletion-report",
+            |_req, ctx| async move {
+                let tenant_id = match own_tenant_param(&ctx)? {
+                    Ok(tenant_id) => tenant_id,
+                    Err(forbidden) => return Ok(forbidden),
+                };
+                let d1 = ctx.env.d1("DB")?;
+                match db::latest_tenant_deletion_report(&d1, &tenant_id).await? {
+                    Some(report) => Response::from_json(&report),
+                    None => errors::error_response(
+                        "DELETION_REPORT_NOT_FOUND",
+                        "no deletion report for tenant",
+                        404,
+                    ),
+                }
+            },
+        )
+        .post_async(
+            "/v1/tenants/:tenant_id/keys/rotate",
+            |mut req, ctx| async move {
+                let tenant_id = match own_tenant_param(&ctx)? {
+                    Ok(tenant_id) => tenant_id,
+                    Err(forbidden) => return Ok(forbidden),
+                };
+                let body: models::RotateDataKeysRequest = {
+                    let text = req.text().await?;
+                    if text.trim().is_empty() {
+                        models::RotateDataKeysRequest::default()
+                    } else {
+                        serde_json::from_str(&text)
+                            .map_err(|e| Error::RustError(format!("invalid JSON body: {e}")))?
+                    }
+                };
+                let d1 = ctx.env.d1("DB")?;
+                if db::get_tenant_status(&d1, &tenant_id).await?.is_none() {
+                    return errors::error_response("TENANT_NOT_FOUND", "tenant not found", 404);
+                }
+                let Some(keyring) = envelope::TenantKeyring::from_env(&ctx.env, &d1, &tenant_id)?
+                else {
+                    return errors::error_response(
+                        "ENCRYPTION_NOT_CONFIGURED",
+                        "no master keys are configured",
+                        409,
+                    );
+                };
+                let outcome = keyring.rotate(body.new_data_key).await?;
+                Response::from_json(&models::RotateDataKeysResponse {
+                    tenant_id,
+                    master_key_id: outcome.master_key_id,
+                    rewrapped: outcome.rewrapped,
+                    active_data_key_id: outcome.active_data_key_id,
+                    data_key_rotated: outcome.data_key_rotated,
+                })
+            },
+        )
+        .put_async(
+            "/v1/tenants/:tenant_id/settings",
+            |mut req, ctx| async move {
+                let tenant_id = match own_tenant_param(&ctx)? {
+                    Ok(tenant_id) => tenant_id,
+                    Err(forbidden) => return Ok(forbidden),
+                };
+                let Ok(body) = req.json::<models::TenantSettings>().await else {
+                    return errors::error_response(
+                        "INVALID_JSON_BODY",
+                        "invalid JSON body for tenant settings",
+                        400,
+                    );
+                };
+                let Some(mode) = secret_scan::ScanMode::parse(&body.secret_scan_mode) else {
+                    return errors::error_response(
+                        "INVALID_SECRET_SCAN_MODE",
+                        "secret_scan_mode must be one of reject, redact, flag",
+                        400,
+                    );
+                };
+                let d1 = ctx.env.d1("DB")?;
+                let now = db::now_iso();
+                if !db::set_tenant_secret_scan_mode(&d1, &tenant_id, mode.as_str(), &now).await? {
+                    return errors::error_response("TENANT_NOT_FOUND", "tenant not found", 404);
+                }
+                Response::from_json(&models::TenantSettings {
+                    secret_scan_mode: mode.as_str().to_string(),
+                })
+            },
+        )
+        // ── Audit log (WS8) ──────────────────────────────────
+        .get_async("/v1/audit", |req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let url = req.url()?;
+            let params: std::collections::HashMap<String, String> = url
+                .query_pairs()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            let actor = params.get("actor").map(|s| s.as_str());
+            let limit = pagination::clamp_limit(params.get("limit").and_then(|s| s.parse().ok()));
+            let cursor =
+                match pagination::AuditCursor::decode(params.get("cursor").map(|s| s.as_str())) {
+                    Ok(c) => c,
+                    Err(_) => {
+                        return errors::error_response(
+                            "INVALID_CURSOR",
+                            "cursor is malformed; echo back the next_cursor from a prior response",
+                            400,
+                        );
+                    }
+                };
+            let d1 = ctx.env.d1("DB")?;
+            let (records, next_cursor) =
+                db::list_audit(&d1, &tenant_ctx.tenant_id, actor, limit, cursor.as_ref()).await?;
+            Response::from_json(&serde_json::json!({
+                "records": records,
+                "next_cursor": next_cursor.map(|c| c.encode()).transpose()?,
+            }))
+        })
+        .get_async("/v1/audit/verify", |req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let url = req.url()?;
+            let params: std::collections::HashMap<String, String> = url
+                .query_pairs()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            let after = match audit::parse_verify_anchor(
+                params.get("after_seq").map(|s| s.as_str()),
+                params.get("after_hash").map(|s| s.as_str()),
+            ) {
+                Ok(after) => after,
+                Err(msg) => return errors::error_response("INVALID_VERIFY_ANCHOR", msg, 400),
+            };
+            let max_rows = params
+                .get("limit")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(audit::MAX_VERIFY_ROWS)
+                .clamp(1, audit::MAX_VERIFY_ROWS);
+            let d1 = ctx.env.d1("DB")?;
+            let after = after.as_ref().map(|(seq, hash)| (*seq, hash.as_str()));
+            Response::from_json(
+                &audit::verify_tenant(&d1, &tenant_ctx.tenant_id, after, max_rows).await?,
+            )
+        })
         // ── Runs (WS2, D1-backed) ────────────────────────────
         .post_async("/v1/runs", |mut req, ctx| async move {
             let body: models::CreateRun = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
             db::create_run(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
@@ -304,7 +773,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             })
         })
         .get_async("/v1/runs", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req.url()?;
             let params: std::collections::HashMap<String, String> = url
                 .query_pairs()
@@ -341,8 +810,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 "next_cursor": next_cursor_str,
             }))
         })
-        .get_async("/v1/runs/:id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/runs/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -353,8 +822,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 None => errors::error_response("RUN_NOT_FOUND", "run not found", 404),
             }
         })
-        .get_async("/v1/pull-requests/:id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/pull-requests/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -374,8 +843,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // endpoints (not overloads of `/cancel` or `/fail`) so the audit
         // trail in events_bronze records the operator's intent
         // unambiguously.
-        .post_async("/v1/runs/:run_id/pause", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .post_async("/v1/runs/:run_id/pause", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             // Auth: builder or admin. Viewer is already caught by the
             // global middleware (it rejects all non-read methods), but
             // we pin the role check here too so the contract is local
@@ -416,8 +885,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 }
             }
         })
-        .post_async("/v1/runs/:run_id/resume", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .post_async("/v1/runs/:run_id/resume", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             if !matches!(
                 tenant_ctx.role,
                 tenant::TenantRole::Builder | tenant::TenantRole::Admin
@@ -449,9 +918,93 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 }
             }
         })
+        // Cancel a run everywhere it lives: the `runs` row (if any), its
+        // PlayManager (so nothing further launches), and every task in
+        // TaskLeaseManager whose job_id is the run id. PlayManager goes
+        // first so it cannot enqueue behind the TaskLeaseManager sweep.
+        .post_async("/v1/runs/:run_id/cancel", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            if !matches!(
+                tenant_ctx.role,
+                tenant::TenantRole::Builder | tenant::TenantRole::Admin
+            ) {
+                return errors::error_response(
+                    "FORBIDDEN",
+                    "cancel requires builder or admin role",
+                    403,
+                );
+            }
+            let run_id = ctx
+                .param("run_id")
+                .expect("param run_id is required by route")
+                .to_string();
+            let d1 = ctx.env.d1("DB")?;
+            let actor = tenant_ctx.actor();
+
+            let run_outcome = db::cancel_run(&d1, &tenant_ctx.tenant_id, &run_id, &actor).await?;
+            if let db::CancelOutcome::Terminal { current_status } = run_outcome {
+                return errors::error_response_with_details(
+                    "RUN_IN_TERMINAL_STATE",
+                    "cannot cancel a run that has already reached a terminal state",
+                    serde_json::json!({ "current_status": current_status }),
+                    409,
+                );
+            }
+
+            let play_ns = ctx.env.durable_object("PLAY_MANAGER")?;
+            let play_stub = play_ns
+                .id_from_name(&play_do_name(&tenant_ctx.tenant_id, &run_id))?
+                .get_stub()?;
+            let play_resp = play_stub
+                .fetch_with_request(Request::new("https://do/cancel", Method::Post)?)
+                .await?;
+            let play_found = play_resp.status_code() == 200;
+
+            let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+            let do_url = build_do_url("/cancel-job", &[("job_id", &run_id)])?;
+            let mut parts = Vec::new();
+            for mut do_resp in shards
+                .fetch_all(|| Request::new(&do_url, Method::Post))
+                .await?
+            {
+                if do_resp.status_code() != 200 {
+                    return match forward_do_response(do_resp).await? {
+                        Some(forwarded) => Ok(forwarded),
+                        None => Response::error("cancel failed", 500),
+                    };
+                }
+                parts.push(do_resp.json::<models::CancelledTasks>().await?);
+            }
+            let tasks = task_shard::merge_cancelled(parts);
+
+            let found_tasks = !tasks.cancelled.is_empty() || !tasks.cancel_requested.is_empty();
+            if run_outcome == db::CancelOutcome::NotFound {
+                if !play_found && !found_tasks {
+                    return errors::error_response("RUN_NOT_FOUND", "run not found", 404);
+                }
+                if play_found {
+                    db::insert_run_cancelled_event(
+                        &d1,
+                        &tenant_ctx.tenant_id,
+                        &run_id,
+                        &actor,
+                        None,
+                    )
+                    .await?;
+                }
+            }
+            db::record_task_cancellations(&d1, &tenant_ctx.tenant_id, &actor, &tasks).await?;
+
+            Response::from_json(&models::RunCancelled {
+                run_id,
+                status: "cancelled".to_string(),
+                cancelled_tasks: tasks.cancelled.into_iter().map(|t| t.id).collect(),
+                cancel_requested_tasks: tasks.cancel_requested.into_iter().map(|t| t.id).collect(),
+            })
+        })
         // ── WS10 pilot baseline metrics (issue #105) ────────
         .get_async("/v1/metrics/pilot", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req.url()?;
             let params: std::collections::HashMap<String, String> = url
                 .query_pairs()
@@ -466,7 +1019,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             };
             let task_type = params.get("task_type").map(|s| s.as_str());
             let d1 = ctx.env.d1("DB")?;
-            let body = metrics::pilot(
+            let mut body = metrics::pilot(
                 &d1,
                 &tenant_ctx.tenant_id,
                 &window,
@@ -474,6 +1027,12 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 task_type,
             )
             .await?;
+            // Limiter counters are best-effort: omitted if the DO is unreachable.
+            if let Some(stub) = rate_limiter_stub(&ctx.env, &tenant_ctx.tenant_id) {
+                if let Ok(mut resp) = stub.fetch_with_str("https://do/stats").await {
+                    body.rate_limit = resp.json().await.ok();
+                }
+            }
             Response::from_json(&body)
         })
         // ── WS2 Tasks (run-scoped, D1-backed) ───────────────
@@ -483,7 +1042,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 .expect("param run_id is required by route")
                 .to_string();
             let body: models::CreateTask = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
             db::create_ws2_task(&d1, &tenant_ctx.tenant_id, &id, &run_id, &body).await?;
@@ -492,8 +1051,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 status: "created".into(),
             })
         })
-        .get_async("/v1/runs/:run_id/tasks", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/runs/:run_id/tasks", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let run_id = ctx
                 .param("run_id")
                 .expect("param run_id is required by route")
@@ -505,7 +1064,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // ── Plans (WS2, D1-backed) ──────────────────────────
         .post_async("/v1/plans", |mut req, ctx| async move {
             let body: models::CreatePlan = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
             db::create_plan(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
@@ -516,11 +1075,24 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── Tool Calls (WS2, D1-backed) ─────────────────────
         .post_async("/v1/tool-calls", |mut req, ctx| async move {
-            let body: models::RecordToolCall = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let mut body: models::RecordToolCall = req.json().await?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let findings = match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut body) {
+                Ok(findings) => findings,
+                Err(rejected) => return secret_rejected_response(&rejected),
+            };
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
             db::record_tool_call(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
+            db::record_secret_scan(
+                &d1,
+                &tenant_ctx.tenant_id,
+                "tool_calls",
+                &id,
+                tenant_ctx.secret_scan_mode,
+                &findings,
+            )
+            .await?;
             Response::from_json(&models::Created {
                 id,
                 status: "recorded".into(),
@@ -529,7 +1101,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // ── Releases (WS2, D1-backed) ───────────────────────
         .post_async("/v1/releases", |mut req, ctx| async move {
             let body: models::CreateRelease = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
             db::create_release(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
@@ -540,11 +1112,24 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── Provenance Events (WS3, D1-backed) ──────────────
         .post_async("/v1/events", |mut req, ctx| async move {
-            let body: models::IngestEvent = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let mut body: models::IngestEvent = req.json().await?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let findings = match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut body) {
+                Ok(findings) => findings,
+                Err(rejected) => return secret_rejected_response(&rejected),
+            };
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
             db::ingest_event(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
+            db::record_secret_scan(
+                &d1,
+                &tenant_ctx.tenant_id,
+                "events",
+                &id,
+                tenant_ctx.secret_scan_mode,
+                &findings,
+            )
+            .await?;
             Response::from_json(&models::EventAck {
                 id,
                 event_type: body.event_type,
@@ -553,13 +1138,26 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── Retrieval & Memory Federation (WS5) ───────────────
         .post_async("/v1/memory/index", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
-            let body: models::UpsertMemoryItemRequest = req.json().await?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let mut body: models::UpsertMemoryItemRequest = req.json().await?;
+            let findings = match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut body) {
+                Ok(findings) => findings,
+                Err(rejected) => return secret_rejected_response(&rejected),
+            };
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
 
             // 1. Persistent storage in D1
             let expires_at = db::upsert_memory_item(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
+            db::record_secret_scan(
+                &d1,
+                &tenant_ctx.tenant_id,
+                "memory_index",
+                &id,
+                tenant_ctx.secret_scan_mode,
+                &findings,
+            )
+            .await?;
 
             // 2. Semantic indexing in Vectorize
             if let Ok(index) = vector_index::SemanticIndex::new(&ctx.env) {
@@ -583,7 +1181,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             })
         })
         .post_async("/v1/memory/retrieve", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let body: models::RetrieveMemoryRequest = req.json().await?;
             let d1 = ctx.env.d1("DB")?;
 
@@ -609,24 +1207,46 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
 
             // 2. Relational Search + Filter via D1
             // Hybrid approach: use semantic IDs if available, or just use D1 filters
-            let response =
+            let mut response =
                 db::retrieve_memory_hybrid(&d1, &tenant_ctx.tenant_id, &body, &semantic_ids)
                     .await?;
 
+            // 3. Federated candidates (WS8), opt-in per request.
+            if body.include_federated {
+                if let Err(reason) = federation::may_receive(&tenant_ctx, "memory") {
+                    return errors::error_response("FEDERATION_FORBIDDEN", &reason, 403);
+                }
+                let sources = db::list_federation_sources(
+                    &d1,
+                    &tenant_ctx.tenant_id,
+                    federation::SCOPE_MEMORY,
+                )
+                .await?;
+                for source in sources {
+                    let remaining = federation::MAX_FEDERATED_CANDIDATES - response.federated.len();
+                    if remaining == 0 {
+                        break;
+                    }
+                    response.federated.extend(
+                        db::retrieve_federated_memory(&d1, &source, &body.query, remaining).await?,
+                    );
+                }
+            }
+
             let _latency_ms = (js_sys::Date::now() - start) as i64;
             // Update latency in response if needed, though D1 usually tracks its own
 
             Response::from_json(&response)
         })
         .post_async("/v1/memory/context-pack", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let body: models::ContextPackRequest = req.json().await?;
             let d1 = ctx.env.d1("DB")?;
             let response = db::build_context_pack(&d1, &tenant_ctx.tenant_id, &body).await?;
             Response::from_json(&response)
         })
-        .post_async("/v1/memory/:id/retire", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .post_async("/v1/memory/:id/retire", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = match ctx.param("id") {
                 Some(k) => k.to_string(),
                 None => return Response::error("missing memory id", 400),
@@ -643,7 +1263,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             }
         })
         .post_async("/v1/memory/gc", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let body: models::MemoryGcRequest = {
                 let text = req.text().await?;
                 if text.trim().is_empty() {
@@ -660,37 +1280,213 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             Response::from_json(&response)
         })
         .post_async("/v1/memory/retrieval-feedback", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let body: models::RetrievalFeedback = req.json().await?;
             let d1 = ctx.env.d1("DB")?;
             db::record_retrieval_feedback(&d1, &tenant_ctx.tenant_id, &body).await?;
             Response::from_json(&models::RetrievalFeedbackAck { recorded: true })
         })
-        .get_async("/v1/memory/eval/summary", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/memory/eval/summary", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let d1 = ctx.env.d1("DB")?;
             let summary = db::memory_eval_summary(&d1, &tenant_ctx.tenant_id).await?;
             Response::from_json(&summary)
         })
         // ── Plays (Orchestration) ──────────────────────────────
-        .post_async("/v1/plays/:name/launch", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
-            let play_name = ctx
+        .post_async("/v1/plays", |mut req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let body: models::CreatePlayDefinition = match serde_json::from_str(&req.text().await?)
+            {
+                Ok(v) => v,
+                Err(e) => return errors::error_response("INVALID_JSON_BODY", &e.to_string(), 400),
+            };
+            if let Err(msg) = models::validate_play_name(&body.definition.name)
+                .and_then(|_| body.definition.validate())
+            {
+                return errors::error_response("INVALID_PLAY_DEFINITION", &msg, 422);
+            }
+            let version = body.version.unwrap_or(models::PlayVersion::INITIAL);
+            let d1 = ctx.env.d1("DB")?;
+            match db::create_play_definition(&d1, &tenant_ctx.tenant_id, &body.definition, version)
+                .await?
+            {
+                Some(stored) => Ok(Response::from_json(&stored)?.with_status(201)),
+                None => errors::error_response(
+                    "PLAY_EXISTS",
+                    &format!("play '{}' already exists", body.definition.name),
+                    409,
+                ),
+            }
+        })
+        .get_async("/v1/plays", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let d1 = ctx.env.d1("DB")?;
+            let plays = db::list_play_definitions(&d1, &tenant_ctx.tenant_id).await?;
+            Response::from_json(&serde_json::json!({ "plays": plays }))
+        })
+        .get_async("/v1/plays/:name", |req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let name = ctx
                 .param("name")
                 .expect("param name is required by route")
                 .to_string();
-            // Bad JSON in the body is a client contract violation: explicitly
-            // reject with 400 instead of silently falling back to a default
-            // request (which would mask the violation as a 200 OK). An empty
-            // body is still accepted and treated as a no-arg launch.
-            let body: models::PlayLaunchRequest = {
-                let text = req.text().await?;
-                if text.trim().is_empty() {
-                    models::PlayLaunchRequest {
-                        play_name: play_name.clone(),
-                        job_id: None,
-                        metadata: None,
-                    }
+            let version = match query_play_version(&req.url()?, "version") {
+                Ok(v) => v,
+                Err(msg) => return errors::error_response("INVALID_PLAY_VERSION", &msg, 400),
+            };
+            let d1 = ctx.env.d1("DB")?;
+            match db::get_stored_play_definition(
+                &d1,
+                &tenant_ctx.tenant_id,
+                &name,
+                version.as_ref(),
+            )
+            .await?
+            {
+                Some(stored) => Response::from_json(&stored),
+                None => play_not_found(&name, version.as_ref()),
+            }
+        })
+        .put_async("/v1/plays/:name", |mut req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let name = ctx
+                .param("name")
+                .expect("param name is required by route")
+                .to_string();
+            let body: models::UpdatePlayDefinition = match serde_json::from_str(&req.text().await?)
+            {
+                Ok(v) => v,
+                Err(e) => return errors::error_response("INVALID_JSON_BODY", &e.to_string(), 400),
+            };
+            let d1 = ctx.env.d1("DB")?;
+            let Some(current) =
+                db::get_stored_play_definition(&d1, &tenant_ctx.tenant_id, &name, None).await?
+            else {
+                return play_not_found(&name, None);
+            };
+            let version = match body.next_version(current.version) {
+                Ok(v) => v,
+                Err(msg) => return errors::error_response("PLAY_VERSION_CONFLICT", &msg, 409),
+            };
+            let def = body.into_definition(name);
+            if let Err(msg) = def.validate() {
+                return errors::error_response("INVALID_PLAY_DEFINITION", &msg, 422);
+            }
+            match db::update_play_definition(
+                &d1,
+                &tenant_ctx.tenant_id,
+                &def,
+                version,
+                &current.version,
+            )
+            .await?
+            {
+                Some(stored) => Response::from_json(&stored),
+                None => errors::error_response(
+                    "PLAY_VERSION_CONFLICT",
+                    &format!(
+                        "play '{}' changed since version {}; retry against the latest",
+                        def.name, current.version
+                    ),
+                    409,
+                ),
+            }
+        })
+        .delete_async("/v1/plays/:name", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let name = ctx
+                .param("name")
+                .expect("param name is required by route")
+                .to_string();
+            let d1 = ctx.env.d1("DB")?;
+            if db::delete_play_definition(&d1, &tenant_ctx.tenant_id, &name).await? {
+                Response::from_json(&serde_json::json!({ "deleted": true }))
+            } else {
+                play_not_found(&name, None)
+            }
+        })
+        .get_async("/v1/plays/:name/versions", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let name = ctx
+                .param("name")
+                .expect("param name is required by route")
+                .to_string();
+            let d1 = ctx.env.d1("DB")?;
+            let Some(latest) =
+                db::get_stored_play_definition(&d1, &tenant_ctx.tenant_id, &name, None).await?
+            else {
+                return play_not_found(&name, None);
+            };
+            let mut versions = db::list_play_versions(&d1, &tenant_ctx.tenant_id, &name).await?;
+            if !versions.iter().any(|v| v.version == latest.version) {
+                // Seeded by a migration after versioning landed.
+                versions.insert(
+                    0,
+                    models::PlayVersionInfo {
+                        version: latest.version,
+                        created_at: latest.created_at,
+                    },
+                );
+            }
+            Response::from_json(&serde_json::json!({ "name": name, "versions": versions }))
+        })
+        .get_async("/v1/plays/:name/diff", |req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let name = ctx
+                .param("name")
+                .expect("param name is required by route")
+                .to_string();
+            let url = req.url()?;
+            let (from, to) = match (
+                query_play_version(&url, "from"),
+                query_play_version(&url, "to"),
+            ) {
+                (Ok(Some(from)), Ok(to)) => (from, to),
+                (Ok(None), _) => {
+                    return errors::error_response(
+                        "INVALID_PLAY_VERSION",
+                        "query parameter 'from' is required",
+                        400,
+                    )
+                }
+                (Err(msg), _) | (_, Err(msg)) => {
+                    return errors::error_response("INVALID_PLAY_VERSION", &msg, 400)
+                }
+            };
+            let d1 = ctx.env.d1("DB")?;
+            let tenant_id = &tenant_ctx.tenant_id;
+            let Some(old) =
+                db::get_stored_play_definition(&d1, tenant_id, &name, Some(&from)).await?
+            else {
+                return play_not_found(&name, Some(&from));
+            };
+            let Some(new) =
+                db::get_stored_play_definition(&d1, tenant_id, &name, to.as_ref()).await?
+            else {
+                return play_not_found(&name, to.as_ref());
+            };
+            Response::from_json(&models::PlayDiff::between(&old, &new))
+        })
+        .post_async("/v1/plays/:name/launch", |mut req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let play_name = ctx
+                .param("name")
+                .expect("param name is required by route")
+                .to_string();
+            // Bad JSON in the body is a client contract violation: explicitly
+            // reject with 400 instead of silently falling back to a default
+            // request (which would mask the violation as a 200 OK). An empty
+            // body is still accepted and treated as a no-arg launch.
+            let body: models::PlayLaunchRequest = {
+                let text = req.text().await?;
+                if text.trim().is_empty() {
+                    models::PlayLaunchRequest {
+                        play_name: play_name.clone(),
+                        job_id: None,
+                        metadata: None,
+                        version: None,
+                        inputs: Default::default(),
+                    }
                 } else {
                     match parse_play_launch_body(&text) {
                         Ok(v) => v,
@@ -705,12 +1501,31 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 }
             };
 
-            // 1. Fetch play definition from D1 (tenant-scoped).
+            // 1. Fetch play definition from D1 (tenant-scoped), the latest
+            //    version unless the body names one.
             let d1 = ctx.env.d1("DB")?;
-            let def = match db::get_play_definition(&d1, &tenant_ctx.tenant_id, &play_name).await? {
+            let def = match body.version {
+                None => db::get_play_definition(&d1, &tenant_ctx.tenant_id, &play_name).await?,
+                Some(version) => db::get_stored_play_definition(
+                    &d1,
+                    &tenant_ctx.tenant_id,
+                    &play_name,
+                    Some(&version),
+                )
+                .await?
+                .map(|stored| stored.definition),
+            };
+            let def = match def {
                 Some(d) => d,
                 None => return Response::error(format!("play '{}' not found", play_name), 404),
             };
+            if let Err(msg) = def.validate() {
+                return errors::error_response("INVALID_PLAY_DEFINITION", &msg, 422);
+            }
+            let inputs = match def.resolve_inputs(&body.inputs) {
+                Ok(inputs) => inputs,
+                Err(msg) => return errors::error_response("INVALID_PLAY_INPUTS", &msg, 422),
+            };
 
             // 2. Launch via PlayManager Durable Object.
             //
@@ -727,6 +1542,9 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             let do_name = play_do_name(&tenant_ctx.tenant_id, &run_id);
             let namespace = ctx.env.durable_object("PLAY_MANAGER")?;
             let stub = namespace.id_from_name(&do_name)?.get_stub()?;
+            // Recorded before the DO holds any state, so a tenant
+            // hard-delete can always find the instance to purge.
+            db::record_play_run(&d1, &tenant_ctx.tenant_id, &run_id, &def.name).await?;
 
             // Plumb tenant_id into the DO via a launch envelope so the DO
             // does not have to (incorrectly) derive it from `self.state.id()`.
@@ -734,10 +1552,14 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             struct LaunchEnvelope<'a> {
                 tenant_id: &'a str,
                 definition: &'a models::PlayDefinition,
+                inputs: &'a std::collections::BTreeMap<String, serde_json::Value>,
+                metadata: Option<&'a serde_json::Value>,
             }
             let envelope = LaunchEnvelope {
                 tenant_id: &tenant_ctx.tenant_id,
                 definition: &def,
+                inputs: &inputs,
+                metadata: body.metadata.as_ref(),
             };
 
             let headers = Headers::new();
@@ -748,7 +1570,10 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 "https://do/launch",
                 &RequestInit {
                     method: Method::Post,
-                    body: Some(JsValue::from_str(&serde_json::to_string(&envelope).map_err(|e| Error::RustError(e.to_string()))?)),
+                    body: Some(JsValue::from_str(
+                        &serde_json::to_string(&envelope)
+                            .map_err(|e| Error::RustError(e.to_string()))?,
+                    )),
                     headers,
                     ..Default::default()
                 },
@@ -758,9 +1583,150 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             let result: serde_json::Value = do_resp.json().await?;
             Response::from_json(&result)
         })
+        .get_async("/v1/plays/runs/:run_id", |req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let run_id = ctx
+                .param("run_id")
+                .expect("param run_id is required by route")
+                .to_string();
+            let format = req
+                .url()?
+                .query_pairs()
+                .find(|(k, _)| k == "format")
+                .map(|(_, v)| v.into_owned())
+                .unwrap_or_else(|| "json".to_string());
+            if !matches!(format.as_str(), "json" | "dot" | "mermaid") {
+                return errors::error_response(
+                    "INVALID_FORMAT",
+                    "format must be one of json, dot, mermaid",
+                    400,
+                );
+            }
+
+            let namespace = ctx.env.durable_object("PLAY_MANAGER")?;
+            let stub = namespace
+                .id_from_name(&play_do_name(&tenant_ctx.tenant_id, &run_id))?
+                .get_stub()?;
+            let mut do_resp = stub
+                .fetch_with_request(Request::new("https://do/status", Method::Get)?)
+                .await?;
+            if do_resp.status_code() == 404 {
+                return errors::error_response("PLAY_RUN_NOT_FOUND", "play run not found", 404);
+            }
+            let status: models::PlayRunStatus = do_resp.json().await?;
+            let (body, content_type) = match format.as_str() {
+                "dot" => (
+                    play_graph::to_dot(&status),
+                    "text/vnd.graphviz; charset=utf-8",
+                ),
+                "mermaid" => (play_graph::to_mermaid(&status), "text/plain; charset=utf-8"),
+                _ => return Response::from_json(&status),
+            };
+            let mut resp = Response::ok(body)?;
+            resp.headers_mut().set("Content-Type", content_type)?;
+            Ok(resp)
+        })
+        .get_async(
+            "/v1/plays/runs/:run_id/tasks/:task_id/result",
+            |_req, ctx| async move {
+                let tenant_ctx = tenant::verified(&ctx.data)?;
+                let run_id = ctx
+                    .param("run_id")
+                    .expect("param run_id is required by route")
+                    .to_string();
+                let task_id = ctx
+                    .param("task_id")
+                    .expect("param task_id is required by route")
+                    .to_string();
+                let namespace = ctx.env.durable_object("PLAY_MANAGER")?;
+                let stub = namespace
+                    .id_from_name(&play_do_name(&tenant_ctx.tenant_id, &run_id))?
+                    .get_stub()?;
+                let do_url = build_do_url("/result", &[("task_id", &task_id)])?;
+                let mut do_resp = stub
+                    .fetch_with_request(Request::new(&do_url, Method::Get)?)
+                    .await?;
+                if do_resp.status_code() == 404 {
+                    return errors::error_response(
+                        "PLAY_TASK_RESULT_NOT_FOUND",
+                        "play run or task result not found",
+                        404,
+                    );
+                }
+                let result: serde_json::Value = do_resp.json().await?;
+                Response::from_json(&result)
+            },
+        )
+        .post_async(
+            "/v1/plays/runs/:run_id/approvals/:task_id",
+            |mut req, ctx| async move {
+                let tenant_ctx = tenant::verified(&ctx.data)?;
+                let run_id = ctx
+                    .param("run_id")
+                    .expect("param run_id is required by route")
+                    .to_string();
+                let task_id = ctx
+                    .param("task_id")
+                    .expect("param task_id is required by route")
+                    .to_string();
+                let Ok(request) = req.json::<models::PlayApprovalRequest>().await else {
+                    return errors::error_response(
+                        "INVALID_JSON_BODY",
+                        "invalid JSON body for play approval",
+                        400,
+                    );
+                };
+                if let Err(msg) = request.validate() {
+                    return errors::error_response("INVALID_APPROVAL", &msg, 422);
+                }
+
+                #[derive(serde::Serialize)]
+                struct ApprovalEnvelope<'a> {
+                    task_id: &'a str,
+                    decision_id: &'a str,
+                    actor: &'a str,
+                    request: &'a models::PlayApprovalRequest,
+                }
+                // The decider is `human:<sub>` from the verified token,
+                // checked against the task's `approvers` in the DO.
+                let actor = match tenant_ctx.approver() {
+                    Ok(actor) => actor,
+                    Err((code, message)) => return errors::error_response(code, message, 403),
+                };
+                let decision_id = generate_id()?;
+                let envelope = ApprovalEnvelope {
+                    task_id: &task_id,
+                    decision_id: &decision_id,
+                    actor: &actor,
+                    request: &request,
+                };
+                let namespace = ctx.env.durable_object("PLAY_MANAGER")?;
+                let stub = namespace
+                    .id_from_name(&play_do_name(&tenant_ctx.tenant_id, &run_id))?
+                    .get_stub()?;
+                let do_req = Request::new_with_init(
+                    "https://do/approval",
+                    &RequestInit {
+                        method: Method::Post,
+                        body: Some(JsValue::from_str(
+                            &serde_json::to_string(&envelope)
+                                .map_err(|e| Error::RustError(e.to_string()))?,
+                        )),
+                        ..Default::default()
+                    },
+                )?;
+                // The DO answers with the recorded decision, or an error
+                // envelope (run or task not found, not awaiting approval,
+                // approver not allowed) passed through as is.
+                let mut do_resp = stub.fetch_with_request(do_req).await?;
+                let status = do_resp.status_code();
+                let body: serde_json::Value = do_resp.json().await?;
+                Ok(Response::from_json(&body)?.with_status(status))
+            },
+        )
         // ── Artifacts (R2-backed) ─────────────────────────────
         .put_async("/v1/artifacts/:key", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let key = match ctx.param("key") {
                 Some(k) => k.to_string(),
                 None => return Response::error("missing artifact key", 400),
@@ -770,8 +1736,10 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 return Response::error("artifact exceeds max size", 413);
             }
             let bucket = ctx.env.bucket("ARTIFACTS")?;
+            let d1 = ctx.env.d1("DB")?;
+            let keyring = envelope::TenantKeyring::from_env(&ctx.env, &d1, &tenant_ctx.tenant_id)?;
             let scoped_key = format!("{}{}", tenant_ctx.r2_prefix(), key);
-            let size = storage::put_blob(&bucket, &scoped_key, data).await?;
+            let size = storage::put_sealed(&bucket, &scoped_key, data, keyring.as_ref()).await?;
             Response::from_json(&serde_json::json!({
                 "key": key,
                 "scoped_key": scoped_key,
@@ -779,15 +1747,17 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 "stored": true,
             }))
         })
-        .get_async("/v1/artifacts/:key", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/artifacts/:key", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let key = match ctx.param("key") {
                 Some(k) => k.to_string(),
                 None => return Response::error("missing artifact key", 400),
             };
             let bucket = ctx.env.bucket("ARTIFACTS")?;
+            let d1 = ctx.env.d1("DB")?;
+            let keyring = envelope::TenantKeyring::from_env(&ctx.env, &d1, &tenant_ctx.tenant_id)?;
             let scoped_key = format!("{}{}", tenant_ctx.r2_prefix(), key);
-            match storage::get_blob(&bucket, &scoped_key).await? {
+            match storage::get_opened(&bucket, &scoped_key, keyring.as_ref()).await? {
                 Some(data) => {
                     let headers = Headers::new();
                     headers.set("content-type", "application/octet-stream")?;
@@ -799,7 +1769,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // ── Policy & Governance (WS4) ──────────────────────────
         .post_async("/v1/policies/check", |mut req, ctx| async move {
             let body: models::PolicyCheckRequest = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let d1 = ctx.env.d1("DB")?;
             let evaluated =
                 policy::evaluate_policy(&ctx.env, &d1, &tenant_ctx.tenant_id, &body).await?;
@@ -818,7 +1788,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // ── Policy Rules CRUD (WS4) ─────────────────────────────
         .post_async("/v1/policies/rules", |mut req, ctx| async move {
             let body: models::CreatePolicyRule = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             // Validate verdict
             match body.verdict.as_str() {
                 "allow" | "deny" | "escalate" => {}
@@ -843,7 +1813,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             })
         })
         .get_async("/v1/policies/rules", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req.url()?;
             let params: std::collections::HashMap<String, String> = url
                 .query_pairs()
@@ -881,8 +1851,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 "next_cursor": next_cursor_str,
             }))
         })
-        .get_async("/v1/policies/rules/:id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/policies/rules/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -899,7 +1869,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 .expect("param id is required by route")
                 .to_string();
             let body: models::UpdatePolicyRule = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             // Validate verdict if provided
             if let Some(ref v) = body.verdict {
                 match v.as_str() {
@@ -929,8 +1899,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 Response::error("rule not found", 404)
             }
         })
-        .delete_async("/v1/policies/rules/:id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .delete_async("/v1/policies/rules/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -945,7 +1915,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── Policy Decision History (WS4) ────────────────────────
         .get_async("/v1/policies/decisions", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req.url()?;
             let params: std::collections::HashMap<String, String> = url
                 .query_pairs()
@@ -974,7 +1944,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── WS7 Verification Evidence ─────────────────────────
         .get_async("/v1/verification/evidence", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req.url()?;
             let params: std::collections::HashMap<String, String> = url
                 .query_pairs()
@@ -1040,53 +2010,52 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // ── Agent Tasks (M1) ──────────────────────────────────
         .post_async("/v1/tasks", |mut req, ctx| async move {
             let body: models::CreateAgentTask = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
-            let d1 = ctx.env.d1("DB")?;
-            let id = generate_id()?;
-
-            // Create in D1 for persistence
-            db::create_task(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
-
-            // Fetch the task as an AgentTask for the DO
-            let task = match db::get_mcp_task_by_id(&d1, &tenant_ctx.tenant_id, &id).await? {
-                Some(t) => t,
-                None => return Response::error("failed to fetch created task", 500),
-            };
-
-            // Enqueue in Durable Object for active management
-            let namespace = ctx.env.durable_object("TASK_LEASE_MANAGER")?;
-            let stub = namespace.id_from_name(&tenant_ctx.tenant_id)?.get_stub()?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            if let Err(e) = capability::validate_requirements(&body.requires) {
+                return errors::error_response("INVALID_REQUIREMENT", &e, 400);
+            }
 
-            let do_req = Request::new_with_init(
-                "https://do/enqueue",
-                &RequestInit {
-                    method: Method::Post,
-                    body: Some(JsValue::from_str(&serde_json::to_string(&task).map_err(|e| Error::RustError(e.to_string()))?)),
-                    ..Default::default()
-                },
-            )?;
-            let do_resp = stub.fetch_with_request(do_req).await?;
+            let mut status = "pending";
+            if let Some(not_before) = &body.not_before {
+                let due_ms = js_sys::Date::parse(not_before);
+                if !due_ms.is_finite() {
+                    return errors::error_response(
+                        "INVALID_NOT_BEFORE",
+                        "not_before must be an RFC 3339 timestamp",
+                        400,
+                    );
+                }
+                if due_ms > js_sys::Date::now() {
+                    status = "scheduled";
+                }
+            }
 
-            // Propagate any non-2xx response (notably 429 QUEUE_FULL backpressure
-            // from TaskLeaseManager when pending_tasks is at MAX_PENDING_TASKS)
-            // to the client. Without this passthrough, the DO's Retry-After +
-            // QUEUE_FULL envelope is silently swallowed and the client sees a
-            // generic success / 500, defeating the backpressure feature.
-            if let Some(forwarded) = forward_do_response(do_resp).await? {
-                return Ok(forwarded);
+            if let Some(key) = &body.idempotency_key {
+                if key.trim().is_empty() || key.len() > MAX_TASK_IDEMPOTENCY_KEY_LEN {
+                    return errors::error_response(
+                        "INVALID_IDEMPOTENCY_KEY",
+                        "idempotency_key must be 1-255 characters",
+                        400,
+                    );
+                }
             }
 
-            Response::from_json(&models::TaskCreated {
-                id,
-                status: "pending".into(),
-            })
+            match enqueue_agent_task(&ctx.env, &tenant_ctx.tenant_id, &body).await? {
+                Ok(mut created) => {
+                    if !created.duplicate {
+                        created.status = status.into();
+                    }
+                    Response::from_json(&created)
+                }
+                Err(forwarded) => Ok(forwarded),
+            }
         })
         // POST /mcp/task/next — claims the next pending task for an agent.
         // This is a state-mutating, non-idempotent operation, so POST is the correct
         // HTTP method. Caching proxies must not cache a "claimed" state, and retry
         // middleware that auto-retries idempotent verbs must not double-claim.
         .post_async("/mcp/task/next", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req.url()?;
             let params: std::collections::HashMap<String, String> = url
                 .query_pairs()
@@ -1097,18 +2066,82 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 Some(id) => id.clone(),
                 None => return Response::error("agent_id required", 400),
             };
-            let caps = params.get("cap").cloned().unwrap_or_default();
-
-            let namespace = ctx.env.durable_object("TASK_LEASE_MANAGER")?;
-            let stub = namespace.id_from_name(&tenant_ctx.tenant_id)?.get_stub()?;
-
-            let do_url = build_do_url("/claim", &[("agent_id", &agent_id), ("caps", &caps)])?;
-            let do_req = Request::new(&do_url, Method::Post)?;
-            let mut do_resp = stub.fetch_with_request(do_req).await?;
+            let d1 = ctx.env.d1("DB")?;
+            if let Some(forbidden) = agent_binding_error(&d1, &tenant_ctx, &agent_id).await? {
+                return Ok(forbidden);
+            }
+            let caps = claim_capabilities(
+                &d1,
+                &tenant_ctx.tenant_id,
+                &agent_id,
+                params.get("cap").map(String::as_str),
+            )
+            .await?;
+            // Long-poll: `?wait=<seconds>` holds the claim open until a
+            // matching task is enqueued (the DO clamps it to 30s).
+            let wait_ms = params
+                .get("wait")
+                .and_then(|w| w.parse::<u64>().ok())
+                .unwrap_or(0)
+                .saturating_mul(1000)
+                .to_string();
 
-            if do_resp.status_code() == 204 {
-                return Ok(Response::empty()?.with_status(204));
+            // With one shard this is a single claim that waits. Otherwise
+            // try the agent's shards in a rotated order without waiting,
+            // then park on shard 0, which every shard wakes when work
+            // arrives, and sweep again on each wake until the wait runs
+            // out.
+            let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+            let order = shards
+                .config
+                .claim_order(&task_do::parse_caps(&caps), random_u32()?);
+            let claim_url = |wait_ms: &str| {
+                build_do_url(
+                    "/claim",
+                    &[
+                        ("agent_id", &agent_id),
+                        ("caps", &caps),
+                        ("wait_ms", wait_ms),
+                    ],
+                )
+            };
+            let mut claimed = None;
+            if order.len() == 1 {
+                let do_req = Request::new(&claim_url(&wait_ms)?, Method::Post)?;
+                let do_resp = shards.stub(order[0])?.fetch_with_request(do_req).await?;
+                if do_resp.status_code() != 204 {
+                    claimed = Some(do_resp);
+                }
+            } else {
+                let deadline =
+                    js_sys::Date::now() as u64 + task_do::clamp_claim_wait_ms(Some(&wait_ms));
+                let waiter = shards.stub(0)?;
+                // Snapshot before each sweep so a wake that lands while
+                // sweeping ends the park at once.
+                let mut seen = claim_wait_generation(&waiter, None, 0).await?;
+                loop {
+                    for shard in &order {
+                        let do_req = Request::new(&claim_url("0")?, Method::Post)?;
+                        let do_resp = shards.stub(*shard)?.fetch_with_request(do_req).await?;
+                        if do_resp.status_code() != 204 {
+                            claimed = Some(do_resp);
+                            break;
+                        }
+                    }
+                    let now = js_sys::Date::now() as u64;
+                    if claimed.is_some() || now >= deadline {
+                        break;
+                    }
+                    let woken = claim_wait_generation(&waiter, Some(seen), deadline - now).await?;
+                    if woken == seen {
+                        break;
+                    }
+                    seen = woken;
+                }
             }
+            let Some(mut do_resp) = claimed else {
+                return Ok(Response::empty()?.with_status(204));
+            };
 
             let mut task: models::AgentTask = do_resp.json().await?;
 
@@ -1157,8 +2190,62 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             )?
             .with_headers(headers))
         })
+        // GET /mcp/task/ws — WebSocket upgrade. The agent advertises
+        // `agent_id` and `cap` once; TaskLeaseManager pushes `task` messages
+        // as work arrives and accepts heartbeat/complete/fail over the
+        // socket (see `models::TaskSocketClientMessage`). The socket is
+        // accepted with the hibernation API, so an idle agent costs nothing.
+        .get_async("/mcp/task/ws", |req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let upgrade = req.headers().get("upgrade")?.unwrap_or_default();
+            if !upgrade.eq_ignore_ascii_case("websocket") {
+                return Response::error("expected Upgrade: websocket", 426);
+            }
+            let url = req.url()?;
+            let params: std::collections::HashMap<String, String> = url
+                .query_pairs()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            let agent_id = match params.get("agent_id") {
+                Some(id) => id.clone(),
+                None => return Response::error("agent_id required", 400),
+            };
+            let d1 = ctx.env.d1("DB")?;
+            if let Some(forbidden) = agent_binding_error(&d1, &tenant_ctx, &agent_id).await? {
+                return Ok(forbidden);
+            }
+            let caps = claim_capabilities(
+                &d1,
+                &tenant_ctx.tenant_id,
+                &agent_id,
+                params.get("cap").map(String::as_str),
+            )
+            .await?;
+
+            // A socket lives on one shard: the agent's own pick among the
+            // shards that hold its task types, stable per agent id.
+            let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+            let start = task_shard::fnv1a(&agent_id) as u32;
+            let shard = shards
+                .config
+                .claim_order(&task_do::parse_caps(&caps), start)[0];
+            let stub = shards.stub(shard)?;
+
+            let do_url = build_do_url("/connect", &[("agent_id", &agent_id), ("caps", &caps)])?;
+            let headers = Headers::new();
+            headers.set("upgrade", "websocket")?;
+            let do_req = Request::new_with_init(
+                &do_url,
+                &RequestInit {
+                    method: Method::Get,
+                    headers,
+                    ..Default::default()
+                },
+            )?;
+            stub.fetch_with_request(do_req).await
+        })
         .post_async("/mcp/task/:id/heartbeat", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let task_id = match ctx.param("id") {
                 Some(id) => id.to_string(),
                 None => return Response::error("missing task id", 400),
@@ -1173,50 +2260,68 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 None => return Response::error("agent_id required", 400),
             };
 
-            let namespace = ctx.env.durable_object("TASK_LEASE_MANAGER")?;
-            let stub = namespace.id_from_name(&tenant_ctx.tenant_id)?.get_stub()?;
-
+            let d1 = ctx.env.d1("DB")?;
+            if let Some(forbidden) = agent_binding_error(&d1, &tenant_ctx, &agent_id).await? {
+                return Ok(forbidden);
+            }
+            let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
             let do_url = build_do_url(
                 "/heartbeat",
                 &[("task_id", &task_id), ("agent_id", &agent_id)],
             )?;
-            let do_req = Request::new(&do_url, Method::Post)?;
-            let do_resp = stub.fetch_with_request(do_req).await?;
+            let mut do_resp = shards
+                .fetch_for_task(&d1, &task_id, || Request::new(&do_url, Method::Post))
+                .await?;
 
             if do_resp.status_code() == 200 {
+                let task: models::AgentTask = do_resp.json().await?;
+                // A pending cancel released the lease instead of extending
+                // it; tell the agent to stop.
+                if task.status == "cancelled" {
+                    db::sync_task_status(&d1, &tenant_ctx.tenant_id, &task).await?;
+                    return Response::from_json(&models::TaskHeartbeatAck {
+                        ok: true,
+                        cancel: true,
+                    });
+                }
                 // Update D1 (best effort)
-                let d1 = ctx.env.d1("DB")?;
                 let _ = db::heartbeat_task(&d1, &tenant_ctx.tenant_id, &task_id, &agent_id).await;
-                Response::from_json(&serde_json::json!({ "ok": true }))
+                Response::from_json(&models::TaskHeartbeatAck {
+                    ok: true,
+                    cancel: false,
+                })
             } else {
                 Response::error("task not found or not owned by agent", 404)
             }
         })
         .post_async("/mcp/task/:id/complete", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let task_id = match ctx.param("id") {
                 Some(id) => id.to_string(),
                 None => return Response::error("missing task id", 400),
             };
             let body: models::TaskCompleteRequest = req.json().await?;
 
-            let namespace = ctx.env.durable_object("TASK_LEASE_MANAGER")?;
-            let stub = namespace.id_from_name(&tenant_ctx.tenant_id)?.get_stub()?;
-
-            let do_req = Request::new_with_init(
-                &format!("https://do/complete/{}", task_id),
-                &RequestInit {
-                    method: Method::Post,
-                    body: Some(JsValue::from_str(&serde_json::to_string(&body).map_err(|e| Error::RustError(e.to_string()))?)),
-                    ..Default::default()
-                },
-            )?;
-            let mut do_resp = stub.fetch_with_request(do_req).await?;
+            let d1 = ctx.env.d1("DB")?;
+            let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+            let body = serde_json::to_string(&body).map_err(|e| Error::RustError(e.to_string()))?;
+            let do_url = format!("https://do/complete/{}", task_id);
+            let mut do_resp = shards
+                .fetch_for_task(&d1, &task_id, || {
+                    Request::new_with_init(
+                        &do_url,
+                        &RequestInit {
+                            method: Method::Post,
+                            body: Some(JsValue::from_str(&body)),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .await?;
 
             if do_resp.status_code() == 200 {
                 let task: models::AgentTask = do_resp.json().await?;
                 // Sync to D1
-                let d1 = ctx.env.d1("DB")?;
                 db::sync_task_status(&d1, &tenant_ctx.tenant_id, &task).await?;
                 Response::from_json(&serde_json::json!({ "status": "completed" }))
             } else {
@@ -1224,43 +2329,272 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             }
         })
         .post_async("/mcp/task/:id/fail", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let task_id = match ctx.param("id") {
                 Some(id) => id.to_string(),
                 None => return Response::error("missing task id", 400),
             };
             let body: models::TaskFailRequest = req.json().await?;
 
-            let namespace = ctx.env.durable_object("TASK_LEASE_MANAGER")?;
-            let stub = namespace.id_from_name(&tenant_ctx.tenant_id)?.get_stub()?;
-
-            let do_req = Request::new_with_init(
-                &format!("https://do/fail/{}", task_id),
-                &RequestInit {
-                    method: Method::Post,
-                    body: Some(JsValue::from_str(&serde_json::to_string(&body).map_err(|e| Error::RustError(e.to_string()))?)),
-                    ..Default::default()
-                },
-            )?;
-            let mut do_resp = stub.fetch_with_request(do_req).await?;
+            let d1 = ctx.env.d1("DB")?;
+            let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+            let body = serde_json::to_string(&body).map_err(|e| Error::RustError(e.to_string()))?;
+            let do_url = format!("https://do/fail/{}", task_id);
+            let mut do_resp = shards
+                .fetch_for_task(&d1, &task_id, || {
+                    Request::new_with_init(
+                        &do_url,
+                        &RequestInit {
+                            method: Method::Post,
+                            body: Some(JsValue::from_str(&body)),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .await?;
 
             if do_resp.status_code() == 200 {
                 let task: models::AgentTask = do_resp.json().await?;
                 // Sync to D1
-                let d1 = ctx.env.d1("DB")?;
                 db::sync_task_status(&d1, &tenant_ctx.tenant_id, &task).await?;
                 Response::from_json(&serde_json::json!({ "status": task.status }))
             } else {
                 Response::error("task not found or not running", 404)
             }
         })
+        // ── Recurring task schedules ──────────────────────────
+        .post_async("/v1/task-schedules", |mut req, ctx| async move {
+            let body: models::CreateTaskSchedule = req.json().await?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let next_ms = match task_schedule::validate(&body, js_sys::Date::now() as u64) {
+                Ok(ms) => ms,
+                Err(e) => return errors::error_response("INVALID_TASK_SCHEDULE", &e, 400),
+            };
+            let schedule = models::TaskSchedule {
+                id: generate_id()?,
+                name: body.name,
+                cron: body.cron,
+                task: body.task,
+                next_run_at: task_schedule::ms_to_iso(next_ms),
+                last_run_at: None,
+                last_task_id: None,
+                last_error: None,
+                created_at: db::now_iso(),
+            };
+            let d1 = ctx.env.d1("DB")?;
+            db::insert_task_schedule(&d1, &tenant_ctx.tenant_id, &schedule).await?;
+            Response::from_json(&schedule)
+        })
+        .get_async("/v1/task-schedules", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let d1 = ctx.env.d1("DB")?;
+            let schedules = db::list_task_schedules(&d1, &tenant_ctx.tenant_id).await?;
+            Response::from_json(&serde_json::json!({ "schedules": schedules }))
+        })
+        .delete_async("/v1/task-schedules/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let id = match ctx.param("id") {
+                Some(id) => id.to_string(),
+                None => return Response::error("missing schedule id", 400),
+            };
+            let d1 = ctx.env.d1("DB")?;
+            if db::delete_task_schedule(&d1, &tenant_ctx.tenant_id, &id).await? {
+                Response::from_json(&serde_json::json!({ "deleted": true }))
+            } else {
+                Response::error("task schedule not found", 404)
+            }
+        })
+        // ── Cancellation ──────────────────────────────────────
+        //
+        // Queued tasks are removed from TaskLeaseManager outright. Leased
+        // ones are flagged, and the agent learns at its next heartbeat
+        // (`cancel: true`), when the lease is released for good.
+        .post_async("/v1/tasks/:id/cancel", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let task_id = match ctx.param("id") {
+                Some(id) => id.to_string(),
+                None => return Response::error("missing task id", 400),
+            };
+
+            let d1 = ctx.env.d1("DB")?;
+            let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+            let do_url = format!("https://do/cancel/{}", task_id);
+            let mut do_resp = shards
+                .fetch_for_task(&d1, &task_id, || Request::new(&do_url, Method::Post))
+                .await?;
+
+            match do_resp.status_code() {
+                200 => {
+                    let outcome: models::CancelledTasks = do_resp.json().await?;
+                    db::record_task_cancellations(
+                        &d1,
+                        &tenant_ctx.tenant_id,
+                        &tenant_ctx.actor(),
+                        &outcome,
+                    )
+                    .await?;
+                    let status = if outcome.cancelled.is_empty() {
+                        "cancel_requested"
+                    } else {
+                        "cancelled"
+                    };
+                    Response::from_json(&models::TaskCancelled {
+                        id: task_id,
+                        status: status.to_string(),
+                    })
+                }
+                // Not queued or leased: already finished, dead-lettered,
+                // or unknown.
+                404 => match db::get_mcp_task_by_id(&d1, &tenant_ctx.tenant_id, &task_id).await? {
+                    Some(task) => errors::error_response_with_details(
+                        "TASK_NOT_CANCELLABLE",
+                        "task is not pending, scheduled or running",
+                        serde_json::json!({ "current_status": task.status }),
+                        409,
+                    ),
+                    None => errors::error_response("TASK_NOT_FOUND", "task not found", 404),
+                },
+                _ => match forward_do_response(do_resp).await? {
+                    Some(forwarded) => Ok(forwarded),
+                    None => Response::error("cancel failed", 500),
+                },
+            }
+        })
+        // GET /v1/tasks/queue — TaskLeaseManager introspection: depth by
+        // type/priority, oldest pending age, live leases, rejections and
+        // PlayManager notifications still being retried.
+        .get_async("/v1/tasks/queue", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+            let mut parts = Vec::new();
+            for mut do_resp in shards
+                .fetch_all(|| Request::new("https://do/queue", Method::Get))
+                .await?
+            {
+                if do_resp.status_code() != 200 {
+                    return match forward_do_response(do_resp).await? {
+                        Some(forwarded) => Ok(forwarded),
+                        None => Response::error("queue snapshot failed", 500),
+                    };
+                }
+                parts.push(do_resp.json::<models::QueueSnapshot>().await?);
+            }
+            Response::from_json(&task_shard::merge_queue_snapshots(parts))
+        })
+        // ── Dead-lettered agent tasks ─────────────────────────
+        .get_async("/v1/tasks/dead-letter", |req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let url = req.url()?;
+            let params: Vec<(String, String)> = url
+                .query_pairs()
+                .filter(|(k, _)| matches!(k.as_ref(), "job_id" | "cursor" | "limit"))
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            let params: Vec<(&str, &str)> = params
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+
+            let limit = pagination::clamp_limit(
+                params
+                    .iter()
+                    .find(|(k, _)| *k == "limit")
+                    .and_then(|(_, v)| v.parse().ok()),
+            ) as usize;
+
+            let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+            let do_url = build_do_url("/dead-letter", &params)?;
+            let mut pages = Vec::new();
+            for mut do_resp in shards
+                .fetch_all(|| Request::new(&do_url, Method::Get))
+                .await?
+            {
+                if do_resp.status_code() != 200 {
+                    return match forward_do_response(do_resp).await? {
+                        Some(forwarded) => Ok(forwarded),
+                        None => Response::error("dead-letter listing failed", 500),
+                    };
+                }
+                let list: models::DeadLetterList = do_resp.json().await?;
+                pages.push((list.tasks, list.next_cursor.is_some()));
+            }
+            let (tasks, next) = task_shard::merge_dead_letter_pages(pages, limit);
+            let next_cursor = next.map(|c| c.encode()).transpose()?;
+            Response::from_json(&models::DeadLetterList { tasks, next_cursor })
+        })
+        .post_async(
+            "/v1/tasks/dead-letter/:id/requeue",
+            |_req, ctx| async move {
+                let tenant_ctx = tenant::verified(&ctx.data)?;
+                let task_id = match ctx.param("id") {
+                    Some(id) => id.to_string(),
+                    None => return Response::error("missing task id", 400),
+                };
+
+                let d1 = ctx.env.d1("DB")?;
+                let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+                let do_url = format!("https://do/dead-letter/requeue/{}", task_id);
+                let mut do_resp = shards
+                    .fetch_for_task(&d1, &task_id, || Request::new(&do_url, Method::Post))
+                    .await?;
+
+                match do_resp.status_code() {
+                    200 => {
+                        let task: models::AgentTask = do_resp.json().await?;
+                        // Sync to D1
+                        let d1 = ctx.env.d1("DB")?;
+                        db::sync_task_status(&d1, &tenant_ctx.tenant_id, &task).await?;
+                        Response::from_json(&task)
+                    }
+                    404 => Response::error("task not in dead-letter store", 404),
+                    _ => match forward_do_response(do_resp).await? {
+                        Some(forwarded) => Ok(forwarded),
+                        None => Response::error("requeue failed", 500),
+                    },
+                }
+            },
+        )
+        .post_async(
+            "/v1/tasks/dead-letter/:id/discard",
+            |_req, ctx| async move {
+                let tenant_ctx = tenant::verified(&ctx.data)?;
+                let task_id = match ctx.param("id") {
+                    Some(id) => id.to_string(),
+                    None => return Response::error("missing task id", 400),
+                };
+
+                let d1 = ctx.env.d1("DB")?;
+                let shards = task_shard::TaskShards::new(&ctx.env, &tenant_ctx.tenant_id)?;
+                let do_url = format!("https://do/dead-letter/discard/{}", task_id);
+                let mut do_resp = shards
+                    .fetch_for_task(&d1, &task_id, || Request::new(&do_url, Method::Post))
+                    .await?;
+
+                if do_resp.status_code() == 200 {
+                    let entry: models::DeadLetterTask = do_resp.json().await?;
+                    Response::from_json(&entry)
+                } else {
+                    Response::error("task not in dead-letter store", 404)
+                }
+            },
+        )
         // ── Agents (M1) ───────────────────────────────────────
         .post_async("/v1/agents", |mut req, ctx| async move {
             let body: models::RegisterAgent = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            if let Err(e) = capability::validate_capabilities(&body.capabilities) {
+                return errors::error_response("INVALID_CAPABILITY", &e, 400);
+            }
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
-            db::register_agent(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
+            db::register_agent(
+                &d1,
+                &tenant_ctx.tenant_id,
+                &id,
+                tenant_ctx.subject.as_deref(),
+                &body,
+            )
+            .await?;
             Response::from_json(&serde_json::json!({
                 "id": id,
                 "name": body.name,
@@ -1268,7 +2602,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             }))
         })
         .get_async("/v1/agents", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req.url()?;
             let params: std::collections::HashMap<String, String> = url
                 .query_pairs()
@@ -1305,12 +2639,25 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             }))
         })
         .post_async("/v1/telemetry", |mut req, ctx| async move {
-            let body: models::TelemetrySnapshot = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let mut body: models::TelemetrySnapshot = req.json().await?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let findings = match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut body) {
+                Ok(findings) => findings,
+                Err(rejected) => return secret_rejected_response(&rejected),
+            };
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
 
             db::record_telemetry(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
+            db::record_secret_scan(
+                &d1,
+                &tenant_ctx.tenant_id,
+                "telemetry_snapshots",
+                &id,
+                tenant_ctx.secret_scan_mode,
+                &findings,
+            )
+            .await?;
 
             Response::from_json(&models::TelemetryAck { id, accepted: true })
         })
@@ -1320,7 +2667,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             if body.idempotency_key.trim().is_empty() {
                 return Response::error("idempotency_key must be non-empty", 400);
             }
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let d1 = ctx.env.d1("DB")?;
 
             // Short-circuit on retry: a row already exists for this idempotency
@@ -1380,7 +2727,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             })
         })
         .get_async("/v1/reasoning-traces", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req.url()?;
             let params: std::collections::HashMap<String, String> = url
                 .query_pairs()
@@ -1422,8 +2769,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // URL" requirement without leaking R2 keys to the public internet.
         .get_async(
             "/v1/reasoning-traces/:id/payload/:field",
-            |req, ctx| async move {
-                let tenant_ctx = tenant::tenant_from_request(&req)?;
+            |_req, ctx| async move {
+                let tenant_ctx = tenant::verified(&ctx.data)?;
                 let trace_id = match ctx.param("id") {
                     Some(v) => v.to_string(),
                     None => return Response::error("missing trace id", 400),
@@ -1465,8 +2812,12 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         )
         // ── Checkpoints (M2) ──────────────────────────────────
         .post_async("/v1/checkpoints", |mut req, ctx| async move {
-            let body: models::CreateCheckpoint = req.json().await?;
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let mut body: models::CreateCheckpoint = req.json().await?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let findings = match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut body) {
+                Ok(findings) => findings,
+                Err(rejected) => return secret_rejected_response(&rejected),
+            };
             let d1 = ctx.env.d1("DB")?;
             let bucket = ctx.env.bucket("ARTIFACTS")?;
             let id = generate_id()?;
@@ -1479,35 +2830,22 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
 
             let state_bytes =
                 serde_json::to_vec(&body.state).map_err(|e| Error::RustError(e.to_string()))?;
-            let size = storage::put_blob(&bucket, &r2_key, state_bytes).await? as i64;
+            let keyring = envelope::TenantKeyring::from_env(&ctx.env, &d1, &tenant_ctx.tenant_id)?;
+            let size =
+                storage::put_sealed(&bucket, &r2_key, state_bytes, keyring.as_ref()).await? as i64;
             db::create_checkpoint(&d1, &tenant_ctx.tenant_id, &id, &body, &r2_key, size).await?;
+            db::record_secret_scan(
+                &d1,
+                &tenant_ctx.tenant_id,
+                "checkpoints",
+                &id,
+                tenant_ctx.secret_scan_mode,
+                &findings,
+            )
+            .await?;
 
             // 3. Update ThreadManager Durable Object (fast state).
-            //
-            // SECURITY: name the DO by `{tenant_id}:{thread_id}` so a
-            // guessable thread_id from tenant A cannot route into tenant
-            // B's ThreadManager instance. Pre-WS8 the DO was named by
-            // thread_id alone.
-            let do_name = thread_do_name(&tenant_ctx.tenant_id, &body.thread_id);
-            let namespace = ctx.env.durable_object("THREAD_MANAGER")?;
-            let stub = namespace.id_from_name(&do_name)?.get_stub()?;
-
-            let headers = {
-                let h = Headers::new();
-                h.set("x-checkpoint-id", &id)?;
-                h
-            };
-
-            let do_req = Request::new_with_init(
-                "https://do/checkpoint",
-                &RequestInit {
-                    method: Method::Post,
-                    body: Some(JsValue::from_str(&serde_json::to_string(&body).map_err(|e| Error::RustError(e.to_string()))?)),
-                    headers,
-                    ..Default::default()
-                },
-            )?;
-            stub.fetch_with_request(do_req).await?;
+            push_thread_checkpoint(&ctx.env, &tenant_ctx.tenant_id, &id, &body).await?;
 
             Response::from_json(&models::CheckpointCreated {
                 id,
@@ -1517,8 +2855,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         .get_async(
             "/v1/checkpoints/threads/:thread_id",
-            |req, ctx| async move {
-                let tenant_ctx = tenant::tenant_from_request(&req)?;
+            |_req, ctx| async move {
+                let tenant_ctx = tenant::verified(&ctx.data)?;
                 let thread_id = ctx
                     .param("thread_id")
                     .expect("param thread_id is required by route")
@@ -1543,7 +2881,14 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 match db::get_latest_checkpoint(&d1, &tenant_ctx.tenant_id, &thread_id).await? {
                     Some(row) => {
                         let bucket = ctx.env.bucket("ARTIFACTS")?;
-                        match storage::get_blob(&bucket, &row.state_r2_key).await? {
+                        let keyring = envelope::TenantKeyring::from_env(
+                            &ctx.env,
+                            &d1,
+                            &tenant_ctx.tenant_id,
+                        )?;
+                        match storage::get_opened(&bucket, &row.state_r2_key, keyring.as_ref())
+                            .await?
+                        {
                             Some(blob) => {
                                 let state: serde_json::Value = serde_json::from_slice(&blob)?;
                                 Response::from_json(&serde_json::json!({
@@ -1558,8 +2903,207 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 }
             },
         )
-        .get_async("/v1/checkpoints/:id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async(
+            "/v1/checkpoints/threads/:thread_id/tree",
+            |_req, ctx| async move {
+                let tenant_ctx = tenant::verified(&ctx.data)?;
+                let thread_id = ctx
+                    .param("thread_id")
+                    .expect("param thread_id is required by route")
+                    .to_string();
+                let d1 = ctx.env.d1("DB")?;
+                let tenant_id = &tenant_ctx.tenant_id;
+                let limit = checkpoint_tree::MAX_TREE_CHECKPOINTS;
+                let mut rows =
+                    db::list_thread_checkpoints(&d1, tenant_id, &thread_id, limit + 1).await?;
+                if rows.is_empty() {
+                    return errors::error_response(
+                        "THREAD_NOT_FOUND",
+                        &format!("thread '{thread_id}' has no checkpoints"),
+                        404,
+                    );
+                }
+                let truncated = rows.len() > limit;
+                rows.truncate(limit);
+                let mut forks =
+                    db::list_checkpoint_forks(&d1, tenant_id, &thread_id, limit + 1).await?;
+                let truncated = truncated || forks.len() > limit;
+                forks.truncate(limit);
+                Response::from_json(&checkpoint_tree::build_tree(
+                    &thread_id,
+                    rows.into_iter().map(|r| r.into_checkpoint()).collect(),
+                    forks.into_iter().map(|r| r.into_checkpoint()).collect(),
+                    truncated,
+                ))
+            },
+        )
+        .get_async(
+            "/v1/checkpoints/:id/diff/:other_id",
+            |_req, ctx| async move {
+                let tenant_ctx = tenant::verified(&ctx.data)?;
+                let d1 = ctx.env.d1("DB")?;
+                let tenant_id = &tenant_ctx.tenant_id;
+                let mut states = Vec::with_capacity(2);
+                for param in ["id", "other_id"] {
+                    let id = ctx
+                        .param(param)
+                        .expect("params id and other_id are required by route");
+                    let Some(row) = db::get_checkpoint_by_id(&d1, tenant_id, id).await? else {
+                        return errors::error_response(
+                            "CHECKPOINT_NOT_FOUND",
+                            &format!("checkpoint '{id}' not found"),
+                            404,
+                        );
+                    };
+                    let Some(state) =
+                        load_checkpoint_state(&ctx.env, &d1, tenant_id, &row.state_r2_key).await?
+                    else {
+                        return errors::error_response(
+                            "CHECKPOINT_STATE_NOT_FOUND",
+                            &format!("state of checkpoint '{id}' not found in R2"),
+                            404,
+                        );
+                    };
+                    states.push((row.id, state));
+                }
+                let (from, from_state) = &states[0];
+                let (to, to_state) = &states[1];
+                Response::from_json(&checkpoint_diff::diff(from, from_state, to, to_state))
+            },
+        )
+        .get_async("/v1/checkpoints/:id/lineage", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let id = ctx
+                .param("id")
+                .expect("param id is required by route")
+                .to_string();
+            let d1 = ctx.env.d1("DB")?;
+            let rows = db::list_checkpoint_ancestors(
+                &d1,
+                &tenant_ctx.tenant_id,
+                &id,
+                checkpoint_tree::MAX_LINEAGE_DEPTH,
+            )
+            .await?;
+            let ancestors = rows.into_iter().map(|r| r.into_checkpoint()).collect();
+            match checkpoint_tree::lineage(&id, ancestors) {
+                Some(lineage) => Response::from_json(&lineage),
+                None => errors::error_response("CHECKPOINT_NOT_FOUND", "checkpoint not found", 404),
+            }
+        })
+        .post_async("/v1/checkpoints/:id/fork", |mut req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let source_id = ctx
+                .param("id")
+                .expect("param id is required by route")
+                .to_string();
+            let text = req.text().await?;
+            let mut fork: models::ForkCheckpoint = if text.trim().is_empty() {
+                models::ForkCheckpoint::default()
+            } else {
+                match serde_json::from_str(&text) {
+                    Ok(fork) => fork,
+                    Err(_) => {
+                        return errors::error_response(
+                            "INVALID_JSON_BODY",
+                            "invalid JSON body for checkpoint fork",
+                            400,
+                        )
+                    }
+                }
+            };
+            let findings = match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut fork) {
+                Ok(findings) => findings,
+                Err(rejected) => return secret_rejected_response(&rejected),
+            };
+            let thread_id = match fork.thread_id.take() {
+                Some(t) if t.trim().is_empty() => {
+                    return errors::error_response(
+                        "INVALID_THREAD_ID",
+                        "thread_id must not be empty",
+                        400,
+                    )
+                }
+                Some(t) => t,
+                None => generate_id()?,
+            };
+
+            let d1 = ctx.env.d1("DB")?;
+            let tenant_id = &tenant_ctx.tenant_id;
+            let Some(source) = db::get_checkpoint_by_id(&d1, tenant_id, &source_id).await? else {
+                return errors::error_response("CHECKPOINT_NOT_FOUND", "checkpoint not found", 404);
+            };
+            let Some(state) =
+                load_checkpoint_state(&ctx.env, &d1, tenant_id, &source.state_r2_key).await?
+            else {
+                return errors::error_response(
+                    "CHECKPOINT_STATE_NOT_FOUND",
+                    "checkpoint state not found in R2",
+                    404,
+                );
+            };
+
+            let forked_from = models::CheckpointBranch {
+                thread_id: source.thread_id.clone(),
+                checkpoint_id: source.id.clone(),
+            };
+            let mut metadata = match fork.metadata {
+                Some(serde_json::Value::Object(map)) => map,
+                None | Some(serde_json::Value::Null) => serde_json::Map::new(),
+                Some(other) => serde_json::Map::from_iter([("metadata".to_string(), other)]),
+            };
+            metadata.insert("forked_from".to_string(), serde_json::json!(forked_from));
+            let body = models::CreateCheckpoint {
+                thread_id,
+                node_id: source.node_id.clone(),
+                parent_id: Some(source.id.clone()),
+                state,
+                metadata: Some(serde_json::Value::Object(metadata)),
+            };
+
+            let id = generate_id()?;
+            let r2_key = format!(
+                "{}checkpoints/{}/{}",
+                tenant_ctx.r2_prefix(),
+                body.thread_id,
+                id
+            );
+            let bucket = ctx.env.bucket("ARTIFACTS")?;
+            let keyring = envelope::TenantKeyring::from_env(&ctx.env, &d1, tenant_id)?;
+            let state_bytes = serde_json::to_vec(&body.state)?;
+            let size =
+                storage::put_sealed(&bucket, &r2_key, state_bytes, keyring.as_ref()).await? as i64;
+            // Claims the thread: the insert only lands while it is empty, so
+            // two forks racing onto one thread_id cannot both succeed.
+            if !db::create_fork_checkpoint(&d1, tenant_id, &id, &body, &r2_key, size).await? {
+                storage::delete_blob(&bucket, &r2_key).await?;
+                return errors::error_response(
+                    "THREAD_EXISTS",
+                    &format!("thread '{}' already has checkpoints", body.thread_id),
+                    409,
+                );
+            }
+            db::record_secret_scan(
+                &d1,
+                tenant_id,
+                "checkpoints",
+                &id,
+                tenant_ctx.secret_scan_mode,
+                &findings,
+            )
+            .await?;
+            push_thread_checkpoint(&ctx.env, tenant_id, &id, &body).await?;
+
+            Ok(Response::from_json(&models::CheckpointForked {
+                id,
+                thread_id: body.thread_id,
+                state_r2_key: r2_key,
+                forked_from,
+            })?
+            .with_status(201))
+        })
+        .get_async("/v1/checkpoints/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -1570,8 +3114,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 None => Response::error("checkpoint not found", 404),
             }
         })
-        .delete_async("/v1/checkpoints/:id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .delete_async("/v1/checkpoints/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = match ctx.param("id") {
                 Some(k) => k.to_string(),
                 None => return Response::error("missing checkpoint id", 400),
@@ -1595,11 +3139,24 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── Memory (WS5: #45) ─────────────────────────────────
         .post_async("/v1/memory", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
-            let body: models::CreateMemory = req.json().await?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let mut body: models::CreateMemory = req.json().await?;
+            let findings = match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut body) {
+                Ok(findings) => findings,
+                Err(rejected) => return secret_rejected_response(&rejected),
+            };
             let d1 = ctx.env.d1("DB")?;
             let id = generate_id()?;
             db::create_memory(&d1, &tenant_ctx.tenant_id, &id, &body).await?;
+            db::record_secret_scan(
+                &d1,
+                &tenant_ctx.tenant_id,
+                "memory",
+                &id,
+                tenant_ctx.secret_scan_mode,
+                &findings,
+            )
+            .await?;
             Response::from_json(&models::MemoryCreated {
                 id,
                 thread_id: body.thread_id,
@@ -1608,7 +3165,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             })
         })
         .get_async("/v1/memory/threads/:thread_id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let thread_id = match ctx.param("thread_id") {
                 Some(t) => t.to_string(),
                 None => return Response::error("missing thread_id", 400),
@@ -1622,7 +3179,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         .get_async(
             "/v1/context-pack/threads/:thread_id",
             |req, ctx| async move {
-                let tenant_ctx = tenant::tenant_from_request(&req)?;
+                let tenant_ctx = tenant::verified(&ctx.data)?;
                 let thread_id = match ctx.param("thread_id") {
                     Some(t) => t.to_string(),
                     None => return Response::error("missing thread_id", 400),
@@ -1644,7 +3201,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // ── Traces / Provenance (WS3: issue #43) ──────────────
         .get_async("/v1/traces/:run_id", |req, ctx| async move {
             let started = js_sys::Date::now();
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let run_id = match ctx.param("run_id") {
                 Some(r) => r.to_string(),
                 None => return Response::error("missing run_id", 400),
@@ -1674,7 +3231,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             )
         })
         .get_async("/v1/traces/:run_id/lineage", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let run_id = match ctx.param("run_id") {
                 Some(r) => r.to_string(),
                 None => return Response::error("missing run_id", 400),
@@ -1702,7 +3259,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // ── Provenance Chain (WS3) ──────────────────────────────
         .get_async("/v1/provenance/:kind/:id", |req, ctx| async move {
             let started = js_sys::Date::now();
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let kind = match ctx.param("kind") {
                 Some(k) => k.to_string(),
                 None => return Response::error("missing kind", 400),
@@ -1744,8 +3301,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             )
         })
         // ── Gold Layer: Run Summaries (WS3) ─────────────────────
-        .get_async("/v1/runs/:run_id/summary", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/runs/:run_id/summary", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let run_id = ctx
                 .param("run_id")
                 .expect("param run_id is required by route")
@@ -1758,7 +3315,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         .get_async("/v1/gold/run-summaries", |req, ctx| async move {
             let started = js_sys::Date::now();
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let limit = parse_limit_query(req.url().ok(), "limit")
                 .unwrap_or(50)
                 .min(200);
@@ -1766,10 +3323,59 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             let summaries = db::list_run_summaries(&d1, &tenant_ctx.tenant_id, limit).await?;
             timed_json_response(started, &serde_json::json!({ "summaries": summaries }))
         })
+        // ── Cross-tenant federation (WS8) ─────────────────────
+        .get_async("/v1/federation/config", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let d1 = ctx.env.d1("DB")?;
+            let config = db::get_federation_config(&d1, &tenant_ctx.tenant_id).await?;
+            Response::from_json(&config)
+        })
+        .put_async("/v1/federation/config", |mut req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let body: tenant_security::FederationConfig = req.json().await?;
+            let config = match federation::validate_config(&tenant_ctx.tenant_id, body) {
+                Ok(c) => c,
+                Err(e) => return errors::error_response("INVALID_FEDERATION_CONFIG", &e, 400),
+            };
+            let d1 = ctx.env.d1("DB")?;
+            db::put_federation_config(&d1, &tenant_ctx.tenant_id, &config).await?;
+            Response::from_json(&config)
+        })
+        .get_async("/v1/federation/run-summaries", |req, ctx| async move {
+            let started = js_sys::Date::now();
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            if let Err(reason) = federation::may_receive(&tenant_ctx, "run_summary") {
+                return errors::error_response("FEDERATION_FORBIDDEN", &reason, 403);
+            }
+            let limit = parse_limit_query(req.url().ok(), "limit")
+                .unwrap_or(50)
+                .min(200);
+            let d1 = ctx.env.d1("DB")?;
+            let sources = db::list_federation_sources(
+                &d1,
+                &tenant_ctx.tenant_id,
+                federation::SCOPE_RUN_SUMMARY,
+            )
+            .await?;
+            let mut summaries = Vec::new();
+            for source in &sources {
+                let rows = db::list_run_summaries(&d1, source, limit).await?;
+                summaries.extend(
+                    rows.into_iter()
+                        .map(|s| federation::federate_run_summary(s, source)),
+                );
+            }
+            summaries.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+            summaries.truncate(limit as usize);
+            timed_json_response(
+                started,
+                &serde_json::json!({ "sources": sources, "summaries": summaries }),
+            )
+        })
         // ── Gold Layer: Task Dependency Graph (WS3: #58) ────────
-        .get_async("/v1/gold/runs/:run_id/task-graph", |req, ctx| async move {
+        .get_async("/v1/gold/runs/:run_id/task-graph", |_req, ctx| async move {
             let started = js_sys::Date::now();
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let run_id = ctx
                 .param("run_id")
                 .expect("param run_id is required by route")
@@ -1788,7 +3394,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             if body.run_id.trim().is_empty() {
                 return Response::error("run_id is required", 400);
             }
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let d1 = ctx.env.d1("DB")?;
             let steps = db::build_replay_plan(
                 &d1,
@@ -1815,7 +3421,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 return Response::error("run_id is required", 400);
             }
 
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let d1 = ctx.env.d1("DB")?;
 
             let steps = db::build_replay_plan(
@@ -1901,7 +3507,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── WS6: Integration Registry ────────────────────────
         .post_async("/v1/integrations", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let body: integrations::RegisterIntegration = req.json().await?;
             if body.name.trim().is_empty() || body.name.len() > 256 {
                 return Response::error("name must be 1-256 characters", 400);
@@ -1917,7 +3523,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             }))
         })
         .get_async("/v1/integrations", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let limit = parse_limit_query(req.url().ok(), "limit")
                 .unwrap_or(50)
                 .min(200);
@@ -1925,8 +3531,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             let list = db::list_integrations(&d1, &tenant_ctx.tenant_id, limit).await?;
             Response::from_json(&serde_json::json!({ "integrations": list }))
         })
-        .get_async("/v1/integrations/:id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/integrations/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -1938,7 +3544,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             }
         })
         .patch_async("/v1/integrations/:id", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -1951,8 +3557,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 None => Response::error("integration not found", 404),
             }
         })
-        .delete_async("/v1/integrations/:id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .delete_async("/v1/integrations/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -1969,14 +3575,19 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         .post_async(
             "/v1/integrations/oxidizedgraph/events",
             |mut req, ctx| async move {
-                let tenant_ctx = tenant::tenant_from_request(&req)?;
-                let batch: integrations::oxidizedgraph::GraphExecBatch = req.json().await?;
+                let tenant_ctx = tenant::verified(&ctx.data)?;
+                let mut batch: integrations::oxidizedgraph::GraphExecBatch = req.json().await?;
                 if batch.events.len() > db::INTEGRATION_BATCH_LIMIT {
                     return Response::error(
                         format!("batch exceeds max {} events", db::INTEGRATION_BATCH_LIMIT),
                         400,
                     );
                 }
+                let findings =
+                    match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut batch) {
+                        Ok(findings) => findings,
+                        Err(rejected) => return secret_rejected_response(&rejected),
+                    };
                 let d1 = ctx.env.d1("DB")?;
                 let bucket = ctx.env.bucket("ARTIFACTS")?;
                 let now = db::now_iso();
@@ -2000,6 +3611,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                     }
                 };
 
+                let keyring =
+                    envelope::TenantKeyring::from_env(&ctx.env, &d1, &tenant_ctx.tenant_id)?;
                 let mut checkpoint_count = 0usize;
                 for evt in &batch.events {
                     if let Some(cp) = integrations::oxidizedgraph::adapt_to_checkpoint(&batch, evt)
@@ -2011,13 +3624,28 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                         );
                         let state_bytes = serde_json::to_vec(&cp.state)
                             .map_err(|e| Error::RustError(e.to_string()))?;
-                        let size = storage::put_blob(&bucket, &r2_key, state_bytes).await? as i64;
+                        let size =
+                            storage::put_sealed(&bucket, &r2_key, state_bytes, keyring.as_ref())
+                                .await? as i64;
                         db::create_checkpoint(&d1, &tenant_ctx.tenant_id, &id, &cp, &r2_key, size)
                             .await?;
                         checkpoint_count += 1;
                     }
                 }
 
+                // One batch fans out into many rows, so its findings are
+                // recorded once, keyed by the integration target
+                // (`integrations.target`), rather than per row.
+                db::record_secret_scan(
+                    &d1,
+                    &tenant_ctx.tenant_id,
+                    "integrations",
+                    "oxidizedgraph",
+                    tenant_ctx.secret_scan_mode,
+                    &findings,
+                )
+                .await?;
+
                 if let Err(e) =
                     db::touch_integration(&d1, &tenant_ctx.tenant_id, "oxidizedgraph", None).await
                 {
@@ -2033,8 +3661,12 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         )
         // ── WS6: aivcs intake ───────────────────────────────
         .post_async("/v1/integrations/aivcs/events", |mut req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
-            let evt: integrations::aivcs::PipelineEvent = req.json().await?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let mut evt: integrations::aivcs::PipelineEvent = req.json().await?;
+            let findings = match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut evt) {
+                Ok(findings) => findings,
+                Err(rejected) => return secret_rejected_response(&rejected),
+            };
             let d1 = ctx.env.d1("DB")?;
             let bucket = ctx.env.bucket("ARTIFACTS")?;
 
@@ -2057,6 +3689,15 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 worker::console_log!("ERROR: aivcs ingest_event failed: {e:?}");
                 return degraded_response("aivcs", "event ingestion temporarily unavailable");
             }
+            db::record_secret_scan(
+                &d1,
+                &tenant_ctx.tenant_id,
+                "events",
+                &evt_id,
+                tenant_ctx.secret_scan_mode,
+                &findings,
+            )
+            .await?;
 
             // Write artifact metadata to R2 so fabric tracks pipeline artifacts
             let mut artifact_count = 0usize;
@@ -2178,13 +3819,27 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         .post_async(
             "/v1/integrations/llama-rs/inference",
             |mut req, ctx| async move {
-                let tenant_ctx = tenant::tenant_from_request(&req)?;
-                let body: integrations::llama_rs::InferenceRequest = req.json().await?;
+                let tenant_ctx = tenant::verified(&ctx.data)?;
+                let mut body: integrations::llama_rs::InferenceRequest = req.json().await?;
+                let findings =
+                    match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut body) {
+                        Ok(findings) => findings,
+                        Err(rejected) => return secret_rejected_response(&rejected),
+                    };
                 let d1 = ctx.env.d1("DB")?;
 
                 let task = integrations::llama_rs::adapt_to_task(&body);
                 let id = generate_id()?;
                 db::create_task(&d1, &tenant_ctx.tenant_id, &id, &task).await?;
+                db::record_secret_scan(
+                    &d1,
+                    &tenant_ctx.tenant_id,
+                    "tasks",
+                    &id,
+                    tenant_ctx.secret_scan_mode,
+                    &findings,
+                )
+                .await?;
 
                 if let Err(e) =
                     db::touch_integration(&d1, &tenant_ctx.tenant_id, "llama_rs", None).await
@@ -2202,8 +3857,13 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         .post_async(
             "/v1/integrations/llama-rs/telemetry",
             |mut req, ctx| async move {
-                let tenant_ctx = tenant::tenant_from_request(&req)?;
-                let telemetry: integrations::llama_rs::InferenceTelemetry = req.json().await?;
+                let tenant_ctx = tenant::verified(&ctx.data)?;
+                let mut telemetry: integrations::llama_rs::InferenceTelemetry = req.json().await?;
+                let findings =
+                    match secret_scan::enforce_body(tenant_ctx.secret_scan_mode, &mut telemetry) {
+                        Ok(findings) => findings,
+                        Err(rejected) => return secret_rejected_response(&rejected),
+                    };
                 let d1 = ctx.env.d1("DB")?;
                 let now = db::now_iso();
 
@@ -2220,6 +3880,15 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 let count =
                     ingest_events_bronze_silver(&d1, &tenant_ctx.tenant_id, graph_events, &now)
                         .await?;
+                db::record_secret_scan(
+                    &d1,
+                    &tenant_ctx.tenant_id,
+                    "integrations",
+                    "llama_rs",
+                    tenant_ctx.secret_scan_mode,
+                    &findings,
+                )
+                .await?;
 
                 if let Err(e) =
                     db::touch_integration(&d1, &tenant_ctx.tenant_id, "llama_rs", None).await
@@ -2237,7 +3906,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         .post_async(
             "/v1/integrations/llama-rs/context",
             |mut req, ctx| async move {
-                let tenant_ctx = tenant::tenant_from_request(&req)?;
+                let tenant_ctx = tenant::verified(&ctx.data)?;
                 let body: integrations::llama_rs::ContextRequest = req.json().await?;
                 let d1 = ctx.env.d1("DB")?;
 
@@ -2257,14 +3926,19 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         // ── Graph Events (M3) ─────────────────────────────────
         .post_async("/v1/graph-events", |mut req, ctx| async move {
             let started = js_sys::Date::now();
-            let body: models::GraphEventBatch = req.json().await?;
+            let mut body: models::GraphEventBatch = req.json().await?;
             if body.events.len() > db::INTEGRATION_BATCH_LIMIT {
                 return Response::error(
                     format!("batch exceeds max {} events", db::INTEGRATION_BATCH_LIMIT),
                     400,
                 );
             }
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
+            let scan_mode = tenant_ctx.secret_scan_mode;
+            let findings = match secret_scan::enforce_each(scan_mode, &mut body.events, "/events") {
+                Ok(findings) => findings,
+                Err(rejected) => return secret_rejected_response(&rejected),
+            };
             let d1 = ctx.env.d1("DB")?;
             let now = js_sys::Date::new_0()
                 .to_iso_string()
@@ -2280,6 +3954,17 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
 
             let count = events.len();
             db::insert_events_bronze(&d1, &tenant_ctx.tenant_id, &events).await?;
+            for ((id, _, _), found) in events.iter().zip(&findings) {
+                db::record_secret_scan(
+                    &d1,
+                    &tenant_ctx.tenant_id,
+                    "events_bronze",
+                    id,
+                    scan_mode,
+                    found,
+                )
+                .await?;
+            }
 
             let mut silver_events: Vec<(String, String, &models::GraphEvent, String)> =
                 Vec::with_capacity(events.len());
@@ -2317,7 +4002,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── AIVCS: change_set routes (issue #148) ────────────────
         .get_async("/v1/change-sets", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req
                 .url()
                 .map_err(|_| Error::RustError("invalid url".into()))?;
@@ -2335,8 +4020,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 Response::error("missing required query parameter: repo", 400)
             }
         })
-        .get_async("/v1/change-sets/:id", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/change-sets/:id", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -2349,7 +4034,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // ── AIVCS: review projections routes (issue #148) ────────
         .get_async("/v1/review-threads", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req
                 .url()
                 .map_err(|_| Error::RustError("invalid url".into()))?;
@@ -2372,8 +4057,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 Response::error("missing required query parameter: pr_id or review_id", 400)
             }
         })
-        .get_async("/v1/review-threads/:id/comments", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/review-threads/:id/comments", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let thread_id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -2384,8 +4069,8 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                     .await?;
             Response::from_json(&serde_json::json!({ "comments": list }))
         })
-        .get_async("/v1/review-threads/:id/anchors", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+        .get_async("/v1/review-threads/:id/anchors", |_req, ctx| async move {
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let thread_id = ctx
                 .param("id")
                 .expect("param id is required by route")
@@ -2398,7 +4083,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         })
         // Issue #159 — all file anchors across a pull request's review threads.
         .get_async("/v1/file-anchors", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req
                 .url()
                 .map_err(|_| Error::RustError("invalid url".into()))?;
@@ -2417,7 +4102,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             }
         })
         .get_async("/v1/human-decisions", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req
                 .url()
                 .map_err(|_| Error::RustError("invalid url".into()))?;
@@ -2425,17 +4110,26 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 .query_pairs()
                 .collect::<std::collections::HashMap<_, _>>();
             let review_id = params.get("review_id").map(|s| s.as_ref());
+            let run_id = params.get("run_id").map(|s| s.as_ref());
             let d1 = ctx.env.d1("DB")?;
-            if let Some(rid) = review_id {
-                let list =
-                    db::list_human_decisions_by_review(&d1, &tenant_ctx.tenant_id, rid).await?;
-                Response::from_json(&serde_json::json!({ "human_decisions": list }))
-            } else {
-                Response::error("missing required query parameter: review_id", 400)
-            }
+            let list = match (review_id, run_id) {
+                (Some(rid), _) => {
+                    db::list_human_decisions_by_review(&d1, &tenant_ctx.tenant_id, rid).await?
+                }
+                (None, Some(rid)) => {
+                    db::list_human_decisions_by_run(&d1, &tenant_ctx.tenant_id, rid).await?
+                }
+                (None, None) => {
+                    return Response::error(
+                        "missing required query parameter: review_id or run_id",
+                        400,
+                    )
+                }
+            };
+            Response::from_json(&serde_json::json!({ "human_decisions": list }))
         })
         .get_async("/v1/ci-check-runs", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req
                 .url()
                 .map_err(|_| Error::RustError("invalid url".into()))?;
@@ -2461,7 +4155,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             }
         })
         .get_async("/v1/events", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req
                 .url()
                 .map_err(|_| Error::RustError("invalid url".into()))?;
@@ -2475,7 +4169,7 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             Response::from_json(&serde_json::json!({ "events": list }))
         })
         .get_async("/v1/branches", |req, ctx| async move {
-            let tenant_ctx = tenant::tenant_from_request(&req)?;
+            let tenant_ctx = tenant::verified(&ctx.data)?;
             let url = req
                 .url()
                 .map_err(|_| Error::RustError("invalid url".into()))?;
@@ -2514,9 +4208,152 @@ pub async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         }
     }
 
+    append_audit(audit_db, audit_who, &method, &path, &response).await;
+
+    // Feed server failures (and the first success after a streak) back into
+    // the tenant's circuit breaker without delaying the response.
+    if let Some((stub, tenant_id)) = limiter {
+        let status = response
+            .as_ref()
+            .ok()
+            .map(|r| r.status_code())
+            .unwrap_or(500);
+        let failed = rate_limit_do::is_failure_status(status);
+        if failed || limiter_streak > 0 {
+            worker_ctx.wait_until(async move {
+                if let Err(e) = rate_limit_report(&stub, &tenant_id, !failed).await {
+                    worker::console_log!("WARN: rate limiter outcome report failed: {e:?}");
+                }
+            });
+        }
+    }
+
     response
 }
 
+/// Reject requests for suspended or deleted tenants (WS8 lifecycle) and load
+/// the tenant's ingest secret-scan mode into the context. Unlike the rate
+/// limiter this fails closed: a missing D1 binding or query error rejects
+/// the request with 503.
+async fn tenant_admission(
+    env: &Env,
+    tenant_ctx: &mut tenant::TenantContext,
+    path: &str,
+) -> std::result::Result<(), (&'static str, &'static str, u16)> {
+    let row = match env.d1("DB") {
+        Ok(d1) => db::get_tenant_admission(&d1, &tenant_ctx.tenant_id).await,
+        Err(e) => Err(e),
+    };
+    match row {
+        Ok(Some(row)) => {
+            tenant_ctx.secret_scan_mode =
+                secret_scan::ScanMode::parse(&row.secret_scan_mode).unwrap_or_default();
+            tenant_lifecycle::admission(
+                tenant_lifecycle::TenantStatus::parse(&row.status),
+                tenant_ctx.role,
+                path,
+            )
+            .map_err(|(code, message)| (code, message, 403))
+        }
+        Ok(None) => Ok(()),
+        // Fail closed: without the row neither the tenant's status nor its
+        // secret-scan mode is known, and defaulting either would let a
+        // suspended tenant in or store secrets under the wrong mode.
+        Err(e) => {
+            worker::console_log!("WARN: tenant status lookup failed (fail-closed): {e:?}");
+            Err((
+                "TENANT_STATUS_UNAVAILABLE",
+                "tenant status is unavailable; retry shortly",
+                503,
+            ))
+        }
+    }
+}
+
+/// 422 for a write refused by the tenant's `reject` secret-scan mode. The
+/// details list where each secret was and which detector matched; the
+/// secret values themselves are never echoed back.
+fn secret_rejected_response(rejected: &secret_scan::SecretRejected) -> Result<Response> {
+    errors::error_response_with_details(
+        "SECRET_DETECTED",
+        "request body contains secrets and this tenant rejects them",
+        serde_json::json!({ "findings": rejected.findings }),
+        422,
+    )
+}
+
+/// Append the request's audit row before the response goes out, so a
+/// request is never answered without its row either in the chain or
+/// recorded as a failed append.
+async fn append_audit(
+    d1: Option<D1Database>,
+    who: Option<(String, String)>,
+    method: &Method,
+    path: &str,
+    response: &Result<Response>,
+) {
+    let (Some(d1), Some((tenant_id, actor))) = (d1, who) else {
+        return;
+    };
+    let entry = audit::AuditEntry::new(actor, method, path, response);
+    audit::append_or_record_failure(&d1, &tenant_id, &entry).await;
+}
+
+/// Stub for the tenant's `TenantRateLimiter`, or `None` when the binding is
+/// absent (local dev without the DO) so the caller can fail open.
+pub(crate) fn rate_limiter_stub(env: &Env, tenant_id: &str) -> Option<Stub> {
+    env.durable_object("RATE_LIMITER")
+        .ok()?
+        .id_from_name(tenant_id)
+        .ok()?
+        .get_stub()
+        .ok()
+}
+
+async fn rate_limit_check(stub: &Stub, tenant_id: &str) -> Result<rate_limit_do::CheckOutcome> {
+    let url = build_do_url("/check", &[("tenant_id", tenant_id)])?;
+    let do_req = Request::new_with_init(
+        &url,
+        &RequestInit {
+            method: Method::Post,
+            ..Default::default()
+        },
+    )?;
+    let mut resp = stub.fetch_with_request(do_req).await?;
+    resp.json().await
+}
+
+async fn rate_limit_report(stub: &Stub, tenant_id: &str, success: bool) -> Result<()> {
+    let url = build_do_url("/outcome", &[("tenant_id", tenant_id)])?;
+    let do_req = Request::new_with_init(
+        &url,
+        &RequestInit {
+            method: Method::Post,
+            body: Some(JsValue::from_str(
+                &serde_json::json!({ "success": success }).to_string(),
+            )),
+            ..Default::default()
+        },
+    )?;
+    stub.fetch_with_request(do_req).await?;
+    Ok(())
+}
+
+/// 429 in the standard error envelope, with `retry-after` both as a header
+/// and in `details` for clients that only read the body.
+fn rate_limited_response(outcome: &rate_limit_do::CheckOutcome) -> Result<Response> {
+    let retry_after = outcome.retry_after_secs.unwrap_or(1);
+    let resp = errors::error_response_with_details(
+        outcome.code.as_deref().unwrap_or("RATE_LIMITED"),
+        outcome.message.as_deref().unwrap_or("rate limit exceeded"),
+        serde_json::json!({ "retry_after_secs": retry_after }),
+        429,
+    )?;
+    resp.headers()
+        .set("retry-after", &retry_after.to_string())?;
+    Ok(resp)
+}
+
 /// Best-effort emit of one request-latency sample to the `PILOT_LATENCY`
 /// Analytics Engine dataset. Failures are deliberately swallowed: a missing
 /// dataset or transient sink error must not turn a successful request into
@@ -2635,11 +4472,165 @@ pub async fn queue(batch: MessageBatch<serde_json::Value>, env: Env, _ctx: Conte
     Ok(())
 }
 
-/// Scheduled event: poll Gemini batch jobs and other background maintenance.
+/// Scheduled event: materialize due recurring tasks, poll Gemini batch jobs
+/// and other background maintenance.
+/// Cron trigger (wrangler.toml) of the Gemini batch-job poll. Every other
+/// trigger is the every-minute one that runs task schedules.
+const GEMINI_POLL_CRON: &str = "*/10 * * * *";
+
 #[event(scheduled)]
 #[allow(unused_must_use)]
-pub async fn scheduled(_event: ScheduledEvent, env: Env, _ctx: ScheduleContext) -> Result<()> {
-    gemini_service::poll_gemini_jobs(&env).await
+pub async fn scheduled(event: ScheduledEvent, env: Env, _ctx: ScheduleContext) -> Result<()> {
+    if event.cron() == GEMINI_POLL_CRON {
+        return gemini_service::poll_gemini_jobs(&env).await;
+    }
+    task_schedule::run_due_schedules(&env).await
+}
+
+/// Capabilities an agent claims with: the `cap` query parameter plus
+/// whatever it registered through `POST /v1/agents`, comma-joined for the
+/// TaskLeaseManager `caps` parameter. Reserved `agent:` entries in `cap`
+/// are dropped; the DO adds the agent's own.
+async fn claim_capabilities(
+    d1: &D1Database,
+    tenant_id: &str,
+    agent_id: &str,
+    cap_param: Option<&str>,
+) -> Result<String> {
+    let mut caps: Vec<String> = cap_param
+        .unwrap_or_default()
+        .split(',')
+        .filter(|c| !c.is_empty() && capability::is_claimable(c))
+        .map(str::to_string)
+        .collect();
+    for cap in db::get_agent_capabilities(d1, tenant_id, agent_id).await? {
+        if !caps.contains(&cap) {
+            caps.push(cap);
+        }
+    }
+    Ok(caps.join(","))
+}
+
+const MAX_TASK_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// The original task for a repeated idempotency key.
+fn duplicate_task_created(task: models::AgentTask) -> models::TaskCreated {
+    models::TaskCreated {
+        id: task.id,
+        status: task.status,
+        duplicate: true,
+    }
+}
+
+/// Persist `body` as a new `mcp_tasks` row and hand it to the tenant's
+/// TaskLeaseManager. Returns the task id, or the DO's non-2xx response
+/// (notably 429 QUEUE_FULL backpressure when pending_tasks is at
+/// MAX_PENDING_TASKS) for the caller to forward. Shared by `POST /v1/tasks`
+/// and recurring task schedules.
+pub(crate) async fn enqueue_agent_task(
+    env: &Env,
+    tenant_id: &str,
+    body: &models::CreateAgentTask,
+) -> Result<std::result::Result<models::TaskCreated, Response>> {
+    let d1 = env.d1("DB")?;
+
+    let id = generate_id()?;
+
+    // Short-circuit on retry, like trace ingest: a task already submitted
+    // with this key inside the window is returned as-is. Claiming the key
+    // in D1 before anything is created makes concurrent submissions agree
+    // on one owner, whichever DO shard their tasks would land on.
+    let window_ms = task_do::idempotency_window_ms(
+        env.var(task_do::IDEMPOTENCY_WINDOW_VAR)
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    );
+    let idempotency_key = body.idempotency_key.as_deref().filter(|_| window_ms > 0);
+    if let Some(key) = idempotency_key {
+        let now_ms = js_sys::Date::now() as u64;
+        let now = task_schedule::ms_to_iso(now_ms);
+        let expires_at = task_schedule::ms_to_iso(now_ms.saturating_add(window_ms));
+        let owner =
+            db::claim_task_idempotency_key(&d1, tenant_id, key, &id, &now, &expires_at).await?;
+        if let Some(original) = owner {
+            let created = match db::get_mcp_task_by_id(&d1, tenant_id, &original).await? {
+                Some(task) => duplicate_task_created(task),
+                // The owner is still being created.
+                None => models::TaskCreated {
+                    id: original,
+                    status: "pending".into(),
+                    duplicate: true,
+                },
+            };
+            return Ok(Ok(created));
+        }
+    }
+
+    // Create in D1 for persistence
+    db::create_task(&d1, tenant_id, &id, body).await?;
+
+    // Fetch the task as an AgentTask for the DO
+    let task = match db::get_mcp_task_by_id(&d1, tenant_id, &id).await? {
+        Some(t) => t,
+        None => return Ok(Err(Response::error("failed to fetch created task", 500)?)),
+    };
+
+    // Enqueue in Durable Object for active management
+    let stub = task_shard::TaskShards::new(env, tenant_id)?.stub_for_task(&task)?;
+
+    let do_req = Request::new_with_init(
+        "https://do/enqueue",
+        &RequestInit {
+            method: Method::Post,
+            body: Some(JsValue::from_str(
+                &serde_json::to_string(&task).map_err(|e| Error::RustError(e.to_string()))?,
+            )),
+            ..Default::default()
+        },
+    )?;
+    let do_resp = stub.fetch_with_request(do_req).await?;
+
+    // Without this passthrough, the DO's Retry-After + QUEUE_FULL envelope
+    // is silently swallowed and the client sees a generic success / 500,
+    // defeating the backpressure feature.
+    match forward_do_response(do_resp).await? {
+        Some(forwarded) => {
+            // Not queued: free the key so the caller's retry is not
+            // answered with this task.
+            if let Some(key) = idempotency_key {
+                db::release_task_idempotency_key(&d1, tenant_id, key, &id).await?;
+                db::delete_task(&d1, tenant_id, &id).await?;
+            }
+            Ok(Err(forwarded))
+        }
+        None => Ok(Ok(models::TaskCreated {
+            id,
+            status: "pending".into(),
+            duplicate: false,
+        })),
+    }
+}
+
+/// Park on a TaskLeaseManager's `/wait` for up to `wait_ms` unless its
+/// generation has already moved past `since`, returning the generation.
+async fn claim_wait_generation(stub: &Stub, since: Option<u64>, wait_ms: u64) -> Result<u64> {
+    let since = since.map(|s| s.to_string());
+    let wait_ms = wait_ms.to_string();
+    let mut params = vec![("wait_ms", wait_ms.as_str())];
+    if let Some(since) = &since {
+        params.push(("since", since));
+    }
+    let req = Request::new(&build_do_url("/wait", &params)?, Method::Post)?;
+    let body: serde_json::Value = stub.fetch_with_request(req).await?.json().await?;
+    Ok(body["generation"].as_u64().unwrap_or_default())
+}
+
+fn random_u32() -> Result<u32> {
+    let mut buf = [0u8; 4];
+    getrandom::getrandom(&mut buf)
+        .map_err(|err| Error::RustError(format!("failed to generate random: {err}")))?;
+    Ok(u32::from_le_bytes(buf))
 }
 
 pub(crate) fn generate_id() -> Result<String> {
@@ -2898,6 +4889,25 @@ fn degraded_response(source: &str, detail: &str) -> Result<Response> {
     Ok(resp.with_status(503))
 }
 
+/// Parse an optional `MAJOR.MINOR.PATCH` query parameter.
+fn query_play_version(
+    url: &Url,
+    key: &str,
+) -> std::result::Result<Option<models::PlayVersion>, String> {
+    url.query_pairs()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| models::PlayVersion::parse(&v))
+        .transpose()
+}
+
+fn play_not_found(name: &str, version: Option<&models::PlayVersion>) -> Result<Response> {
+    let msg = match version {
+        Some(v) => format!("play '{name}' has no version {v}"),
+        None => format!("play '{name}' not found"),
+    };
+    errors::error_response("PLAY_NOT_FOUND", &msg, 404)
+}
+
 /// Parse a `PlayLaunchRequest` from a raw JSON string.
 ///
 /// Returns `Err(())` on a parse failure so callers can map to a stable error
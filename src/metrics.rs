@@ -40,6 +40,10 @@ pub struct PilotMetrics {
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     pub null_reasons: BTreeMap<&'static str, &'static str>,
     pub meta: Meta,
+    /// Edge rate-limiter counters for the tenant (WS8). Filled in by the
+    /// handler from the `TenantRateLimiter` DO; omitted when unavailable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<crate::rate_limit_do::RateLimitStats>,
 }
 
 #[derive(Debug, Serialize)]
@@ -264,6 +268,7 @@ pub fn assemble(
             generated_at,
             tenant_id: tenant_id.to_string(),
         },
+        rate_limit: None,
     }
 }
 
@@ -823,6 +828,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn serialized_shape_omits_rate_limit_until_attached() {
+        let mut m = assemble("1d", 86_400, "t", inputs_for_assemble_happy(), "now".into());
+        assert!(serde_json::to_value(&m)
+            .unwrap()
+            .get("rate_limit")
+            .is_none());
+
+        m.rate_limit = Some(crate::rate_limit_do::RateLimitStats {
+            config: crate::tenant_security::TenantRateLimitConfig::default(),
+            window_count: 3,
+            consecutive_failures: 0,
+            circuit_open_until_ms: None,
+            counters: crate::rate_limit_do::RateLimitCounters {
+                allowed_total: 3,
+                rejected_rate_total: 1,
+                ..Default::default()
+            },
+        });
+        let json = serde_json::to_value(&m).unwrap();
+        assert_eq!(json["rate_limit"]["counters"]["rejected_rate_total"], 1);
+        assert_eq!(json["rate_limit"]["window_count"], 3);
+    }
+
     #[test]
     fn serialized_shape_keeps_null_reasons_when_nonempty() {
         let inputs = PilotInputs::default();
@@ -42,6 +42,8 @@ pub enum HumanDecisionType {
     Pause,
     Resume,
     Merge,
+    /// Turn down a play approval task outright.
+    Reject,
 }
 
 impl HumanDecisionType {
@@ -55,12 +57,13 @@ impl HumanDecisionType {
             Self::Pause => "pause",
             Self::Resume => "resume",
             Self::Merge => "merge",
+            Self::Reject => "reject",
         }
     }
 }
 
 /// Error returned when [`HumanDecisionType::from_str`] sees a value that
-/// is not one of the five known variants. Kept as a distinct type so
+/// is not one of the six known variants. Kept as a distinct type so
 /// callers can match it directly without stringly-typed comparisons.
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -72,7 +75,7 @@ impl std::fmt::Display for ParseHumanDecisionTypeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "unknown human_decision type {:?}; expected one of approve | request_changes | pause | resume | merge",
+            "unknown human_decision type {:?}; expected one of approve | request_changes | pause | resume | merge | reject",
             self.got
         )
     }
@@ -90,6 +93,7 @@ impl FromStr for HumanDecisionType {
             "pause" => Ok(Self::Pause),
             "resume" => Ok(Self::Resume),
             "merge" => Ok(Self::Merge),
+            "reject" => Ok(Self::Reject),
             other => Err(ParseHumanDecisionTypeError {
                 got: other.to_string(),
             }),
@@ -169,6 +173,7 @@ mod tests {
             HumanDecisionType::Pause,
             HumanDecisionType::Resume,
             HumanDecisionType::Merge,
+            HumanDecisionType::Reject,
         ];
         for variant in all {
             let s = variant.as_str();
@@ -190,6 +195,7 @@ mod tests {
             (HumanDecisionType::Pause, "\"pause\""),
             (HumanDecisionType::Resume, "\"resume\""),
             (HumanDecisionType::Merge, "\"merge\""),
+            (HumanDecisionType::Reject, "\"reject\""),
         ];
         for (variant, expected_json) in cases {
             let json = serde_json::to_string(&variant).expect("serialize");
@@ -57,6 +57,10 @@ pub struct RetrieveMemoryRequest {
     pub include_unsafe: bool,
     #[serde(default)]
     pub include_conflicted: bool,
+    /// Also return anonymized items shared by partner tenants (WS8
+    /// federation). Requires a federation-enabled token.
+    #[serde(default)]
+    pub include_federated: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -85,6 +89,9 @@ pub struct MemoryCandidate {
     pub conflicted: bool,
     pub estimated_tokens: usize,
     pub score: f64,
+    /// Set only on federated candidates: the tenant that shared the item.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_tenant: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -97,6 +104,10 @@ pub struct RetrieveMemoryResponse {
     pub unsafe_filtered: usize,
     pub conflict_filtered: usize,
     pub items: Vec<MemoryCandidate>,
+    /// Anonymized partner-tenant candidates, kept apart from `items` so they
+    /// never count against the caller's own ranking or context budget.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub federated: Vec<MemoryCandidate>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 // ── Agent Task Queue (M1) ───────────────────────────────────────
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CreateAgentTask {
     pub job_id: String,
     pub task_type: String,
@@ -13,6 +13,21 @@ pub struct CreateAgentTask {
     pub play_id: Option<String>,
     pub parent_task_id: Option<String>,
     pub max_retries: Option<i32>,
+    /// RFC 3339 timestamp before which the task cannot be claimed.
+    /// TaskLeaseManager holds it out of the pending queue until its alarm
+    /// finds it due.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<String>,
+    /// Capability requirements the claiming agent must meet, e.g.
+    /// `rust>=1.80`, `repo:stevedores-org/*`, `!agent:builder-7`. See
+    /// [`crate::capability`] for the grammar.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// Client-chosen dedupe key. A repeat submission with the same key
+    /// inside the tenant's idempotency window returns the original task
+    /// instead of creating a new one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -47,12 +62,123 @@ pub struct AgentTask {
     /// as stale (notification suppressed) rather than cross-tenant routed.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tenant_id: Option<String>,
+    /// Unix millis at which TaskLeaseManager first queued the task. Drives
+    /// priority aging; kept across retries so a requeued task does not lose
+    /// the time it already waited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enqueued_at_ms: Option<u64>,
+    /// Every failed attempt so far, oldest first. Travels with the task
+    /// through retries and into the dead-letter store.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<TaskAttemptFailure>,
+    /// Copied from [`CreateAgentTask::not_before`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<String>,
+    /// Set when the task is cancelled while leased. The holder's next
+    /// heartbeat releases the lease and tells it to stop.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cancel_requested: bool,
+    /// Copied from [`CreateAgentTask::requires`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// Copied from [`CreateAgentTask::idempotency_key`]; play tasks use
+    /// their deterministic id so re-materialization cannot duplicate them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// Per-attempt time limit in millis. Unlike the lease, heartbeats do not
+    /// extend it; a timed-out attempt fails like an expired lease.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Unix millis at which the current attempt times out, set on claim
+    /// from `timeout_ms`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<u64>,
+}
+
+/// One failed attempt of an [`AgentTask`]: an explicit `/fail` from the
+/// agent or a lease that expired without a heartbeat.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskAttemptFailure {
+    /// 1-based attempt number (`retry_count + 1` when the attempt failed).
+    pub attempt: i32,
+    pub agent_id: Option<String>,
+    pub error: String,
+    /// Lease deadline the attempt held when it failed.
+    pub lease_expires_at: Option<String>,
+    pub failed_at: String,
+}
+
+/// An agent task that exhausted `max_retries`, parked in the tenant's
+/// TaskLeaseManager until an operator requeues or discards it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeadLetterTask {
+    pub task: AgentTask,
+    pub dead_lettered_at: String,
+}
+
+/// Response of `GET /v1/tasks/dead-letter`, newest first.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DeadLetterList {
+    pub tasks: Vec<DeadLetterTask>,
+    pub next_cursor: Option<String>,
+}
+
+/// Response of `GET /v1/tasks/queue`: a point-in-time view of the tenant's
+/// TaskLeaseManager, for telling why agents are idle.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueueSnapshot {
+    pub pending_total: usize,
+    /// Held back by `not_before`; not claimable yet.
+    pub scheduled_total: usize,
+    pub max_pending: usize,
+    /// Pending tasks grouped by `(task_type, priority)`, highest priority
+    /// first.
+    pub depth: Vec<QueueDepth>,
+    /// Age of the longest-waiting pending task, if any.
+    pub oldest_pending_age_ms: Option<u64>,
+    pub active: Vec<ActiveLease>,
+    /// Enqueues rejected with `QUEUE_FULL` since the DO was created.
+    pub rejected_total: u64,
+    pub dead_letter_total: usize,
+    /// Completion notifications to PlayManager still awaiting a retry.
+    pub pending_notifies: Vec<PendingNotifyInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueueDepth {
+    pub task_type: String,
+    pub priority: i32,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActiveLease {
+    pub task_id: String,
+    pub job_id: String,
+    pub task_type: String,
+    pub agent_id: Option<String>,
+    pub lease_expires_at: Option<String>,
+    #[serde(default)]
+    pub cancel_requested: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PendingNotifyInfo {
+    /// PlayManager DO name (`{tenant_id}:play:{run_id}`).
+    pub target_name: String,
+    pub play_task_id: String,
+    pub attempts: u32,
+    pub next_attempt_at_ms: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct TaskCreated {
     pub id: String,
     pub status: String,
+    /// The `idempotency_key` matched an earlier submission; `id` and
+    /// `status` are the original task's.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub duplicate: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -65,6 +191,118 @@ pub struct TaskFailRequest {
     pub error: String,
 }
 
+/// Response of `POST /mcp/task/:id/heartbeat`. `cancel` means the task was
+/// cancelled: the lease is already released and the agent should stop.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TaskHeartbeatAck {
+    pub ok: bool,
+    #[serde(default)]
+    pub cancel: bool,
+}
+
+// ── Cancellation ────────────────────────────────────────────────
+
+/// Tasks TaskLeaseManager cancelled in one request. Queued tasks are
+/// removed outright; leased ones are only flagged until their agent's next
+/// heartbeat.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct CancelledTasks {
+    pub cancelled: Vec<AgentTask>,
+    pub cancel_requested: Vec<AgentTask>,
+}
+
+/// Response of `POST /v1/tasks/:id/cancel`. `status` is `cancelled` for a
+/// task removed from the queue, `cancel_requested` for a leased one.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TaskCancelled {
+    pub id: String,
+    pub status: String,
+}
+
+/// Response of `POST /v1/runs/:run_id/cancel`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RunCancelled {
+    pub run_id: String,
+    pub status: String,
+    pub cancelled_tasks: Vec<String>,
+    pub cancel_requested_tasks: Vec<String>,
+}
+
+// ── Task Socket (WebSocket claiming) ────────────────────────────
+
+/// Agent → TaskLeaseManager message on `/mcp/task/ws`. Each names the task
+/// the socket currently holds.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskSocketClientMessage {
+    Heartbeat {
+        task_id: String,
+    },
+    Complete {
+        task_id: String,
+        #[serde(default)]
+        result: Option<serde_json::Value>,
+    },
+    Fail {
+        task_id: String,
+        error: String,
+    },
+}
+
+impl TaskSocketClientMessage {
+    pub fn task_id(&self) -> &str {
+        match self {
+            Self::Heartbeat { task_id }
+            | Self::Complete { task_id, .. }
+            | Self::Fail { task_id, .. } => task_id,
+        }
+    }
+}
+
+/// TaskLeaseManager → agent message on `/mcp/task/ws`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskSocketServerMessage {
+    /// A task leased to this socket; heartbeat it like an HTTP claim.
+    Task { task: Box<AgentTask> },
+    /// A heartbeat/complete/fail was applied; `status` is the task's new
+    /// status (`running`, `completed`, `pending` on retry, `failed`).
+    Ack { task_id: String, status: String },
+    Error {
+        task_id: Option<String>,
+        message: String,
+    },
+}
+
+// ── Recurring Task Schedules ────────────────────────────────────
+
+/// Body of `POST /v1/task-schedules`. The scheduled handler enqueues a copy
+/// of `task` every time `cron` (five fields, UTC) fires.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct CreateTaskSchedule {
+    pub name: String,
+    pub cron: String,
+    pub task: CreateAgentTask,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskSchedule {
+    pub id: String,
+    pub name: String,
+    pub cron: String,
+    pub task: CreateAgentTask,
+    /// Next fire time; the first scheduled tick at or after it enqueues
+    /// the task.
+    pub next_run_at: String,
+    pub last_run_at: Option<String>,
+    pub last_task_id: Option<String>,
+    /// Why the most recent fire did not enqueue a task; cleared by the next
+    /// fire that does.
+    #[serde(default)]
+    pub last_error: Option<String>,
+    pub created_at: String,
+}
+
 // ── Agent Registration (M1) ─────────────────────────────────────
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -116,6 +354,120 @@ pub struct CheckpointCreated {
     pub state_r2_key: String,
 }
 
+/// `POST /v1/checkpoints/:id/fork`: start a new thread whose first
+/// checkpoint copies the state of `:id` and has it as parent.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ForkCheckpoint {
+    /// Thread to create; generated when absent. Must have no checkpoints.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    /// Merged into the new checkpoint's metadata, next to `forked_from`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointForked {
+    pub id: String,
+    pub thread_id: String,
+    pub state_r2_key: String,
+    pub forked_from: CheckpointBranch,
+}
+
+/// A checkpoint and the thread it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointBranch {
+    pub thread_id: String,
+    pub checkpoint_id: String,
+}
+
+/// `GET /v1/checkpoints/threads/:thread_id/tree`: every checkpoint of a
+/// thread with its `parent_id` edges, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointTree {
+    pub thread_id: String,
+    /// Checkpoints without a parent in this thread: the thread's first
+    /// checkpoint, or one forked from another thread or whose parent was
+    /// deleted.
+    pub roots: Vec<String>,
+    pub nodes: Vec<CheckpointTreeNode>,
+    /// The thread has more checkpoints, or more forks off them, than were
+    /// listed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointTreeNode {
+    #[serde(flatten)]
+    pub checkpoint: Checkpoint,
+    /// Checkpoints of this thread taken on top of this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<String>,
+    /// Threads forked from this checkpoint, by their first checkpoint.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forks: Vec<CheckpointBranch>,
+}
+
+/// `GET /v1/checkpoints/:id/lineage`: the `parent_id` chain leading to a
+/// checkpoint, across the threads it was forked from.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointLineage {
+    pub checkpoint_id: String,
+    /// Root first, ending with the checkpoint itself.
+    pub path: Vec<Checkpoint>,
+    /// `false` if the chain stops short of a root: a parent was deleted
+    /// or the chain is longer than the walk allows.
+    pub complete: bool,
+}
+
+/// `GET /v1/checkpoints/:id/diff/:other_id`: how the state of `from`
+/// became the state of `to`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointDiff {
+    pub from: String,
+    pub to: String,
+    /// RFC 6902 operations that turn `from`'s state into `to`'s, in
+    /// application order.
+    pub patch: Vec<JsonPatchOp>,
+    pub summary: CheckpointDiffSummary,
+}
+
+/// One RFC 6902 operation. Only the three a structural diff emits.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOp {
+    Add {
+        path: String,
+        value: serde_json::Value,
+    },
+    Remove {
+        path: String,
+    },
+    Replace {
+        path: String,
+        value: serde_json::Value,
+    },
+}
+
+impl JsonPatchOp {
+    pub fn path(&self) -> &str {
+        match self {
+            JsonPatchOp::Add { path, .. }
+            | JsonPatchOp::Remove { path }
+            | JsonPatchOp::Replace { path, .. } => path,
+        }
+    }
+}
+
+/// JSON pointers (RFC 6901) of the keys and array items a diff touched.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointDiffSummary {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
 // ── Graph Events (M3: event pipeline) ───────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -1,10 +1,129 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct PlayDefinition {
     pub name: String,
     pub goal: String,
     pub tasks: Vec<PlayTaskDefinition>,
+    #[serde(default, skip_serializing_if = "PlayErrorPolicy::is_fail_fast")]
+    pub on_error: PlayErrorPolicy,
+    /// Typed launch inputs, referenced from task `params` as
+    /// `{{inputs.<name>}}` (see [`crate::play_template`]).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<String, PlayInputSpec>,
+}
+
+/// Declaration of one launch input. An input without a `default` is
+/// required.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayInputSpec {
+    #[serde(rename = "type")]
+    pub input_type: PlayInputType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayInputType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Object,
+    Array,
+    /// Any JSON value.
+    Any,
+}
+
+impl PlayInputType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlayInputType::String => "string",
+            PlayInputType::Number => "number",
+            PlayInputType::Integer => "integer",
+            PlayInputType::Boolean => "boolean",
+            PlayInputType::Object => "object",
+            PlayInputType::Array => "array",
+            PlayInputType::Any => "any",
+        }
+    }
+
+    pub fn matches(&self, value: &serde_json::Value) -> bool {
+        match self {
+            PlayInputType::String => value.is_string(),
+            PlayInputType::Number => value.is_number(),
+            PlayInputType::Integer => value.is_i64() || value.is_u64(),
+            PlayInputType::Boolean => value.is_boolean(),
+            PlayInputType::Object => value.is_object(),
+            PlayInputType::Array => value.is_array(),
+            PlayInputType::Any => true,
+        }
+    }
+
+    /// Whether template paths can index into a value of this type.
+    pub fn is_container(&self) -> bool {
+        matches!(
+            self,
+            PlayInputType::Object | PlayInputType::Array | PlayInputType::Any
+        )
+    }
+}
+
+/// What a run does when a task fails with no `on_failure` handler.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayErrorPolicy {
+    /// Fail the run at once and cancel its in-flight tasks.
+    #[default]
+    FailFast,
+    /// Keep running every branch that does not depend on the failure; the
+    /// run still ends `failed`.
+    Continue,
+}
+
+impl PlayErrorPolicy {
+    pub fn is_fail_fast(&self) -> bool {
+        *self == PlayErrorPolicy::FailFast
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlayErrorPolicy::FailFast => "fail_fast",
+            PlayErrorPolicy::Continue => "continue",
+        }
+    }
+}
+
+/// Lifecycle of a play run in PlayManager. `running` until it settles into
+/// one of the terminal states.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayStatus {
+    #[default]
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl PlayStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlayStatus::Running => "running",
+            PlayStatus::Succeeded => "succeeded",
+            PlayStatus::Failed => "failed",
+            PlayStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        *self != PlayStatus::Running
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -14,13 +133,708 @@ pub struct PlayTaskDefinition {
     pub priority: i32,
     pub params: Option<serde_json::Value>,
     pub depends_on: Vec<String>,
+    /// Capability requirements copied onto the materialized task.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// Launch only if this holds against a parent's result; otherwise the
+    /// task is skipped, and so are dependents whose parents were all
+    /// skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<PlayCondition>,
+    /// Fan out one child task per element of an array in a parent's
+    /// result. The task completes, with the children's results in order,
+    /// once every child has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<PlayMap>,
+    /// Join a `map` task: its children's results are passed in as
+    /// `params.results`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reduce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<PlayRetryPolicy>,
+    /// Per-attempt time limit, enforced by TaskLeaseManager whatever the
+    /// agent's heartbeats. A timed-out attempt counts against `retry`.
+    /// For an `approval` task, how long to wait for a decision before
+    /// `approval.on_timeout` applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Task launched, with `params.failure`, if this one fails for good.
+    /// It is only ever launched that way, never by its own `depends_on`.
+    /// A failure with no handler fails the whole run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+    /// Who may decide an `approval` task and what happens when nobody
+    /// does in time. Only valid on `approval` tasks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<PlayApprovalPolicy>,
+}
+
+/// `task_type` of a human approval gate. PlayManager holds the task open
+/// instead of enqueueing it until a human approves, requests changes or
+/// rejects (`POST /v1/plays/runs/:run_id/approvals/:task_id`). Approval
+/// completes the task with the decision as its result; the other two
+/// fail it, so its `on_failure` handler (or the run's error policy)
+/// takes over.
+pub const APPROVAL_TASK_TYPE: &str = "approval";
+
+impl PlayTaskDefinition {
+    pub fn is_approval(&self) -> bool {
+        self.task_type == APPROVAL_TASK_TYPE
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct PlayApprovalPolicy {
+    /// People allowed to decide, as `human:<sub>` for the `sub` claim of
+    /// their token, e.g. `human:jane`; empty for anyone whose token has a
+    /// `sub`. Service tokens can never decide.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approvers: Vec<String>,
+    #[serde(default)]
+    pub on_timeout: PlayApprovalTimeout,
+}
+
+/// What happens to an approval task still undecided after `timeout_secs`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayApprovalTimeout {
+    /// Open a policy escalation for the gate and keep waiting.
+    #[default]
+    Escalate,
+    /// Fail the task as if it had been rejected.
+    Reject,
+}
+
+/// A test on one parent's result.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayCondition {
+    /// Parent task whose result is tested; must be in `depends_on`.
+    pub task: String,
+    /// JSON pointer into the result; empty for the whole result.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub path: String,
+    #[serde(flatten)]
+    pub test: PlayConditionTest,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayConditionTest {
+    Equals(serde_json::Value),
+    NotEquals(serde_json::Value),
+    /// Whether the pointed-at value is present and not `null`.
+    Exists(bool),
+}
+
+impl PlayCondition {
+    /// Evaluate against the parent's result (`None` if it returned none).
+    pub fn holds(&self, result: Option<&serde_json::Value>) -> bool {
+        let value = result.and_then(|r| r.pointer(&self.path));
+        match &self.test {
+            PlayConditionTest::Equals(want) => value == Some(want),
+            PlayConditionTest::NotEquals(want) => value != Some(want),
+            PlayConditionTest::Exists(want) => value.is_some_and(|v| !v.is_null()) == *want,
+        }
+    }
+}
+
+/// Where a `map` task finds its items.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayMap {
+    /// Parent task whose result holds the array; must be in `depends_on`.
+    pub from: String,
+    /// JSON pointer to the array; empty for the whole result. An array of
+    /// more than 256 items fails the map.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayRetryPolicy {
+    /// Retries after the first attempt before the task fails.
+    pub max_retries: i32,
+}
+
+impl PlayDefinition {
+    /// Check that task ids are unique and every reference points at a
+    /// task it can actually see: `when`, `map` and `reduce` name one of
+    /// the task's own parents, `reduce` names a `map` task,
+    /// `on_failure` names another task, and `approval` settings sit on
+    /// approval tasks only.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let mut ids = HashSet::new();
+        for t in &self.tasks {
+            if t.id.is_empty() || t.id.contains('.') {
+                return Err(format!("task id '{}' must be non-empty without '.'", t.id));
+            }
+            if !ids.insert(t.id.as_str()) {
+                return Err(format!("duplicate task id '{}'", t.id));
+            }
+        }
+        let find = |id: &str| self.tasks.iter().find(|t| t.id == id);
+        for t in &self.tasks {
+            for dep in &t.depends_on {
+                if find(dep).is_none() || dep == &t.id {
+                    return Err(format!("task '{}' depends on unknown task '{dep}'", t.id));
+                }
+            }
+            let parent = |field: &str, id: &str| {
+                if t.depends_on.iter().any(|d| d == id) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "task '{}': {field} task '{id}' must be in depends_on",
+                        t.id
+                    ))
+                }
+            };
+            if let Some(when) = &t.when {
+                parent("when", &when.task)?;
+            }
+            if let Some(map) = &t.map {
+                parent("map", &map.from)?;
+            }
+            if let Some(reduce) = &t.reduce {
+                parent("reduce", reduce)?;
+                if find(reduce).is_none_or(|r| r.map.is_none()) {
+                    return Err(format!(
+                        "task '{}': reduce task '{reduce}' is not a map task",
+                        t.id
+                    ));
+                }
+            }
+            if t.retry.as_ref().is_some_and(|r| r.max_retries < 0) {
+                return Err(format!("task '{}': retry.max_retries must be >= 0", t.id));
+            }
+            if t.timeout_secs == Some(0) {
+                return Err(format!("task '{}': timeout_secs must be > 0", t.id));
+            }
+            if let Some(handler) = &t.on_failure {
+                if find(handler).is_none() || handler == &t.id {
+                    return Err(format!(
+                        "task '{}': on_failure task '{handler}' is unknown",
+                        t.id
+                    ));
+                }
+            }
+            if t.is_approval() {
+                if t.map.is_some() || t.retry.is_some() {
+                    return Err(format!(
+                        "task '{}': approval tasks cannot have map or retry",
+                        t.id
+                    ));
+                }
+            } else if t.approval.is_some() {
+                return Err(format!(
+                    "task '{}': approval is only valid on task_type '{APPROVAL_TASK_TYPE}'",
+                    t.id
+                ));
+            }
+            if let Some(approver) = t
+                .approval
+                .iter()
+                .flat_map(|a| &a.approvers)
+                .find(|a| a.strip_prefix("human:").is_none_or(str::is_empty))
+            {
+                return Err(format!(
+                    "task '{}': approver '{approver}' must be a human:<sub> actor",
+                    t.id
+                ));
+            }
+        }
+        if let Some(id) = self.find_cycle() {
+            return Err(format!("task '{id}' is part of a depends_on cycle"));
+        }
+        for (name, spec) in &self.inputs {
+            if let Some(default) = &spec.default {
+                if !spec.input_type.matches(default) {
+                    return Err(format!(
+                        "input '{name}': default is not of type {}",
+                        spec.input_type.as_str()
+                    ));
+                }
+            }
+        }
+        crate::play_template::check(self)
+    }
+
+    /// Check launch `provided` inputs against [`PlayDefinition::inputs`]
+    /// and fill in defaults. Unknown, missing and mistyped inputs are
+    /// errors.
+    pub fn resolve_inputs(
+        &self,
+        provided: &BTreeMap<String, serde_json::Value>,
+    ) -> std::result::Result<BTreeMap<String, serde_json::Value>, String> {
+        if let Some(name) = provided.keys().find(|k| !self.inputs.contains_key(*k)) {
+            return Err(format!("unknown input '{name}'"));
+        }
+        let mut resolved = BTreeMap::new();
+        for (name, spec) in &self.inputs {
+            let value = match (provided.get(name), &spec.default) {
+                (Some(v), _) => v,
+                (None, Some(default)) => default,
+                (None, None) => return Err(format!("missing required input '{name}'")),
+            };
+            if !spec.input_type.matches(value) {
+                return Err(format!(
+                    "input '{name}' must be of type {}",
+                    spec.input_type.as_str()
+                ));
+            }
+            resolved.insert(name.clone(), value.clone());
+        }
+        Ok(resolved)
+    }
+
+    /// A task on a `depends_on` cycle, if there is one. Kahn's algorithm
+    /// leaves the tasks on or behind a cycle unordered; following unordered
+    /// parents from any of them ends up going round the cycle.
+    fn find_cycle(&self) -> Option<&str> {
+        let mut pending: HashMap<&str, usize> = self
+            .tasks
+            .iter()
+            .map(|t| (t.id.as_str(), t.depends_on.len()))
+            .collect();
+        let mut ready: Vec<&str> = pending
+            .iter()
+            .filter(|(_, n)| **n == 0)
+            .map(|(id, _)| *id)
+            .collect();
+        while let Some(id) = ready.pop() {
+            pending.remove(id);
+            for t in &self.tasks {
+                let deps = t.depends_on.iter().filter(|d| *d == id).count();
+                if let Some(n) = pending.get_mut(t.id.as_str()).filter(|_| deps > 0) {
+                    *n -= deps;
+                    if *n == 0 {
+                        ready.push(t.id.as_str());
+                    }
+                }
+            }
+        }
+        let mut id = self
+            .tasks
+            .iter()
+            .map(|t| t.id.as_str())
+            .find(|id| pending.contains_key(id))?;
+        for _ in 0..pending.len() {
+            let task = self.tasks.iter().find(|t| t.id == id)?;
+            id = task
+                .depends_on
+                .iter()
+                .map(String::as_str)
+                .find(|d| pending.contains_key(d))?;
+        }
+        Some(id)
+    }
+}
+
+/// Check the name of a play created through `POST /v1/plays`. `runs` is
+/// taken by `/v1/plays/runs/:run_id`.
+pub fn validate_play_name(name: &str) -> std::result::Result<(), String> {
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if name.is_empty() || name.len() > 64 || !valid_chars {
+        return Err(format!(
+            "play name '{name}' must be 1-64 characters of [A-Za-z0-9_-]"
+        ));
+    }
+    if name == "runs" {
+        return Err("play name 'runs' is reserved".to_string());
+    }
+    Ok(())
+}
+
+/// Semantic version of a stored play definition, `MAJOR.MINOR.PATCH`.
+/// Each update stores a new version, strictly greater than the last.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[serde(try_from = "String", into = "String")]
+pub struct PlayVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl PlayVersion {
+    /// Version of a newly created play unless the request names one, and
+    /// of every play that predates versioning.
+    pub const INITIAL: PlayVersion = PlayVersion {
+        major: 1,
+        minor: 0,
+        patch: 0,
+    };
+
+    pub fn parse(s: &str) -> std::result::Result<Self, String> {
+        let invalid = || format!("invalid version '{s}': expected MAJOR.MINOR.PATCH");
+        let mut parts = s.split('.').map(|p| {
+            let leading_zero = p.len() > 1 && p.starts_with('0');
+            if leading_zero || !p.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            p.parse::<u32>().ok()
+        });
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) => Ok(PlayVersion {
+                major,
+                minor,
+                patch,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn bump(self, bump: PlayVersionBump) -> Self {
+        match bump {
+            PlayVersionBump::Major => PlayVersion {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            PlayVersionBump::Minor => PlayVersion {
+                minor: self.minor + 1,
+                patch: 0,
+                ..self
+            },
+            PlayVersionBump::Patch => PlayVersion {
+                patch: self.patch + 1,
+                ..self
+            },
+        }
+    }
+}
+
+impl fmt::Display for PlayVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl TryFrom<String> for PlayVersion {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        PlayVersion::parse(&s)
+    }
+}
+
+impl From<PlayVersion> for String {
+    fn from(v: PlayVersion) -> String {
+        v.to_string()
+    }
+}
+
+/// Which part of the version `PUT /v1/plays/:name` increments when the
+/// request does not name a version.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayVersionBump {
+    Major,
+    Minor,
+    #[default]
+    Patch,
+}
+
+/// `POST /v1/plays` body.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CreatePlayDefinition {
+    #[serde(flatten)]
+    pub definition: PlayDefinition,
+    /// Defaults to [`PlayVersion::INITIAL`].
+    #[serde(default)]
+    pub version: Option<PlayVersion>,
+}
+
+/// `PUT /v1/plays/:name` body: the full new definition. Without an
+/// explicit `version`, the current one is bumped by `bump`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct UpdatePlayDefinition {
+    pub goal: String,
+    pub tasks: Vec<PlayTaskDefinition>,
+    #[serde(default)]
+    pub on_error: PlayErrorPolicy,
+    #[serde(default)]
+    pub inputs: BTreeMap<String, PlayInputSpec>,
+    #[serde(default)]
+    pub version: Option<PlayVersion>,
+    #[serde(default)]
+    pub bump: PlayVersionBump,
+}
+
+impl UpdatePlayDefinition {
+    /// The version to store this update as, given the play's `current`
+    /// one. An explicit version must move forward.
+    pub fn next_version(&self, current: PlayVersion) -> std::result::Result<PlayVersion, String> {
+        match self.version {
+            Some(v) if v <= current => Err(format!(
+                "version {v} must be greater than the current version {current}"
+            )),
+            Some(v) => Ok(v),
+            None => Ok(current.bump(self.bump)),
+        }
+    }
+
+    pub fn into_definition(self, name: String) -> PlayDefinition {
+        PlayDefinition {
+            name,
+            goal: self.goal,
+            tasks: self.tasks,
+            on_error: self.on_error,
+            inputs: self.inputs,
+        }
+    }
+}
+
+/// One version of a play definition as stored.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct StoredPlayDefinition {
+    pub version: PlayVersion,
+    pub created_at: String,
+    #[serde(flatten)]
+    pub definition: PlayDefinition,
+}
+
+/// `GET /v1/plays` entry: a play at its latest version.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlaySummary {
+    pub name: String,
+    pub goal: String,
+    pub version: PlayVersion,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayVersionInfo {
+    pub version: PlayVersion,
+    pub created_at: String,
+}
+
+/// `GET /v1/plays/:name/diff?from=&to=`: what changed between two
+/// versions of a play. Tasks are matched by id.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayDiff {
+    pub name: String,
+    pub from: PlayVersion,
+    pub to: PlayVersion,
+    /// Play-level fields (`goal`, `on_error`, `inputs`) that changed.
+    pub changes: BTreeMap<String, PlayFieldChange>,
+    pub added_tasks: Vec<String>,
+    pub removed_tasks: Vec<String>,
+    pub changed_tasks: Vec<PlayTaskChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayFieldChange {
+    pub from: serde_json::Value,
+    pub to: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayTaskChange {
+    pub id: String,
+    pub changes: BTreeMap<String, PlayFieldChange>,
+}
+
+impl PlayDiff {
+    pub fn between(from: &StoredPlayDefinition, to: &StoredPlayDefinition) -> Self {
+        let play_fields = |d: &PlayDefinition| serde_json::json!({ "goal": d.goal, "on_error": d.on_error, "inputs": d.inputs });
+        let changes = field_changes(&play_fields(&from.definition), &play_fields(&to.definition));
+        let old: HashMap<&str, &PlayTaskDefinition> = from
+            .definition
+            .tasks
+            .iter()
+            .map(|t| (t.id.as_str(), t))
+            .collect();
+        let new_ids: HashSet<&str> = to.definition.tasks.iter().map(|t| t.id.as_str()).collect();
+
+        let mut added_tasks = Vec::new();
+        let mut changed_tasks = Vec::new();
+        for task in &to.definition.tasks {
+            match old.get(task.id.as_str()) {
+                None => added_tasks.push(task.id.clone()),
+                Some(prev) => {
+                    let to_value =
+                        |t: &PlayTaskDefinition| serde_json::to_value(t).unwrap_or_default();
+                    let changes = field_changes(&to_value(prev), &to_value(task));
+                    if !changes.is_empty() {
+                        changed_tasks.push(PlayTaskChange {
+                            id: task.id.clone(),
+                            changes,
+                        });
+                    }
+                }
+            }
+        }
+        let removed_tasks = from
+            .definition
+            .tasks
+            .iter()
+            .filter(|t| !new_ids.contains(t.id.as_str()))
+            .map(|t| t.id.clone())
+            .collect();
+        PlayDiff {
+            name: to.definition.name.clone(),
+            from: from.version,
+            to: to.version,
+            changes,
+            added_tasks,
+            removed_tasks,
+            changed_tasks,
+        }
+    }
+}
+
+/// Top-level keys of two JSON objects whose values differ. A key missing
+/// on one side (a skipped default) compares as `null`.
+fn field_changes(
+    from: &serde_json::Value,
+    to: &serde_json::Value,
+) -> BTreeMap<String, PlayFieldChange> {
+    let empty = serde_json::Map::new();
+    let from = from.as_object().unwrap_or(&empty);
+    let to = to.as_object().unwrap_or(&empty);
+    let keys: std::collections::BTreeSet<&String> = from.keys().chain(to.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let a = from.get(key).cloned().unwrap_or_default();
+            let b = to.get(key).cloned().unwrap_or_default();
+            (a != b).then(|| (key.clone(), PlayFieldChange { from: a, to: b }))
+        })
+        .collect()
+}
+
+/// Final outcome of a play task, sent by TaskLeaseManager to the run's
+/// PlayManager once the task completes or fails for good.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayTaskOutcome {
+    /// Play-side task id (the AgentTask id minus its `{run_id}-` prefix).
+    pub task_id: String,
+    /// `completed` or `failed`.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Agent that ran the last attempt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+}
+
+/// `GET /v1/plays/runs/:run_id`: a play run's progress, read from its
+/// PlayManager.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayRunStatus {
+    pub run_id: String,
+    pub play: String,
+    pub status: PlayStatus,
+    /// First failure without an `on_failure` handler, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_task: Option<String>,
+    /// `metadata` from the launch request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    /// Every task of the run, parents first. Expanded `map` tasks are
+    /// preceded by their children (`{map}.{i}`).
+    pub tasks: Vec<PlayRunTask>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayRunTask {
+    pub id: String,
+    pub task_type: String,
+    /// `pending`, `running`, `awaiting_approval`, `completed`, `failed`,
+    /// `skipped` or `cancelled`.
+    pub state: String,
+    /// Edges into this task as the run executes them: an expanded `map`
+    /// task depends on its children, which take the map's own parents.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launched_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// API path serving the task's result, when it returned one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_ref: Option<String>,
+    /// Set on approval tasks once they have been opened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<PlayApprovalState>,
+}
+
+/// `POST /v1/plays/runs/:run_id/approvals/:task_id`: a decision on an
+/// approval task. The deciding actor is `human:<sub>` from the verified
+/// token, never a body field. Recorded in the `human_decision` projection too; that route is the
+/// only thing that resolves the gate, and a `human_decision` row written any
+/// other way does not.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayApprovalRequest {
+    /// `approve`, `request_changes` or `reject`.
+    pub decision: crate::models::HumanDecisionType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl PlayApprovalRequest {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        use crate::models::HumanDecisionType as D;
+        match self.decision {
+            D::Approve | D::RequestChanges | D::Reject => Ok(()),
+            other => Err(format!(
+                "decision '{}' must be approve, request_changes or reject",
+                other.as_str()
+            )),
+        }
+    }
+}
+
+/// Where an approval task stands, in [`PlayRunTask::approval`].
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayApprovalState {
+    pub requested_at: String,
+    /// When `approval.on_timeout` applies, if the task has a timeout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<String>,
+    /// Policy escalation opened when the task timed out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation_id: Option<String>,
+    /// The task's rendered params, for the approver to review.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<PlayApprovalDecision>,
+}
+
+/// A recorded decision on an approval task; an approved task's result.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlayApprovalDecision {
+    /// Id of the `human_decision` row.
+    pub id: String,
+    pub actor: String,
+    pub decision: crate::models::HumanDecisionType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub decided_at: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct PlayLaunchRequest {
     pub play_name: String,
     pub job_id: Option<String>,
+    /// Opaque caller data, stored with the run and returned by
+    /// `GET /v1/plays/runs/:run_id`.
     pub metadata: Option<serde_json::Value>,
+    /// Launch this stored version instead of the latest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<PlayVersion>,
+    /// Values for the play's declared [`PlayDefinition::inputs`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<String, serde_json::Value>,
 }
 
 #[allow(dead_code)]
@@ -177,6 +177,24 @@ pub struct TenantProvisionRequest {
     pub plan: String,
     #[serde(default)]
     pub quota_runs_per_minute: i64,
+    /// Edge API rate limit (requests/minute). Omitted keeps the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_requests_per_minute: Option<u64>,
+    /// Extra requests allowed above the sustained rate within one window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_burst_limit: Option<u64>,
+    /// Ingest secret handling for a new tenant: `reject`, `redact` or
+    /// `flag` (`redact` when omitted). Ignored when re-provisioning; an
+    /// existing tenant changes it through `PUT /v1/tenants/:tenant_id/settings`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_scan_mode: Option<String>,
+}
+
+/// `PUT /v1/tenants/:tenant_id/settings` request and response.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TenantSettings {
+    /// Ingest secret handling: `reject`, `redact` or `flag`.
+    pub secret_scan_mode: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -186,6 +204,212 @@ pub struct TenantProvisionResponse {
     pub provisioned_in_ms: i64,
 }
 
+// ── WS8: Cross-tenant federation ───────────────────────────────
+
+/// Gold run summary as seen by a partner tenant: the run id and actor
+/// identities are stripped, only aggregate shape is shared.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FederatedRunSummary {
+    pub source_tenant: String,
+    pub event_count: i32,
+    pub first_event_at: Option<String>,
+    pub last_event_at: Option<String>,
+    pub event_types: Vec<String>,
+    pub updated_at: String,
+}
+
+// ── WS8: Tenant lifecycle (suspend / resume / export / delete) ──
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct TenantSuspendRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TenantLifecycleResponse {
+    pub tenant_id: String,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub updated_at: String,
+}
+
+/// One D1 table written into a tenant export archive.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportedTable {
+    pub table: String,
+    pub rows: usize,
+    /// R2 key of the `{table}.json` file inside the archive.
+    pub key: String,
+    /// Hex SHA-256 of the file body, so the archive can be checked offline.
+    pub sha256: String,
+}
+
+/// One R2 object copied into a tenant export archive.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportedObject {
+    pub source_key: String,
+    pub archive_key: String,
+    pub size: u64,
+    /// Copied still sealed; decrypt with the wrapped data keys exported in
+    /// `d1/tenant_data_keys.json` and the master key that wraps them.
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+/// Manifest stored as `manifest.json` at the root of an export archive and
+/// returned by `POST /v1/tenants/:tenant_id/export` and each
+/// `POST /v1/tenants/:tenant_id/export/:export_id` that continues it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TenantExportManifest {
+    pub tenant_id: String,
+    pub export_id: String,
+    pub archive_prefix: String,
+    pub created_at: String,
+    pub tables: Vec<ExportedTable>,
+    pub objects: Vec<ExportedObject>,
+    /// Legacy R2 prefixes not exported because they overlap another
+    /// layout (see `tenant_lifecycle::legacy_r2_prefixes`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_prefixes: Vec<String>,
+    /// `false` while R2 objects are still to be copied: `objects` lists
+    /// those copied so far, and the export is continued by POSTing to
+    /// `/v1/tenants/:tenant_id/export/:export_id` until this is `true`.
+    #[serde(default)]
+    pub complete: bool,
+}
+
+/// Per-table outcome of a tenant hard-delete. `rows_remaining` is a
+/// re-count taken after the DELETE, not derived from `rows_deleted`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TableDeletion {
+    pub table: String,
+    pub rows_deleted: usize,
+    pub rows_remaining: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Deletion report for `DELETE /v1/tenants/:tenant_id`. `digest` is the hex
+/// SHA-256 of the report serialized with an empty `digest`, so a stored copy
+/// can be re-verified with `tenant_lifecycle::report_digest`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TenantDeletionReport {
+    pub tenant_id: String,
+    pub report_id: String,
+    pub deleted_at: String,
+    pub tables: Vec<TableDeletion>,
+    pub r2_prefix: String,
+    /// Pre-`r2_prefix` key layouts purged alongside `r2_prefix`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub legacy_prefixes: Vec<String>,
+    /// Legacy layouts left alone because they overlap another tenant's
+    /// keys; any entry here keeps `verified` false.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_prefixes: Vec<String>,
+    pub objects_deleted: usize,
+    pub objects_remaining: usize,
+    /// Task shards, rate limiter, play runs and threads whose Durable
+    /// Object storage was emptied.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub durable_objects_purged: usize,
+    /// Memory ids removed from the Vectorize index.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub vectors_deleted: usize,
+    /// True only when every table and every R2 prefix re-counted to zero
+    /// and no prefix was skipped.
+    pub verified: bool,
+    pub digest: String,
+}
+
+// ── WS8: Audit log ─────────────────────────────────────────────
+
+/// One row of the per-tenant audit chain. `hash` is the hex SHA-256 of the
+/// row with `hash` itself blanked (see `audit::record_hash`), and
+/// `prev_hash` is the previous row's `hash`, so editing or removing any row
+/// breaks every link after it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditRecord {
+    pub tenant_id: String,
+    pub seq: i64,
+    pub id: String,
+    pub actor: String,
+    pub method: String,
+    /// Router template (`/v1/runs/:run_id/pause`), or the raw path when no
+    /// route matched.
+    pub route: String,
+    pub path: String,
+    /// `:param` values from the path, plus the created `id` from the
+    /// response body when the route has none.
+    pub target_ids: std::collections::BTreeMap<String, String>,
+    pub status: u16,
+    /// `success`, `failure` or `denied`.
+    pub outcome: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    pub created_at: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// An audit entry the router could not append to the chain.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditAppendFailure {
+    pub id: String,
+    pub tenant_id: String,
+    pub actor: String,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub error: String,
+    pub created_at: String,
+}
+
+/// A break found by `GET /v1/audit/verify`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditChainIssue {
+    /// 0 for `append_failed`, which has no row in the chain.
+    pub seq: i64,
+    /// `gap`, `broken_link`, `hash_mismatch` or `append_failed`.
+    pub kind: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditVerifyResponse {
+    pub tenant_id: String,
+    pub verified: bool,
+    pub records_checked: usize,
+    pub head_seq: i64,
+    pub head_hash: String,
+    /// `false` when rows remain past `head_seq`: continue with
+    /// `?after_seq={head_seq}&after_hash={head_hash}`.
+    pub complete: bool,
+    pub issues: Vec<AuditChainIssue>,
+}
+
+// ── WS8: Envelope encryption key rotation ──────────────────────
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct RotateDataKeysRequest {
+    /// Also retire the active data key so new writes use a fresh one.
+    #[serde(default)]
+    pub new_data_key: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RotateDataKeysResponse {
+    pub tenant_id: String,
+    /// Master key every data key is now wrapped under.
+    pub master_key_id: String,
+    pub rewrapped: usize,
+    pub active_data_key_id: String,
+    pub data_key_rotated: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct PutPolicyDefinitionRequest {
     pub bundle: serde_json::Value,
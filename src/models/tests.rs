@@ -470,9 +470,11 @@ fn agent_task_created_serializes() {
     let tc = TaskCreated {
         id: "abc".into(),
         status: "pending".into(),
+        duplicate: false,
     };
     let json = serde_json::to_value(&tc).unwrap();
     assert_eq!(json["status"], "pending");
+    assert!(json.get("duplicate").is_none());
 }
 
 #[test]
@@ -1315,6 +1317,14 @@ fn agent_task_with_memory_context_none() {
         completed_at: None,
         memory_context: None,
         tenant_id: None,
+        enqueued_at_ms: None,
+        failures: Vec::new(),
+        not_before: None,
+        cancel_requested: false,
+        requires: Vec::new(),
+        idempotency_key: None,
+        timeout_ms: None,
+        deadline_ms: None,
     };
     let json = serde_json::to_string(&task).unwrap();
     let parsed: AgentTask = serde_json::from_str(&json).unwrap();
@@ -1350,6 +1360,14 @@ fn agent_task_with_memory_context_some() {
             "## Memory: Past Experience\n- Fixed similar build issue with cargo cache".into(),
         ),
         tenant_id: None,
+        enqueued_at_ms: None,
+        failures: Vec::new(),
+        not_before: None,
+        cancel_requested: false,
+        requires: Vec::new(),
+        idempotency_key: None,
+        timeout_ms: None,
+        deadline_ms: None,
     };
     let json = serde_json::to_string(&task).unwrap();
     let parsed: AgentTask = serde_json::from_str(&json).unwrap();
@@ -1385,6 +1403,14 @@ fn agent_task_memory_context_round_trip() {
             "## Memory Context\nPrevious analysis on similar codebase. Use AST traversal.".into(),
         ),
         tenant_id: None,
+        enqueued_at_ms: None,
+        failures: Vec::new(),
+        not_before: None,
+        cancel_requested: false,
+        requires: Vec::new(),
+        idempotency_key: None,
+        timeout_ms: None,
+        deadline_ms: None,
     };
     let json = serde_json::to_string(&task).unwrap();
     let parsed: AgentTask = serde_json::from_str(&json).unwrap();
@@ -1752,3 +1778,548 @@ fn checkpoint_created_round_trips_documented_shape() {
     let parsed: CheckpointCreated = serde_json::from_str(&json).unwrap();
     assert_eq!(original, parsed);
 }
+
+/// The task socket protocol is `type`-tagged JSON; agents written against
+/// the documented wire shape must parse into the right variant.
+#[test]
+fn task_socket_messages_use_type_tag() {
+    let msg: TaskSocketClientMessage =
+        serde_json::from_str(r#"{"type":"complete","task_id":"t1","result":{"ok":true}}"#).unwrap();
+    assert_eq!(msg.task_id(), "t1");
+    assert!(matches!(
+        msg,
+        TaskSocketClientMessage::Complete {
+            result: Some(_),
+            ..
+        }
+    ));
+
+    let msg: TaskSocketClientMessage =
+        serde_json::from_str(r#"{"type":"heartbeat","task_id":"t2"}"#).unwrap();
+    assert_eq!(
+        msg,
+        TaskSocketClientMessage::Heartbeat {
+            task_id: "t2".into()
+        }
+    );
+
+    let ack = TaskSocketServerMessage::Ack {
+        task_id: "t1".into(),
+        status: "completed".into(),
+    };
+    let json = serde_json::to_value(&ack).unwrap();
+    assert_eq!(json["type"], "ack");
+    assert_eq!(json["status"], "completed");
+}
+
+// ── Play DAG definitions ────────────────────────────────────────
+
+fn play_task(id: &str, deps: &[&str]) -> PlayTaskDefinition {
+    PlayTaskDefinition {
+        id: id.into(),
+        task_type: "t".into(),
+        priority: 0,
+        params: None,
+        depends_on: deps.iter().map(|d| d.to_string()).collect(),
+        requires: Vec::new(),
+        when: None,
+        map: None,
+        reduce: None,
+        retry: None,
+        timeout_secs: None,
+        on_failure: None,
+        approval: None,
+    }
+}
+
+fn play(tasks: Vec<PlayTaskDefinition>) -> PlayDefinition {
+    PlayDefinition {
+        name: "p".into(),
+        goal: "g".into(),
+        tasks,
+        on_error: PlayErrorPolicy::FailFast,
+        inputs: Default::default(),
+    }
+}
+
+#[test]
+fn play_task_definition_parses_dag_fields() {
+    let input = r#"{
+        "id": "scan", "task_type": "scan", "priority": 1, "params": null,
+        "depends_on": ["list"],
+        "when": {"task": "list", "path": "/count", "not_equals": 0},
+        "map": {"from": "list", "path": "/hosts"},
+        "retry": {"max_retries": 5},
+        "timeout_secs": 120,
+        "on_failure": "cleanup"
+    }"#;
+    let parsed: PlayTaskDefinition = serde_json::from_str(input).unwrap();
+    let when = parsed.when.as_ref().unwrap();
+    assert_eq!(
+        when.test,
+        PlayConditionTest::NotEquals(serde_json::json!(0))
+    );
+    assert_eq!(parsed.map.as_ref().unwrap().path, "/hosts");
+    assert_eq!(parsed.retry.as_ref().unwrap().max_retries, 5);
+    assert_eq!(parsed.timeout_secs, Some(120));
+
+    let json = serde_json::to_string(&parsed).unwrap();
+    let round: PlayTaskDefinition = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, round);
+}
+
+#[test]
+fn play_task_definition_without_dag_fields_serializes_as_before() {
+    let json = serde_json::to_value(play_task("a", &[])).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "id": "a", "task_type": "t", "priority": 0, "params": null, "depends_on": []
+        })
+    );
+}
+
+#[test]
+fn play_condition_tests_pointer_into_result() {
+    let result = serde_json::json!({"status": "ok", "err": null});
+    let cond = |path: &str, test| PlayCondition {
+        task: "a".into(),
+        path: path.into(),
+        test,
+    };
+    let ok = serde_json::json!("ok");
+    assert!(cond("/status", PlayConditionTest::Equals(ok.clone())).holds(Some(&result)));
+    assert!(!cond("/status", PlayConditionTest::NotEquals(ok.clone())).holds(Some(&result)));
+    assert!(cond("/missing", PlayConditionTest::NotEquals(ok)).holds(Some(&result)));
+    assert!(cond("/status", PlayConditionTest::Exists(true)).holds(Some(&result)));
+    assert!(cond("/err", PlayConditionTest::Exists(false)).holds(Some(&result)));
+    assert!(cond("", PlayConditionTest::Exists(false)).holds(None));
+}
+
+#[test]
+fn play_definition_validate_accepts_well_formed_dag() {
+    let def = play(vec![
+        play_task("list", &[]),
+        PlayTaskDefinition {
+            map: Some(PlayMap {
+                from: "list".into(),
+                path: String::new(),
+            }),
+            on_failure: Some("cleanup".into()),
+            ..play_task("scan", &["list"])
+        },
+        PlayTaskDefinition {
+            reduce: Some("scan".into()),
+            ..play_task("sum", &["scan"])
+        },
+        play_task("cleanup", &[]),
+    ]);
+    assert_eq!(def.validate(), Ok(()));
+}
+
+#[test]
+fn play_definition_validate_rejects_bad_references() {
+    let cases = vec![
+        play(vec![play_task("a", &[]), play_task("a", &[])]),
+        play(vec![play_task("a.0", &[])]),
+        play(vec![play_task("a", &["nope"])]),
+        play(vec![
+            play_task("a", &[]),
+            PlayTaskDefinition {
+                when: Some(PlayCondition {
+                    task: "a".into(),
+                    path: String::new(),
+                    test: PlayConditionTest::Exists(true),
+                }),
+                ..play_task("b", &[])
+            },
+        ]),
+        play(vec![
+            play_task("a", &[]),
+            PlayTaskDefinition {
+                reduce: Some("a".into()),
+                ..play_task("b", &["a"])
+            },
+        ]),
+        play(vec![PlayTaskDefinition {
+            on_failure: Some("missing".into()),
+            ..play_task("a", &[])
+        }]),
+        play(vec![PlayTaskDefinition {
+            timeout_secs: Some(0),
+            ..play_task("a", &[])
+        }]),
+    ];
+    for def in cases {
+        assert!(def.validate().is_err(), "{def:?}");
+    }
+}
+
+#[test]
+fn play_task_outcome_omits_absent_fields() {
+    let outcome = PlayTaskOutcome {
+        task_id: "a".into(),
+        status: "completed".into(),
+        result: None,
+        error: None,
+        agent_id: None,
+    };
+    let json = serde_json::to_string(&outcome).unwrap();
+    assert_eq!(json, r#"{"task_id":"a","status":"completed"}"#);
+}
+
+#[test]
+fn play_definition_on_error_defaults_to_fail_fast() {
+    let parsed: PlayDefinition =
+        serde_json::from_str(r#"{"name":"p","goal":"g","tasks":[]}"#).unwrap();
+    assert_eq!(parsed.on_error, PlayErrorPolicy::FailFast);
+    assert!(!serde_json::to_string(&parsed).unwrap().contains("on_error"));
+
+    let parsed: PlayDefinition =
+        serde_json::from_str(r#"{"name":"p","goal":"g","tasks":[],"on_error":"continue"}"#)
+            .unwrap();
+    assert_eq!(parsed.on_error, PlayErrorPolicy::Continue);
+}
+
+#[test]
+fn play_status_as_str_matches_serde() {
+    for status in [
+        PlayStatus::Running,
+        PlayStatus::Succeeded,
+        PlayStatus::Failed,
+        PlayStatus::Cancelled,
+    ] {
+        assert_eq!(
+            serde_json::to_value(status).unwrap(),
+            serde_json::json!(status.as_str())
+        );
+    }
+    assert!(!PlayStatus::Running.is_terminal());
+    assert!(PlayStatus::Failed.is_terminal());
+}
+
+// ── Play definition versioning ──────────────────────────────────
+
+fn version(s: &str) -> PlayVersion {
+    PlayVersion::parse(s).unwrap()
+}
+
+fn stored(v: &str, def: PlayDefinition) -> StoredPlayDefinition {
+    StoredPlayDefinition {
+        version: version(v),
+        created_at: "2026-01-01T00:00:00Z".into(),
+        definition: def,
+    }
+}
+
+#[test]
+fn play_definition_rejects_depends_on_cycles() {
+    let cyclic = play(vec![
+        play_task("root", &[]),
+        play_task("a", &["root", "c"]),
+        play_task("b", &["a"]),
+        play_task("c", &["b"]),
+    ]);
+    let err = cyclic.validate().unwrap_err();
+    assert!(err.contains("cycle"), "{err}");
+
+    // `after` only sits behind the cycle; the error names a task on it.
+    let behind = play(vec![
+        play_task("after", &["b"]),
+        play_task("a", &["b"]),
+        play_task("b", &["a"]),
+    ]);
+    let err = behind.validate().unwrap_err();
+    assert!(!err.contains("'after'"), "{err}");
+
+    let diamond = play(vec![
+        play_task("a", &[]),
+        play_task("b", &["a"]),
+        play_task("c", &["a"]),
+        play_task("d", &["b", "c"]),
+    ]);
+    assert_eq!(diamond.validate(), Ok(()));
+}
+
+#[test]
+fn play_name_validation() {
+    assert_eq!(validate_play_name("sre-incident_2"), Ok(()));
+    assert!(validate_play_name("").is_err());
+    assert!(validate_play_name("a/b").is_err());
+    assert!(validate_play_name(&"x".repeat(65)).is_err());
+    assert!(validate_play_name("runs").is_err());
+}
+
+#[test]
+fn play_version_parses_orders_and_round_trips() {
+    assert_eq!(
+        version("1.10.0"),
+        PlayVersion {
+            major: 1,
+            minor: 10,
+            patch: 0
+        }
+    );
+    assert!(version("1.10.0") > version("1.9.3"));
+    assert!(version("2.0.0") > version("1.99.99"));
+    for bad in [
+        "1.0",
+        "1.0.0.0",
+        "v1.0.0",
+        "1.0.-1",
+        "01.0.0",
+        "1.0.0-rc1",
+        "",
+    ] {
+        assert!(PlayVersion::parse(bad).is_err(), "{bad}");
+    }
+    assert_eq!(
+        serde_json::to_value(version("1.2.3")).unwrap(),
+        serde_json::json!("1.2.3")
+    );
+    assert!(serde_json::from_str::<PlayVersion>(r#""1.2""#).is_err());
+}
+
+#[test]
+fn play_version_bumps_reset_lower_parts() {
+    let v = version("1.2.3");
+    assert_eq!(v.bump(PlayVersionBump::Patch), version("1.2.4"));
+    assert_eq!(v.bump(PlayVersionBump::Minor), version("1.3.0"));
+    assert_eq!(v.bump(PlayVersionBump::Major), version("2.0.0"));
+}
+
+#[test]
+fn play_update_next_version_must_move_forward() {
+    let update: UpdatePlayDefinition = serde_json::from_str(r#"{"goal":"g","tasks":[]}"#).unwrap();
+    assert_eq!(update.next_version(version("1.2.3")), Ok(version("1.2.4")));
+
+    let update: UpdatePlayDefinition =
+        serde_json::from_str(r#"{"goal":"g","tasks":[],"bump":"minor"}"#).unwrap();
+    assert_eq!(update.next_version(version("1.2.3")), Ok(version("1.3.0")));
+
+    let update: UpdatePlayDefinition =
+        serde_json::from_str(r#"{"goal":"g","tasks":[],"version":"1.2.3"}"#).unwrap();
+    assert!(update.next_version(version("1.2.3")).is_err());
+    assert_eq!(update.next_version(version("1.0.0")), Ok(version("1.2.3")));
+}
+
+#[test]
+fn create_play_definition_body_flattens_the_definition() {
+    let body: CreatePlayDefinition = serde_json::from_str(
+        r#"{"name":"deploy","goal":"g","tasks":[],"on_error":"continue","version":"0.1.0"}"#,
+    )
+    .unwrap();
+    assert_eq!(body.definition.name, "deploy");
+    assert_eq!(body.definition.on_error, PlayErrorPolicy::Continue);
+    assert_eq!(body.version, Some(version("0.1.0")));
+
+    let json = serde_json::to_value(stored("1.0.0", body.definition)).unwrap();
+    assert_eq!(json["version"], "1.0.0");
+    assert_eq!(json["name"], "deploy");
+}
+
+#[test]
+fn play_launch_request_accepts_a_version() {
+    let req: PlayLaunchRequest = serde_json::from_str(
+        r#"{"play_name":"deploy","job_id":null,"metadata":null,"version":"1.1.0"}"#,
+    )
+    .unwrap();
+    assert_eq!(req.version, Some(version("1.1.0")));
+}
+
+#[test]
+fn play_diff_reports_added_removed_and_changed_tasks() {
+    let old = play(vec![
+        play_task("build", &[]),
+        play_task("test", &["build"]),
+        play_task("lint", &[]),
+    ]);
+    let mut new = play(vec![
+        play_task("build", &[]),
+        PlayTaskDefinition {
+            priority: 5,
+            timeout_secs: Some(60),
+            ..play_task("test", &["build"])
+        },
+        play_task("ship", &["test"]),
+    ]);
+    new.goal = "ship it".into();
+
+    let diff = PlayDiff::between(&stored("1.0.0", old), &stored("1.1.0", new));
+    assert_eq!(diff.from, version("1.0.0"));
+    assert_eq!(diff.to, version("1.1.0"));
+    assert_eq!(diff.changes.keys().collect::<Vec<_>>(), vec!["goal"]);
+    assert_eq!(diff.changes["goal"].to, serde_json::json!("ship it"));
+    assert_eq!(diff.added_tasks, vec!["ship".to_string()]);
+    assert_eq!(diff.removed_tasks, vec!["lint".to_string()]);
+    assert_eq!(diff.changed_tasks.len(), 1);
+    let test = &diff.changed_tasks[0];
+    assert_eq!(test.id, "test");
+    assert_eq!(
+        test.changes.keys().collect::<Vec<_>>(),
+        vec!["priority", "timeout_secs"]
+    );
+    assert_eq!(test.changes["timeout_secs"].from, serde_json::Value::Null);
+    assert_eq!(test.changes["timeout_secs"].to, serde_json::json!(60));
+}
+
+// ── Play launch inputs ──────────────────────────────────────────
+
+use std::collections::BTreeMap;
+
+fn play_with_inputs(inputs: serde_json::Value) -> PlayDefinition {
+    PlayDefinition {
+        inputs: serde_json::from_value(inputs).unwrap(),
+        ..play(vec![])
+    }
+}
+
+#[test]
+fn play_inputs_resolve_defaults_and_check_types() {
+    let def = play_with_inputs(serde_json::json!({
+        "service": {"type": "string"},
+        "replicas": {"type": "integer", "default": 2},
+        "dry_run": {"type": "boolean", "default": false, "description": "no writes"},
+    }));
+    assert_eq!(def.validate(), Ok(()));
+
+    let provided = BTreeMap::from([
+        ("service".to_string(), serde_json::json!("api")),
+        ("replicas".to_string(), serde_json::json!(5)),
+    ]);
+    let resolved = def.resolve_inputs(&provided).unwrap();
+    assert_eq!(resolved["service"], "api");
+    assert_eq!(resolved["replicas"], 5);
+    assert_eq!(resolved["dry_run"], false);
+
+    let err = def.resolve_inputs(&BTreeMap::new()).unwrap_err();
+    assert_eq!(err, "missing required input 'service'");
+
+    let wrong_type = BTreeMap::from([
+        ("service".to_string(), serde_json::json!("api")),
+        ("replicas".to_string(), serde_json::json!(1.5)),
+    ]);
+    assert_eq!(
+        def.resolve_inputs(&wrong_type).unwrap_err(),
+        "input 'replicas' must be of type integer"
+    );
+
+    let unknown = BTreeMap::from([
+        ("service".to_string(), serde_json::json!("api")),
+        ("region".to_string(), serde_json::json!("eu")),
+    ]);
+    assert_eq!(
+        def.resolve_inputs(&unknown).unwrap_err(),
+        "unknown input 'region'"
+    );
+}
+
+#[test]
+fn play_validate_checks_input_defaults_and_templates() {
+    let bad_default = play_with_inputs(serde_json::json!({
+        "replicas": {"type": "integer", "default": "two"},
+    }));
+    assert!(bad_default
+        .validate()
+        .unwrap_err()
+        .contains("default is not of type integer"));
+
+    let undeclared = PlayDefinition {
+        tasks: vec![PlayTaskDefinition {
+            params: Some(serde_json::json!({"svc": "{{inputs.service}}"})),
+            ..play_task("a", &[])
+        }],
+        ..play(vec![])
+    };
+    assert!(undeclared
+        .validate()
+        .unwrap_err()
+        .contains("undeclared input"));
+}
+
+#[test]
+fn play_launch_request_carries_inputs() {
+    let req: PlayLaunchRequest = serde_json::from_str(
+        r#"{"play_name":"deploy","job_id":null,"metadata":null,"inputs":{"service":"api"}}"#,
+    )
+    .unwrap();
+    assert_eq!(req.inputs["service"], "api");
+}
+
+#[test]
+fn play_validate_checks_approval_settings() {
+    let gate = |approval: Option<PlayApprovalPolicy>| PlayTaskDefinition {
+        task_type: APPROVAL_TASK_TYPE.into(),
+        approval,
+        ..play_task("gate", &[])
+    };
+    let sign_off = PlayApprovalPolicy {
+        approvers: vec!["human:jane".into()],
+        on_timeout: PlayApprovalTimeout::Reject,
+    };
+    assert!(play(vec![gate(None)]).validate().is_ok());
+    assert!(play(vec![gate(Some(sign_off.clone()))]).validate().is_ok());
+
+    let on_work_task = play(vec![PlayTaskDefinition {
+        approval: Some(sign_off),
+        ..play_task("a", &[])
+    }]);
+    assert!(on_work_task
+        .validate()
+        .unwrap_err()
+        .contains("only valid on task_type 'approval'"));
+
+    let retried = play(vec![PlayTaskDefinition {
+        retry: Some(PlayRetryPolicy { max_retries: 1 }),
+        ..gate(None)
+    }]);
+    assert!(retried
+        .validate()
+        .unwrap_err()
+        .contains("cannot have map or retry"));
+
+    for approver in ["agent:ci", "human:", "tenant:acme:admin"] {
+        let err = play(vec![gate(Some(PlayApprovalPolicy {
+            approvers: vec![approver.into()],
+            ..Default::default()
+        }))])
+        .validate()
+        .unwrap_err();
+        assert!(err.contains("must be a human:<sub> actor"), "{err}");
+    }
+}
+
+#[test]
+fn play_approval_policy_defaults_to_escalating() {
+    let task: PlayTaskDefinition = serde_json::from_value(serde_json::json!({
+        "id": "gate", "task_type": "approval", "priority": 0, "params": null,
+        "depends_on": [], "timeout_secs": 3600, "approval": {}
+    }))
+    .unwrap();
+    assert!(task.is_approval());
+    assert_eq!(
+        task.approval.unwrap().on_timeout,
+        PlayApprovalTimeout::Escalate
+    );
+}
+
+#[test]
+fn play_approval_request_validates_decision() {
+    let req = |decision| PlayApprovalRequest {
+        decision,
+        reason: None,
+    };
+    assert!(req(HumanDecisionType::Approve).validate().is_ok());
+    assert!(req(HumanDecisionType::Reject).validate().is_ok());
+    assert!(req(HumanDecisionType::Merge)
+        .validate()
+        .unwrap_err()
+        .contains("must be approve, request_changes or reject"));
+
+    // A body-supplied actor from older clients is dropped, not trusted.
+    let legacy: PlayApprovalRequest = serde_json::from_value(serde_json::json!({
+        "actor": "human:jane", "decision": "approve"
+    }))
+    .unwrap();
+    assert_eq!(legacy, req(HumanDecisionType::Approve));
+}
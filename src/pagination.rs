@@ -122,6 +122,62 @@ impl AgentsCursor {
     }
 }
 
+/// Sort key for `/v1/audit`: rows are ordered by their chain `seq`, which
+/// is unique per tenant, so no tiebreaker is needed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuditCursor {
+    #[serde(rename = "s")]
+    pub seq: i64,
+}
+
+impl AuditCursor {
+    pub fn encode(&self) -> Result<String> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| worker::Error::RustError(format!("encode cursor: {e}")))?;
+        Ok(hex::encode(json))
+    }
+
+    pub fn decode(raw: Option<&str>) -> Result<Option<Self>> {
+        let Some(raw) = raw.filter(|s| !s.is_empty()) else {
+            return Ok(None);
+        };
+        let bytes = hex::decode(raw)
+            .map_err(|e| worker::Error::RustError(format!("decode cursor hex: {e}")))?;
+        let cursor: Self = serde_json::from_slice(&bytes)
+            .map_err(|e| worker::Error::RustError(format!("decode cursor json: {e}")))?;
+        Ok(Some(cursor))
+    }
+}
+
+/// Sort key for `/v1/tasks/dead-letter`: newest first by
+/// `dead_lettered_at`, then task id descending as the tiebreaker.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeadLetterCursor {
+    #[serde(rename = "c")]
+    pub dead_lettered_at: String,
+    #[serde(rename = "i")]
+    pub id: String,
+}
+
+impl DeadLetterCursor {
+    pub fn encode(&self) -> Result<String> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| worker::Error::RustError(format!("encode cursor: {e}")))?;
+        Ok(hex::encode(json))
+    }
+
+    pub fn decode(raw: Option<&str>) -> Result<Option<Self>> {
+        let Some(raw) = raw.filter(|s| !s.is_empty()) else {
+            return Ok(None);
+        };
+        let bytes = hex::decode(raw)
+            .map_err(|e| worker::Error::RustError(format!("decode cursor hex: {e}")))?;
+        let cursor: Self = serde_json::from_slice(&bytes)
+            .map_err(|e| worker::Error::RustError(format!("decode cursor json: {e}")))?;
+        Ok(Some(cursor))
+    }
+}
+
 /// Clamp a client-supplied `?limit=` to the documented bounds.
 ///
 /// Default 50, hard max 200 — matches what the existing handler already did
@@ -226,4 +282,33 @@ mod tests {
         assert!(AgentsCursor::decode(Some("not-hex-zzzz")).is_err());
         assert!(AgentsCursor::decode(Some("deadbeef")).is_err());
     }
+
+    // ── AuditCursor ────────────────────────────────────────────
+
+    #[test]
+    fn audit_cursor_roundtrips_through_hex() {
+        let cursor = AuditCursor { seq: 42 };
+        let encoded = cursor.encode().expect("encode");
+        let decoded = AuditCursor::decode(Some(&encoded))
+            .expect("decode")
+            .expect("some");
+        assert_eq!(decoded, cursor);
+        assert!(AuditCursor::decode(Some("deadbeef")).is_err());
+    }
+
+    // ── DeadLetterCursor ───────────────────────────────────────
+
+    #[test]
+    fn dead_letter_cursor_roundtrips_through_hex() {
+        let cursor = DeadLetterCursor {
+            dead_lettered_at: "2026-05-13T10:00:00Z".into(),
+            id: "task-1".into(),
+        };
+        let encoded = cursor.encode().expect("encode");
+        let decoded = DeadLetterCursor::decode(Some(&encoded))
+            .expect("decode")
+            .expect("some");
+        assert_eq!(decoded, cursor);
+        assert!(DeadLetterCursor::decode(Some("deadbeef")).is_err());
+    }
 }
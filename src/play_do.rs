@@ -1,6 +1,10 @@
-use crate::models::{AgentTask, PlayDefinition, PlayTaskDefinition};
+use crate::models::{
+    AgentTask, CancelledTasks, HumanDecisionType, PlayApprovalDecision, PlayApprovalRequest,
+    PlayApprovalState, PlayApprovalTimeout, PlayDefinition, PlayRunStatus, PlayRunTask, PlayStatus,
+    PlayTaskDefinition, PlayTaskOutcome,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use wasm_bindgen::JsValue;
 use worker::*;
 
@@ -11,6 +15,12 @@ use worker::*;
 struct LaunchEnvelope {
     tenant_id: String,
     definition: PlayDefinition,
+    /// Launch inputs, checked and defaulted by the caller.
+    #[serde(default)]
+    inputs: BTreeMap<String, serde_json::Value>,
+    /// Caller's `metadata` from the launch request, kept as-is.
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -32,6 +42,202 @@ struct PlayState {
     /// `/task-completed` handlers below for the read-derive-write contract.
     #[serde(default)]
     state_version: u64,
+    /// Tasks cancelled by `/cancel`; never launched again.
+    #[serde(default)]
+    cancelled_tasks: HashSet<String>,
+    /// Results of completed tasks, map children included, for `when`,
+    /// `map` and `reduce` to read. Held in memory only: [`load_state`]
+    /// reads each one from its own [`result_key`] and rebuilds a finished
+    /// map's from its children. States saved before `result_refs` carry
+    /// them inline and are read from there.
+    #[serde(default, skip_serializing)]
+    results: HashMap<String, serde_json::Value>,
+    /// Tasks whose result is stored under [`result_key`].
+    #[serde(default)]
+    result_refs: HashSet<String>,
+    /// Tasks that failed for good, with their last error.
+    #[serde(default)]
+    failed_tasks: HashMap<String, String>,
+    /// Tasks never run: their `when` did not hold, every parent was
+    /// skipped or failed, or (for an `on_failure` handler) nothing it
+    /// handles failed.
+    #[serde(default)]
+    skipped_tasks: HashSet<String>,
+    /// Items of each expanded `map` task; child `i` runs as `{map}.{i}`.
+    #[serde(default)]
+    map_items: HashMap<String, Vec<serde_json::Value>>,
+    /// Released `on_failure` handlers and the failure each one handles.
+    #[serde(default)]
+    triggered_handlers: HashMap<String, PlayFailure>,
+    /// First failure without an `on_failure` handler. The run ends
+    /// `failed`: at once under `fail_fast` (nothing further is launched),
+    /// or once the branches that do not depend on it finish under
+    /// `continue`.
+    #[serde(default)]
+    failed_task: Option<String>,
+    /// Where the run is in its lifecycle; see [`next_status`].
+    #[serde(default)]
+    status: PlayStatus,
+    /// Set once a terminal `succeeded` / `failed` status has been written
+    /// to D1, so a retried event does not record it twice.
+    #[serde(default)]
+    outcome_recorded: bool,
+    /// Launch / finish times and agent per task, for `/status`.
+    #[serde(default)]
+    task_meta: HashMap<String, TaskMeta>,
+    /// Launch inputs, defaults filled in, for `{{inputs.*}}` templates.
+    #[serde(default)]
+    inputs: BTreeMap<String, serde_json::Value>,
+    /// Opened approval tasks. One awaiting a decision is also in
+    /// `active_tasks`, but was never enqueued on TaskLeaseManager.
+    #[serde(default)]
+    approvals: HashMap<String, ApprovalGate>,
+    /// Caller's `metadata` from the launch request, returned by `/status`.
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct ApprovalGate {
+    #[serde(flatten)]
+    status: PlayApprovalState,
+    /// `status.deadline` in epoch ms while the timeout is still pending;
+    /// cleared once it has fired.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deadline_ms: Option<u64>,
+}
+
+/// Body of `/approval`, forwarded by
+/// `POST /v1/plays/runs/:run_id/approvals/:task_id`.
+#[derive(Serialize, Deserialize, Debug)]
+struct ApprovalEnvelope {
+    task_id: String,
+    /// Id for the `human_decision` row, minted by the caller.
+    decision_id: String,
+    /// `human:<sub>` of the verified caller (`TenantContext::approver`).
+    actor: String,
+    request: PlayApprovalRequest,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+struct TaskMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    launched_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    finished_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    agent_id: Option<String>,
+}
+
+/// The failure an `on_failure` handler is launched for, passed to it as
+/// `params.failure`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct PlayFailure {
+    task: String,
+    error: String,
+}
+
+/// Body of `/task-completed`: an outcome from TaskLeaseManager, or the
+/// bare task id (a completion) from notifications queued before outcomes
+/// existed.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum TaskEvent {
+    Outcome(PlayTaskOutcome),
+    Completed(String),
+}
+
+impl TaskEvent {
+    fn into_outcome(self) -> PlayTaskOutcome {
+        match self {
+            TaskEvent::Outcome(o) => o,
+            TaskEvent::Completed(task_id) => PlayTaskOutcome {
+                task_id,
+                status: "completed".to_string(),
+                result: None,
+                error: None,
+                agent_id: None,
+            },
+        }
+    }
+}
+
+/// Retries a play task gets when its definition sets no `retry` policy.
+const DEFAULT_MAX_RETRIES: i32 = 3;
+
+/// Items a `map` task may expand to. Each child adds its id, timings and
+/// item to the `state` value, which DO storage caps at 128 KiB; a longer
+/// source array fails the map instead.
+const MAX_MAP_ITEMS: usize = 256;
+
+const RESULT_PREFIX: &str = "result:";
+
+/// Storage key of one task's result, kept out of the `state` value that
+/// every event rewrites.
+fn result_key(task_id: &str) -> String {
+    format!("{RESULT_PREFIX}{task_id}")
+}
+
+/// Keys per `get_multiple` when loading results.
+const RESULT_BATCH: usize = 128;
+
+/// Read the run's state with every task result filled in.
+async fn load_state(storage: &Storage) -> Result<Option<PlayState>> {
+    let Some(mut state) = storage.get::<PlayState>("state").await? else {
+        return Ok(None);
+    };
+    let keys: Vec<String> = state
+        .result_refs
+        .iter()
+        .filter(|id| !state.results.contains_key(*id))
+        .map(|id| result_key(id))
+        .collect();
+    for chunk in keys.chunks(RESULT_BATCH) {
+        let stored = storage.get_multiple(chunk.to_vec()).await?;
+        for (key, result) in crate::task_do::map_values::<serde_json::Value>(stored)? {
+            if let Some(id) = key.strip_prefix(RESULT_PREFIX) {
+                state.results.insert(id.to_string(), result);
+            }
+        }
+    }
+    rebuild_map_results(&mut state);
+    Ok(Some(state))
+}
+
+/// Write the run's state, first storing each result that has no key yet.
+/// A finished map's result is left out: it is rebuilt from its children.
+async fn save_state(storage: &Storage, state: &mut PlayState) -> Result<()> {
+    let unsaved: Vec<String> = state
+        .results
+        .keys()
+        .filter(|id| !state.result_refs.contains(*id) && !state.map_items.contains_key(*id))
+        .cloned()
+        .collect();
+    for id in unsaved {
+        storage.put(&result_key(&id), &state.results[&id]).await?;
+        state.result_refs.insert(id);
+    }
+    storage.put("state", &*state).await
+}
+
+/// Set the result of every finished map to its children's results, in
+/// item order.
+fn rebuild_map_results(state: &mut PlayState) {
+    let finished: Vec<(String, usize)> = state
+        .map_items
+        .iter()
+        .filter(|(id, _)| state.completed_tasks.contains(*id))
+        .map(|(id, items)| (id.clone(), items.len()))
+        .collect();
+    for (id, len) in finished {
+        let results = (0..len)
+            .map(|i| {
+                let child = map_child_id(&id, i);
+                state.results.get(&child).cloned().unwrap_or_default()
+            })
+            .collect();
+        state.results.insert(id, serde_json::Value::Array(results));
+    }
 }
 
 #[durable_object]
@@ -55,6 +261,8 @@ impl DurableObject for PlayManager {
                 let LaunchEnvelope {
                     tenant_id,
                     definition: def,
+                    inputs,
+                    metadata,
                 } = envelope;
 
                 if tenant_id.is_empty() {
@@ -73,6 +281,20 @@ impl DurableObject for PlayManager {
                     completed_tasks: HashSet::new(),
                     active_tasks: HashSet::new(),
                     state_version: 0,
+                    cancelled_tasks: HashSet::new(),
+                    results: HashMap::new(),
+                    result_refs: HashSet::new(),
+                    failed_tasks: HashMap::new(),
+                    skipped_tasks: HashSet::new(),
+                    map_items: HashMap::new(),
+                    triggered_handlers: HashMap::new(),
+                    failed_task: None,
+                    status: PlayStatus::Running,
+                    outcome_recorded: false,
+                    task_meta: HashMap::new(),
+                    inputs,
+                    approvals: HashMap::new(),
+                    metadata,
                 };
 
                 // Materialize initial tasks. `materialize_eligible_tasks` is
@@ -88,35 +310,307 @@ impl DurableObject for PlayManager {
                 }))
             }
             (Method::Post, "/task-completed") => {
-                let task_id: String = req.json().await?;
+                let outcome = req.json::<TaskEvent>().await?.into_outcome();
+                self.record_outcome(outcome).await
+            }
+            (Method::Post, "/task-failed") => {
+                // A task TaskLeaseManager gave up on (retries spent, lease
+                // or timeout expiry included). Without this the task sat in
+                // `active_tasks` forever and the run never finished.
+                let mut outcome: PlayTaskOutcome = req.json().await?;
+                outcome.status = "failed".to_string();
+                self.record_outcome(outcome).await
+            }
+            (Method::Get, "/status") => {
+                let Some(state) = self.state.storage().get::<PlayState>("state").await? else {
+                    return Response::error("play run not found", 404);
+                };
+                Response::from_json(&run_status(&state))
+            }
+            (Method::Get, "/result") => {
+                let task_id = req
+                    .url()?
+                    .query_pairs()
+                    .find(|(k, _)| k == "task_id")
+                    .map(|(_, v)| v.into_owned())
+                    .unwrap_or_default();
                 let storage = self.state.storage();
-                // Atomic read-modify-write of the completion event. We then
-                // call `materialize_eligible_tasks(None)` which will read
-                // the freshly-persisted state, derive to_launch, mark
-                // active, and persist BEFORE issuing outbound RPCs to
-                // TaskLeaseManager — closing the TOCTOU window that
-                // previously existed between derive (L74) and write (L99).
-                let mut state: PlayState = storage
-                    .get("state")
+                if let Some(result) = storage
+                    .get::<serde_json::Value>(&result_key(&task_id))
                     .await?
-                    .ok_or_else(|| Error::RustError("state not found".into()))?;
-
-                state.completed_tasks.insert(task_id.clone());
-                state.active_tasks.remove(&task_id);
-                state.state_version = state.state_version.wrapping_add(1);
+                {
+                    return Response::from_json(&result);
+                }
+                // Map results and those of states saved inline.
+                let Some(state) = load_state(&storage).await? else {
+                    return Response::error("play run not found", 404);
+                };
+                match state.results.get(&task_id) {
+                    Some(result) => Response::from_json(result),
+                    None => Response::error("task result not found", 404),
+                }
+            }
+            (Method::Post, "/approval") => {
+                let envelope: ApprovalEnvelope = req.json().await?;
+                self.record_approval(envelope).await
+            }
+            (Method::Post, "/cancel") => {
+                // Stop the run: nothing not yet completed is launched
+                // again. TaskLeaseManager cancellation of the tasks
+                // already enqueued is driven by the caller (lib.rs
+                // `/v1/runs/:run_id/cancel`), which targets the run's
+                // job_id there.
+                let storage = self.state.storage();
+                let Some(mut state) = load_state(&storage).await? else {
+                    return Response::error("play run not found", 404);
+                };
+                let cancelled = mark_cancelled(&mut state);
+                if !cancelled.is_empty() {
+                    state.state_version = state.state_version.wrapping_add(1);
+                    save_state(&storage, &mut state).await?;
+                }
+                Response::from_json(&serde_json::json!({
+                    "run_id": state.run_id,
+                    "cancelled_tasks": cancelled,
+                }))
+            }
+            (Method::Post, "/purge") => {
+                // Tenant hard-delete: drop the run and any pending
+                // approval alarm.
+                let storage = self.state.storage();
+                storage.delete_alarm().await?;
+                storage.delete_all().await?;
+                Response::empty().map(|r| r.with_status(204))
+            }
+            _ => Response::error("not found", 404),
+        }
+    }
 
-                storage.put("state", &state).await?;
+    /// Apply the timeouts of approval tasks that are past their deadline.
+    async fn alarm(&self) -> Result<Response> {
+        let storage = self.state.storage();
+        let Some(state) = load_state(&storage).await? else {
+            return Response::ok("ok");
+        };
+        let now_ms = js_sys::Date::now() as u64;
+        let expired = expired_approvals(&state, now_ms);
+        if expired.is_empty() {
+            self.schedule_approval_alarm(&state).await?;
+            return Response::ok("ok");
+        }
 
-                self.materialize_eligible_tasks(None).await?;
+        let d1 = self.env.d1("DB")?;
+        let mut escalations = Vec::new();
+        for (task_id, on_timeout) in &expired {
+            if *on_timeout != PlayApprovalTimeout::Escalate {
+                continue;
+            }
+            let escalation_id = crate::generate_id()?;
+            let gate = &state.approvals[task_id].status;
+            crate::db::create_policy_escalation(
+                &d1,
+                &state.tenant_id,
+                &escalation_id,
+                &format!("{}-{}", state.run_id, task_id),
+                "play.approval",
+                "play-manager",
+                Some(&format!(
+                    "plays/{}/runs/{}/tasks/{}",
+                    state.definition.name, state.run_id, task_id
+                )),
+                crate::policy::RiskLevel::High,
+                Some(&serde_json::json!({
+                    "play": state.definition.name,
+                    "run_id": state.run_id,
+                    "task_id": task_id,
+                    "requested_at": gate.requested_at,
+                    "deadline": gate.deadline,
+                    "params": gate.params,
+                })),
+            )
+            .await?;
+            worker::console_log!(
+                "play_do: approval {} of run {} timed out; escalated as {}",
+                task_id,
+                state.run_id,
+                escalation_id,
+            );
+            escalations.push((task_id.clone(), escalation_id));
+        }
 
-                Response::ok("ok")
+        // Re-read: a decision may have landed while the escalations were
+        // being written.
+        let mut latest: PlayState = load_state(&storage)
+            .await?
+            .ok_or_else(|| Error::RustError("state not found".into()))?;
+        let now = crate::db::now_iso();
+        let mut changed = false;
+        for (task_id, escalation_id) in escalations {
+            changed |= escalate_approval(&mut latest, &task_id, escalation_id);
+        }
+        for (task_id, on_timeout) in expired {
+            if on_timeout == PlayApprovalTimeout::Reject {
+                changed |= time_out_approval(&mut latest, &task_id, &now);
             }
-            _ => Response::error("not found", 404),
         }
+        if changed {
+            latest.state_version = latest.state_version.wrapping_add(1);
+            save_state(&storage, &mut latest).await?;
+        }
+        self.schedule_approval_alarm(&latest).await?;
+        self.materialize_eligible_tasks(None).await?;
+        Response::ok("ok")
     }
 }
 
 impl PlayManager {
+    /// Record a human decision on an approval task: write it to the
+    /// `human_decision` projection, resolve the task, then launch whatever
+    /// that released.
+    async fn record_approval(&self, envelope: ApprovalEnvelope) -> Result<Response> {
+        let ApprovalEnvelope {
+            task_id,
+            decision_id,
+            actor,
+            request,
+        } = envelope;
+        let storage = self.state.storage();
+        let Some(state) = load_state(&storage).await? else {
+            return crate::errors::error_response("PLAY_RUN_NOT_FOUND", "play run not found", 404);
+        };
+        if let Err((code, msg, status)) = check_approval(&state, &task_id, &actor) {
+            return crate::errors::error_response(code, &msg, status);
+        }
+
+        let d1 = self.env.d1("DB")?;
+        crate::db::create_human_decision(
+            &d1,
+            &state.tenant_id,
+            &decision_id,
+            &crate::models::CreateHumanDecision {
+                run_id: Some(state.run_id.clone()),
+                review_id: None,
+                actor: actor.clone(),
+                decision_type: request.decision,
+                reason: request.reason.clone(),
+                policy_decision_id: None,
+                resulting_event_id: None,
+            },
+        )
+        .await?;
+
+        // Re-read: another decision may have resolved the task while the
+        // row was being written.
+        let mut latest: PlayState = load_state(&storage)
+            .await?
+            .ok_or_else(|| Error::RustError("state not found".into()))?;
+        if let Err((code, msg, status)) = check_approval(&latest, &task_id, &actor) {
+            return crate::errors::error_response(code, &msg, status);
+        }
+        let decision = PlayApprovalDecision {
+            id: decision_id,
+            actor,
+            decision: request.decision,
+            reason: request.reason,
+            decided_at: crate::db::now_iso(),
+        };
+        apply_approval(&mut latest, &task_id, decision.clone());
+        latest.state_version = latest.state_version.wrapping_add(1);
+        save_state(&storage, &mut latest).await?;
+
+        self.materialize_eligible_tasks(None).await?;
+
+        Response::from_json(&decision)
+    }
+
+    /// Point the DO alarm at the earliest pending approval deadline.
+    async fn schedule_approval_alarm(&self, state: &PlayState) -> Result<()> {
+        if let Some(at_ms) = next_approval_deadline(state) {
+            self.state.storage().set_alarm(at_ms as i64).await?;
+        }
+        Ok(())
+    }
+
+    /// Fold a task outcome into the run, then launch whatever it released.
+    async fn record_outcome(&self, outcome: PlayTaskOutcome) -> Result<Response> {
+        let storage = self.state.storage();
+        // Atomic read-modify-write of the outcome event. We then call
+        // `materialize_eligible_tasks(None)` which will read the
+        // freshly-persisted state, derive to_launch, mark active, and
+        // persist BEFORE issuing outbound RPCs to TaskLeaseManager —
+        // closing the TOCTOU window that previously existed between
+        // derive (L74) and write (L99).
+        let mut state: PlayState = load_state(&storage)
+            .await?
+            .ok_or_else(|| Error::RustError("state not found".into()))?;
+
+        if apply_outcome(&mut state, &outcome) {
+            let meta = state.task_meta.entry(outcome.task_id.clone()).or_default();
+            meta.finished_at = Some(crate::db::now_iso());
+            meta.agent_id = outcome.agent_id.clone();
+            state.state_version = state.state_version.wrapping_add(1);
+            save_state(&storage, &mut state).await?;
+        }
+
+        self.materialize_eligible_tasks(None).await?;
+
+        Response::ok("ok")
+    }
+
+    /// Record a run that finished on its own: under `fail_fast`, cancel
+    /// the tasks still in flight on TaskLeaseManager, then write the
+    /// status to the run's `runs` row and emit `run.succeeded` /
+    /// `run.failed`. Errors bubble up so the TaskLeaseManager notify that
+    /// got us here is retried; `outcome_recorded` makes the retry cheap.
+    async fn record_finished(&self, state: &PlayState) -> Result<()> {
+        if state.outcome_recorded || state.status == PlayStatus::Cancelled {
+            return Ok(());
+        }
+        worker::console_log!(
+            "play_do: run {} {} (failed task: {:?})",
+            state.run_id,
+            state.status.as_str(),
+            state.failed_task,
+        );
+        let d1 = self.env.d1("DB")?;
+        if state.status == PlayStatus::Failed && state.definition.on_error.is_fail_fast() {
+            let shards = crate::task_shard::TaskShards::new(&self.env, &state.tenant_id)?;
+            let url = format!("https://do/cancel-job?job_id={}", state.run_id);
+            let mut parts = Vec::new();
+            for mut resp in shards
+                .fetch_all(|| Request::new(&url, Method::Post))
+                .await?
+            {
+                if resp.status_code() != 200 {
+                    return Err(Error::RustError(format!(
+                        "TLM cancel-job for run {} returned {}",
+                        state.run_id,
+                        resp.status_code()
+                    )));
+                }
+                parts.push(resp.json::<CancelledTasks>().await?);
+            }
+            let cancelled = crate::task_shard::merge_cancelled(parts);
+            crate::db::record_task_cancellations(&d1, &state.tenant_id, "play-manager", &cancelled)
+                .await?;
+        }
+        crate::db::finish_run(
+            &d1,
+            &state.tenant_id,
+            &state.run_id,
+            state.status.as_str(),
+            run_outcome_payload(state),
+        )
+        .await?;
+
+        let storage = self.state.storage();
+        let mut latest: PlayState = load_state(&storage)
+            .await?
+            .ok_or_else(|| Error::RustError("state not found".into()))?;
+        latest.outcome_recorded = true;
+        save_state(&storage, &mut latest).await
+    }
+
     /// Atomically materialize all newly-eligible tasks.
     ///
     /// Read-derive-write contract:
@@ -141,28 +635,50 @@ impl PlayManager {
         let storage = self.state.storage();
         let mut state: PlayState = match seed {
             Some(s) => s,
-            None => storage
-                .get("state")
+            None => load_state(&storage)
                 .await?
                 .ok_or_else(|| Error::RustError("state not found".into()))?,
         };
 
-        let to_launch = derive_to_launch(&state);
+        // Fold in skips, map expansions, finished maps and template
+        // failures before deciding what to launch; persisted with the
+        // state below.
+        let to_launch = prepare_launches(&mut state);
+        if state.status.is_terminal() {
+            save_state(&storage, &mut state).await?;
+            return self.record_finished(&state).await;
+        }
+        // Approval tasks wait here for a human; they never reach
+        // TaskLeaseManager.
+        let (approvals, to_launch): (Vec<_>, Vec<_>) =
+            to_launch.into_iter().partition(|t| t.is_approval());
+        if !approvals.is_empty() {
+            let now_ms = js_sys::Date::now() as u64;
+            for task_def in &approvals {
+                open_approval(
+                    &mut state,
+                    task_def,
+                    now_ms,
+                    crate::task_schedule::ms_to_iso,
+                );
+            }
+            state.state_version = state.state_version.wrapping_add(1);
+            save_state(&storage, &mut state).await?;
+            self.schedule_approval_alarm(&state).await?;
+        }
         if to_launch.is_empty() {
             // Still need to persist the seed on first launch even when the
             // play has no eligible tasks (e.g. all gated by deps).
-            storage.put("state", &state).await?;
+            save_state(&storage, &mut state).await?;
             return Ok(());
         }
 
-        // Route to the per-tenant TaskLeaseManager. The TLM DO is named by
-        // tenant_id everywhere else in the worker (see lib.rs handlers for
-        // /mcp/task/*) so we use the same naming here. Previously this used
+        // Route to the tenant's TaskLeaseManager shards, placed the same
+        // way as `POST /v1/tasks` (see `task_shard`). Previously this used
         // `self.state.id().to_string()` (the PlayManager's instance UUID),
         // which created a stray TLM per play run and broke cross-DO
         // coordination across the tenant's other task surfaces.
-        let task_ns = self.env.durable_object("TASK_LEASE_MANAGER")?;
-        let task_stub = task_ns.id_from_name(&state.tenant_id)?.get_stub()?;
+        let task_shards = crate::task_shard::TaskShards::new(&self.env, &state.tenant_id)?;
 
         // PR #132 crr finding (play_do.rs:178): the previous implementation
         // marked ALL `to_launch` tasks as active and persisted that state
@@ -188,35 +704,26 @@ impl PlayManager {
         // outbound RPCs so a concurrent handler observing storage sees a
         // consistent baseline. On the `/task-completed` path this is a
         // no-op rewrite of the just-persisted state from the caller.
-        storage.put("state", &state).await?;
+        save_state(&storage, &mut state).await?;
 
         for task_def in to_launch {
-            let task = AgentTask {
-                id: format!("{}-{}", state.run_id, task_def.id),
-                job_id: state.run_id.clone(),
-                task_type: task_def.task_type.clone(),
-                priority: task_def.priority,
-                status: "pending".to_string(),
-                params: task_def.params.clone(),
-                result: None,
-                agent_id: None,
-                graph_ref: None,
-                play_id: Some(state.definition.name.clone()),
-                parent_task_id: None,
-                retry_count: 0,
-                max_retries: 3,
-                lease_expires_at: None,
-                created_at: js_sys::Date::new_0().to_iso_string().as_string().unwrap_or_default(),
-                completed_at: None,
-                memory_context: None,
-                tenant_id: Some(state.tenant_id.clone()),
-            };
+            let task = play_agent_task(
+                &state,
+                &task_def,
+                js_sys::Date::new_0()
+                    .to_iso_string()
+                    .as_string()
+                    .unwrap_or_default(),
+            );
 
             let do_req = Request::new_with_init(
                 "https://do/enqueue",
                 &RequestInit {
                     method: Method::Post,
-                    body: Some(JsValue::from_str(&serde_json::to_string(&task).map_err(|e| Error::RustError(e.to_string()))?)),
+                    body: Some(JsValue::from_str(
+                        &serde_json::to_string(&task)
+                            .map_err(|e| Error::RustError(e.to_string()))?,
+                    )),
                     ..Default::default()
                 },
             )?;
@@ -227,7 +734,11 @@ impl PlayManager {
             // the error; the next materialize call will retry this and
             // remaining tasks because `derive_to_launch` still includes
             // anything not yet in `active_tasks`/`completed_tasks`.
-            let mut resp = match task_stub.fetch_with_request(do_req).await {
+            let mut resp = match task_shards
+                .stub_for_task(&task)?
+                .fetch_with_request(do_req)
+                .await
+            {
                 Ok(r) => r,
                 Err(e) => {
                     worker::console_log!(
@@ -276,8 +787,7 @@ impl PlayManager {
             // input gate yielded on the outbound RPC's await. Without
             // this re-read we would clobber concurrent `completed_tasks`
             // / `active_tasks.remove(...)` updates.
-            let mut latest: PlayState = storage
-                .get("state")
+            let mut latest: PlayState = load_state(&storage)
                 .await?
                 .ok_or_else(|| Error::RustError("state not found".into()))?;
 
@@ -293,10 +803,19 @@ impl PlayManager {
             // human reading state would see a contradictory record.
             // Guard against this by only marking active when the task
             // is not already completed. (No-op in the common case.)
-            if !latest.completed_tasks.contains(&task_def.id) {
+            // Likewise a `/cancel` or a failure that landed meanwhile wins.
+            if !latest.completed_tasks.contains(&task_def.id)
+                && !latest.failed_tasks.contains_key(&task_def.id)
+                && !is_cancelled(&latest, &task_def.id)
+            {
                 latest.active_tasks.insert(task_def.id.clone());
+                latest
+                    .task_meta
+                    .entry(task_def.id.clone())
+                    .or_default()
+                    .launched_at = Some(task.created_at.clone());
                 latest.state_version = latest.state_version.wrapping_add(1);
-                storage.put("state", &latest).await?;
+                save_state(&storage, &mut latest).await?;
             }
             state = latest;
         }
@@ -404,24 +923,512 @@ pub(crate) fn is_enqueue_success(status: u16) -> bool {
 /// be unit-tested directly on host (non-wasm) without a Workers runtime.
 /// The TOCTOU fix in `materialize_eligible_tasks` relies on this being a
 /// pure function of `state` — no hidden I/O.
+///
+/// Returns launch-ready definitions: map children come out as `{map}.{i}`
+/// with `item` / `index` params, released handlers with `failure`, and
+/// reduce steps with `results`. Call [`settle`] first so skips and map
+/// expansions are up to date.
 fn derive_to_launch(state: &PlayState) -> Vec<PlayTaskDefinition> {
     let mut to_launch = Vec::new();
+    let halted = state.failed_task.is_some() && state.definition.on_error.is_fail_fast();
+    if state.status.is_terminal() || halted {
+        return to_launch;
+    }
+    let launchable = |id: &str| {
+        !is_resolved(state, id) && !state.active_tasks.contains(id) && !is_cancelled(state, id)
+    };
     for task_def in &state.definition.tasks {
-        if state.completed_tasks.contains(&task_def.id) || state.active_tasks.contains(&task_def.id)
-        {
+        if !launchable(&task_def.id) {
             continue;
         }
-        let all_deps_met = task_def
-            .depends_on
-            .iter()
-            .all(|dep_id| state.completed_tasks.contains(dep_id));
-        if all_deps_met {
-            to_launch.push(task_def.clone());
+        if is_failure_handler(&state.definition, &task_def.id) {
+            if let Some(failure) = state.triggered_handlers.get(&task_def.id) {
+                let failure = serde_json::to_value(failure).unwrap_or_default();
+                to_launch.push(instantiate(task_def, &task_def.id, [("failure", failure)]));
+            }
+            continue;
+        }
+        if let Some(items) = state.map_items.get(&task_def.id) {
+            for (i, item) in items.iter().enumerate() {
+                let child_id = map_child_id(&task_def.id, i);
+                if launchable(&child_id) {
+                    let extra = [("item", item.clone()), ("index", serde_json::json!(i))];
+                    to_launch.push(instantiate(task_def, &child_id, extra));
+                }
+            }
+            continue;
+        }
+        if task_def.map.is_some() {
+            // Not expanded yet; `settle` does that once the parent is done.
+            continue;
+        }
+        // Parents skipped or failed do not hold a task back, but at least
+        // one must have completed (otherwise `settle` skips it).
+        let deps = &task_def.depends_on;
+        let all_deps_met = deps.iter().all(|dep_id| {
+            state.completed_tasks.contains(dep_id) || ran_without_result(state, dep_id)
+        }) && (deps.is_empty()
+            || deps.iter().any(|d| state.completed_tasks.contains(d)));
+        let when_holds = task_def
+            .when
+            .as_ref()
+            .is_none_or(|w| w.holds(state.results.get(&w.task)));
+        if !all_deps_met || !when_holds {
+            continue;
+        }
+        match &task_def.reduce {
+            Some(map_id) => {
+                let results = state.results.get(map_id).cloned().unwrap_or_default();
+                to_launch.push(instantiate(task_def, &task_def.id, [("results", results)]));
+            }
+            None => to_launch.push(task_def.clone()),
         }
     }
     to_launch
 }
 
+/// What `materialize_eligible_tasks` launches next, with `params`
+/// templates rendered (see [`crate::play_template`]). Runs [`settle`] and
+/// [`advance_status`] first. A task whose templates do not resolve fails
+/// instead of launching, which can release a handler or settle further
+/// tasks, so this repeats until every launch renders.
+fn prepare_launches(state: &mut PlayState) -> Vec<PlayTaskDefinition> {
+    loop {
+        if settle(state) {
+            state.state_version = state.state_version.wrapping_add(1);
+        }
+        if advance_status(state) {
+            state.state_version = state.state_version.wrapping_add(1);
+        }
+        let candidates = derive_to_launch(state);
+        if candidates.is_empty() {
+            return candidates;
+        }
+        // Render the definitions being launched, then derive again from
+        // them: `item`, `failure` and `results` are merged in after
+        // rendering, so data from other tasks is never expanded.
+        let mut rendered = state.clone();
+        let mut failures = Vec::new();
+        {
+            let without_result = |id: &str| ran_without_result(state, id);
+            let ctx = crate::play_template::TemplateContext {
+                inputs: &state.inputs,
+                results: &state.results,
+                without_result: &without_result,
+            };
+            for task_def in &mut rendered.definition.tasks {
+                let launching = candidates.iter().any(|c| {
+                    c.id == task_def.id
+                        || c.id
+                            .rsplit_once('.')
+                            .is_some_and(|(map_id, _)| map_id == task_def.id)
+                });
+                let Some(params) = task_def.params.as_mut().filter(|_| launching) else {
+                    continue;
+                };
+                match crate::play_template::render(params, &ctx) {
+                    Ok(value) => *params = value,
+                    Err(e) => failures.push((task_def.id.clone(), format!("params: {e}"))),
+                }
+            }
+        }
+        if failures.is_empty() {
+            return derive_to_launch(&rendered);
+        }
+        for (id, error) in failures {
+            fail_task(state, &id, error);
+        }
+        state.state_version = state.state_version.wrapping_add(1);
+    }
+}
+
+/// Resolve everything that follows from `state` without running a task:
+/// skip tasks whose `when` fails or whose parents were all skipped or
+/// failed, skip `on_failure` handlers nothing triggered, expand `map`
+/// tasks whose parent finished, and complete (or fail) maps whose
+/// children all finished. Repeats until nothing changes, since one skip
+/// can settle the next task. Returns `true` if the state changed.
+fn settle(state: &mut PlayState) -> bool {
+    let mut changed = false;
+    loop {
+        let mut progressed = false;
+        for i in 0..state.definition.tasks.len() {
+            let task_def = state.definition.tasks[i].clone();
+            let id = task_def.id.as_str();
+            if is_resolved(state, id) || state.active_tasks.contains(id) {
+                continue;
+            }
+            if is_failure_handler(&state.definition, id) {
+                let untriggered = !state.triggered_handlers.contains_key(id)
+                    && state
+                        .definition
+                        .tasks
+                        .iter()
+                        .filter(|t| t.on_failure.as_deref() == Some(id))
+                        .all(|t| is_resolved(state, &t.id));
+                if untriggered {
+                    state.skipped_tasks.insert(id.to_string());
+                    progressed = true;
+                }
+                continue;
+            }
+            if let Some(items) = state.map_items.get(id) {
+                let children: Vec<String> = (0..items.len()).map(|i| map_child_id(id, i)).collect();
+                if let Some(child) = children
+                    .iter()
+                    .find(|c| state.failed_tasks.contains_key(*c))
+                {
+                    let error = format!("{child}: {}", state.failed_tasks[child]);
+                    fail_task(state, id, error);
+                    progressed = true;
+                } else if children.iter().all(|c| state.completed_tasks.contains(c)) {
+                    let results = children
+                        .iter()
+                        .map(|c| state.results.get(c).cloned().unwrap_or_default())
+                        .collect();
+                    state
+                        .results
+                        .insert(id.to_string(), serde_json::Value::Array(results));
+                    state.completed_tasks.insert(id.to_string());
+                    progressed = true;
+                }
+                continue;
+            }
+
+            let deps = &task_def.depends_on;
+            if !deps.iter().all(|d| is_resolved(state, d)) {
+                continue;
+            }
+            let parents_skipped =
+                !deps.is_empty() && deps.iter().all(|d| ran_without_result(state, d));
+            let when_fails = task_def
+                .when
+                .as_ref()
+                .is_some_and(|w| !w.holds(state.results.get(&w.task)));
+            if parents_skipped || when_fails {
+                state.skipped_tasks.insert(id.to_string());
+                progressed = true;
+                continue;
+            }
+            if let Some(map) = &task_def.map {
+                let source = state
+                    .results
+                    .get(&map.from)
+                    .and_then(|r| r.pointer(&map.path));
+                match source.and_then(|v| v.as_array()) {
+                    Some(items) if items.is_empty() => {
+                        state.results.insert(id.to_string(), serde_json::json!([]));
+                        state.completed_tasks.insert(id.to_string());
+                    }
+                    Some(items) if items.len() > MAX_MAP_ITEMS => {
+                        let error = format!(
+                            "map source '{}' has {} items; the limit is {MAX_MAP_ITEMS}",
+                            map.from,
+                            items.len()
+                        );
+                        fail_task(state, id, error);
+                    }
+                    Some(items) => {
+                        state.map_items.insert(id.to_string(), items.clone());
+                    }
+                    None => {
+                        let error = format!("map source '{}' is not an array", map.from);
+                        fail_task(state, id, error);
+                    }
+                }
+                progressed = true;
+            }
+        }
+        if !progressed {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+/// The status `state` has reached. A failure with no `on_failure` handler
+/// fails the run at once under `fail_fast`, and under `continue` once
+/// nothing is in flight or launchable. Otherwise the run succeeds when
+/// every task is resolved (completed, skipped, or failed and handled).
+/// Terminal statuses stick. Expects a [`settle`]d state.
+fn next_status(state: &PlayState) -> PlayStatus {
+    if state.status.is_terminal() {
+        return state.status;
+    }
+    let idle = state.active_tasks.is_empty() && derive_to_launch(state).is_empty();
+    if state.failed_task.is_some() {
+        if state.definition.on_error.is_fail_fast() || idle {
+            return PlayStatus::Failed;
+        }
+        return PlayStatus::Running;
+    }
+    let all_resolved = state
+        .definition
+        .tasks
+        .iter()
+        .all(|t| is_resolved(state, &t.id));
+    if all_resolved && state.active_tasks.is_empty() {
+        PlayStatus::Succeeded
+    } else {
+        PlayStatus::Running
+    }
+}
+
+/// Move `state` to [`next_status`]. Returns `true` if it changed.
+fn advance_status(state: &mut PlayState) -> bool {
+    let next = next_status(state);
+    let changed = next != state.status;
+    state.status = next;
+    changed
+}
+
+/// Build the `/status` view of a run. Expanded `map` tasks are listed
+/// after their children, which inherit the map's parents, so `tasks`
+/// stays in dependency order.
+fn run_status(state: &PlayState) -> PlayRunStatus {
+    let entry = |id: &str, task_def: &PlayTaskDefinition, depends_on: Vec<String>| {
+        let meta = state.task_meta.get(id).cloned().unwrap_or_default();
+        PlayRunTask {
+            id: id.to_string(),
+            task_type: task_def.task_type.clone(),
+            state: task_state(state, id).to_string(),
+            depends_on,
+            on_failure: task_def.on_failure.clone(),
+            launched_at: meta.launched_at,
+            finished_at: meta.finished_at,
+            agent_id: meta.agent_id,
+            error: state.failed_tasks.get(id).cloned(),
+            result_ref: has_result(state, id)
+                .then(|| format!("/v1/plays/runs/{}/tasks/{}/result", state.run_id, id)),
+            approval: state.approvals.get(id).map(|g| g.status.clone()),
+        }
+    };
+    let mut tasks = Vec::new();
+    for task_def in &state.definition.tasks {
+        match state.map_items.get(&task_def.id) {
+            Some(items) => {
+                let children: Vec<String> = (0..items.len())
+                    .map(|i| map_child_id(&task_def.id, i))
+                    .collect();
+                for child in &children {
+                    tasks.push(entry(child, task_def, task_def.depends_on.clone()));
+                }
+                tasks.push(entry(&task_def.id, task_def, children));
+            }
+            None => tasks.push(entry(&task_def.id, task_def, task_def.depends_on.clone())),
+        }
+    }
+    PlayRunStatus {
+        run_id: state.run_id.clone(),
+        play: state.definition.name.clone(),
+        status: state.status,
+        failed_task: state.failed_task.clone(),
+        metadata: state.metadata.clone(),
+        tasks,
+    }
+}
+
+fn task_state(state: &PlayState, task_id: &str) -> &'static str {
+    if state.completed_tasks.contains(task_id) {
+        "completed"
+    } else if state.failed_tasks.contains_key(task_id) {
+        "failed"
+    } else if state.skipped_tasks.contains(task_id) {
+        "skipped"
+    } else if is_cancelled(state, task_id) {
+        "cancelled"
+    } else if is_awaiting_approval(state, task_id) {
+        "awaiting_approval"
+    } else if state.active_tasks.contains(task_id) {
+        "running"
+    } else {
+        "pending"
+    }
+}
+
+/// Payload of the `run.succeeded` / `run.failed` graph event.
+fn run_outcome_payload(state: &PlayState) -> serde_json::Value {
+    let mut skipped: Vec<_> = state.skipped_tasks.iter().collect();
+    skipped.sort();
+    let failed: std::collections::BTreeMap<_, _> = state.failed_tasks.iter().collect();
+    serde_json::json!({
+        "play": state.definition.name,
+        "status": state.status.as_str(),
+        "on_error": state.definition.on_error,
+        "failed_task": state.failed_task,
+        "failed_tasks": failed,
+        "skipped_tasks": skipped,
+        "completed_tasks": state.completed_tasks.len(),
+    })
+}
+
+/// The AgentTask a launched play task is enqueued as. Its id is
+/// `{run_id}-{task_id}`, which [`crate::task_do::play_task_outcome`] strips
+/// back to the play-side id.
+fn play_agent_task(
+    state: &PlayState,
+    task_def: &PlayTaskDefinition,
+    created_at: String,
+) -> AgentTask {
+    AgentTask {
+        id: format!("{}-{}", state.run_id, task_def.id),
+        job_id: state.run_id.clone(),
+        task_type: task_def.task_type.clone(),
+        priority: task_def.priority,
+        status: "pending".to_string(),
+        params: task_def.params.clone(),
+        result: None,
+        agent_id: None,
+        graph_ref: None,
+        play_id: Some(state.definition.name.clone()),
+        parent_task_id: None,
+        retry_count: 0,
+        max_retries: task_def
+            .retry
+            .as_ref()
+            .map_or(DEFAULT_MAX_RETRIES, |r| r.max_retries),
+        lease_expires_at: None,
+        created_at,
+        completed_at: None,
+        memory_context: None,
+        tenant_id: Some(state.tenant_id.clone()),
+        enqueued_at_ms: None,
+        failures: Vec::new(),
+        not_before: None,
+        cancel_requested: false,
+        requires: task_def.requires.clone(),
+        idempotency_key: Some(format!("{}-{}", state.run_id, task_def.id)),
+        timeout_ms: task_def.timeout_secs.map(|s| s * 1000),
+        deadline_ms: None,
+    }
+}
+
+/// Whether `task_id` has a result, without needing `state.results`
+/// loaded.
+fn has_result(state: &PlayState, task_id: &str) -> bool {
+    state.results.contains_key(task_id)
+        || state.result_refs.contains(task_id)
+        || (state.map_items.contains_key(task_id) && state.completed_tasks.contains(task_id))
+}
+
+/// Fold a task's final outcome from TaskLeaseManager into `state`. The
+/// same outcome reported twice is a no-op the second time. Returns `true`
+/// if the state changed.
+fn apply_outcome(state: &mut PlayState, outcome: &PlayTaskOutcome) -> bool {
+    let id = outcome.task_id.as_str();
+    let was_active = state.active_tasks.remove(id);
+    if state.completed_tasks.contains(id) || state.failed_tasks.contains_key(id) {
+        return was_active;
+    }
+    if outcome.status == "failed" {
+        let error = outcome.error.clone().unwrap_or_default();
+        if is_map_child(state, id) {
+            // The map itself fails when `settle` sees this.
+            state.failed_tasks.insert(id.to_string(), error);
+        } else {
+            fail_task(state, id, error);
+        }
+    } else {
+        state.completed_tasks.insert(id.to_string());
+        if let Some(result) = &outcome.result {
+            state.results.insert(id.to_string(), result.clone());
+        }
+    }
+    true
+}
+
+/// Record `task_id` as failed and release its `on_failure` handler, or
+/// fail the run if it has none. A cancelled run stays cancelled.
+fn fail_task(state: &mut PlayState, task_id: &str, error: String) {
+    state
+        .failed_tasks
+        .insert(task_id.to_string(), error.clone());
+    if state.cancelled_tasks.contains(task_id) {
+        return;
+    }
+    let handler = state
+        .definition
+        .tasks
+        .iter()
+        .find(|t| t.id == task_id)
+        .and_then(|t| t.on_failure.clone());
+    match handler {
+        Some(handler) => {
+            state
+                .triggered_handlers
+                .entry(handler)
+                .or_insert(PlayFailure {
+                    task: task_id.to_string(),
+                    error,
+                });
+        }
+        None => {
+            state.failed_task.get_or_insert_with(|| task_id.to_string());
+        }
+    }
+}
+
+/// Whether `task_id` has finished one way or another and will not run.
+fn is_resolved(state: &PlayState, task_id: &str) -> bool {
+    state.completed_tasks.contains(task_id)
+        || state.failed_tasks.contains_key(task_id)
+        || state.skipped_tasks.contains(task_id)
+        || state.cancelled_tasks.contains(task_id)
+}
+
+/// Whether `task_id` ended without a result its dependents can build on.
+/// A failed parent counts like a skipped one: its failure is either
+/// handled by an `on_failure` branch or has already failed the run.
+fn ran_without_result(state: &PlayState, task_id: &str) -> bool {
+    state.skipped_tasks.contains(task_id) || state.failed_tasks.contains_key(task_id)
+}
+
+fn is_cancelled(state: &PlayState, task_id: &str) -> bool {
+    state.cancelled_tasks.contains(task_id)
+        || task_id
+            .rsplit_once('.')
+            .is_some_and(|(map_id, _)| state.cancelled_tasks.contains(map_id))
+}
+
+fn is_map_child(state: &PlayState, task_id: &str) -> bool {
+    task_id
+        .rsplit_once('.')
+        .is_some_and(|(map_id, _)| state.map_items.contains_key(map_id))
+}
+
+fn is_failure_handler(definition: &PlayDefinition, task_id: &str) -> bool {
+    definition
+        .tasks
+        .iter()
+        .any(|t| t.on_failure.as_deref() == Some(task_id))
+}
+
+fn map_child_id(map_id: &str, index: usize) -> String {
+    format!("{map_id}.{index}")
+}
+
+/// Copy of `task_def` to launch as `id`, with `extra` merged into its
+/// params. Non-object params are kept under `params`.
+fn instantiate<const N: usize>(
+    task_def: &PlayTaskDefinition,
+    id: &str,
+    extra: [(&str, serde_json::Value); N],
+) -> PlayTaskDefinition {
+    let mut params = match &task_def.params {
+        Some(serde_json::Value::Object(map)) => map.clone(),
+        None | Some(serde_json::Value::Null) => serde_json::Map::new(),
+        Some(other) => serde_json::Map::from_iter([("params".to_string(), other.clone())]),
+    };
+    for (key, value) in extra {
+        params.insert(key.to_string(), value);
+    }
+    PlayTaskDefinition {
+        id: id.to_string(),
+        params: Some(serde_json::Value::Object(params)),
+        ..task_def.clone()
+    }
+}
+
 /// Apply a `/task-completed` event idempotently. The same `task_id` reported
 /// twice is a no-op the second time. Returns `true` if the state changed.
 #[allow(dead_code)]
@@ -431,10 +1438,235 @@ fn mark_completed(state: &mut PlayState, task_id: &str) -> bool {
     inserted || removed
 }
 
+/// Cancel every task of the play that has not completed, clearing the
+/// active set. Returns the newly cancelled task ids, sorted; a second call
+/// returns none.
+fn mark_cancelled(state: &mut PlayState) -> Vec<String> {
+    let mut cancelled: Vec<String> = state
+        .definition
+        .tasks
+        .iter()
+        .map(|t| t.id.clone())
+        .filter(|id| !is_resolved(state, id))
+        .collect();
+    cancelled.sort();
+    state.cancelled_tasks.extend(cancelled.iter().cloned());
+    state.active_tasks.clear();
+    if !state.status.is_terminal() {
+        state.status = PlayStatus::Cancelled;
+    }
+    cancelled
+}
+
+/// Hold `task_def`, an approval task, open for a human decision instead
+/// of enqueueing it. `iso` formats epoch ms as a timestamp.
+fn open_approval(
+    state: &mut PlayState,
+    task_def: &PlayTaskDefinition,
+    now_ms: u64,
+    iso: fn(u64) -> String,
+) {
+    let deadline_ms = task_def.timeout_secs.map(|s| now_ms + s * 1000);
+    let requested_at = iso(now_ms);
+    state.active_tasks.insert(task_def.id.clone());
+    state
+        .task_meta
+        .entry(task_def.id.clone())
+        .or_default()
+        .launched_at = Some(requested_at.clone());
+    state.approvals.insert(
+        task_def.id.clone(),
+        ApprovalGate {
+            status: PlayApprovalState {
+                requested_at,
+                deadline: deadline_ms.map(iso),
+                escalation_id: None,
+                params: task_def.params.clone(),
+                decision: None,
+            },
+            deadline_ms,
+        },
+    );
+}
+
+/// Whether `task_id` is an approval task still waiting for a decision.
+fn is_awaiting_approval(state: &PlayState, task_id: &str) -> bool {
+    state.active_tasks.contains(task_id)
+        && state
+            .approvals
+            .get(task_id)
+            .is_some_and(|g| g.status.decision.is_none())
+}
+
+/// Check that `actor` may decide `task_id` now. The error is the API
+/// error code, message and HTTP status.
+fn check_approval(
+    state: &PlayState,
+    task_id: &str,
+    actor: &str,
+) -> std::result::Result<(), (&'static str, String, u16)> {
+    let Some(task_def) = state.definition.tasks.iter().find(|t| t.id == task_id) else {
+        return Err((
+            "PLAY_TASK_NOT_FOUND",
+            format!("play run has no task '{task_id}'"),
+            404,
+        ));
+    };
+    if !task_def.is_approval() {
+        return Err((
+            "NOT_AN_APPROVAL_TASK",
+            format!("task '{task_id}' is not an approval task"),
+            409,
+        ));
+    }
+    if state.status.is_terminal() || !is_awaiting_approval(state, task_id) {
+        return Err((
+            "APPROVAL_NOT_PENDING",
+            format!("task '{task_id}' is not awaiting approval"),
+            409,
+        ));
+    }
+    let approvers = task_def
+        .approval
+        .as_ref()
+        .map_or(&[][..], |a| a.approvers.as_slice());
+    if !approvers.is_empty() && !approvers.iter().any(|a| a == actor) {
+        return Err((
+            "APPROVER_NOT_ALLOWED",
+            format!("'{actor}' may not decide task '{task_id}'"),
+            403,
+        ));
+    }
+    Ok(())
+}
+
+/// Resolve an awaiting approval task with `decision`: `approve` completes
+/// it with the decision as its result, anything else fails it. Returns
+/// `true` if the state changed.
+fn apply_approval(state: &mut PlayState, task_id: &str, decision: PlayApprovalDecision) -> bool {
+    if !is_awaiting_approval(state, task_id) {
+        return false;
+    }
+    let outcome = if decision.decision == HumanDecisionType::Approve {
+        PlayTaskOutcome {
+            task_id: task_id.to_string(),
+            status: "completed".to_string(),
+            result: serde_json::to_value(&decision).ok(),
+            error: None,
+            agent_id: None,
+        }
+    } else {
+        let mut error = format!("{} by {}", decision.decision.as_str(), decision.actor);
+        if let Some(reason) = &decision.reason {
+            error.push_str(": ");
+            error.push_str(reason);
+        }
+        PlayTaskOutcome {
+            task_id: task_id.to_string(),
+            status: "failed".to_string(),
+            result: None,
+            error: Some(error),
+            agent_id: None,
+        }
+    };
+    state
+        .task_meta
+        .entry(task_id.to_string())
+        .or_default()
+        .finished_at = Some(decision.decided_at.clone());
+    if let Some(gate) = state.approvals.get_mut(task_id) {
+        gate.status.decision = Some(decision);
+        gate.deadline_ms = None;
+    }
+    apply_outcome(state, &outcome)
+}
+
+/// Awaiting approval tasks whose deadline has passed at `now_ms`, with
+/// what their policy says to do about it.
+fn expired_approvals(state: &PlayState, now_ms: u64) -> Vec<(String, PlayApprovalTimeout)> {
+    if state.status.is_terminal() {
+        return Vec::new();
+    }
+    let mut expired: Vec<_> = state
+        .approvals
+        .iter()
+        .filter(|(id, gate)| {
+            is_awaiting_approval(state, id) && gate.deadline_ms.is_some_and(|d| d <= now_ms)
+        })
+        .map(|(id, _)| {
+            let on_timeout = state
+                .definition
+                .tasks
+                .iter()
+                .find(|t| &t.id == id)
+                .and_then(|t| t.approval.as_ref())
+                .map(|a| a.on_timeout)
+                .unwrap_or_default();
+            (id.clone(), on_timeout)
+        })
+        .collect();
+    expired.sort_by(|a, b| a.0.cmp(&b.0));
+    expired
+}
+
+/// Record the policy escalation opened for a timed-out approval task,
+/// which keeps waiting without a deadline. Returns `true` if the state
+/// changed.
+fn escalate_approval(state: &mut PlayState, task_id: &str, escalation_id: String) -> bool {
+    if !is_awaiting_approval(state, task_id) {
+        return false;
+    }
+    let Some(gate) = state.approvals.get_mut(task_id) else {
+        return false;
+    };
+    gate.status.escalation_id = Some(escalation_id);
+    gate.deadline_ms = None;
+    true
+}
+
+/// Fail a timed-out approval task as if it had been rejected. Returns
+/// `true` if the state changed.
+fn time_out_approval(state: &mut PlayState, task_id: &str, now: &str) -> bool {
+    if !is_awaiting_approval(state, task_id) {
+        return false;
+    }
+    if let Some(gate) = state.approvals.get_mut(task_id) {
+        gate.deadline_ms = None;
+    }
+    state
+        .task_meta
+        .entry(task_id.to_string())
+        .or_default()
+        .finished_at = Some(now.to_string());
+    apply_outcome(
+        state,
+        &PlayTaskOutcome {
+            task_id: task_id.to_string(),
+            status: "failed".to_string(),
+            result: None,
+            error: Some("approval timed out".to_string()),
+            agent_id: None,
+        },
+    )
+}
+
+/// Earliest deadline among approval tasks still awaiting a decision.
+fn next_approval_deadline(state: &PlayState) -> Option<u64> {
+    if state.status.is_terminal() {
+        return None;
+    }
+    state
+        .approvals
+        .iter()
+        .filter(|(id, _)| is_awaiting_approval(state, id))
+        .filter_map(|(_, gate)| gate.deadline_ms)
+        .min()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::PlayTaskDefinition;
+    use crate::models::{PlayErrorPolicy, PlayTaskDefinition};
 
     fn task(id: &str, deps: &[&str]) -> PlayTaskDefinition {
         PlayTaskDefinition {
@@ -443,6 +1675,14 @@ mod tests {
             priority: 0,
             params: None,
             depends_on: deps.iter().map(|s| s.to_string()).collect(),
+            requires: Vec::new(),
+            when: None,
+            map: None,
+            reduce: None,
+            retry: None,
+            timeout_secs: None,
+            on_failure: None,
+            approval: None,
         }
     }
 
@@ -452,12 +1692,28 @@ mod tests {
                 name: "p".to_string(),
                 goal: "g".to_string(),
                 tasks,
+                on_error: PlayErrorPolicy::FailFast,
+                inputs: Default::default(),
             },
             run_id: "run-1".to_string(),
             tenant_id: "tenant-test".to_string(),
             completed_tasks: HashSet::new(),
             active_tasks: HashSet::new(),
             state_version: 0,
+            cancelled_tasks: HashSet::new(),
+            results: HashMap::new(),
+            result_refs: HashSet::new(),
+            failed_tasks: HashMap::new(),
+            skipped_tasks: HashSet::new(),
+            map_items: HashMap::new(),
+            triggered_handlers: HashMap::new(),
+            failed_task: None,
+            status: PlayStatus::Running,
+            outcome_recorded: false,
+            task_meta: HashMap::new(),
+            inputs: BTreeMap::new(),
+            approvals: HashMap::new(),
+            metadata: None,
         }
     }
 
@@ -538,6 +1794,8 @@ mod tests {
                 task("child_b", &["root"]),
                 task("grandchild", &["child_a", "child_b"]),
             ],
+            on_error: PlayErrorPolicy::FailFast,
+            inputs: Default::default(),
         }
     }
 
@@ -551,6 +1809,8 @@ mod tests {
                 task("middle", &["root"]),
                 task("leaf", &["middle"]),
             ],
+            on_error: PlayErrorPolicy::FailFast,
+            inputs: Default::default(),
         };
         let state = make_state(def.tasks.clone());
         let eligible = derive_to_launch(&state);
@@ -858,4 +2118,855 @@ mod tests {
         state.state_version = state.state_version.wrapping_add(1);
         assert_eq!(state.state_version, 2);
     }
+
+    #[test]
+    fn cancelled_play_launches_nothing_further() {
+        let mut state = make_state(vec![task("a", &[]), task("b", &["a"]), task("c", &[])]);
+        state.completed_tasks.insert("c".to_string());
+        state.active_tasks.insert("a".to_string());
+
+        assert_eq!(mark_cancelled(&mut state), vec!["a", "b"]);
+        assert!(state.active_tasks.is_empty());
+        assert!(
+            mark_cancelled(&mut state).is_empty(),
+            "cancel is idempotent"
+        );
+
+        // `a` finishing after the cancel must not release `b`.
+        mark_completed(&mut state, "a");
+        assert!(derive_to_launch(&state).is_empty());
+    }
+
+    // ── Conditional edges, map/reduce, on_failure ────────────────────
+
+    use crate::models::{PlayCondition, PlayConditionTest, PlayMap, PlayStatus};
+    use serde_json::json;
+
+    fn completed(state: &mut PlayState, id: &str, result: serde_json::Value) {
+        state.active_tasks.insert(id.to_string());
+        assert!(apply_outcome(
+            state,
+            &PlayTaskOutcome {
+                task_id: id.to_string(),
+                status: "completed".to_string(),
+                result: Some(result),
+                error: None,
+                agent_id: None,
+            },
+        ));
+        settle(state);
+    }
+
+    fn failed(state: &mut PlayState, id: &str, error: &str) {
+        state.active_tasks.insert(id.to_string());
+        assert!(apply_outcome(
+            state,
+            &PlayTaskOutcome {
+                task_id: id.to_string(),
+                status: "failed".to_string(),
+                result: None,
+                error: Some(error.to_string()),
+                agent_id: None,
+            },
+        ));
+        settle(state);
+    }
+
+    fn launch_ids(state: &PlayState) -> Vec<String> {
+        derive_to_launch(state).into_iter().map(|t| t.id).collect()
+    }
+
+    fn when(task_id: &str, path: &str, equals: serde_json::Value) -> PlayCondition {
+        PlayCondition {
+            task: task_id.to_string(),
+            path: path.to_string(),
+            test: PlayConditionTest::Equals(equals),
+        }
+    }
+
+    #[test]
+    fn conditional_edge_takes_only_the_matching_branch() {
+        let mut state = make_state(vec![
+            task("check", &[]),
+            PlayTaskDefinition {
+                when: Some(when("check", "/healthy", json!(true))),
+                ..task("celebrate", &["check"])
+            },
+            PlayTaskDefinition {
+                when: Some(when("check", "/healthy", json!(false))),
+                ..task("remediate", &["check"])
+            },
+            task("report", &["celebrate", "remediate"]),
+        ]);
+        completed(&mut state, "check", json!({"healthy": false}));
+
+        assert!(state.skipped_tasks.contains("celebrate"));
+        assert_eq!(launch_ids(&state), vec!["remediate"]);
+
+        // The join runs off whichever branch was taken.
+        completed(&mut state, "remediate", json!(null));
+        assert_eq!(launch_ids(&state), vec!["report"]);
+    }
+
+    #[test]
+    fn skip_propagates_until_a_task_has_a_completed_parent() {
+        let mut state = make_state(vec![
+            task("a", &[]),
+            PlayTaskDefinition {
+                when: Some(when("a", "", json!("go"))),
+                ..task("b", &["a"])
+            },
+            task("c", &["b"]),
+            task("d", &["c"]),
+        ]);
+        completed(&mut state, "a", json!("stop"));
+
+        assert_eq!(
+            state.skipped_tasks,
+            HashSet::from(["b".to_string(), "c".to_string(), "d".to_string()]),
+        );
+        assert!(launch_ids(&state).is_empty());
+    }
+
+    #[test]
+    fn map_fans_out_per_item_and_reduce_gets_results_in_order() {
+        let mut state = make_state(vec![
+            task("list", &[]),
+            PlayTaskDefinition {
+                params: Some(json!({"mode": "scan"})),
+                map: Some(PlayMap {
+                    from: "list".to_string(),
+                    path: "/hosts".to_string(),
+                }),
+                ..task("scan", &["list"])
+            },
+            PlayTaskDefinition {
+                reduce: Some("scan".to_string()),
+                ..task("summarize", &["scan"])
+            },
+        ]);
+        completed(&mut state, "list", json!({"hosts": ["h1", "h2"]}));
+
+        let launched = derive_to_launch(&state);
+        let ids: Vec<_> = launched.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["scan.0", "scan.1"]);
+        assert_eq!(
+            launched[1].params,
+            Some(json!({"mode": "scan", "item": "h2", "index": 1})),
+        );
+
+        // Children finishing out of order still reduce in item order.
+        completed(&mut state, "scan.1", json!("ok-2"));
+        assert!(!state.completed_tasks.contains("scan"));
+        assert!(launch_ids(&state).contains(&"scan.0".to_string()));
+        completed(&mut state, "scan.0", json!("ok-1"));
+
+        assert!(state.completed_tasks.contains("scan"));
+        let launched = derive_to_launch(&state);
+        assert_eq!(launched.len(), 1);
+        assert_eq!(launched[0].id, "summarize");
+        assert_eq!(
+            launched[0].params,
+            Some(json!({"results": ["ok-1", "ok-2"]}))
+        );
+    }
+
+    #[test]
+    fn map_over_empty_array_completes_without_children() {
+        let mut state = make_state(vec![
+            task("list", &[]),
+            PlayTaskDefinition {
+                map: Some(PlayMap {
+                    from: "list".to_string(),
+                    path: String::new(),
+                }),
+                ..task("scan", &["list"])
+            },
+            task("after", &["scan"]),
+        ]);
+        completed(&mut state, "list", json!([]));
+
+        assert_eq!(state.results.get("scan"), Some(&json!([])));
+        assert_eq!(launch_ids(&state), vec!["after"]);
+    }
+
+    #[test]
+    fn map_over_too_many_items_fails_the_map() {
+        let mut state = make_state(vec![
+            task("list", &[]),
+            PlayTaskDefinition {
+                map: Some(PlayMap {
+                    from: "list".to_string(),
+                    path: String::new(),
+                }),
+                ..task("scan", &["list"])
+            },
+        ]);
+        completed(&mut state, "list", json!(vec![0; MAX_MAP_ITEMS + 1]));
+
+        assert!(launch_ids(&state).is_empty());
+        assert!(!state.map_items.contains_key("scan"));
+        assert!(state.failed_tasks["scan"].contains("the limit is 256"));
+    }
+
+    #[test]
+    fn results_are_stored_apart_from_state_and_maps_rebuilt() {
+        let mut state = make_state(vec![task("list", &[]), task("each", &["list"])]);
+        completed(&mut state, "list", json!(["a", "b"]));
+        state
+            .map_items
+            .insert("each".to_string(), vec![json!("a"), json!("b")]);
+        completed(&mut state, "each.0", json!(1));
+        completed(&mut state, "each.1", json!(2));
+        assert!(state.completed_tasks.contains("each"));
+        // What `save_state` records: every result but the map's.
+        state.result_refs = ["list", "each.0", "each.1"].map(String::from).into();
+
+        let json = serde_json::to_value(&state).unwrap();
+        assert!(json.get("results").is_none());
+        let mut stored: PlayState = serde_json::from_value(json).unwrap();
+        assert!(stored.results.is_empty());
+        assert!(has_result(&stored, "list") && has_result(&stored, "each"));
+        assert!(!has_result(&stored, "missing"));
+
+        // What `load_state` reads back from the per-task keys.
+        for id in ["list", "each.0", "each.1"] {
+            stored
+                .results
+                .insert(id.to_string(), state.results[id].clone());
+        }
+        rebuild_map_results(&mut stored);
+        assert_eq!(stored.results["each"], json!([1, 2]));
+    }
+
+    #[test]
+    fn inline_results_of_older_states_are_still_read() {
+        let mut json = serde_json::to_value(make_state(vec![task("a", &[])])).unwrap();
+        json["results"] = json!({"a": 7});
+        let state: PlayState = serde_json::from_value(json).unwrap();
+        assert_eq!(state.results["a"], json!(7));
+        assert!(state.result_refs.is_empty());
+    }
+
+    #[test]
+    fn map_over_non_array_fails_the_run() {
+        let mut state = make_state(vec![
+            task("list", &[]),
+            PlayTaskDefinition {
+                map: Some(PlayMap {
+                    from: "list".to_string(),
+                    path: "/missing".to_string(),
+                }),
+                ..task("scan", &["list"])
+            },
+        ]);
+        completed(&mut state, "list", json!({}));
+
+        assert_eq!(state.failed_task.as_deref(), Some("scan"));
+        assert!(launch_ids(&state).is_empty());
+    }
+
+    #[test]
+    fn failed_map_child_fails_the_map_into_its_handler() {
+        let mut state = make_state(vec![
+            task("list", &[]),
+            PlayTaskDefinition {
+                map: Some(PlayMap {
+                    from: "list".to_string(),
+                    path: String::new(),
+                }),
+                on_failure: Some("cleanup".to_string()),
+                ..task("scan", &["list"])
+            },
+            task("cleanup", &[]),
+        ]);
+        completed(&mut state, "list", json!(["h1", "h2"]));
+        failed(&mut state, "scan.1", "unreachable");
+
+        assert_eq!(state.failed_tasks["scan"], "scan.1: unreachable");
+        assert_eq!(state.failed_task, None);
+        let launched = derive_to_launch(&state);
+        // scan.0 is still leased on TLM; only the handler is new.
+        assert_eq!(launched[0].id, "cleanup");
+        assert_eq!(
+            launched[0].params,
+            Some(json!({"failure": {"task": "scan", "error": "scan.1: unreachable"}})),
+        );
+    }
+
+    #[test]
+    fn on_failure_handler_runs_instead_of_dependents() {
+        let mut state = make_state(vec![
+            PlayTaskDefinition {
+                on_failure: Some("rollback".to_string()),
+                ..task("deploy", &[])
+            },
+            task("verify", &["deploy"]),
+            task("rollback", &[]),
+            task("notify", &["verify", "rollback"]),
+        ]);
+        assert_eq!(
+            launch_ids(&state),
+            vec!["deploy"],
+            "handler waits for a failure"
+        );
+
+        failed(&mut state, "deploy", "exit 1");
+        assert!(state.skipped_tasks.contains("verify"));
+        assert_eq!(launch_ids(&state), vec!["rollback"]);
+
+        completed(&mut state, "rollback", json!(null));
+        assert_eq!(launch_ids(&state), vec!["notify"]);
+    }
+
+    #[test]
+    fn untriggered_handler_is_skipped_once_its_task_succeeds() {
+        let mut state = make_state(vec![
+            PlayTaskDefinition {
+                on_failure: Some("rollback".to_string()),
+                ..task("deploy", &[])
+            },
+            task("rollback", &[]),
+            task("notify", &["deploy", "rollback"]),
+        ]);
+        completed(&mut state, "deploy", json!(null));
+
+        assert!(state.skipped_tasks.contains("rollback"));
+        assert_eq!(launch_ids(&state), vec!["notify"]);
+    }
+
+    #[test]
+    fn unhandled_failure_fails_the_run() {
+        let mut state = make_state(vec![task("a", &[]), task("b", &[]), task("c", &["a"])]);
+        state.active_tasks.insert("b".to_string());
+        failed(&mut state, "a", "boom");
+
+        assert_eq!(state.failed_task.as_deref(), Some("a"));
+        assert!(launch_ids(&state).is_empty());
+        // b finishing afterwards releases nothing.
+        completed(&mut state, "b", json!(null));
+        assert!(launch_ids(&state).is_empty());
+    }
+
+    #[test]
+    fn repeated_outcome_is_a_no_op() {
+        let mut state = make_state(vec![task("a", &[])]);
+        completed(&mut state, "a", json!(1));
+        let outcome = PlayTaskOutcome {
+            task_id: "a".to_string(),
+            status: "failed".to_string(),
+            result: None,
+            error: Some("late".to_string()),
+            agent_id: None,
+        };
+        assert!(!apply_outcome(&mut state, &outcome));
+        assert!(state.failed_tasks.is_empty());
+        assert_eq!(state.results["a"], json!(1));
+    }
+
+    #[test]
+    fn task_event_accepts_bare_task_id_and_outcome() {
+        let legacy: TaskEvent = serde_json::from_str(r#""root""#).unwrap();
+        let outcome = legacy.into_outcome();
+        assert_eq!(outcome.task_id, "root");
+        assert_eq!(outcome.status, "completed");
+
+        let event: TaskEvent =
+            serde_json::from_str(r#"{"task_id":"scan.0","status":"failed","error":"x"}"#).unwrap();
+        let outcome = event.into_outcome();
+        assert_eq!(outcome.task_id, "scan.0");
+        assert_eq!(outcome.error.as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn cancelled_map_launches_no_more_children() {
+        let mut state = make_state(vec![
+            task("list", &[]),
+            PlayTaskDefinition {
+                map: Some(PlayMap {
+                    from: "list".to_string(),
+                    path: String::new(),
+                }),
+                ..task("scan", &["list"])
+            },
+        ]);
+        completed(&mut state, "list", json!([1, 2]));
+        assert_eq!(mark_cancelled(&mut state), vec!["scan"]);
+        assert!(launch_ids(&state).is_empty());
+    }
+
+    // ── Run status: running → succeeded / failed / cancelled ─────────
+
+    #[test]
+    fn run_succeeds_once_every_task_is_resolved() {
+        let mut state = make_state(vec![
+            task("a", &[]),
+            PlayTaskDefinition {
+                when: Some(when("a", "", json!(true))),
+                ..task("b", &["a"])
+            },
+        ]);
+        assert!(!advance_status(&mut state));
+        completed(&mut state, "a", json!(false));
+        assert!(advance_status(&mut state));
+        assert_eq!(state.status, PlayStatus::Succeeded);
+    }
+
+    #[test]
+    fn fail_fast_fails_the_run_with_work_still_in_flight() {
+        let mut state = make_state(vec![task("a", &[]), task("b", &[]), task("c", &["b"])]);
+        state.active_tasks.insert("b".to_string());
+        failed(&mut state, "a", "boom");
+
+        assert!(state.active_tasks.contains("b"));
+        assert!(advance_status(&mut state));
+        assert_eq!(state.status, PlayStatus::Failed);
+        let payload = run_outcome_payload(&state);
+        assert_eq!(payload["failed_task"], "a");
+        assert_eq!(payload["failed_tasks"], json!({"a": "boom"}));
+    }
+
+    #[test]
+    fn continue_on_error_runs_independent_branches_then_fails() {
+        let mut state = make_state(vec![
+            task("a", &[]),
+            task("a_next", &["a"]),
+            task("b", &[]),
+            task("b_next", &["b"]),
+        ]);
+        state.definition.on_error = PlayErrorPolicy::Continue;
+        state.active_tasks.insert("b".to_string());
+        failed(&mut state, "a", "boom");
+
+        assert!(!advance_status(&mut state));
+        assert_eq!(state.status, PlayStatus::Running);
+        assert!(state.skipped_tasks.contains("a_next"));
+
+        completed(&mut state, "b", json!(null));
+        assert_eq!(launch_ids(&state), vec!["b_next"]);
+        assert!(!advance_status(&mut state));
+
+        completed(&mut state, "b_next", json!(null));
+        assert!(advance_status(&mut state));
+        assert_eq!(state.status, PlayStatus::Failed);
+    }
+
+    #[test]
+    fn handled_failure_lets_the_run_succeed() {
+        let mut state = make_state(vec![
+            PlayTaskDefinition {
+                on_failure: Some("rollback".to_string()),
+                ..task("deploy", &[])
+            },
+            task("rollback", &[]),
+        ]);
+        failed(&mut state, "deploy", "exit 1");
+        assert!(!advance_status(&mut state), "rollback still to run");
+        completed(&mut state, "rollback", json!(null));
+        assert!(advance_status(&mut state));
+        assert_eq!(state.status, PlayStatus::Succeeded);
+    }
+
+    #[test]
+    fn terminal_status_sticks() {
+        let mut state = make_state(vec![task("a", &[]), task("b", &[])]);
+        state.active_tasks.insert("a".to_string());
+        mark_cancelled(&mut state);
+        assert_eq!(state.status, PlayStatus::Cancelled);
+        assert!(!advance_status(&mut state));
+
+        let mut state = make_state(vec![task("a", &[])]);
+        completed(&mut state, "a", json!(null));
+        advance_status(&mut state);
+        assert!(mark_cancelled(&mut state).is_empty());
+        assert_eq!(state.status, PlayStatus::Succeeded);
+    }
+
+    #[test]
+    fn run_status_reports_state_timings_and_result_refs() {
+        let mut state = make_state(vec![
+            PlayTaskDefinition {
+                on_failure: Some("rollback".to_string()),
+                ..task("build", &[])
+            },
+            task("ship", &["build"]),
+            task("rollback", &[]),
+        ]);
+        state.task_meta.insert(
+            "build".to_string(),
+            TaskMeta {
+                launched_at: Some("2026-01-01T00:00:00Z".to_string()),
+                finished_at: Some("2026-01-01T00:01:00Z".to_string()),
+                agent_id: Some("agent-1".to_string()),
+            },
+        );
+        completed(&mut state, "build", json!({"ok": true}));
+        state.active_tasks.insert("ship".to_string());
+        state.metadata = Some(json!({"ticket": "OPS-12"}));
+
+        let status = run_status(&state);
+        assert_eq!(status.play, "p");
+        assert_eq!(status.metadata, Some(json!({"ticket": "OPS-12"})));
+        assert_eq!(status.status, PlayStatus::Running);
+        let states: Vec<_> = status
+            .tasks
+            .iter()
+            .map(|t| (t.id.as_str(), t.state.as_str()))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                ("build", "completed"),
+                ("ship", "running"),
+                ("rollback", "skipped")
+            ]
+        );
+        let build = &status.tasks[0];
+        assert_eq!(build.agent_id.as_deref(), Some("agent-1"));
+        assert_eq!(build.on_failure.as_deref(), Some("rollback"));
+        assert_eq!(
+            build.result_ref.as_deref(),
+            Some("/v1/plays/runs/run-1/tasks/build/result")
+        );
+        assert!(status.tasks[1].result_ref.is_none());
+    }
+
+    #[test]
+    fn run_status_lists_map_children_before_the_map() {
+        let mut state = make_state(vec![task("list", &[]), task("each", &["list"])]);
+        completed(&mut state, "list", json!(null));
+        state
+            .map_items
+            .insert("each".to_string(), vec![json!(1), json!(2)]);
+        failed(&mut state, "each.1", "boom");
+
+        let status = run_status(&state);
+        let ids: Vec<_> = status.tasks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["list", "each.0", "each.1", "each"]);
+        assert_eq!(status.tasks[1].depends_on, vec!["list".to_string()]);
+        assert_eq!(status.tasks[2].state, "failed");
+        assert_eq!(status.tasks[2].error.as_deref(), Some("boom"));
+        assert_eq!(
+            status.tasks[3].depends_on,
+            vec!["each.0".to_string(), "each.1".to_string()]
+        );
+    }
+
+    // ── Parameter templates ──────────────────────────────────────────
+
+    fn with_params(task_def: PlayTaskDefinition, params: serde_json::Value) -> PlayTaskDefinition {
+        PlayTaskDefinition {
+            params: Some(params),
+            ..task_def
+        }
+    }
+
+    #[test]
+    fn prepare_launches_renders_inputs_and_upstream_results() {
+        let mut state = make_state(vec![
+            with_params(
+                task("investigate", &[]),
+                json!({"svc": "{{inputs.service}}"}),
+            ),
+            with_params(
+                task("fix", &["investigate"]),
+                json!({"cause": "{{tasks.investigate.result.root_cause}}",
+                       "note": "fix {{inputs.service}}"}),
+            ),
+        ]);
+        state.inputs = BTreeMap::from([("service".to_string(), json!("api"))]);
+
+        let launched = prepare_launches(&mut state);
+        assert_eq!(launched.len(), 1);
+        assert_eq!(launched[0].params, Some(json!({"svc": "api"})));
+
+        completed(&mut state, "investigate", json!({"root_cause": "disk"}));
+        let launched = prepare_launches(&mut state);
+        assert_eq!(launched.len(), 1);
+        assert_eq!(
+            launched[0].params,
+            Some(json!({"cause": "disk", "note": "fix api"}))
+        );
+    }
+
+    #[test]
+    fn completed_task_result_flows_into_downstream_params() {
+        use crate::models::TaskCompleteRequest;
+        use crate::task_do;
+
+        let mut state = make_state(vec![
+            task("investigate", &[]),
+            with_params(
+                task("fix", &["investigate"]),
+                json!({"cause": "{{tasks.investigate.result.root_cause}}",
+                       "hosts": "{{tasks.investigate.result.hosts}}"}),
+            ),
+        ]);
+        let launched = prepare_launches(&mut state);
+        assert_eq!(launched.len(), 1);
+        state.active_tasks.insert(launched[0].id.clone());
+
+        // The agent completes through TaskLeaseManager with the body
+        // `/mcp/task/:id/complete` forwards.
+        let agent_task = play_agent_task(&state, &launched[0], "ts".to_string());
+        let mut active = HashMap::from([(agent_task.id.clone(), agent_task.clone())]);
+        let body = serde_json::to_value(TaskCompleteRequest {
+            result: Some(json!({"root_cause": "disk", "hosts": ["a", "b"]})),
+        })
+        .unwrap();
+        let done = task_do::complete_task(&mut active, &agent_task.id, Some(body), "ts").unwrap();
+        assert!(apply_outcome(
+            &mut state,
+            &task_do::play_task_outcome(&done)
+        ));
+
+        let launched = prepare_launches(&mut state);
+        assert_eq!(launched.len(), 1);
+        assert_eq!(
+            launched[0].params,
+            Some(json!({"cause": "disk", "hosts": ["a", "b"]}))
+        );
+        assert_eq!(
+            state.results["investigate"],
+            json!({"root_cause": "disk", "hosts": ["a", "b"]})
+        );
+    }
+
+    #[test]
+    fn unresolvable_template_fails_the_task_into_its_handler() {
+        let mut state = make_state(vec![
+            task("investigate", &[]),
+            PlayTaskDefinition {
+                on_failure: Some("page".to_string()),
+                ..with_params(
+                    task("fix", &["investigate"]),
+                    json!({"cause": "{{tasks.investigate.result.root_cause}}"}),
+                )
+            },
+            task("page", &[]),
+        ]);
+        completed(&mut state, "investigate", json!({}));
+
+        let launched = prepare_launches(&mut state);
+        let ids: Vec<_> = launched.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["page"]);
+        assert_eq!(
+            state.failed_tasks["fix"],
+            "params: 'tasks.investigate.result.root_cause' not found"
+        );
+        assert_eq!(state.status, PlayStatus::Running);
+    }
+
+    #[test]
+    fn map_items_are_not_expanded_as_templates() {
+        let mut state = make_state(vec![
+            task("list", &[]),
+            PlayTaskDefinition {
+                map: Some(PlayMap {
+                    from: "list".to_string(),
+                    path: String::new(),
+                }),
+                ..with_params(
+                    task("each", &["list"]),
+                    json!({"svc": "{{inputs.service}}"}),
+                )
+            },
+        ]);
+        state.inputs = BTreeMap::from([("service".to_string(), json!("api"))]);
+        completed(&mut state, "list", json!(["{{inputs.service}}"]));
+
+        let launched = prepare_launches(&mut state);
+        assert_eq!(launched.len(), 1);
+        assert_eq!(
+            launched[0].params,
+            Some(json!({"svc": "api", "item": "{{inputs.service}}", "index": 0}))
+        );
+    }
+
+    fn approval_gate(id: &str, deps: &[&str]) -> PlayTaskDefinition {
+        PlayTaskDefinition {
+            task_type: crate::models::APPROVAL_TASK_TYPE.to_string(),
+            ..task(id, deps)
+        }
+    }
+
+    fn iso(ms: u64) -> String {
+        format!("t+{ms}")
+    }
+
+    fn decision(kind: HumanDecisionType, reason: Option<&str>) -> PlayApprovalDecision {
+        PlayApprovalDecision {
+            id: "hd-1".to_string(),
+            actor: "human:jane".to_string(),
+            decision: kind,
+            reason: reason.map(str::to_string),
+            decided_at: "t+5000".to_string(),
+        }
+    }
+
+    #[test]
+    fn approval_task_holds_the_run_until_approved() {
+        let mut state = make_state(vec![
+            task("diagnose", &[]),
+            PlayTaskDefinition {
+                params: Some(serde_json::json!({"plan": "restart api"})),
+                ..approval_gate("sign_off", &["diagnose"])
+            },
+            task("remediate", &["sign_off"]),
+        ]);
+        completed(&mut state, "diagnose", serde_json::json!({}));
+        let launch = derive_to_launch(&state);
+        assert_eq!(launch.len(), 1);
+        assert!(launch[0].is_approval());
+
+        open_approval(&mut state, &launch[0], 1000, iso);
+        assert_eq!(task_state(&state, "sign_off"), "awaiting_approval");
+        assert!(launch_ids(&state).is_empty());
+        assert_eq!(next_status(&state), PlayStatus::Running);
+        assert_eq!(next_approval_deadline(&state), None);
+
+        assert!(check_approval(&state, "sign_off", "human:jane").is_ok());
+        assert!(apply_approval(
+            &mut state,
+            "sign_off",
+            decision(HumanDecisionType::Approve, None)
+        ));
+        assert_eq!(task_state(&state, "sign_off"), "completed");
+        assert_eq!(state.results["sign_off"]["decision"], "approve");
+        assert_eq!(launch_ids(&state), vec!["remediate"]);
+
+        let status = run_status(&state);
+        let approval = status.tasks[1].approval.as_ref().unwrap();
+        assert_eq!(approval.requested_at, "t+1000");
+        assert_eq!(approval.params.as_ref().unwrap()["plan"], "restart api");
+        assert_eq!(approval.decision.as_ref().unwrap().actor, "human:jane");
+        assert_eq!(status.tasks[1].finished_at.as_deref(), Some("t+5000"));
+
+        let (code, _, http) = check_approval(&state, "sign_off", "human:jane").unwrap_err();
+        assert_eq!((code, http), ("APPROVAL_NOT_PENDING", 409));
+    }
+
+    #[test]
+    fn rejected_or_changes_requested_approval_fails_into_its_handler() {
+        for (kind, error) in [
+            (HumanDecisionType::Reject, "reject by human:jane: too risky"),
+            (
+                HumanDecisionType::RequestChanges,
+                "request_changes by human:jane: too risky",
+            ),
+        ] {
+            let mut state = make_state(vec![
+                PlayTaskDefinition {
+                    on_failure: Some("page".to_string()),
+                    ..approval_gate("sign_off", &[])
+                },
+                task("remediate", &["sign_off"]),
+                task("page", &[]),
+            ]);
+            settle(&mut state);
+            let launch = derive_to_launch(&state);
+            open_approval(&mut state, &launch[0], 0, iso);
+            assert!(apply_approval(
+                &mut state,
+                "sign_off",
+                decision(kind, Some("too risky"))
+            ));
+            settle(&mut state);
+            assert_eq!(state.failed_tasks["sign_off"], error);
+            assert_eq!(launch_ids(&state), vec!["page"]);
+            assert!(state.skipped_tasks.contains("remediate"));
+        }
+    }
+
+    #[test]
+    fn check_approval_rejects_unknown_work_and_unlisted_approvers() {
+        let mut state = make_state(vec![
+            task("work", &[]),
+            PlayTaskDefinition {
+                approval: Some(crate::models::PlayApprovalPolicy {
+                    approvers: vec!["human:sre-lead".to_string()],
+                    ..Default::default()
+                }),
+                ..approval_gate("sign_off", &[])
+            },
+        ]);
+        let code = |state: &PlayState, id: &str, actor: &str| {
+            check_approval(state, id, actor).err().map(|e| (e.0, e.2))
+        };
+        assert_eq!(
+            code(&state, "nope", "human:jane"),
+            Some(("PLAY_TASK_NOT_FOUND", 404))
+        );
+        assert_eq!(
+            code(&state, "work", "human:jane"),
+            Some(("NOT_AN_APPROVAL_TASK", 409))
+        );
+        // Not opened yet.
+        assert_eq!(
+            code(&state, "sign_off", "human:sre-lead"),
+            Some(("APPROVAL_NOT_PENDING", 409))
+        );
+        let gate = state.definition.tasks[1].clone();
+        open_approval(&mut state, &gate, 0, iso);
+        assert_eq!(
+            code(&state, "sign_off", "human:jane"),
+            Some(("APPROVER_NOT_ALLOWED", 403))
+        );
+        assert_eq!(code(&state, "sign_off", "human:sre-lead"), None);
+
+        mark_cancelled(&mut state);
+        assert_eq!(
+            code(&state, "sign_off", "human:sre-lead"),
+            Some(("APPROVAL_NOT_PENDING", 409))
+        );
+    }
+
+    #[test]
+    fn approval_timeouts_escalate_or_reject() {
+        let timed = |id: &str, secs: u64, on_timeout: PlayApprovalTimeout| PlayTaskDefinition {
+            timeout_secs: Some(secs),
+            approval: Some(crate::models::PlayApprovalPolicy {
+                approvers: Vec::new(),
+                on_timeout,
+            }),
+            ..approval_gate(id, &[])
+        };
+        let mut state = make_state(vec![
+            timed("escalated", 60, PlayApprovalTimeout::Escalate),
+            timed("rejected", 30, PlayApprovalTimeout::Reject),
+        ]);
+        for gate in derive_to_launch(&state) {
+            open_approval(&mut state, &gate, 0, iso);
+        }
+        assert_eq!(
+            state.approvals["rejected"].status.deadline.as_deref(),
+            Some("t+30000")
+        );
+        assert_eq!(next_approval_deadline(&state), Some(30_000));
+        assert!(expired_approvals(&state, 29_999).is_empty());
+        assert_eq!(
+            expired_approvals(&state, 60_000),
+            vec![
+                ("escalated".to_string(), PlayApprovalTimeout::Escalate),
+                ("rejected".to_string(), PlayApprovalTimeout::Reject),
+            ]
+        );
+
+        assert!(escalate_approval(
+            &mut state,
+            "escalated",
+            "esc-1".to_string()
+        ));
+        assert!(time_out_approval(&mut state, "rejected", "t+60000"));
+        assert_eq!(state.failed_tasks["rejected"], "approval timed out");
+        assert_eq!(task_state(&state, "escalated"), "awaiting_approval");
+        assert_eq!(
+            state.approvals["escalated"].status.escalation_id.as_deref(),
+            Some("esc-1")
+        );
+        // The escalated approval keeps waiting, with no further deadline.
+        assert!(expired_approvals(&state, 120_000).is_empty());
+        assert_eq!(next_approval_deadline(&state), None);
+        assert!(!time_out_approval(&mut state, "rejected", "t+60000"));
+    }
 }
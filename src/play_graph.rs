@@ -0,0 +1,198 @@
+//! DOT and Mermaid renderings of a play run's DAG, for
+//! `GET /v1/plays/runs/:run_id?format=dot|mermaid`. Nodes are coloured by
+//! task state; `on_failure` edges are dashed.
+
+use crate::models::PlayRunStatus;
+use std::fmt::Write;
+
+/// Fill colour per task state, shared by both renderings.
+fn state_color(state: &str) -> &'static str {
+    match state {
+        "completed" => "#9be29b",
+        "running" => "#8ecdf5",
+        "awaiting_approval" => "#f7d774",
+        "failed" => "#f29191",
+        "skipped" => "#eeeeee",
+        "cancelled" => "#c8c8c8",
+        _ => "#ffffff",
+    }
+}
+
+const STATES: [&str; 7] = [
+    "pending",
+    "running",
+    "awaiting_approval",
+    "completed",
+    "failed",
+    "skipped",
+    "cancelled",
+];
+
+/// Graphviz DOT, e.g. `dot -Tsvg`.
+pub fn to_dot(run: &PlayRunStatus) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph {} {{", dot_quote(&run.run_id));
+    let _ = writeln!(out, "  rankdir=LR;");
+    let _ = writeln!(
+        out,
+        "  label={};",
+        dot_quote(&format!("{} ({})", run.play, run.status.as_str()))
+    );
+    let _ = writeln!(out, "  node [shape=box, style=\"rounded,filled\"];");
+    for task in &run.tasks {
+        let _ = writeln!(
+            out,
+            "  {} [label={}, fillcolor=\"{}\"{}];",
+            dot_quote(&task.id),
+            dot_quote(&format!("{}\n{} · {}", task.id, task.task_type, task.state)),
+            state_color(&task.state),
+            if task.state == "skipped" {
+                ", fontcolor=\"#888888\""
+            } else {
+                ""
+            },
+        );
+    }
+    for task in &run.tasks {
+        for dep in &task.depends_on {
+            let _ = writeln!(out, "  {} -> {};", dot_quote(dep), dot_quote(&task.id));
+        }
+        if let Some(handler) = &task.on_failure {
+            let _ = writeln!(
+                out,
+                "  {} -> {} [style=dashed, label=\"on_failure\"];",
+                dot_quote(&task.id),
+                dot_quote(handler)
+            );
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Mermaid `flowchart`, e.g. for a Markdown code block. Task ids are
+/// replaced by positional node ids since Mermaid ids cannot hold every
+/// character a task id can.
+pub fn to_mermaid(run: &PlayRunStatus) -> String {
+    let node = |id: &str| {
+        run.tasks
+            .iter()
+            .position(|t| t.id == id)
+            .map_or_else(|| mermaid_id_fallback(id), |i| format!("t{i}"))
+    };
+    let mut out = String::from("flowchart LR\n");
+    for (i, task) in run.tasks.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "  t{i}[\"{}<br/>{} · {}\"]:::{}",
+            mermaid_escape(&task.id),
+            mermaid_escape(&task.task_type),
+            task.state,
+            task.state,
+        );
+    }
+    for task in &run.tasks {
+        for dep in &task.depends_on {
+            let _ = writeln!(out, "  {} --> {}", node(dep), node(&task.id));
+        }
+        if let Some(handler) = &task.on_failure {
+            let _ = writeln!(
+                out,
+                "  {} -. on_failure .-> {}",
+                node(&task.id),
+                node(handler)
+            );
+        }
+    }
+    for state in STATES {
+        let _ = writeln!(out, "  classDef {state} fill:{}", state_color(state));
+    }
+    out
+}
+
+fn dot_quote(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+/// Node id for an edge endpoint missing from `tasks` (not expected, but
+/// keeps the output parseable).
+fn mermaid_id_fallback(id: &str) -> String {
+    let cleaned: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("x_{cleaned}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PlayRunTask, PlayStatus};
+
+    fn task(id: &str, state: &str, deps: &[&str]) -> PlayRunTask {
+        PlayRunTask {
+            id: id.to_string(),
+            task_type: "work".to_string(),
+            state: state.to_string(),
+            depends_on: deps.iter().map(|d| d.to_string()).collect(),
+            on_failure: None,
+            launched_at: None,
+            finished_at: None,
+            agent_id: None,
+            error: None,
+            result_ref: None,
+            approval: None,
+        }
+    }
+
+    fn run() -> PlayRunStatus {
+        PlayRunStatus {
+            run_id: "run-1".to_string(),
+            play: "deploy".to_string(),
+            status: PlayStatus::Running,
+            failed_task: None,
+            metadata: None,
+            tasks: vec![
+                PlayRunTask {
+                    on_failure: Some("rollback".to_string()),
+                    ..task("build", "completed", &[])
+                },
+                task("ship \"it\"", "running", &["build"]),
+                task("rollback", "skipped", &[]),
+            ],
+        }
+    }
+
+    #[test]
+    fn dot_colours_nodes_by_state_and_dashes_handler_edges() {
+        let dot = to_dot(&run());
+        assert!(dot.starts_with("digraph \"run-1\" {\n"));
+        assert!(
+            dot.contains("\"build\" [label=\"build\\nwork · completed\", fillcolor=\"#9be29b\"];")
+        );
+        assert!(dot.contains("\"build\" -> \"ship \\\"it\\\"\";"));
+        assert!(dot.contains("\"build\" -> \"rollback\" [style=dashed, label=\"on_failure\"];"));
+        assert!(dot.contains("fontcolor=\"#888888\""));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn mermaid_uses_positional_ids_and_state_classes() {
+        let mermaid = to_mermaid(&run());
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("  t1[\"ship #quot;it#quot;<br/>work · running\"]:::running\n"));
+        assert!(mermaid.contains("  t0 --> t1\n"));
+        assert!(mermaid.contains("  t0 -. on_failure .-> t2\n"));
+        assert!(mermaid.contains("  classDef failed fill:#f29191\n"));
+    }
+}
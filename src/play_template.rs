@@ -0,0 +1,410 @@
+//! Parameter templates in play task `params`.
+//!
+//! A string value may reference launch inputs and upstream results:
+//!
+//! - `{{inputs.service}}`, `{{inputs.target.region}}`
+//! - `{{tasks.investigate.result}}`, `{{tasks.investigate.result.root_cause}}`
+//!
+//! Path segments after the first two index into objects by key and into
+//! arrays by position. A string that is exactly one template takes the
+//! referenced value as-is, so `"{{inputs.replicas}}"` becomes a number; a
+//! template embedded in longer text is spliced in as text.
+//!
+//! [`check`] validates the references of a definition up front (launch and
+//! `POST`/`PUT /v1/plays`): every input is declared, and every task whose
+//! result is read is an ancestor through `depends_on`, so its result is
+//! in when the template is rendered. [`render`] runs in PlayManager as
+//! each task is materialized.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde_json::Value;
+
+use crate::models::{PlayDefinition, PlayTaskDefinition};
+
+/// One `{{...}}` reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reference {
+    Input { name: String, path: Vec<String> },
+    TaskResult { task: String, path: Vec<String> },
+}
+
+/// Values templates resolve against.
+pub struct TemplateContext<'a> {
+    pub inputs: &'a BTreeMap<String, Value>,
+    pub results: &'a HashMap<String, Value>,
+    /// Tasks that finished without a result (skipped, or failed and
+    /// handled). Their references render as `null` rather than failing,
+    /// since a task may run with only some of its parents.
+    pub without_result: &'a dyn Fn(&str) -> bool,
+}
+
+/// Check every template in `def`'s task params.
+pub fn check(def: &PlayDefinition) -> Result<(), String> {
+    for task in &def.tasks {
+        let Some(params) = &task.params else {
+            continue;
+        };
+        let ancestors = ancestors(def, task);
+        for reference in references(params)? {
+            match reference {
+                Reference::Input { name, path } => {
+                    let Some(spec) = def.inputs.get(&name) else {
+                        return Err(format!(
+                            "task '{}': template references undeclared input '{name}'",
+                            task.id
+                        ));
+                    };
+                    if !path.is_empty() && !spec.input_type.is_container() {
+                        return Err(format!(
+                            "task '{}': input '{name}' is of type {} and has no fields",
+                            task.id,
+                            spec.input_type.as_str()
+                        ));
+                    }
+                }
+                Reference::TaskResult { task: source, .. } => {
+                    if !ancestors.contains(source.as_str()) {
+                        return Err(format!(
+                            "task '{}': template reads the result of '{source}', \
+                             which is not upstream of it in depends_on",
+                            task.id
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `params` with every template resolved.
+pub fn render(params: &Value, ctx: &TemplateContext<'_>) -> Result<Value, String> {
+    match params {
+        Value::String(s) => render_str(s, ctx),
+        Value::Array(items) => items
+            .iter()
+            .map(|v| render(v, ctx))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), render(v, ctx)?)))
+            .collect::<Result<_, String>>()
+            .map(Value::Object),
+        other => Ok(other.clone()),
+    }
+}
+
+/// Every reference in `params`, in order.
+pub fn references(params: &Value) -> Result<Vec<Reference>, String> {
+    let mut out = Vec::new();
+    collect_references(params, &mut out)?;
+    Ok(out)
+}
+
+fn collect_references(value: &Value, out: &mut Vec<Reference>) -> Result<(), String> {
+    match value {
+        Value::String(s) => {
+            for segment in split(s)? {
+                if let Segment::Template(reference) = segment {
+                    out.push(reference);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_references(item, out)?;
+            }
+        }
+        Value::Object(map) => {
+            for item in map.values() {
+                collect_references(item, out)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Template(Reference),
+}
+
+/// Split a string into literal text and parsed templates.
+fn split(s: &str) -> Result<Vec<Segment<'_>>, String> {
+    let mut segments = Vec::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            segments.push(Segment::Text(&rest[..start]));
+        }
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            return Err(format!("unclosed '{{{{' in template '{s}'"));
+        };
+        segments.push(Segment::Template(parse_reference(after[..end].trim())?));
+        rest = &after[end + 2..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+    Ok(segments)
+}
+
+fn parse_reference(expr: &str) -> Result<Reference, String> {
+    let parts: Vec<&str> = expr.split('.').collect();
+    let valid = |p: &&str| {
+        !p.is_empty()
+            && p.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+    if !parts.iter().all(valid) {
+        return Err(format!("invalid template reference '{{{{{expr}}}}}'"));
+    }
+    let path = |from: usize| parts[from..].iter().map(|p| p.to_string()).collect();
+    match parts.as_slice() {
+        ["inputs", name, ..] => Ok(Reference::Input {
+            name: name.to_string(),
+            path: path(2),
+        }),
+        ["tasks", task, "result", ..] => Ok(Reference::TaskResult {
+            task: task.to_string(),
+            path: path(3),
+        }),
+        _ => Err(format!(
+            "template reference '{{{{{expr}}}}}' must start with 'inputs.<name>' \
+             or 'tasks.<id>.result'"
+        )),
+    }
+}
+
+fn render_str(s: &str, ctx: &TemplateContext<'_>) -> Result<Value, String> {
+    if !s.contains("{{") {
+        return Ok(Value::String(s.to_string()));
+    }
+    let segments = split(s)?;
+    if let [Segment::Template(reference)] = segments.as_slice() {
+        return resolve(reference, ctx);
+    }
+    let mut out = String::new();
+    for segment in &segments {
+        match segment {
+            Segment::Text(text) => out.push_str(text),
+            Segment::Template(reference) => match resolve(reference, ctx)? {
+                Value::String(v) => out.push_str(&v),
+                Value::Null => {}
+                other => out.push_str(&other.to_string()),
+            },
+        }
+    }
+    Ok(Value::String(out))
+}
+
+fn resolve(reference: &Reference, ctx: &TemplateContext<'_>) -> Result<Value, String> {
+    let (root, path, what) = match reference {
+        Reference::Input { name, path } => match ctx.inputs.get(name) {
+            Some(v) => (v, path, format!("inputs.{name}")),
+            None => return Err(format!("input '{name}' was not provided")),
+        },
+        Reference::TaskResult { task, path } => match ctx.results.get(task) {
+            Some(v) => (v, path, format!("tasks.{task}.result")),
+            None if (ctx.without_result)(task) => return Ok(Value::Null),
+            None => return Err(format!("task '{task}' has no result")),
+        },
+    };
+    let mut value = root;
+    let mut at = what;
+    for key in path {
+        let next = match value {
+            Value::Object(map) => map.get(key),
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+        at = format!("{at}.{key}");
+        value = next.ok_or_else(|| format!("'{at}' not found"))?;
+    }
+    Ok(value.clone())
+}
+
+/// Tasks `task` transitively depends on.
+fn ancestors<'a>(def: &'a PlayDefinition, task: &'a PlayTaskDefinition) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    let mut stack: Vec<&str> = task.depends_on.iter().map(String::as_str).collect();
+    while let Some(id) = stack.pop() {
+        if seen.insert(id) {
+            if let Some(parent) = def.tasks.iter().find(|t| t.id == id) {
+                stack.extend(parent.depends_on.iter().map(String::as_str));
+            }
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PlayErrorPolicy, PlayInputSpec, PlayInputType};
+    use serde_json::json;
+
+    fn task(id: &str, deps: &[&str], params: Value) -> PlayTaskDefinition {
+        PlayTaskDefinition {
+            id: id.to_string(),
+            task_type: "t".to_string(),
+            priority: 0,
+            params: Some(params),
+            depends_on: deps.iter().map(|d| d.to_string()).collect(),
+            requires: Vec::new(),
+            when: None,
+            map: None,
+            reduce: None,
+            retry: None,
+            timeout_secs: None,
+            on_failure: None,
+            approval: None,
+        }
+    }
+
+    fn def(tasks: Vec<PlayTaskDefinition>) -> PlayDefinition {
+        let input = |input_type| PlayInputSpec {
+            input_type,
+            default: None,
+            description: None,
+        };
+        PlayDefinition {
+            name: "p".to_string(),
+            goal: "g".to_string(),
+            tasks,
+            on_error: PlayErrorPolicy::FailFast,
+            inputs: BTreeMap::from([
+                ("service".to_string(), input(PlayInputType::String)),
+                ("target".to_string(), input(PlayInputType::Object)),
+            ]),
+        }
+    }
+
+    fn render_with(
+        params: Value,
+        inputs: &BTreeMap<String, Value>,
+        results: &HashMap<String, Value>,
+    ) -> Result<Value, String> {
+        let skipped = |id: &str| id == "skipped";
+        let ctx = TemplateContext {
+            inputs,
+            results,
+            without_result: &skipped,
+        };
+        render(&params, &ctx)
+    }
+
+    #[test]
+    fn parses_input_and_task_result_references() {
+        let refs = references(&json!({
+            "a": "{{ inputs.service }}",
+            "b": ["x {{tasks.investigate.result.causes.0}} y"],
+            "c": 3,
+        }))
+        .unwrap();
+        assert_eq!(
+            refs,
+            vec![
+                Reference::Input {
+                    name: "service".to_string(),
+                    path: vec![],
+                },
+                Reference::TaskResult {
+                    task: "investigate".to_string(),
+                    path: vec!["causes".to_string(), "0".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_references() {
+        for bad in [
+            "{{inputs}}",
+            "{{tasks.a}}",
+            "{{tasks.a.output}}",
+            "{{env.HOME}}",
+            "{{inputs..x}}",
+            "{{inputs.service",
+        ] {
+            assert!(references(&json!(bad)).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn check_requires_declared_inputs_and_upstream_results() {
+        let ok = def(vec![
+            task("investigate", &[], json!({"svc": "{{inputs.service}}"})),
+            task("fix", &["investigate"], json!({})),
+            task(
+                "verify",
+                &["fix"],
+                json!({"cause": "{{tasks.investigate.result.root_cause}}",
+                       "region": "{{inputs.target.region}}"}),
+            ),
+        ]);
+        assert_eq!(check(&ok), Ok(()));
+
+        let undeclared = def(vec![task("a", &[], json!("{{inputs.nope}}"))]);
+        assert!(check(&undeclared)
+            .unwrap_err()
+            .contains("undeclared input 'nope'"));
+
+        let scalar_path = def(vec![task("a", &[], json!("{{inputs.service.name}}"))]);
+        assert!(check(&scalar_path).unwrap_err().contains("has no fields"));
+
+        let sibling = def(vec![
+            task("a", &[], json!({})),
+            task("b", &[], json!("{{tasks.a.result}}")),
+        ]);
+        assert!(check(&sibling).unwrap_err().contains("not upstream"));
+    }
+
+    #[test]
+    fn whole_string_templates_keep_their_type() {
+        let inputs = BTreeMap::from([("replicas".to_string(), json!(3))]);
+        let results = HashMap::from([(
+            "investigate".to_string(),
+            json!({"root_cause": "disk full", "hosts": ["h1", "h2"]}),
+        )]);
+        let rendered = render_with(
+            json!({
+                "replicas": "{{inputs.replicas}}",
+                "hosts": "{{ tasks.investigate.result.hosts }}",
+                "first": "{{tasks.investigate.result.hosts.0}}",
+                "summary": "fix {{tasks.investigate.result.root_cause}} x{{inputs.replicas}}",
+                "plain": "no templates",
+            }),
+            &inputs,
+            &results,
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            json!({
+                "replicas": 3,
+                "hosts": ["h1", "h2"],
+                "first": "h1",
+                "summary": "fix disk full x3",
+                "plain": "no templates",
+            })
+        );
+    }
+
+    #[test]
+    fn missing_values_fail_unless_the_task_ran_without_result() {
+        let inputs = BTreeMap::new();
+        let results = HashMap::from([("a".to_string(), json!({"x": 1}))]);
+        let err = render_with(json!("{{tasks.a.result.y}}"), &inputs, &results).unwrap_err();
+        assert_eq!(err, "'tasks.a.result.y' not found");
+        assert!(render_with(json!("{{tasks.b.result}}"), &inputs, &results).is_err());
+        assert_eq!(
+            render_with(json!("{{tasks.skipped.result.x}}"), &inputs, &results),
+            Ok(Value::Null)
+        );
+    }
+}
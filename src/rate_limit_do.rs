@@ -0,0 +1,403 @@
+//! Per-tenant API rate limiter (WS8).
+//!
+//! One `TenantRateLimiter` instance per tenant (named by `tenant_id`) owns
+//! the fixed window and circuit breaker from
+//! `tenant_security::check_rate_limit`. `fetch` in `lib.rs` asks it before
+//! routing (`POST /check`) and reports 5xx outcomes afterwards
+//! (`POST /outcome`) so repeated server failures trip the breaker. The
+//! tenant's limits are pushed at provisioning time (`PUT /config`) and the
+//! counters are read back by `/v1/metrics/pilot` (`GET /stats`).
+
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::models::TenantProvisionRequest;
+use crate::tenant_security::{
+    check_rate_limit, RateLimitError, RateLimitState, TenantRateLimitConfig,
+};
+
+const CONFIG_KEY: &str = "config";
+const STATE_KEY: &str = "state";
+
+/// Monotonic counters surfaced through the metrics endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitCounters {
+    pub allowed_total: u64,
+    pub rejected_rate_total: u64,
+    pub rejected_circuit_total: u64,
+    pub failures_reported_total: u64,
+}
+
+/// Everything the limiter persists besides its config, kept under one key
+/// so each `/check` is a single storage write.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct LimiterState {
+    pub window: RateLimitState,
+    pub counters: RateLimitCounters,
+}
+
+/// Response body of `POST /check`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckOutcome {
+    pub allowed: bool,
+    /// `RATE_LIMITED` or `CIRCUIT_OPEN` when rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Whole seconds, rounded up; only set when rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+    /// Lets the caller skip the `/outcome` round-trip for successful
+    /// responses when there is no failure streak to reset.
+    pub consecutive_failures: u32,
+}
+
+/// Response body of `GET /stats`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitStats {
+    pub config: TenantRateLimitConfig,
+    pub window_count: u64,
+    pub consecutive_failures: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_open_until_ms: Option<u64>,
+    pub counters: RateLimitCounters,
+}
+
+/// Pure helper: run one admission check and update the counters.
+pub(crate) fn admit(
+    tenant_id: &str,
+    config: &TenantRateLimitConfig,
+    state: &mut LimiterState,
+    now_ms: u64,
+) -> CheckOutcome {
+    match check_rate_limit(tenant_id, config, &mut state.window, now_ms) {
+        Ok(()) => {
+            state.counters.allowed_total += 1;
+            CheckOutcome {
+                allowed: true,
+                code: None,
+                message: None,
+                retry_after_secs: None,
+                consecutive_failures: state.window.consecutive_failures(),
+            }
+        }
+        Err(err) => {
+            let code = match err {
+                RateLimitError::Exceeded { .. } => {
+                    state.counters.rejected_rate_total += 1;
+                    "RATE_LIMITED"
+                }
+                RateLimitError::CircuitOpen { .. } => {
+                    state.counters.rejected_circuit_total += 1;
+                    "CIRCUIT_OPEN"
+                }
+            };
+            let retry_ms = state.window.retry_after_ms(config, now_ms);
+            CheckOutcome {
+                allowed: false,
+                code: Some(code.to_string()),
+                message: Some(err.to_string()),
+                retry_after_secs: Some(retry_ms.div_ceil(1000).max(1)),
+                consecutive_failures: state.window.consecutive_failures(),
+            }
+        }
+    }
+}
+
+/// Pure helper: fold a routed response's outcome into the breaker state.
+pub(crate) fn record_outcome(state: &mut LimiterState, success: bool) {
+    if success {
+        state.window.record_success();
+    } else {
+        state.window.record_failure();
+        state.counters.failures_reported_total += 1;
+    }
+}
+
+/// Pure helper: 5xx responses count against the circuit breaker; client
+/// errors (4xx) are the caller's problem and must not trip it.
+pub(crate) fn is_failure_status(status: u16) -> bool {
+    status >= 500
+}
+
+/// Body of `PUT /config`: a partial update merged over the stored config,
+/// so fields the caller does not set (e.g. `quota_bytes`, which
+/// provisioning never sends) keep their current value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitConfigUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst_limit: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota_bytes: Option<u64>,
+}
+
+impl RateLimitConfigUpdate {
+    pub(crate) fn apply(&self, mut config: TenantRateLimitConfig) -> TenantRateLimitConfig {
+        if let Some(rpm) = self.requests_per_minute {
+            config.requests_per_minute = rpm;
+        }
+        if let Some(burst) = self.burst_limit {
+            config.burst_limit = burst;
+        }
+        if let Some(quota) = self.quota_bytes {
+            config.quota_bytes = quota;
+        }
+        config
+    }
+}
+
+/// Pure helper: the limiter config a provisioning request asks for. Unset
+/// fields (and a non-positive rate) keep the tenant's `stored` limits, or
+/// `TenantRateLimitConfig::default()` for a new tenant.
+pub(crate) fn config_for_provision(
+    body: &TenantProvisionRequest,
+    stored: Option<TenantRateLimitConfig>,
+) -> TenantRateLimitConfig {
+    let current = stored.unwrap_or_default();
+    TenantRateLimitConfig {
+        requests_per_minute: body
+            .api_requests_per_minute
+            .filter(|rpm| *rpm > 0)
+            .unwrap_or(current.requests_per_minute),
+        burst_limit: body.api_burst_limit.unwrap_or(current.burst_limit),
+        ..current
+    }
+}
+
+pub(crate) fn stats_from(config: &TenantRateLimitConfig, state: &LimiterState) -> RateLimitStats {
+    RateLimitStats {
+        config: config.clone(),
+        window_count: state.window.window_count() as u64,
+        consecutive_failures: state.window.consecutive_failures(),
+        circuit_open_until_ms: state.window.circuit_open_until(),
+        counters: state.counters.clone(),
+    }
+}
+
+#[derive(Deserialize)]
+struct OutcomeBody {
+    success: bool,
+}
+
+#[durable_object]
+pub struct TenantRateLimiter {
+    state: State,
+    #[allow(dead_code)]
+    env: Env,
+}
+
+impl TenantRateLimiter {
+    async fn load(&self) -> Result<(TenantRateLimitConfig, LimiterState)> {
+        let storage = self.state.storage();
+        let config = storage
+            .get::<TenantRateLimitConfig>(CONFIG_KEY)
+            .await?
+            .unwrap_or_default();
+        let state = storage
+            .get::<LimiterState>(STATE_KEY)
+            .await?
+            .unwrap_or_default();
+        Ok((config, state))
+    }
+}
+
+impl DurableObject for TenantRateLimiter {
+    fn new(state: State, env: Env) -> Self {
+        Self { state, env }
+    }
+
+    async fn fetch(&self, mut req: Request) -> Result<Response> {
+        let url = req.url()?;
+        let tenant_id = url
+            .query_pairs()
+            .find(|(k, _)| k == "tenant_id")
+            .map(|(_, v)| v.into_owned())
+            .unwrap_or_default();
+
+        match (req.method(), url.path()) {
+            (Method::Post, "/check") => {
+                let (config, mut state) = self.load().await?;
+                let outcome = admit(&tenant_id, &config, &mut state, js_sys::Date::now() as u64);
+                self.state.storage().put(STATE_KEY, &state).await?;
+                Response::from_json(&outcome)
+            }
+            (Method::Post, "/outcome") => {
+                let body: OutcomeBody = req.json().await?;
+                let (_, mut state) = self.load().await?;
+                record_outcome(&mut state, body.success);
+                self.state.storage().put(STATE_KEY, &state).await?;
+                Response::empty().map(|r| r.with_status(204))
+            }
+            (Method::Put, "/config") => {
+                let update: RateLimitConfigUpdate = req.json().await?;
+                let (stored, _) = self.load().await?;
+                let config = update.apply(stored);
+                if config.requests_per_minute == 0 {
+                    return Response::error("requests_per_minute must be positive", 400);
+                }
+                self.state.storage().put(CONFIG_KEY, &config).await?;
+                Response::from_json(&config)
+            }
+            (Method::Get, "/stats") => {
+                let (config, state) = self.load().await?;
+                Response::from_json(&stats_from(&config, &state))
+            }
+            (Method::Post, "/purge") => {
+                self.state.storage().delete_all().await?;
+                Response::empty().map(|r| r.with_status(204))
+            }
+            _ => Response::error("Not Found", 404),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(rpm: u64, burst: u64) -> TenantRateLimitConfig {
+        TenantRateLimitConfig {
+            requests_per_minute: rpm,
+            burst_limit: burst,
+            ..TenantRateLimitConfig::default()
+        }
+    }
+
+    #[test]
+    fn admit_counts_allowed_and_rejected() {
+        let cfg = config(2, 0);
+        let mut state = LimiterState::default();
+        assert!(admit("t1", &cfg, &mut state, 1_000).allowed);
+        assert!(admit("t1", &cfg, &mut state, 2_000).allowed);
+        let rejected = admit("t1", &cfg, &mut state, 3_000);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.code.as_deref(), Some("RATE_LIMITED"));
+        // The first minute's window ends at 60s → 57s from now.
+        assert_eq!(rejected.retry_after_secs, Some(57));
+        assert_eq!(state.counters.allowed_total, 2);
+        assert_eq!(state.counters.rejected_rate_total, 1);
+    }
+
+    #[test]
+    fn reported_failures_trip_the_circuit() {
+        let cfg = config(100, 0);
+        let mut state = LimiterState::default();
+        for _ in 0..5 {
+            record_outcome(&mut state, false);
+        }
+        assert_eq!(state.counters.failures_reported_total, 5);
+        let outcome = admit("t1", &cfg, &mut state, 10_000);
+        assert!(!outcome.allowed);
+        assert_eq!(outcome.code.as_deref(), Some("CIRCUIT_OPEN"));
+        assert_eq!(outcome.retry_after_secs, Some(30));
+        assert_eq!(state.counters.rejected_circuit_total, 1);
+    }
+
+    #[test]
+    fn success_resets_streak_before_threshold() {
+        let cfg = config(100, 0);
+        let mut state = LimiterState::default();
+        for _ in 0..4 {
+            record_outcome(&mut state, false);
+        }
+        record_outcome(&mut state, true);
+        let outcome = admit("t1", &cfg, &mut state, 10_000);
+        assert!(outcome.allowed);
+        assert_eq!(outcome.consecutive_failures, 0);
+    }
+
+    #[test]
+    fn retry_after_is_at_least_one_second() {
+        let cfg = config(1, 0);
+        let mut state = LimiterState::default();
+        assert!(admit("t1", &cfg, &mut state, 0).allowed);
+        let outcome = admit("t1", &cfg, &mut state, 59_999);
+        assert_eq!(outcome.retry_after_secs, Some(1));
+    }
+
+    #[test]
+    fn only_server_errors_count_as_failures() {
+        assert!(!is_failure_status(200));
+        assert!(!is_failure_status(404));
+        assert!(!is_failure_status(429));
+        assert!(is_failure_status(500));
+        assert!(is_failure_status(503));
+    }
+
+    #[test]
+    fn provision_config_defaults_and_overrides() {
+        let mut body = TenantProvisionRequest {
+            tenant_id: "t1".into(),
+            display_name: "T1".into(),
+            plan: String::new(),
+            quota_runs_per_minute: 0,
+            api_requests_per_minute: None,
+            api_burst_limit: None,
+            secret_scan_mode: None,
+        };
+        assert_eq!(
+            config_for_provision(&body, None),
+            TenantRateLimitConfig::default()
+        );
+
+        body.api_requests_per_minute = Some(600);
+        body.api_burst_limit = Some(0);
+        let cfg = config_for_provision(&body, None);
+        assert_eq!(cfg.requests_per_minute, 600);
+        assert_eq!(cfg.burst_limit, 0);
+
+        body.api_requests_per_minute = Some(0);
+        assert_eq!(config_for_provision(&body, None).requests_per_minute, 120);
+    }
+
+    #[test]
+    fn reprovision_without_limits_keeps_stored_limits() {
+        let body = TenantProvisionRequest {
+            tenant_id: "t1".into(),
+            display_name: "T1 renamed".into(),
+            plan: String::new(),
+            quota_runs_per_minute: 0,
+            api_requests_per_minute: None,
+            api_burst_limit: Some(7),
+            secret_scan_mode: None,
+        };
+        let cfg = config_for_provision(&body, Some(config(900, 50)));
+        assert_eq!(cfg.requests_per_minute, 900);
+        assert_eq!(cfg.burst_limit, 7);
+    }
+
+    #[test]
+    fn config_update_merges_over_stored_config() {
+        let stored = TenantRateLimitConfig {
+            quota_bytes: 42,
+            ..config(900, 50)
+        };
+        let update = RateLimitConfigUpdate {
+            requests_per_minute: Some(60),
+            ..Default::default()
+        };
+        let merged = update.apply(stored);
+        assert_eq!(merged.requests_per_minute, 60);
+        assert_eq!(merged.burst_limit, 50);
+        assert_eq!(merged.quota_bytes, 42);
+        assert_eq!(
+            RateLimitConfigUpdate::default().apply(config(1, 2)),
+            config(1, 2)
+        );
+    }
+
+    #[test]
+    fn stats_reflect_state() {
+        let cfg = config(10, 5);
+        let mut state = LimiterState::default();
+        admit("t1", &cfg, &mut state, 1_000);
+        let stats = stats_from(&cfg, &state);
+        assert_eq!(stats.window_count, 1);
+        assert_eq!(stats.counters.allowed_total, 1);
+        assert_eq!(stats.config.burst_limit, 5);
+        assert!(stats.circuit_open_until_ms.is_none());
+    }
+}
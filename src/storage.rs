@@ -1,5 +1,7 @@
 use worker::*;
 
+use crate::envelope::{self, TenantKeyring};
+
 /// Store a blob in R2 and return its size.
 pub async fn put_blob(bucket: &Bucket, key: &str, data: Vec<u8>) -> Result<u64> {
     let size = data.len() as u64;
@@ -22,6 +24,44 @@ pub async fn get_blob(bucket: &Bucket, key: &str) -> Result<Option<Vec<u8>>> {
     }
 }
 
+/// Store a tenant blob in R2, sealed with the tenant's active data key when
+/// encryption is configured. Returns the plaintext size.
+pub async fn put_sealed(
+    bucket: &Bucket,
+    key: &str,
+    data: Vec<u8>,
+    keyring: Option<&TenantKeyring<'_>>,
+) -> Result<u64> {
+    let size = data.len() as u64;
+    let body = match keyring {
+        Some(keyring) => keyring.seal(&data).await?,
+        None => data,
+    };
+    bucket.put(key, body).execute().await?;
+    Ok(size)
+}
+
+/// Retrieve a tenant blob, decrypting it if it was sealed. Objects written
+/// before encryption was enabled come back unchanged.
+pub async fn get_opened(
+    bucket: &Bucket,
+    key: &str,
+    keyring: Option<&TenantKeyring<'_>>,
+) -> Result<Option<Vec<u8>>> {
+    let Some(bytes) = get_blob(bucket, key).await? else {
+        return Ok(None);
+    };
+    if !envelope::is_sealed(&bytes) {
+        return Ok(Some(bytes));
+    }
+    match keyring {
+        Some(keyring) => keyring.open(&bytes).await.map(Some),
+        None => Err(Error::RustError(format!(
+            "{key} is encrypted but no master keys are configured"
+        ))),
+    }
+}
+
 /// Delete a blob from R2.
 pub async fn delete_blob(bucket: &Bucket, key: &str) -> Result<()> {
     bucket.delete(key).await
@@ -1,7 +1,17 @@
-use crate::models::{AgentTask, TaskFailRequest};
+use crate::capability::CapabilitySet;
+use crate::models::{
+    ActiveLease, AgentTask, CancelledTasks, DeadLetterList, DeadLetterTask, PendingNotifyInfo,
+    PlayTaskOutcome, QueueDepth, QueueSnapshot, TaskAttemptFailure, TaskCompleteRequest,
+    TaskFailRequest, TaskSocketClientMessage, TaskSocketServerMessage,
+};
+use crate::pagination::DeadLetterCursor;
 use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Poll, Waker};
 use worker::*;
 
 #[allow(dead_code)]
@@ -13,21 +23,75 @@ struct TaskLeaseState {
 
 /// Default lease window in milliseconds (5 minutes). Mirrors the values used
 /// inside the DO fetch handler.
-#[allow(dead_code)]
 pub(crate) const LEASE_WINDOW_MS: u64 = 300 * 1000;
 
 // ── Pure state-machine helpers (PR #142 unit coverage) ──────────────────
 //
 // Extracted so native unit tests can exercise queue/lease logic without
-// standing up DO storage. Production handlers still inline equivalent
-// logic; a follow-up refactor may wire these through directly.
+// standing up DO storage. `/claim`, `/fail`, the alarm and the dead-letter
+// routes go through these; the other handlers still inline equivalent logic.
 
-#[allow(dead_code)]
 pub(crate) fn enqueue_task(pending: &mut VecDeque<AgentTask>, task: AgentTask) {
     pending.push_back(task);
 }
 
-#[allow(dead_code)]
+/// A pending task gains one priority point for every `AGING_INTERVAL_MS` it
+/// has waited, so a steady stream of high-priority work cannot starve the
+/// rest of the queue indefinitely.
+pub(crate) const AGING_INTERVAL_MS: u64 = 60_000;
+
+/// Priority points a job loses for each lease it already holds. One point
+/// per lease means a job at priority 10 can hold roughly ten more agents
+/// than a job at priority 0 before the two alternate.
+pub(crate) const FAIR_SHARE_PENALTY: i64 = 1;
+
+/// Score used to order claims: `priority`, plus the aging boost, minus the
+/// fair-share penalty for leases the task's job already holds. Tasks without
+/// `enqueued_at_ms` (state persisted before aging existed) get no boost.
+pub(crate) fn effective_priority(task: &AgentTask, now_ms: u64, job_leases: usize) -> i64 {
+    let aging = task
+        .enqueued_at_ms
+        .map(|at| now_ms.saturating_sub(at) / AGING_INTERVAL_MS)
+        .unwrap_or(0);
+    let aging = i64::try_from(aging).unwrap_or(i64::MAX);
+    let penalty = i64::try_from(job_leases)
+        .unwrap_or(i64::MAX)
+        .saturating_mul(FAIR_SHARE_PENALTY);
+    i64::from(task.priority)
+        .saturating_add(aging)
+        .saturating_sub(penalty)
+}
+
+/// Pick the pending task `agent` should claim next: the highest
+/// `effective_priority` among tasks it can claim (task type and `requires`,
+/// see [`CapabilitySet::can_claim`]), with ties going to the task queued
+/// first.
+pub(crate) fn select_claim_index(
+    pending: &VecDeque<AgentTask>,
+    active: &HashMap<String, AgentTask>,
+    agent: &CapabilitySet,
+    now_ms: u64,
+) -> Option<usize> {
+    let mut job_leases: HashMap<&str, usize> = HashMap::new();
+    for task in active.values() {
+        *job_leases.entry(task.job_id.as_str()).or_default() += 1;
+    }
+
+    let mut best: Option<(usize, i64)> = None;
+    for (idx, task) in pending.iter().enumerate() {
+        if !agent.can_claim(&task.task_type, &task.requires) {
+            continue;
+        }
+        let leases = job_leases.get(task.job_id.as_str()).copied().unwrap_or(0);
+        let score = effective_priority(task, now_ms, leases);
+        // Strictly greater keeps the earliest queue position on ties.
+        if best.is_none_or(|(_, top)| score > top) {
+            best = Some((idx, score));
+        }
+    }
+    best.map(|(idx, _)| idx)
+}
+
 pub(crate) fn claim_next_task(
     pending: &mut VecDeque<AgentTask>,
     active: &mut HashMap<String, AgentTask>,
@@ -36,15 +100,14 @@ pub(crate) fn claim_next_task(
     now_ms: u64,
     lease_expires_at: String,
 ) -> Option<AgentTask> {
-    let task_idx = pending
-        .iter()
-        .position(|t| caps.is_empty() || caps.contains(&t.task_type))?;
+    let agent = CapabilitySet::new(agent_id, caps);
+    let task_idx = select_claim_index(pending, active, &agent, now_ms)?;
 
     let mut task = pending.remove(task_idx)?;
     task.status = "running".to_string();
     task.agent_id = Some(agent_id.to_string());
     task.lease_expires_at = Some(lease_expires_at);
-    let _ = now_ms;
+    task.deadline_ms = task.timeout_ms.map(|t| now_ms + t);
 
     active.insert(task.id.clone(), task.clone());
     Some(task)
@@ -68,36 +131,60 @@ pub(crate) fn find_expired_lease_ids(
     to_release
 }
 
+/// Error recorded on a task whose lease ran out without a heartbeat.
+pub(crate) const LEASE_EXPIRED_ERROR: &str = "lease expired";
+
+/// Error recorded on an attempt that ran past the task's `timeout_ms`.
+pub(crate) const TIMED_OUT_ERROR: &str = "timed out";
+
+/// Error PlayManager is given for a play task that was cancelled.
+pub(crate) const CANCELLED_ERROR: &str = "cancelled";
+
+/// Why the lease on `task` should be released at `now_ms`, if it should:
+/// the attempt's deadline passed (heartbeats or not), or the lease itself,
+/// expiring at `lease_expires_ms`, ran out.
+pub(crate) fn lease_release_error(
+    task: &AgentTask,
+    lease_expires_ms: Option<u64>,
+    now_ms: u64,
+) -> Option<&'static str> {
+    if task.deadline_ms.is_some_and(|d| d <= now_ms) {
+        Some(TIMED_OUT_ERROR)
+    } else if lease_expires_ms.is_some_and(|e| e <= now_ms) {
+        Some(LEASE_EXPIRED_ERROR)
+    } else {
+        None
+    }
+}
+
 #[allow(dead_code)]
 pub(crate) fn expire_leases(
     active: &mut HashMap<String, AgentTask>,
     pending: &mut VecDeque<AgentTask>,
+    dead_letter: &mut Vec<DeadLetterTask>,
     now_ms: u64,
     completed_at_iso: &str,
 ) -> Vec<String> {
-    let to_release = find_expired_lease_ids(active, now_ms);
+    let to_release: Vec<(String, &str)> = active
+        .iter()
+        .filter_map(|(id, task)| {
+            let lease = task
+                .lease_expires_at
+                .as_deref()
+                .and_then(|s| s.parse().ok());
+            lease_release_error(task, lease, now_ms).map(|error| (id.clone(), error))
+        })
+        .collect();
     let mut released = Vec::with_capacity(to_release.len());
-    for id in to_release {
-        if let Some(mut task) = active.remove(&id) {
-            if task.retry_count < task.max_retries {
-                task.status = "pending".to_string();
-                task.retry_count += 1;
-                task.agent_id = None;
-                task.lease_expires_at = None;
-                pending.push_back(task);
-            } else {
-                task.status = "failed".to_string();
-                task.result =
-                    Some(serde_json::json!({ "error": "lease expired and no retries left" }));
-                task.completed_at = Some(completed_at_iso.to_string());
-            }
+    for (id, error) in to_release {
+        if let Some(task) = active.remove(&id) {
+            retry_or_dead_letter(task, error, completed_at_iso, pending, dead_letter);
             released.push(id);
         }
     }
     released
 }
 
-#[allow(dead_code)]
 pub(crate) fn complete_task(
     active: &mut HashMap<String, AgentTask>,
     task_id: &str,
@@ -111,15 +198,45 @@ pub(crate) fn complete_task(
     Some(task)
 }
 
-#[allow(dead_code)]
 pub(crate) fn fail_task(
     active: &mut HashMap<String, AgentTask>,
     pending: &mut VecDeque<AgentTask>,
+    dead_letter: &mut Vec<DeadLetterTask>,
     task_id: &str,
     error: &str,
     completed_at_iso: &str,
 ) -> Option<AgentTask> {
-    let mut task = active.remove(task_id)?;
+    let task = active.remove(task_id)?;
+    Some(retry_or_dead_letter(
+        task,
+        error,
+        completed_at_iso,
+        pending,
+        dead_letter,
+    ))
+}
+
+/// Record a failed attempt on `task`, then requeue it while retries remain
+/// or move it to `dead_letter` once `max_retries` is spent. Returns the
+/// task as it now stands (`pending` or `failed`).
+pub(crate) fn retry_or_dead_letter(
+    mut task: AgentTask,
+    error: &str,
+    failed_at_iso: &str,
+    pending: &mut VecDeque<AgentTask>,
+    dead_letter: &mut Vec<DeadLetterTask>,
+) -> AgentTask {
+    task.failures.push(TaskAttemptFailure {
+        attempt: task.retry_count + 1,
+        agent_id: task.agent_id.clone(),
+        error: error.to_string(),
+        lease_expires_at: task.lease_expires_at.clone(),
+        failed_at: failed_at_iso.to_string(),
+    });
+    // A cancelled task is never retried, whatever its budget.
+    if task.cancel_requested {
+        return finish_cancelled(task, failed_at_iso);
+    }
     if task.retry_count < task.max_retries {
         task.status = "pending".to_string();
         task.retry_count += 1;
@@ -129,9 +246,375 @@ pub(crate) fn fail_task(
     } else {
         task.status = "failed".to_string();
         task.result = Some(serde_json::json!({ "error": error }));
-        task.completed_at = Some(completed_at_iso.to_string());
+        task.completed_at = Some(failed_at_iso.to_string());
+        dead_letter.push(DeadLetterTask {
+            task: task.clone(),
+            dead_lettered_at: failed_at_iso.to_string(),
+        });
     }
-    Some(task)
+    task
+}
+
+// ── Pending queue storage ───────────────────────────────────────────────
+
+/// Pending tasks are stored one per key, `pending:{seq}`, where `seq` is a
+/// zero-padded counter that only grows. Listing the prefix returns the
+/// queue in order, and an enqueue or claim writes only the keys it changes
+/// instead of the whole queue.
+pub(crate) const PENDING_PREFIX: &str = "pending:";
+
+/// Index of the pending queue by priority,
+/// `pending_priority:{band}:{seq}` → `seq`, where `band` sorts the highest
+/// priority first. Listing its front finds the tasks most likely to win a
+/// claim without reading the rest of the queue.
+pub(crate) const PENDING_PRIORITY_PREFIX: &str = "pending_priority:";
+
+/// Next `seq` to hand out, shared by pending and scheduled keys.
+pub(crate) const PENDING_SEQ_KEY: &str = "pending_seq";
+
+/// Number of pending tasks, kept in step with the keys so the backpressure
+/// check does not list the whole queue.
+pub(crate) const PENDING_LEN_KEY: &str = "pending_len";
+
+/// Earlier layout: the whole queue serialized under one key. Migrated to
+/// per-task keys the first time the queue is loaded.
+pub(crate) const LEGACY_PENDING_KEY: &str = "pending";
+
+/// Version of the queue's storage layout. Below [`QUEUE_LAYOUT_VERSION`]
+/// the queue is rebuilt once (legacy keys migrated, counters and the
+/// priority index written) before anything else touches it.
+pub(crate) const QUEUE_LAYOUT_KEY: &str = "queue_layout";
+pub(crate) const QUEUE_LAYOUT_VERSION: u32 = 2;
+
+/// Tasks a claim reads from each of the front of the queue (the oldest,
+/// which aging favours) and the front of the priority index. Only when
+/// none of them suits the agent does the claim page further through the
+/// queue.
+pub(crate) const CLAIM_WINDOW: usize = 64;
+
+pub(crate) fn pending_key(seq: u64) -> String {
+    format!("{PENDING_PREFIX}{seq:020}")
+}
+
+pub(crate) fn pending_priority_key(priority: i32, seq: u64) -> String {
+    let band = i64::from(i32::MAX) - i64::from(priority);
+    format!("{PENDING_PRIORITY_PREFIX}{band:010}:{seq:020}")
+}
+
+pub(crate) fn parse_pending_key(key: &str) -> Option<u64> {
+    key.strip_prefix(PENDING_PREFIX)?.parse().ok()
+}
+
+/// Add a page of `(seq, task)` read from storage to the tasks a claim
+/// chooses from, skipping tasks already seen (including ones claimed
+/// since), and keep `tasks` in queue order so ties still go to the task
+/// queued first.
+pub(crate) fn merge_claim_window(
+    tasks: &mut VecDeque<AgentTask>,
+    seqs: &mut HashMap<String, u64>,
+    page: Vec<(u64, AgentTask)>,
+) {
+    let mut merged: Vec<(u64, AgentTask)> = tasks
+        .drain(..)
+        .map(|task| (seqs.get(&task.id).copied().unwrap_or(u64::MAX), task))
+        .collect();
+    for (seq, task) in page {
+        if seqs.contains_key(&task.id) {
+            continue;
+        }
+        seqs.insert(task.id.clone(), seq);
+        merged.push((seq, task));
+    }
+    merged.sort_by_key(|(seq, _)| *seq);
+    tasks.extend(merged.into_iter().map(|(_, task)| task));
+}
+
+/// Storage writes that turn the stored queue into the in-memory one.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct PendingWrites {
+    pub puts: Vec<(u64, AgentTask)>,
+    pub deletes: Vec<u64>,
+    pub next_seq: u64,
+}
+
+/// Diff the stored `(seq, task)` pairs (in seq order) against `tasks`.
+/// A task keeps its key while it is unchanged and still follows every key
+/// kept before it; from the first task that does not (new, modified or
+/// moved), the rest of the queue gets fresh keys so list order stays queue
+/// order. Pushes to the back, the common case, only write the new tasks.
+pub(crate) fn plan_pending_writes(
+    stored: &[(u64, AgentTask)],
+    tasks: &VecDeque<AgentTask>,
+    next_seq: u64,
+) -> PendingWrites {
+    let by_id: HashMap<&str, (u64, &AgentTask)> = stored
+        .iter()
+        .map(|(seq, task)| (task.id.as_str(), (*seq, task)))
+        .collect();
+    let mut kept: std::collections::HashSet<u64> = std::collections::HashSet::new();
+    let mut writes = PendingWrites {
+        next_seq,
+        ..Default::default()
+    };
+    let mut last_kept: Option<u64> = None;
+    let mut rewriting = false;
+    for task in tasks {
+        if !rewriting {
+            if let Some((seq, old)) = by_id.get(task.id.as_str()) {
+                if last_kept.is_none_or(|last| *seq > last) && *old == task {
+                    kept.insert(*seq);
+                    last_kept = Some(*seq);
+                    continue;
+                }
+            }
+            rewriting = true;
+        }
+        writes.puts.push((writes.next_seq, task.clone()));
+        writes.next_seq += 1;
+    }
+    writes.deletes = stored
+        .iter()
+        .map(|(seq, _)| *seq)
+        .filter(|seq| !kept.contains(seq))
+        .collect();
+    writes
+}
+
+// ── Dead-letter store ───────────────────────────────────────────────────
+
+/// Dead-lettered tasks are stored one per key,
+/// `dead_letter:{dead_lettered_at}:{task_id}`. ISO timestamps sort in time
+/// order, so listing the prefix in reverse walks the store newest first,
+/// the `/dead-letter` page order, and adding or removing an entry writes
+/// only its own keys.
+pub(crate) const DEAD_LETTER_PREFIX: &str = "dead_letter:";
+
+/// `dead_letter_id:{task_id}` holds the task's `dead_letter:` key, for
+/// requeue and discard by id.
+pub(crate) const DEAD_LETTER_ID_PREFIX: &str = "dead_letter_id:";
+
+/// Number of dead-lettered tasks, kept in step with the entries so the cap
+/// and `/queue` never list the store.
+pub(crate) const DEAD_LETTER_LEN_KEY: &str = "dead_letter_len";
+
+/// Earlier layout: every entry in one `Vec<DeadLetterTask>`, oldest first.
+/// Migrated to per-task keys the first time the store is touched.
+pub(crate) const LEGACY_DEAD_LETTER_KEY: &str = "dead_letter";
+
+/// Cap on dead-lettered tasks kept per TaskLeaseManager. Past this the
+/// oldest entries are evicted (and logged) so a persistently failing job
+/// cannot grow DO storage without bound; the D1 `mcp_tasks` row of an
+/// evicted task still records it as `failed`.
+pub(crate) const MAX_DEAD_LETTER_TASKS: usize = 1_000;
+
+/// Entries read per `list` call when paging the store.
+const DEAD_LETTER_LIST_BATCH: usize = 128;
+
+pub(crate) fn dead_letter_key(dead_lettered_at: &str, task_id: &str) -> String {
+    format!("{DEAD_LETTER_PREFIX}{dead_lettered_at}:{task_id}")
+}
+
+pub(crate) fn dead_letter_id_key(task_id: &str) -> String {
+    format!("{DEAD_LETTER_ID_PREFIX}{task_id}")
+}
+
+/// One page of dead-lettered tasks, newest first, optionally narrowed to
+/// one `job_id`. Returns the page and the cursor for the next one. Used on
+/// the entries a shard listed and to merge the pages of several shards.
+pub(crate) fn dead_letter_page(
+    dead_letter: &[DeadLetterTask],
+    job_id: Option<&str>,
+    after: Option<&DeadLetterCursor>,
+    limit: usize,
+) -> (Vec<DeadLetterTask>, Option<DeadLetterCursor>) {
+    let mut matching: Vec<&DeadLetterTask> = dead_letter
+        .iter()
+        .filter(|entry| job_id.is_none_or(|job| entry.task.job_id == job))
+        .filter(|entry| {
+            after.is_none_or(|c| {
+                (entry.dead_lettered_at.as_str(), entry.task.id.as_str())
+                    < (c.dead_lettered_at.as_str(), c.id.as_str())
+            })
+        })
+        .collect();
+    matching.sort_by(|a, b| {
+        (b.dead_lettered_at.as_str(), b.task.id.as_str())
+            .cmp(&(a.dead_lettered_at.as_str(), a.task.id.as_str()))
+    });
+
+    let has_more = matching.len() > limit;
+    let page: Vec<DeadLetterTask> = matching.into_iter().take(limit).cloned().collect();
+    let next = if has_more {
+        page.last().map(|entry| DeadLetterCursor {
+            dead_lettered_at: entry.dead_lettered_at.clone(),
+            id: entry.task.id.clone(),
+        })
+    } else {
+        None
+    };
+    (page, next)
+}
+
+/// Turn a dead-lettered entry back into a pending task with a fresh retry
+/// budget. The failure history is kept so the next dead-lettering still
+/// shows every earlier attempt.
+pub(crate) fn requeued_task(entry: DeadLetterTask, now_ms: u64) -> AgentTask {
+    let mut task = entry.task;
+    task.status = "pending".to_string();
+    task.retry_count = 0;
+    task.result = None;
+    task.completed_at = None;
+    task.agent_id = None;
+    task.lease_expires_at = None;
+    task.enqueued_at_ms = Some(now_ms);
+    task
+}
+
+// ── Delayed tasks ───────────────────────────────────────────────────────
+
+/// Tasks enqueued with a future `not_before` are stored one per key,
+/// `scheduled:{due_at_ms}:{seq}`, so listing the prefix returns them
+/// soonest first. They are not claimable until the alarm moves them into
+/// `pending`.
+pub(crate) const SCHEDULED_PREFIX: &str = "scheduled:";
+
+/// Number of scheduled tasks, counted toward the backpressure cap.
+pub(crate) const SCHEDULED_LEN_KEY: &str = "scheduled_len";
+
+/// Earlier layout: every scheduled task in one `Vec<ScheduledTask>`.
+/// Migrated with the rest of the queue (see [`QUEUE_LAYOUT_KEY`]).
+pub(crate) const LEGACY_SCHEDULED_KEY: &str = "scheduled";
+
+pub(crate) fn scheduled_key(due_at_ms: u64, seq: u64) -> String {
+    format!("{SCHEDULED_PREFIX}{due_at_ms:020}:{seq:020}")
+}
+
+/// Mark `task` terminally cancelled and drop its lease.
+pub(crate) fn finish_cancelled(mut task: AgentTask, cancelled_at_iso: &str) -> AgentTask {
+    task.status = "cancelled".to_string();
+    task.lease_expires_at = None;
+    task.completed_at = Some(cancelled_at_iso.to_string());
+    task
+}
+
+/// Cancel every queued or leased task matching `matches`. Pending and
+/// scheduled tasks are removed and marked `cancelled`. Leased tasks stay
+/// in `active` with `cancel_requested` set: the lease is released at the
+/// holder's next heartbeat (or fail / lease expiry), never retried.
+pub(crate) fn cancel_tasks(
+    pending: &mut VecDeque<AgentTask>,
+    scheduled: &mut Vec<ScheduledTask>,
+    active: &mut HashMap<String, AgentTask>,
+    matches: impl Fn(&AgentTask) -> bool,
+    cancelled_at_iso: &str,
+) -> CancelledTasks {
+    let mut out = CancelledTasks::default();
+    let (hit, kept): (VecDeque<_>, VecDeque<_>) = pending.drain(..).partition(|t| matches(t));
+    *pending = kept;
+    out.cancelled.extend(
+        hit.into_iter()
+            .map(|t| finish_cancelled(t, cancelled_at_iso)),
+    );
+
+    let (hit, kept): (Vec<_>, Vec<_>) = scheduled.drain(..).partition(|s| matches(&s.task));
+    *scheduled = kept;
+    out.cancelled.extend(
+        hit.into_iter()
+            .map(|s| finish_cancelled(s.task, cancelled_at_iso)),
+    );
+
+    for task in active.values_mut().filter(|t| matches(t)) {
+        task.cancel_requested = true;
+        out.cancel_requested.push(task.clone());
+    }
+    out
+}
+
+/// Extend the lease on `task_id` if `agent_id` holds it. A task flagged
+/// by [`cancel_tasks`] is instead removed and returned as `cancelled`.
+pub(crate) fn heartbeat_task(
+    active: &mut HashMap<String, AgentTask>,
+    task_id: &str,
+    agent_id: &str,
+    lease_expires_iso: String,
+    now_iso: &str,
+) -> Option<AgentTask> {
+    let task = active.get_mut(task_id)?;
+    if task.agent_id.as_deref() != Some(agent_id) {
+        return None;
+    }
+    if task.cancel_requested {
+        let task = active.remove(task_id)?;
+        return Some(finish_cancelled(task, now_iso));
+    }
+    task.lease_expires_at = Some(lease_expires_iso);
+    Some(task.clone())
+}
+
+/// A task held back until `due_at_ms` (its parsed `not_before`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct ScheduledTask {
+    pub due_at_ms: u64,
+    pub task: AgentTask,
+}
+
+/// Move every task due by `now_ms` onto the pending queue. Aging starts from
+/// the due time, not the original enqueue, so a long delay does not turn
+/// into a priority boost. Returns the promoted task ids.
+pub(crate) fn promote_due_tasks(
+    scheduled: &mut Vec<ScheduledTask>,
+    pending: &mut VecDeque<AgentTask>,
+    now_ms: u64,
+) -> Vec<String> {
+    let due = scheduled.partition_point(|s| s.due_at_ms <= now_ms);
+    scheduled
+        .drain(..due)
+        .map(
+            |ScheduledTask {
+                 due_at_ms,
+                 mut task,
+             }| {
+                task.enqueued_at_ms = Some(due_at_ms);
+                let id = task.id.clone();
+                enqueue_task(pending, task);
+                id
+            },
+        )
+        .collect()
+}
+
+/// When the alarm should fire next: the lease/notify sweep interval while
+/// `needs_sweep`, pulled earlier if the soonest scheduled task, due at
+/// `next_due_ms`, comes due first.
+pub(crate) fn next_alarm_at(
+    now_ms: u64,
+    needs_sweep: bool,
+    next_due_ms: Option<u64>,
+) -> Option<u64> {
+    let sweep = needs_sweep.then_some(now_ms + LEASE_SWEEP_INTERVAL_MS);
+    let due = next_due_ms.map(|d| d.max(now_ms));
+    match (sweep, due) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// ── Idempotent submission ──────────────────────────────────────────────
+//
+// Keys are claimed in D1 (`task_idempotency_keys`) by `enqueue_agent_task`
+// before a task reaches any shard, so the DO keeps no per-key state.
+
+/// Worker var overriding how long an idempotency key dedupes, in seconds.
+pub(crate) const IDEMPOTENCY_WINDOW_VAR: &str = "TASK_IDEMPOTENCY_WINDOW_SECS";
+
+pub(crate) const DEFAULT_IDEMPOTENCY_WINDOW_SECS: u64 = 86_400;
+
+/// Parse `TASK_IDEMPOTENCY_WINDOW_SECS` into milliseconds. Missing or
+/// malformed falls back to the 24h default; `0` disables deduping.
+pub(crate) fn idempotency_window_ms(raw: Option<&str>) -> u64 {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_IDEMPOTENCY_WINDOW_SECS)
+        .saturating_mul(1_000)
 }
 
 /// Maximum number of pending tasks held in a single TaskLeaseManager DO.
@@ -161,6 +644,12 @@ pub enum EnqueueDecision {
     Reject,
 }
 
+/// Depth the backpressure cap applies to. Delayed tasks count too: they
+/// land in `pending` when due.
+pub fn queue_depth(pending_len: usize, scheduled_len: usize) -> usize {
+    pending_len.saturating_add(scheduled_len)
+}
+
 pub fn enqueue_decision(current_len: usize, max: usize) -> EnqueueDecision {
     if current_len >= max {
         EnqueueDecision::Reject
@@ -170,7 +659,7 @@ pub fn enqueue_decision(current_len: usize, max: usize) -> EnqueueDecision {
 }
 
 /// A pending notification to the per-tenant PlayManager DO, recording a
-/// completed task that we failed to notify the first time. Persisted in
+/// finished task that we failed to notify the first time. Persisted in
 /// DO storage under key `notify_pending` so retries survive isolate
 /// eviction. See `try_notify_play_manager` and the alarm handler for the
 /// retry mechanics.
@@ -185,6 +674,143 @@ pub(crate) struct PendingNotify {
     pub(crate) attempts: u32,
     /// Earliest unix-millis at which the next retry may run.
     pub(crate) next_attempt_at_ms: u64,
+    /// What to report. Absent on entries persisted before failures were
+    /// reported too, which are completions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) outcome: Option<PlayTaskOutcome>,
+}
+
+/// Whether `task` has reached an end PlayManager must hear about:
+/// completed, dead-lettered or cancelled.
+pub(crate) fn is_play_outcome(task: &AgentTask) -> bool {
+    matches!(task.status.as_str(), "completed" | "failed" | "cancelled")
+}
+
+/// The outcome PlayManager is told about for a play task that completed,
+/// was dead-lettered or was cancelled; a cancelled task is reported as a
+/// failure so the run does not wait on it forever. The play-side id is the AgentTask id minus its
+/// `{run_id}-` prefix; play task ids may themselves contain `-`.
+///
+/// `task.result` holds the whole completion body (`{"result": ...}`, see
+/// [`TaskCompleteRequest`]); PlayManager gets only the agent's result.
+pub(crate) fn play_task_outcome(task: &AgentTask) -> PlayTaskOutcome {
+    let task_id = task
+        .id
+        .strip_prefix(&format!("{}-", task.job_id))
+        .unwrap_or(&task.id)
+        .to_string();
+    let cancelled = task.status == "cancelled";
+    let failed = cancelled || task.status == "failed";
+    PlayTaskOutcome {
+        task_id,
+        status: if cancelled {
+            "failed".to_string()
+        } else {
+            task.status.clone()
+        },
+        result: if failed {
+            None
+        } else {
+            task.result.clone().and_then(completion_result)
+        },
+        error: if cancelled {
+            Some(CANCELLED_ERROR.to_string())
+        } else if failed {
+            task.failures.last().map(|f| f.error.clone())
+        } else {
+            None
+        },
+        agent_id: if failed {
+            task.failures.last().and_then(|f| f.agent_id.clone())
+        } else {
+            task.agent_id.clone()
+        },
+    }
+}
+
+/// The agent's result out of a stored completion body. A body that is not a
+/// [`TaskCompleteRequest`] carries no result.
+fn completion_result(body: serde_json::Value) -> Option<serde_json::Value> {
+    serde_json::from_value::<TaskCompleteRequest>(body)
+        .ok()
+        .and_then(|req| req.result)
+}
+
+/// Storage key for `Vec<PendingNotify>`.
+pub(crate) const NOTIFY_PENDING_KEY: &str = "notify_pending";
+
+/// Build the `/queue` introspection view from raw DO state. Leases are
+/// listed soonest-expiring first; pending tasks without `enqueued_at_ms`
+/// (queued before it existed) do not count towards the oldest age.
+pub(crate) fn queue_snapshot(
+    pending: &VecDeque<AgentTask>,
+    scheduled: &[ScheduledTask],
+    active: &HashMap<String, AgentTask>,
+    notify_pending: &[PendingNotify],
+    rejected_total: u64,
+    dead_letter_total: usize,
+    now_ms: u64,
+) -> QueueSnapshot {
+    let mut counts: HashMap<(&str, i32), usize> = HashMap::new();
+    for task in pending {
+        *counts
+            .entry((task.task_type.as_str(), task.priority))
+            .or_default() += 1;
+    }
+    let mut depth: Vec<QueueDepth> = counts
+        .into_iter()
+        .map(|((task_type, priority), count)| QueueDepth {
+            task_type: task_type.to_string(),
+            priority,
+            count,
+        })
+        .collect();
+    depth.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.task_type.cmp(&b.task_type))
+    });
+
+    let mut leases: Vec<ActiveLease> = active
+        .values()
+        .map(|task| ActiveLease {
+            task_id: task.id.clone(),
+            job_id: task.job_id.clone(),
+            task_type: task.task_type.clone(),
+            agent_id: task.agent_id.clone(),
+            lease_expires_at: task.lease_expires_at.clone(),
+            cancel_requested: task.cancel_requested,
+        })
+        .collect();
+    leases.sort_by(|a, b| {
+        a.lease_expires_at
+            .cmp(&b.lease_expires_at)
+            .then_with(|| a.task_id.cmp(&b.task_id))
+    });
+
+    QueueSnapshot {
+        pending_total: pending.len(),
+        scheduled_total: scheduled.len(),
+        max_pending: MAX_PENDING_TASKS,
+        depth,
+        oldest_pending_age_ms: pending
+            .iter()
+            .filter_map(|t| t.enqueued_at_ms)
+            .min()
+            .map(|at| now_ms.saturating_sub(at)),
+        active: leases,
+        rejected_total,
+        dead_letter_total,
+        pending_notifies: notify_pending
+            .iter()
+            .map(|n| PendingNotifyInfo {
+                target_name: n.target_name.clone(),
+                play_task_id: n.play_task_id.clone(),
+                attempts: n.attempts,
+                next_attempt_at_ms: n.next_attempt_at_ms,
+            })
+            .collect(),
+    }
 }
 
 /// Default lease-expiry sweep interval (ms). Used both for the
@@ -214,16 +840,96 @@ pub(crate) fn next_attempt_at(now_ms: u64, attempts: u32) -> u64 {
     now_ms.saturating_add(backoff_ms)
 }
 
+// ── Long-poll and WebSocket claiming ───────────────────────────────────
+
+/// Upper bound on how long a long-poll `/claim` is held open. Kept well
+/// under the Workers request limits and typical proxy idle timeouts.
+pub(crate) const MAX_CLAIM_WAIT_MS: u64 = 30_000;
+
+/// Parse the DO's `?wait_ms=` claim parameter, clamped to
+/// `MAX_CLAIM_WAIT_MS`. Missing or malformed means no wait.
+pub(crate) fn clamp_claim_wait_ms(raw: Option<&str>) -> u64 {
+    raw.and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(0)
+        .min(MAX_CLAIM_WAIT_MS)
+}
+
+pub(crate) fn parse_caps(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Per-socket state, stored as the WebSocket attachment so it survives
+/// hibernation. An agent socket holds at most one lease at a time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct AgentSocket {
+    pub agent_id: String,
+    pub caps: Vec<String>,
+    pub task_id: Option<String>,
+}
+
+/// Wakes long-poll claims parked in this DO instance. Each `notify` bumps
+/// a generation; a waiter resolves once the generation moves past the one
+/// it saw before its last claim attempt.
+#[derive(Default)]
+pub(crate) struct ClaimWaiters {
+    generation: Cell<u64>,
+    wakers: RefCell<Vec<Waker>>,
+}
+
+impl ClaimWaiters {
+    pub fn generation(&self) -> u64 {
+        self.generation.get()
+    }
+
+    pub fn notify(&self) {
+        self.generation.set(self.generation.get().wrapping_add(1));
+        for waker in self.wakers.borrow_mut().drain(..) {
+            waker.wake();
+        }
+    }
+
+    pub fn changed_since(&self, seen: u64) -> ClaimWaitersChanged<'_> {
+        ClaimWaitersChanged {
+            waiters: self,
+            seen,
+        }
+    }
+}
+
+pub(crate) struct ClaimWaitersChanged<'a> {
+    waiters: &'a ClaimWaiters,
+    seen: u64,
+}
+
+impl Future for ClaimWaitersChanged<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<()> {
+        if self.waiters.generation() != self.seen {
+            return Poll::Ready(());
+        }
+        self.waiters.wakers.borrow_mut().push(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
 #[durable_object]
 pub struct TaskLeaseManager {
     state: State,
-    #[allow(dead_code)]
     env: Env,
+    claim_waiters: ClaimWaiters,
 }
 
 impl DurableObject for TaskLeaseManager {
     fn new(state: State, env: Env) -> Self {
-        Self { state, env }
+        Self {
+            state,
+            env,
+            claim_waiters: ClaimWaiters::default(),
+        }
     }
 
     async fn fetch(&self, mut req: Request) -> Result<Response> {
@@ -255,54 +961,46 @@ impl DurableObject for TaskLeaseManager {
             .strip_prefix("/complete/")
             .and_then(decode_task_id_segment);
         let fail_task_id = path.strip_prefix("/fail/").and_then(decode_task_id_segment);
+        let cancel_task_id = path
+            .strip_prefix("/cancel/")
+            .and_then(decode_task_id_segment);
+        let requeue_task_id = path
+            .strip_prefix("/dead-letter/requeue/")
+            .and_then(decode_task_id_segment);
+        let discard_task_id = path
+            .strip_prefix("/dead-letter/discard/")
+            .and_then(decode_task_id_segment);
 
         match (method, path.as_str()) {
             (Method::Post, "/enqueue") => {
-                let task: AgentTask = req.json().await?;
+                let mut task: AgentTask = req.json().await?;
                 let storage = self.state.storage();
-
-                let mut pending: VecDeque<AgentTask> = storage
-                    .get("pending")
-                    .await
-                    .ok()
-                    .flatten()
-                    .unwrap_or_default();
+                let now = js_sys::Date::now() as u64;
 
                 // Backpressure: reject before the queue grows unbounded.
-                // Counter persists across requests so operators can see
-                // sustained pressure via a future /metrics endpoint.
-                if enqueue_decision(pending.len(), MAX_PENDING_TASKS) == EnqueueDecision::Reject {
-                    let prev_rejected: u64 = storage
-                        .get(PENDING_REJECTED_TOTAL_KEY)
-                        .await
-                        .ok()
-                        .flatten()
-                        .unwrap_or(0);
-                    let next_rejected = prev_rejected.saturating_add(1);
-                    storage
-                        .put(PENDING_REJECTED_TOTAL_KEY, next_rejected)
-                        .await?;
-
-                    let headers = Headers::new();
-                    headers.set("retry-after", &ENQUEUE_RETRY_AFTER_SECS.to_string())?;
-                    headers.set("content-type", "application/json")?;
-                    let body = serde_json::json!({
-                        "error": {
-                            "code": "QUEUE_FULL",
-                            "message": "pending_tasks queue at capacity; retry after the indicated delay",
-                            "details": {
-                                "max_pending_tasks": MAX_PENDING_TASKS,
-                                "retry_after_seconds": ENQUEUE_RETRY_AFTER_SECS,
-                            }
-                        }
-                    });
-                    return Ok(Response::from_json(&body)?
-                        .with_status(429)
-                        .with_headers(headers));
+                if enqueue_decision(
+                    queue_depth(pending_len(&storage).await?, scheduled_len(&storage).await?),
+                    MAX_PENDING_TASKS,
+                ) == EnqueueDecision::Reject
+                {
+                    return reject_queue_full(&storage).await;
                 }
 
-                pending.push_back(task);
-                storage.put("pending", pending).await?;
+                let due_at_ms = task
+                    .not_before
+                    .as_deref()
+                    .map(js_sys::Date::parse)
+                    .filter(|ms| ms.is_finite() && *ms as u64 > now)
+                    .map(|ms| ms as u64);
+                if let Some(due_at_ms) = due_at_ms {
+                    put_scheduled(&storage, due_at_ms, task).await?;
+                    ensure_alarm_by(&storage, due_at_ms).await?;
+                    return Response::ok("scheduled");
+                }
+
+                task.enqueued_at_ms.get_or_insert(now);
+                let tenant_id = task.tenant_id.clone();
+                append_pending(&storage, &[task]).await?;
 
                 // PR #132 crr finding (task_do.rs:30): the first `/enqueue`
                 // with no active tasks left the DO with no alarm scheduled,
@@ -310,6 +1008,7 @@ impl DurableObject for TaskLeaseManager {
                 // else (a /claim) happened to set one. Always ensure an
                 // alarm is pending after enqueue.
                 ensure_sweep_alarm(&storage).await?;
+                self.task_available(tenant_id.as_deref()).await?;
 
                 Response::ok("enqueued")
             }
@@ -321,59 +1020,68 @@ impl DurableObject for TaskLeaseManager {
                     .map(|(k, v)| (k.into_owned(), v.into_owned()))
                     .collect();
                 let agent_id = params.get("agent_id").cloned().unwrap_or_default();
-                let caps_str = params.get("caps").cloned().unwrap_or_default();
-                let caps: Vec<String> = caps_str
-                    .split(',')
-                    .filter(|s| !s.is_empty())
-                    .map(|s| s.to_string())
-                    .collect();
-
-                let storage = self.state.storage();
-                let mut pending: VecDeque<AgentTask> = storage
-                    .get("pending")
-                    .await
-                    .ok()
-                    .flatten()
-                    .unwrap_or_default();
-                let mut active: std::collections::HashMap<String, AgentTask> = storage
-                    .get("active")
-                    .await
-                    .ok()
-                    .flatten()
-                    .unwrap_or_default();
+                let caps = parse_caps(params.get("caps").map(String::as_str).unwrap_or_default());
+                let wait_ms = clamp_claim_wait_ms(params.get("wait_ms").map(String::as_str));
 
-                // Find first task that matches capabilities
-                let task_idx = pending
-                    .iter()
-                    .position(|t| caps.is_empty() || caps.contains(&t.task_type));
-
-                if let Some(idx) = task_idx {
-                    let mut task = pending.remove(idx).unwrap();
-                    task.status = "running".to_string();
-                    task.agent_id = Some(agent_id);
-
-                    // Set lease for 5 minutes
-                    let now = js_sys::Date::now() as u64;
-                    let expires = now + (300 * 1000);
-                    task.lease_expires_at = Some(
-                        js_sys::Date::new(&serde_wasm_bindgen::to_value(&expires).unwrap_or_default())
-                            .to_iso_string()
-                            .as_string()
-                            .unwrap_or_default(),
-                    );
-
-                    active.insert(task.id.clone(), task.clone());
-
-                    storage.put("pending", pending).await?;
-                    storage.put("active", active).await?;
-
-                    // Set alarm to check for lease expiry
-                    let _ = storage.set_alarm(expires as i64).await;
-
-                    Response::from_json(&task)
-                } else {
-                    Ok(Response::empty()?.with_status(204))
+                match self.claim_with_wait(&agent_id, &caps, wait_ms).await? {
+                    Some(task) => Response::from_json(&task),
+                    None => Ok(Response::empty()?.with_status(204)),
+                }
+            }
+            (Method::Post, "/wake") => {
+                self.claim_waiters.notify();
+                Response::ok("woken")
+            }
+            (Method::Post, "/wait") => {
+                // Park until this instance is notified (a task enqueued
+                // here or a `/wake` from another shard) or `wait_ms`
+                // passes. Claims nothing; returns the generation so the
+                // caller can tell a wake from a timeout.
+                let params: std::collections::HashMap<String, String> = req
+                    .url()?
+                    .query_pairs()
+                    .into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect();
+                let wait_ms = clamp_claim_wait_ms(params.get("wait_ms").map(String::as_str));
+                if let Some(seen) = params.get("since").and_then(|s| s.parse::<u64>().ok()) {
+                    if wait_ms > 0 {
+                        let timeout = Delay::from(std::time::Duration::from_millis(wait_ms));
+                        futures_util::future::select(
+                            self.claim_waiters.changed_since(seen),
+                            timeout,
+                        )
+                        .await;
+                    }
+                }
+                Response::from_json(&serde_json::json!({
+                    "generation": self.claim_waiters.generation(),
+                }))
+            }
+            (Method::Get, "/connect") => {
+                let params: std::collections::HashMap<String, String> = req
+                    .url()?
+                    .query_pairs()
+                    .into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect();
+                let agent_id = params.get("agent_id").cloned().unwrap_or_default();
+                if agent_id.is_empty() {
+                    return Response::error("agent_id required", 400);
                 }
+                let caps = parse_caps(params.get("caps").map(String::as_str).unwrap_or_default());
+
+                let pair = WebSocketPair::new()?;
+                self.state
+                    .accept_websocket_with_tags(&pair.server, &[agent_id.as_str()]);
+                pair.server.serialize_attachment(AgentSocket {
+                    agent_id,
+                    caps,
+                    task_id: None,
+                })?;
+                // Hand the new agent anything already waiting.
+                self.dispatch_to_sockets().await?;
+                Response::from_websocket(pair.client)
             }
             (Method::Post, "/heartbeat") => {
                 let params: std::collections::HashMap<String, String> = req
@@ -385,30 +1093,30 @@ impl DurableObject for TaskLeaseManager {
                 let task_id = params.get("task_id").cloned().unwrap_or_default();
                 let agent_id = params.get("agent_id").cloned().unwrap_or_default();
 
-                let storage = self.state.storage();
-                let mut active: std::collections::HashMap<String, AgentTask> = storage
-                    .get("active")
-                    .await
-                    .ok()
-                    .flatten()
+                match self.heartbeat(&task_id, &agent_id).await? {
+                    Some(task) => Response::from_json(&task),
+                    None => Response::error("task not found or not owned by agent", 404),
+                }
+            }
+            (Method::Post, _) if cancel_task_id.is_some() => {
+                let task_id = cancel_task_id.unwrap_or_default();
+                let outcome = self.cancel(|t| t.id == task_id).await?;
+                if outcome.cancelled.is_empty() && outcome.cancel_requested.is_empty() {
+                    return Response::error("task not pending, scheduled or running", 404);
+                }
+                Response::from_json(&outcome)
+            }
+            (Method::Post, "/cancel-job") => {
+                let job_id = req
+                    .url()?
+                    .query_pairs()
+                    .find(|(k, _)| k == "job_id")
+                    .map(|(_, v)| v.into_owned())
                     .unwrap_or_default();
-
-                if let Some(task) = active.get_mut(&task_id) {
-                    if task.agent_id.as_ref() == Some(&agent_id) {
-                        let now = js_sys::Date::now() as u64;
-                        let expires = now + (300 * 1000);
-                        task.lease_expires_at = Some(
-                            js_sys::Date::new(&serde_wasm_bindgen::to_value(&expires).unwrap_or_default())
-                                .to_iso_string()
-                                .as_string()
-                                .unwrap_or_default(),
-                        );
-
-                        storage.put("active", active).await?;
-                        return Response::ok("ok");
-                    }
+                if job_id.is_empty() {
+                    return Response::error("job_id required", 400);
                 }
-                Response::error("task not found or not owned by agent", 404)
+                Response::from_json(&self.cancel(|t| t.job_id == job_id).await?)
             }
             (Method::Post, _) if complete_task_id.is_some() => {
                 let task_id = complete_task_id.unwrap_or_default();
@@ -417,68 +1125,9 @@ impl DurableObject for TaskLeaseManager {
                 }
                 let result: Option<serde_json::Value> = req.json().await.ok();
 
-                let storage = self.state.storage();
-                let mut active: std::collections::HashMap<String, AgentTask> = storage
-                    .get("active")
-                    .await
-                    .ok()
-                    .flatten()
-                    .unwrap_or_default();
-
-                if let Some(mut task) = active.remove(&task_id) {
-                    task.status = "completed".to_string();
-                    task.result = result;
-                    task.completed_at =
-                        Some(js_sys::Date::new_0().to_iso_string().as_string().unwrap_or_default());
-
-                    let job_id = task.job_id.clone();
-                    let task_tenant = task.tenant_id.clone();
-
-                    storage.put("active", active).await?;
-
-                    // If task belongs to a play, notify PlayManager. The
-                    // PlayManager DO is tenant-namespaced (see lib.rs
-                    // `/v1/plays/:name/launch`) so we must reconstruct the
-                    // name as `{tenant_id}:play:{run_id}`. If the task is
-                    // missing tenant_id (legacy persisted state from before
-                    // WS8), we skip the notification rather than routing to
-                    // a potentially cross-tenant DO instance.
-                    let play_task_id = task_id
-                        .split('-')
-                        .next_back()
-                        .unwrap_or(&task_id)
-                        .to_string();
-
-                    if let Some(tenant_id) = task_tenant.as_deref() {
-                        if !tenant_id.is_empty() {
-                            let do_name = format!("{}:play:{}", tenant_id, job_id);
-                            // PR #132 crr finding (task_do.rs:134): the previous
-                            // notification was `let _ = play_stub.fetch_with_request().await;`
-                            // which silently dropped delivery failures and orphaned
-                            // any downstream tasks if PlayManager was unreachable.
-                            // We now (1) attempt the notify, (2) on failure persist
-                            // a PendingNotify entry to DO storage, (3) ensure an
-                            // alarm is scheduled to drive the retry loop, and (4)
-                            // surface the failure with a warn log visible in
-                            // `wrangler tail`.
-                            self.try_notify_play_manager(&do_name, &play_task_id, 1)
-                                .await;
-                        } else {
-                            worker::console_log!(
-                                "skipping PlayManager notify for task {}: empty tenant_id",
-                                task_id
-                            );
-                        }
-                    } else {
-                        worker::console_log!(
-                            "skipping PlayManager notify for task {}: tenant_id missing (pre-WS8 task)",
-                            task_id
-                        );
-                    }
-
-                    Response::from_json(&task)
-                } else {
-                    Response::error("task not found or not running", 404)
+                match self.complete(&task_id, result).await? {
+                    Some(task) => Response::from_json(&task),
+                    None => Response::error("task not found or not running", 404),
                 }
             }
             (Method::Post, _) if fail_task_id.is_some() => {
@@ -488,40 +1137,117 @@ impl DurableObject for TaskLeaseManager {
                 }
                 let fail_req: TaskFailRequest = req.json().await?;
 
+                match self.fail(&task_id, &fail_req.error).await? {
+                    Some(task) => Response::from_json(&task),
+                    None => Response::error("task not found or not running", 404),
+                }
+            }
+            (Method::Get, "/queue") => {
                 let storage = self.state.storage();
-                let mut active: std::collections::HashMap<String, AgentTask> = storage
+                let pending = PendingQueue::load(&storage).await?;
+                let scheduled: Vec<ScheduledTask> = list_scheduled(&storage, None)
+                    .await?
+                    .into_iter()
+                    .map(|(_, s)| s)
+                    .collect();
+                let active: HashMap<String, AgentTask> = storage
                     .get("active")
                     .await
                     .ok()
                     .flatten()
                     .unwrap_or_default();
-                let mut pending: VecDeque<AgentTask> = storage
-                    .get("pending")
+                let notify_pending: Vec<PendingNotify> = storage
+                    .get(NOTIFY_PENDING_KEY)
                     .await
                     .ok()
                     .flatten()
                     .unwrap_or_default();
+                let rejected_total: u64 = storage
+                    .get(PENDING_REJECTED_TOTAL_KEY)
+                    .await
+                    .ok()
+                    .flatten()
+                    .unwrap_or(0);
+                let dead_letter_total = dead_letter_len(&storage).await?;
+
+                Response::from_json(&queue_snapshot(
+                    &pending.tasks,
+                    &scheduled,
+                    &active,
+                    &notify_pending,
+                    rejected_total,
+                    dead_letter_total,
+                    js_sys::Date::now() as u64,
+                ))
+            }
+            (Method::Get, "/dead-letter") => {
+                let params: std::collections::HashMap<String, String> = req
+                    .url()?
+                    .query_pairs()
+                    .into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect();
+                let after = match DeadLetterCursor::decode(params.get("cursor").map(String::as_str))
+                {
+                    Ok(c) => c,
+                    Err(_) => return Response::error("invalid cursor", 400),
+                };
+                let limit = crate::pagination::clamp_limit(
+                    params.get("limit").and_then(|v| v.parse().ok()),
+                ) as usize;
+
+                let (tasks, next) = list_dead_letter_page(
+                    &self.state.storage(),
+                    params.get("job_id").map(String::as_str),
+                    after.as_ref(),
+                    limit,
+                )
+                .await?;
+                let next_cursor = next.map(|c| c.encode()).transpose()?;
+                Response::from_json(&DeadLetterList { tasks, next_cursor })
+            }
+            (Method::Post, _) if requeue_task_id.is_some() => {
+                let task_id = requeue_task_id.unwrap_or_default();
+                let storage = self.state.storage();
 
-                if let Some(mut task) = active.remove(&task_id) {
-                    if task.retry_count < task.max_retries {
-                        task.status = "pending".to_string();
-                        task.retry_count += 1;
-                        task.agent_id = None;
-                        task.lease_expires_at = None;
-                        pending.push_back(task.clone());
-                        storage.put("pending", pending).await?;
-                    } else {
-                        task.status = "failed".to_string();
-                        task.result = Some(serde_json::json!({ "error": fail_req.error }));
-                        task.completed_at =
-                            Some(js_sys::Date::new_0().to_iso_string().as_string().unwrap_or_default());
-                    }
-                    storage.put("active", active).await?;
-                    Response::from_json(&task)
-                } else {
-                    Response::error("task not found or not running", 404)
+                // Requeue is an enqueue: honour the same backpressure cap.
+                if enqueue_decision(
+                    queue_depth(pending_len(&storage).await?, scheduled_len(&storage).await?),
+                    MAX_PENDING_TASKS,
+                ) == EnqueueDecision::Reject
+                {
+                    return reject_queue_full(&storage).await;
+                }
+                let Some(entry) = get_dead_letter(&storage, &task_id).await? else {
+                    return Response::error("task not in dead-letter store", 404);
+                };
+                let task = requeued_task(entry, js_sys::Date::now() as u64);
+                // Pending first, so a failed write leaves the task dead-lettered
+                // rather than in neither store.
+                append_pending(&storage, std::slice::from_ref(&task)).await?;
+                take_dead_letter(&storage, &task_id).await?;
+                ensure_sweep_alarm(&storage).await?;
+                self.task_available(task.tenant_id.as_deref()).await?;
+                Response::from_json(&task)
+            }
+            (Method::Post, _) if discard_task_id.is_some() => {
+                let task_id = discard_task_id.unwrap_or_default();
+                match take_dead_letter(&self.state.storage(), &task_id).await? {
+                    Some(entry) => Response::from_json(&entry),
+                    None => Response::error("task not in dead-letter store", 404),
                 }
             }
+            (Method::Post, "/purge") => {
+                // Tenant hard-delete: drop every queue, lease and
+                // dead-letter entry, and disconnect the tenant's agents.
+                for ws in self.state.get_websockets() {
+                    let _ = ws.close(Some(1008), Some("tenant deleted"));
+                }
+                let storage = self.state.storage();
+                storage.delete_alarm().await?;
+                storage.delete_all().await?;
+                Response::empty().map(|r| r.with_status(204))
+            }
             _ => Response::error("not found", 404),
         }
     }
@@ -534,49 +1260,62 @@ impl DurableObject for TaskLeaseManager {
             .ok()
             .flatten()
             .unwrap_or_default();
-        let mut pending: VecDeque<AgentTask> = storage
-            .get("pending")
-            .await
-            .ok()
-            .flatten()
-            .unwrap_or_default();
+        // Only this sweep's additions: tasks to push onto the queue and
+        // tasks to dead-letter.
+        let mut requeue = VecDeque::new();
+        let mut dead_letter = Vec::new();
 
         let now = js_sys::Date::now() as u64;
+        let (due_keys, mut due): (Vec<String>, Vec<ScheduledTask>) =
+            list_scheduled(&storage, Some(now))
+                .await?
+                .into_iter()
+                .unzip();
+        let promoted = promote_due_tasks(&mut due, &mut requeue, now);
+        if !promoted.is_empty() {
+            worker::console_log!("Promoted {} scheduled task(s) to pending", promoted.len());
+        }
+        let now_iso = js_sys::Date::new_0()
+            .to_iso_string()
+            .as_string()
+            .unwrap_or_default();
         let mut to_release = Vec::new();
 
         for (id, task) in &active {
-            if let Some(expires_str) = &task.lease_expires_at {
-                let expires_ms = js_sys::Date::parse(expires_str);
-                if expires_ms.is_finite() {
-                    let expires = expires_ms as u64;
-                    if expires <= now {
-                        to_release.push(id.clone());
-                    }
-                }
+            let expires_ms = task
+                .lease_expires_at
+                .as_deref()
+                .map(js_sys::Date::parse)
+                .filter(|ms| ms.is_finite())
+                .map(|ms| ms as u64);
+            if let Some(error) = lease_release_error(task, expires_ms, now) {
+                to_release.push((id.clone(), error));
             }
         }
 
-        for id in to_release {
-            if let Some(mut task) = active.remove(&id) {
-                worker::console_log!("Releasing expired lease for task {}", id);
-                if task.retry_count < task.max_retries {
-                    task.status = "pending".to_string();
-                    task.retry_count += 1;
-                    task.agent_id = None;
-                    task.lease_expires_at = None;
-                    pending.push_back(task);
-                } else {
-                    task.status = "failed".to_string();
-                    task.result =
-                        Some(serde_json::json!({ "error": "lease expired and no retries left" }));
-                    task.completed_at =
-                        Some(js_sys::Date::new_0().to_iso_string().as_string().unwrap_or_default());
+        let mut finished = Vec::new();
+        for (id, error) in to_release {
+            if let Some(task) = active.remove(&id) {
+                worker::console_log!("Releasing lease for task {}: {}", id, error);
+                let task =
+                    retry_or_dead_letter(task, error, &now_iso, &mut requeue, &mut dead_letter);
+                if is_play_outcome(&task) {
+                    finished.push(task);
                 }
             }
         }
+        put_dead_letter(&storage, &dead_letter).await?;
 
         storage.put("active", active).await?;
-        storage.put("pending", pending).await?;
+        append_pending(&storage, requeue.make_contiguous()).await?;
+        remove_scheduled(&storage, &due_keys).await?;
+        if !requeue.is_empty() {
+            let tenant_id = requeue.iter().find_map(|t| t.tenant_id.clone());
+            self.task_available(tenant_id.as_deref()).await?;
+        }
+        for task in &finished {
+            self.notify_play_outcome(task).await;
+        }
 
         // Drive the PlayManager notification retry loop (PR #132 crr
         // finding on task_do.rs:134). Pending entries persist across
@@ -586,7 +1325,8 @@ impl DurableObject for TaskLeaseManager {
         self.drive_notify_retries(now).await;
 
         // If there are still active tasks OR pending notifications,
-        // schedule the next sweep alarm.
+        // schedule the next sweep alarm — earlier if a delayed task comes
+        // due first.
         let active: std::collections::HashMap<String, AgentTask> = storage
             .get("active")
             .await
@@ -594,47 +1334,527 @@ impl DurableObject for TaskLeaseManager {
             .flatten()
             .unwrap_or_default();
         let notify_pending: Vec<PendingNotify> = storage
-            .get("notify_pending")
+            .get(NOTIFY_PENDING_KEY)
             .await
             .ok()
             .flatten()
             .unwrap_or_default();
-        if !active.is_empty() || !notify_pending.is_empty() {
-            let _ = storage
-                .set_alarm((now + LEASE_SWEEP_INTERVAL_MS) as i64)
-                .await;
+        let needs_sweep = !active.is_empty() || !notify_pending.is_empty();
+        if let Some(at) = next_alarm_at(now, needs_sweep, next_scheduled_due(&storage).await?) {
+            let _ = storage.set_alarm(at as i64).await;
         }
 
         Response::ok("alarm processed")
     }
+
+    async fn websocket_message(
+        &self,
+        ws: WebSocket,
+        message: WebSocketIncomingMessage,
+    ) -> Result<()> {
+        let WebSocketIncomingMessage::String(text) = message else {
+            return ws.send(&TaskSocketServerMessage::Error {
+                task_id: None,
+                message: "expected a JSON text message".into(),
+            });
+        };
+        let reply = match serde_json::from_str::<TaskSocketClientMessage>(&text) {
+            Ok(msg) => self.handle_socket_message(&ws, msg).await?,
+            Err(e) => TaskSocketServerMessage::Error {
+                task_id: None,
+                message: format!("invalid message: {e}"),
+            },
+        };
+        ws.send(&reply)?;
+        // The agent may be idle again after complete/fail.
+        self.dispatch_to_sockets().await
+    }
+
+    async fn websocket_close(
+        &self,
+        ws: WebSocket,
+        code: usize,
+        reason: String,
+        _was_clean: bool,
+    ) -> Result<()> {
+        // A lease still held by this socket is left to expire and retry
+        // through the normal sweep.
+        let _ = ws.close(Some(code as u16), Some(reason));
+        Ok(())
+    }
+
+    async fn websocket_error(&self, ws: WebSocket, error: Error) -> Result<()> {
+        if let Ok(Some(agent)) = ws.deserialize_attachment::<AgentSocket>() {
+            worker::console_log!("WARN: agent socket {} errored: {}", agent.agent_id, error);
+        }
+        Ok(())
+    }
+}
+
+fn iso_from_ms(ms: u64) -> String {
+    js_sys::Date::new(&serde_wasm_bindgen::to_value(&ms).unwrap_or_default())
+        .to_iso_string()
+        .as_string()
+        .unwrap_or_default()
+}
+
+fn iso_now() -> String {
+    js_sys::Date::new_0()
+        .to_iso_string()
+        .as_string()
+        .unwrap_or_default()
 }
 
 impl TaskLeaseManager {
-    /// Attempt to notify PlayManager that a task completed. On failure,
-    /// persist a `PendingNotify` entry and ensure an alarm is scheduled
-    /// so the retry loop in `drive_notify_retries` will pick it up.
+    /// Claim the best pending task for `agent_id`, persisting the lease.
+    async fn try_claim(&self, agent_id: &str, caps: &[String]) -> Result<Option<AgentTask>> {
+        Ok(self
+            .claim_for(&[(agent_id, caps)])
+            .await?
+            .into_iter()
+            .next()
+            .flatten())
+    }
+
+    /// Claim the best pending task for each `(agent_id, caps)` in turn,
+    /// persisting the leases. Priority + aging + per-job fair share; see
+    /// `select_claim_index`. Reads a [`ClaimWindow`], paging further only
+    /// while nothing read so far suits the agent.
+    async fn claim_for(&self, agents: &[(&str, &[String])]) -> Result<Vec<Option<AgentTask>>> {
+        let storage = self.state.storage();
+        let mut window = ClaimWindow::read(&storage).await?;
+        let mut active: std::collections::HashMap<String, AgentTask> = storage
+            .get("active")
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+
+        let now = js_sys::Date::now() as u64;
+        let expires = now + LEASE_WINDOW_MS;
+        let mut claimed = Vec::with_capacity(agents.len());
+        let mut taken = Vec::new();
+        for (agent_id, caps) in agents {
+            let task = loop {
+                if let Some(task) = claim_next_task(
+                    &mut window.tasks,
+                    &mut active,
+                    agent_id,
+                    caps,
+                    now,
+                    iso_from_ms(expires),
+                ) {
+                    break Some(task);
+                }
+                if !window.read_more(&storage).await? {
+                    break None;
+                }
+            };
+            if let Some(task) = &task {
+                if let Some(seq) = window.seqs.get(&task.id) {
+                    taken.push((*seq, task.clone()));
+                }
+            }
+            claimed.push(task);
+        }
+        if taken.is_empty() {
+            return Ok(claimed);
+        }
+        remove_pending(&storage, &taken).await?;
+        storage.put("active", active).await?;
+
+        // Set alarm to check for lease expiry (or the attempt timing out,
+        // if sooner), without pushing back an earlier one (e.g. a delayed
+        // task coming due).
+        let check_at = taken
+            .iter()
+            .map(|(_, task)| task.deadline_ms.map_or(expires, |d| d.min(expires)))
+            .min()
+            .unwrap_or(expires);
+        let _ = ensure_alarm_by(&storage, check_at).await;
+        Ok(claimed)
+    }
+
+    /// Long-poll claim: retry whenever a task becomes available until one
+    /// matches or `wait_ms` elapses. `wait_ms = 0` is a plain claim.
+    async fn claim_with_wait(
+        &self,
+        agent_id: &str,
+        caps: &[String],
+        wait_ms: u64,
+    ) -> Result<Option<AgentTask>> {
+        let deadline = js_sys::Date::now() as u64 + wait_ms;
+        loop {
+            // Snapshot before claiming so an enqueue that lands while the
+            // claim awaits storage still wakes the wait below.
+            let seen = self.claim_waiters.generation();
+            if let Some(task) = self.try_claim(agent_id, caps).await? {
+                return Ok(Some(task));
+            }
+            let now = js_sys::Date::now() as u64;
+            if now >= deadline {
+                return Ok(None);
+            }
+            let timeout = Delay::from(std::time::Duration::from_millis(deadline - now));
+            futures_util::future::select(self.claim_waiters.changed_since(seen), timeout).await;
+        }
+    }
+
+    /// Extend the lease on `task_id` if `agent_id` holds it. Returns the
+    /// task as it now stands: `running`, or `cancelled` if a cancel was
+    /// pending, in which case the lease has been released.
+    async fn heartbeat(&self, task_id: &str, agent_id: &str) -> Result<Option<AgentTask>> {
+        let storage = self.state.storage();
+        let mut active: std::collections::HashMap<String, AgentTask> = storage
+            .get("active")
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+
+        let expires = js_sys::Date::now() as u64 + LEASE_WINDOW_MS;
+        let task = heartbeat_task(
+            &mut active,
+            task_id,
+            agent_id,
+            iso_from_ms(expires),
+            &iso_now(),
+        );
+        if let Some(task) = &task {
+            storage.put("active", active).await?;
+            if is_play_outcome(task) {
+                self.notify_play_outcome(task).await;
+            }
+        }
+        Ok(task)
+    }
+
+    /// Cancel the queued and leased tasks matching `matches`; see
+    /// [`cancel_tasks`].
+    async fn cancel(&self, matches: impl Fn(&AgentTask) -> bool) -> Result<CancelledTasks> {
+        let storage = self.state.storage();
+        let mut pending = PendingQueue::load(&storage).await?;
+        let (scheduled_keys, mut scheduled): (Vec<String>, Vec<ScheduledTask>) =
+            list_scheduled(&storage, None).await?.into_iter().unzip();
+        let scheduled_ids: Vec<String> = scheduled.iter().map(|s| s.task.id.clone()).collect();
+        let mut active: std::collections::HashMap<String, AgentTask> = storage
+            .get("active")
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+
+        let outcome = cancel_tasks(
+            &mut pending.tasks,
+            &mut scheduled,
+            &mut active,
+            matches,
+            &iso_now(),
+        );
+        pending.save(&storage).await?;
+        let kept: std::collections::HashSet<&str> =
+            scheduled.iter().map(|s| s.task.id.as_str()).collect();
+        let removed: Vec<String> = scheduled_keys
+            .into_iter()
+            .zip(&scheduled_ids)
+            .filter(|(_, id)| !kept.contains(id.as_str()))
+            .map(|(key, _)| key)
+            .collect();
+        remove_scheduled(&storage, &removed).await?;
+        if !outcome.cancel_requested.is_empty() {
+            storage.put("active", active).await?;
+        }
+        for task in &outcome.cancelled {
+            self.notify_play_outcome(task).await;
+        }
+        Ok(outcome)
+    }
+
+    /// Mark an active task completed and notify its PlayManager, if any.
+    async fn complete(
+        &self,
+        task_id: &str,
+        result: Option<serde_json::Value>,
+    ) -> Result<Option<AgentTask>> {
+        let storage = self.state.storage();
+        let mut active: std::collections::HashMap<String, AgentTask> = storage
+            .get("active")
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+
+        let Some(task) = complete_task(&mut active, task_id, result, &iso_now()) else {
+            return Ok(None);
+        };
+        storage.put("active", active).await?;
+
+        self.notify_play_outcome(&task).await;
+        Ok(Some(task))
+    }
+
+    /// Record a failed attempt: requeue while retries remain, otherwise
+    /// dead-letter.
+    async fn fail(&self, task_id: &str, error: &str) -> Result<Option<AgentTask>> {
+        let storage = self.state.storage();
+        let mut active: std::collections::HashMap<String, AgentTask> = storage
+            .get("active")
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let mut requeue = VecDeque::new();
+        let mut dead_letter = Vec::new();
+
+        let Some(task) = fail_task(
+            &mut active,
+            &mut requeue,
+            &mut dead_letter,
+            task_id,
+            error,
+            &iso_now(),
+        ) else {
+            return Ok(None);
+        };
+        // Dead-letter first: if a later write fails, the exhausted task is
+        // still recorded rather than dropped from `active` with nowhere to go.
+        put_dead_letter(&storage, &dead_letter).await?;
+        storage.put("active", active).await?;
+        append_pending(&storage, requeue.make_contiguous()).await?;
+        if is_play_outcome(&task) {
+            self.notify_play_outcome(&task).await;
+        } else {
+            self.task_available(task.tenant_id.as_deref()).await?;
+        }
+        Ok(Some(task))
+    }
+
+    /// Something was added to `pending`: wake parked long-poll claims and
+    /// push work to idle socket agents.
+    async fn task_available(&self, tenant_id: Option<&str>) -> Result<()> {
+        self.claim_waiters.notify();
+        if let Some(tenant_id) = tenant_id {
+            self.wake_claim_shard(tenant_id).await;
+        }
+        self.dispatch_to_sockets().await
+    }
+
+    /// With several shards, long-poll claims park on shard 0 (see
+    /// `/wait`), so the other shards tell it when work arrives. Best
+    /// effort: a lost wake only delays a claim until its wait runs out.
+    async fn wake_claim_shard(&self, tenant_id: &str) {
+        let Ok(shards) = crate::task_shard::TaskShards::new(&self.env, tenant_id) else {
+            return;
+        };
+        if shards.config.shards <= 1 {
+            return;
+        }
+        let Ok(id) = shards.id(0) else {
+            return;
+        };
+        if id == self.state.id() {
+            return;
+        }
+        let sent = match (id.get_stub(), Request::new("https://do/wake", Method::Post)) {
+            (Ok(stub), Ok(req)) => stub.fetch_with_request(req).await.map(drop),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        };
+        if let Err(e) = sent {
+            worker::console_log!("WARN: waking claim shard for {} failed: {}", tenant_id, e);
+        }
+    }
+
+    /// Lease one pending task to each connected agent socket that is idle
+    /// and has a matching task, sending it as a `task` message.
+    async fn dispatch_to_sockets(&self) -> Result<()> {
+        let mut idle = Vec::new();
+        for ws in self.state.get_websockets() {
+            let Ok(Some(agent)) = ws.deserialize_attachment::<AgentSocket>() else {
+                continue;
+            };
+            if agent.task_id.is_none() {
+                idle.push((ws, agent));
+            }
+        }
+        if idle.is_empty() {
+            return Ok(());
+        }
+        // One claim window for every idle socket, not a read per socket.
+        let agents: Vec<(&str, &[String])> = idle
+            .iter()
+            .map(|(_, agent)| (agent.agent_id.as_str(), agent.caps.as_slice()))
+            .collect();
+        let claimed = self.claim_for(&agents).await?;
+        for ((ws, mut agent), task) in idle.into_iter().zip(claimed) {
+            let Some(task) = task else {
+                continue;
+            };
+            agent.task_id = Some(task.id.clone());
+            ws.serialize_attachment(&agent)?;
+            self.sync_task_to_d1(&task).await;
+            // A failed send leaves the lease to expire and retry normally.
+            if let Err(e) = ws.send(&TaskSocketServerMessage::Task {
+                task: Box::new(task),
+            }) {
+                worker::console_log!("WARN: task push to agent {} failed: {}", agent.agent_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Mirror a task changed over a socket into D1, as the lib.rs HTTP
+    /// handlers do for their routes. Best effort.
+    async fn sync_task_to_d1(&self, task: &AgentTask) {
+        let (Some(tenant_id), Ok(d1)) = (task.tenant_id.as_deref(), self.env.d1("DB")) else {
+            return;
+        };
+        if let Err(e) = crate::db::sync_task_status(&d1, tenant_id, task).await {
+            worker::console_log!("WARN: D1 sync for task {} failed: {}", task.id, e);
+        }
+    }
+
+    /// Handle one agent message. Returns the reply to send.
+    async fn handle_socket_message(
+        &self,
+        ws: &WebSocket,
+        msg: TaskSocketClientMessage,
+    ) -> Result<TaskSocketServerMessage> {
+        let mut agent: AgentSocket = ws
+            .deserialize_attachment()?
+            .ok_or_else(|| Error::RustError("socket has no agent attachment".into()))?;
+        let task_id = msg.task_id().to_string();
+        if agent.task_id.as_deref() != Some(task_id.as_str()) {
+            return Ok(TaskSocketServerMessage::Error {
+                task_id: Some(task_id),
+                message: "task not leased to this socket".into(),
+            });
+        }
+
+        let outcome = match msg {
+            TaskSocketClientMessage::Heartbeat { .. } => {
+                match self.heartbeat(&task_id, &agent.agent_id).await? {
+                    Some(task) if task.status == "running" => {
+                        return Ok(TaskSocketServerMessage::Ack {
+                            task_id,
+                            status: task.status,
+                        });
+                    }
+                    // Cancelled: ack with `cancelled` and free the socket.
+                    Some(task) => Some(task),
+                    None => {
+                        agent.task_id = None;
+                        ws.serialize_attachment(&agent)?;
+                        return Ok(TaskSocketServerMessage::Error {
+                            task_id: Some(task_id),
+                            message: "lease lost".into(),
+                        });
+                    }
+                }
+            }
+            TaskSocketClientMessage::Complete { result, .. } => {
+                let body = serde_json::to_value(TaskCompleteRequest { result })
+                    .map_err(|e| Error::RustError(e.to_string()))?;
+                self.complete(&task_id, Some(body)).await?
+            }
+            TaskSocketClientMessage::Fail { error, .. } => self.fail(&task_id, &error).await?,
+        };
+
+        agent.task_id = None;
+        ws.serialize_attachment(&agent)?;
+        let reply = match outcome {
+            Some(task) => {
+                self.sync_task_to_d1(&task).await;
+                TaskSocketServerMessage::Ack {
+                    task_id,
+                    status: task.status,
+                }
+            }
+            None => TaskSocketServerMessage::Error {
+                task_id: Some(task_id),
+                message: "task not found or not running".into(),
+            },
+        };
+        Ok(reply)
+    }
+
+    /// Tell the task's PlayManager, if it belongs to a play, that it
+    /// completed, failed for good or was cancelled. The PlayManager DO is
+    /// tenant-namespaced (see lib.rs `/v1/plays/:name/launch`) so we must
+    /// reconstruct the name as `{tenant_id}:play:{run_id}`. If the task is
+    /// missing tenant_id (legacy persisted state from before WS8), we skip
+    /// the notification rather than routing to a potentially cross-tenant
+    /// DO instance.
+    async fn notify_play_outcome(&self, task: &AgentTask) {
+        if task.play_id.is_none() {
+            return;
+        }
+        match task.tenant_id.as_deref() {
+            Some(tenant_id) if !tenant_id.is_empty() => {
+                let do_name = format!("{}:play:{}", tenant_id, task.job_id);
+                // PR #132 crr finding (task_do.rs:134): the previous
+                // notification was `let _ = play_stub.fetch_with_request().await;`
+                // which silently dropped delivery failures and orphaned
+                // any downstream tasks if PlayManager was unreachable.
+                // We now (1) attempt the notify, (2) on failure persist
+                // a PendingNotify entry to DO storage, (3) ensure an
+                // alarm is scheduled to drive the retry loop, and (4)
+                // surface the failure with a warn log visible in
+                // `wrangler tail`.
+                let outcome = play_task_outcome(task);
+                self.try_notify_play_manager(&do_name, &outcome.task_id, Some(&outcome), 1)
+                    .await;
+            }
+            Some(_) => worker::console_log!(
+                "skipping PlayManager notify for task {}: empty tenant_id",
+                task.id
+            ),
+            None => worker::console_log!(
+                "skipping PlayManager notify for task {}: tenant_id missing (pre-WS8 task)",
+                task.id
+            ),
+        }
+    }
+
+    /// Attempt to notify PlayManager that a task completed
+    /// (`/task-completed`) or failed for good (`/task-failed`). On failure,
+    /// persist a `PendingNotify` entry and ensure an alarm is scheduled so
+    /// the retry loop in `drive_notify_retries` will pick it up.
     ///
     /// `attempts` is the attempt number being recorded (1 for the
     /// initial direct call). The function is fire-and-forget from the
     /// caller's perspective — errors are logged + persisted, not bubbled.
-    async fn try_notify_play_manager(&self, target_name: &str, play_task_id: &str, attempts: u32) {
+    /// `outcome` is `None` only for entries persisted before outcomes
+    /// existed, which are delivered as the bare task id (a completion).
+    async fn try_notify_play_manager(
+        &self,
+        target_name: &str,
+        play_task_id: &str,
+        outcome: Option<&PlayTaskOutcome>,
+        attempts: u32,
+    ) {
+        let body = match outcome {
+            Some(o) => serde_json::to_string(o),
+            None => serde_json::to_string(play_task_id),
+        };
+        let url = match outcome {
+            Some(o) if o.status != "completed" => "https://do/task-failed",
+            _ => "https://do/task-completed",
+        };
         let do_req = match Request::new_with_init(
-            "https://do/task-completed",
+            url,
             &RequestInit {
                 method: Method::Post,
-                body: Some(
-                    match serde_wasm_bindgen::to_value(&play_task_id.to_string()) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            worker::console_log!(
-                                "task_do: failed to serialize play_task_id {}: {}",
-                                play_task_id,
-                                e
-                            );
-                            return;
-                        }
-                    },
-                ),
+                body: Some(match body {
+                    Ok(v) => wasm_bindgen::JsValue::from_str(&v),
+                    Err(e) => {
+                        worker::console_log!(
+                            "task_do: failed to serialize play_task_id {}: {}",
+                            play_task_id,
+                            e
+                        );
+                        return;
+                    }
+                }),
                 ..Default::default()
             },
         ) {
@@ -693,7 +1913,7 @@ impl TaskLeaseManager {
         let now = js_sys::Date::now() as u64;
         let storage = self.state.storage();
         let mut pending_list: Vec<PendingNotify> = storage
-            .get("notify_pending")
+            .get(NOTIFY_PENDING_KEY)
             .await
             .ok()
             .flatten()
@@ -704,9 +1924,10 @@ impl TaskLeaseManager {
             play_task_id: play_task_id.to_string(),
             attempts,
             next_attempt_at_ms: next_at,
+            outcome: outcome.cloned(),
         };
         upsert_pending_notify(&mut pending_list, entry);
-        if let Err(e) = storage.put("notify_pending", pending_list).await {
+        if let Err(e) = storage.put(NOTIFY_PENDING_KEY, pending_list).await {
             worker::console_log!(
                 "task_do: failed to persist notify_pending for {}/{}: {}",
                 target_name,
@@ -727,7 +1948,7 @@ impl TaskLeaseManager {
     async fn drive_notify_retries(&self, now_ms: u64) {
         let storage = self.state.storage();
         let pending_list: Vec<PendingNotify> = storage
-            .get("notify_pending")
+            .get(NOTIFY_PENDING_KEY)
             .await
             .ok()
             .flatten()
@@ -743,7 +1964,7 @@ impl TaskLeaseManager {
         // try_notify_play_manager will re-upsert with the bumped attempt
         // count. This avoids double-counting attempts on isolate
         // eviction mid-tick.
-        if let Err(e) = storage.put("notify_pending", deferred).await {
+        if let Err(e) = storage.put(NOTIFY_PENDING_KEY, deferred).await {
             worker::console_log!("task_do: failed to checkpoint notify_pending: {}", e);
             return;
         }
@@ -755,6 +1976,7 @@ impl TaskLeaseManager {
             self.try_notify_play_manager(
                 &entry.target_name,
                 &entry.play_task_id,
+                entry.outcome.as_ref(),
                 entry.attempts.saturating_add(1),
             )
             .await;
@@ -777,108 +1999,713 @@ async fn ensure_sweep_alarm(storage: &Storage) -> Result<()> {
     Ok(())
 }
 
-/// Pure helper: split a `notify_pending` list into (due_now, deferred)
-/// based on `now_ms`. Extracted for unit-testability.
-pub(crate) fn split_due_pending(
-    pending: &[PendingNotify],
-    now_ms: u64,
-) -> (Vec<PendingNotify>, Vec<PendingNotify>) {
-    let mut due = Vec::new();
-    let mut deferred = Vec::new();
-    for entry in pending {
-        if entry.next_attempt_at_ms <= now_ms {
-            due.push(entry.clone());
-        } else {
-            deferred.push(entry.clone());
-        }
-    }
-    (due, deferred)
-}
-
-/// Pure helper: percent-decode a `/complete/<task_id>` or
-/// `/fail/<task_id>` path tail into the canonical task id. Returns
-/// `None` for an empty segment, an invalid UTF-8 percent-encoding, or
-/// a decoded value that is empty after decoding.
-///
-/// crr2 MEDIUM finding (task_do.rs:87): the previous strip_prefix path
-/// kept the percent-encoded form (e.g. `task%2Dabc`) and used it as a
-/// hashmap key. The corresponding `/enqueue` path deserialises the
-/// task id from JSON, so the key in `active` was already in canonical
-/// form — meaning a percent-encoded `/complete/...` lookup silently
-/// missed and returned `404 task not found`. Decoding here makes the
-/// keying consistent regardless of caller encoding.
-///
-/// Extracted as a `pub(crate)` free function so it can be unit-tested
-/// without spinning up a Workers runtime.
-pub(crate) fn decode_task_id_segment(raw: &str) -> Option<String> {
-    if raw.is_empty() {
-        return None;
-    }
-    let decoded = percent_decode_str(raw).decode_utf8().ok()?;
-    let decoded = decoded.into_owned();
-    if decoded.is_empty() {
-        None
-    } else {
-        Some(decoded)
+/// Make sure an alarm fires no later than `at_ms`, moving an existing later
+/// alarm earlier. Used when a delayed task is enqueued.
+async fn ensure_alarm_by(storage: &Storage, at_ms: u64) -> Result<()> {
+    let current = storage.get_alarm().await.ok().flatten();
+    if current.is_none_or(|c| c > at_ms as i64) {
+        storage.set_alarm(at_ms as i64).await?;
     }
+    Ok(())
 }
 
-/// Pure helper: extract `task_id` from a DO request path of the form
-/// `/<route>/<task_id>` for the parameterised routes `/complete` and
-/// `/fail`. Returns `Some(task_id)` when the path matches the given
-/// route prefix and the task_id segment is non-empty; `None` otherwise.
-///
-/// Extracted as a test-only free function so the routing contract
-/// (mirroring `path.strip_prefix("/complete/")` in the live `fetch`
-/// handler) is unit-testable on host without spinning up a Workers
-/// runtime. This backs the regression test for the PR #132 routing bug
-/// where the old `/complete` static match arm never fired against the
-/// lib.rs caller's `/complete/<task_id>` path.
-#[cfg(test)]
-pub(crate) fn parse_task_id_from_path(path: &str, route: &str) -> Option<String> {
-    let prefix = format!("/{}/", route.trim_matches('/'));
-    let tail = path.strip_prefix(&prefix)?;
-    // Mirror the production decode step (crr2 MEDIUM finding) so this
-    // helper stays a faithful model of the live `fetch` routing.
-    decode_task_id_segment(tail)
+/// Bump the rejection counter and build the 429 `QUEUE_FULL` envelope
+/// (with `retry-after`) shared by `/enqueue` and dead-letter requeue.
+/// The counter persists across requests so operators can see sustained
+/// pressure via `GET /v1/tasks/queue`.
+async fn reject_queue_full(storage: &Storage) -> Result<Response> {
+    let prev_rejected: u64 = storage
+        .get(PENDING_REJECTED_TOTAL_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0);
+    let next_rejected = prev_rejected.saturating_add(1);
+    storage
+        .put(PENDING_REJECTED_TOTAL_KEY, next_rejected)
+        .await?;
+
+    let headers = Headers::new();
+    headers.set("retry-after", &ENQUEUE_RETRY_AFTER_SECS.to_string())?;
+    headers.set("content-type", "application/json")?;
+    let body = serde_json::json!({
+        "error": {
+            "code": "QUEUE_FULL",
+            "message": "pending_tasks queue at capacity; retry after the indicated delay",
+            "details": {
+                "max_pending_tasks": MAX_PENDING_TASKS,
+                "retry_after_seconds": ENQUEUE_RETRY_AFTER_SECS,
+            }
+        }
+    });
+    Ok(Response::from_json(&body)?
+        .with_status(429)
+        .with_headers(headers))
 }
 
-/// Pure helper: upsert a PendingNotify into the in-memory list, keyed
-/// by `(target_name, play_task_id)`. If the key already exists we
-/// replace it (matters for retry re-enqueue with bumped attempt count).
-pub(crate) fn upsert_pending_notify(list: &mut Vec<PendingNotify>, entry: PendingNotify) {
-    if let Some(existing) = list
-        .iter_mut()
-        .find(|e| e.target_name == entry.target_name && e.play_task_id == entry.play_task_id)
-    {
-        *existing = entry;
-    } else {
-        list.push(entry);
-    }
+/// The whole pending queue as loaded from storage, for the routes that
+/// need all of it (`/queue`, cancel). Handlers work on `tasks`;
+/// [`PendingQueue::save`] writes back only what changed. Enqueue and claim
+/// use [`append_pending`] and [`ClaimWindow`] instead.
+pub(crate) struct PendingQueue {
+    pub tasks: VecDeque<AgentTask>,
+    stored: Vec<(u64, AgentTask)>,
+    next_seq: u64,
+    legacy: bool,
+    /// `PENDING_SEQ_KEY` / `PENDING_LEN_KEY` as loaded; `None` before the
+    /// keys existed.
+    stored_seq: Option<u64>,
+    stored_len: Option<usize>,
+    /// Write the priority index for every kept task, not just new ones
+    /// (the layout migration).
+    index_all: bool,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    // ── enqueue_decision (backpressure rule) ────────────────────
+/// Durable Object storage caps `put`/`delete` batches at 128 keys.
+const STORAGE_BATCH: usize = 128;
 
-    #[test]
-    fn enqueue_decision_accepts_when_below_capacity() {
-        assert_eq!(enqueue_decision(0, 10), EnqueueDecision::Accept);
-        assert_eq!(enqueue_decision(5, 10), EnqueueDecision::Accept);
-        assert_eq!(enqueue_decision(9, 10), EnqueueDecision::Accept);
+impl PendingQueue {
+    pub(crate) async fn load(storage: &Storage) -> Result<Self> {
+        ensure_queue_layout(storage).await?;
+        Self::read(storage).await
     }
 
-    #[test]
-    fn enqueue_decision_rejects_at_capacity() {
-        assert_eq!(enqueue_decision(10, 10), EnqueueDecision::Reject);
-        assert_eq!(enqueue_decision(11, 10), EnqueueDecision::Reject);
-        assert_eq!(enqueue_decision(usize::MAX, 10), EnqueueDecision::Reject);
+    async fn read(storage: &Storage) -> Result<Self> {
+        let stored: Vec<(u64, AgentTask)> =
+            list_values(storage, ListOptions::new().prefix(PENDING_PREFIX))
+                .await?
+                .into_iter()
+                .filter_map(|(key, task)| Some((parse_pending_key(&key)?, task)))
+                .collect();
+        let stored_seq: Option<u64> = storage.get(PENDING_SEQ_KEY).await.ok().flatten();
+        let next_seq = stored_seq.unwrap_or_else(|| stored.last().map_or(0, |(seq, _)| seq + 1));
+
+        let legacy: Option<VecDeque<AgentTask>> =
+            storage.get(LEGACY_PENDING_KEY).await.ok().flatten();
+        let mut tasks: VecDeque<AgentTask> = stored.iter().map(|(_, t)| t.clone()).collect();
+        if let Some(old) = &legacy {
+            // Everything in the old vector was queued first.
+            for task in old.iter().rev() {
+                tasks.push_front(task.clone());
+            }
+        }
+        let stored_len: Option<usize> = storage.get(PENDING_LEN_KEY).await.ok().flatten();
+        Ok(Self {
+            tasks,
+            stored,
+            next_seq,
+            legacy: legacy.is_some(),
+            stored_seq,
+            stored_len,
+            index_all: false,
+        })
     }
 
-    #[test]
-    fn enqueue_decision_uses_configured_max_pending_tasks() {
+    pub(crate) async fn save(&mut self, storage: &Storage) -> Result<()> {
+        let writes = plan_pending_writes(&self.stored, &self.tasks, self.next_seq);
+        let deleted: std::collections::HashSet<u64> = writes.deletes.iter().copied().collect();
+        let mut index: Vec<(u64, i32)> = writes
+            .puts
+            .iter()
+            .map(|(seq, task)| (*seq, task.priority))
+            .collect();
+        if self.index_all {
+            index.extend(
+                self.stored
+                    .iter()
+                    .filter(|(seq, _)| !deleted.contains(seq))
+                    .map(|(seq, task)| (*seq, task.priority)),
+            );
+        }
+        for chunk in writes.puts.chunks(STORAGE_BATCH) {
+            let batch = js_sys::Object::new();
+            for (seq, task) in chunk {
+                js_sys::Reflect::set(
+                    &batch,
+                    &wasm_bindgen::JsValue::from_str(&pending_key(*seq)),
+                    &serde_wasm_bindgen::to_value(task)?,
+                )?;
+            }
+            storage.put_multiple_raw(batch).await?;
+        }
+        put_priority_index(storage, &index).await?;
+        let mut deletes: Vec<String> = Vec::with_capacity(writes.deletes.len() * 2);
+        for (seq, task) in self.stored.iter().filter(|(seq, _)| deleted.contains(seq)) {
+            deletes.push(pending_key(*seq));
+            deletes.push(pending_priority_key(task.priority, *seq));
+        }
+        for chunk in deletes.chunks(STORAGE_BATCH) {
+            storage.delete_multiple(chunk.to_vec()).await?;
+        }
+        if self.stored_seq != Some(writes.next_seq) {
+            storage.put(PENDING_SEQ_KEY, writes.next_seq).await?;
+            self.stored_seq = Some(writes.next_seq);
+        }
+        if self.legacy {
+            storage.delete(LEGACY_PENDING_KEY).await?;
+            self.legacy = false;
+        }
+        if self.stored_len != Some(self.tasks.len()) {
+            storage.put(PENDING_LEN_KEY, self.tasks.len()).await?;
+            self.stored_len = Some(self.tasks.len());
+        }
+
+        let mut stored: Vec<(u64, AgentTask)> = self
+            .stored
+            .drain(..)
+            .filter(|(seq, _)| !deleted.contains(seq))
+            .collect();
+        stored.extend(writes.puts);
+        self.stored = stored;
+        self.next_seq = writes.next_seq;
+        self.index_all = false;
+        Ok(())
+    }
+}
+
+/// Bring a queue saved under an older layout up to date: the legacy
+/// single-key pending and scheduled vectors move to per-task keys, and the
+/// counters and priority index are written. One `get` once migrated.
+async fn ensure_queue_layout(storage: &Storage) -> Result<()> {
+    let version: u32 = storage
+        .get(QUEUE_LAYOUT_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0);
+    if version >= QUEUE_LAYOUT_VERSION {
+        return Ok(());
+    }
+    let mut pending = PendingQueue::read(storage).await?;
+    pending.index_all = true;
+    pending.save(storage).await?;
+
+    let legacy: Vec<ScheduledTask> = storage
+        .get(LEGACY_SCHEDULED_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let mut scheduled_len: usize = storage
+        .get(SCHEDULED_LEN_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0);
+    for entry in legacy {
+        put_scheduled_entry(storage, entry).await?;
+        scheduled_len += 1;
+    }
+    storage.put(SCHEDULED_LEN_KEY, scheduled_len).await?;
+    storage.delete(LEGACY_SCHEDULED_KEY).await?;
+    storage.put(QUEUE_LAYOUT_KEY, QUEUE_LAYOUT_VERSION).await
+}
+
+async fn put_priority_index(storage: &Storage, index: &[(u64, i32)]) -> Result<()> {
+    for chunk in index.chunks(STORAGE_BATCH) {
+        let batch = js_sys::Object::new();
+        for (seq, priority) in chunk {
+            js_sys::Reflect::set(
+                &batch,
+                &wasm_bindgen::JsValue::from_str(&pending_priority_key(*priority, *seq)),
+                &serde_wasm_bindgen::to_value(seq)?,
+            )?;
+        }
+        storage.put_multiple_raw(batch).await?;
+    }
+    Ok(())
+}
+
+/// Take the next `n` seqs from `PENDING_SEQ_KEY`, returning the first.
+async fn reserve_seqs(storage: &Storage, n: usize) -> Result<u64> {
+    let first: u64 = storage
+        .get(PENDING_SEQ_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0);
+    storage.put(PENDING_SEQ_KEY, first + n as u64).await?;
+    Ok(first)
+}
+
+/// Push `tasks` onto the back of the queue, writing only their own keys
+/// and the counters.
+pub(crate) async fn append_pending(storage: &Storage, tasks: &[AgentTask]) -> Result<()> {
+    if tasks.is_empty() {
+        return Ok(());
+    }
+    let len = pending_len(storage).await?;
+    let first = reserve_seqs(storage, tasks.len()).await?;
+    let entries: Vec<(u64, &AgentTask)> = (first..).zip(tasks).collect();
+    for chunk in entries.chunks(STORAGE_BATCH) {
+        let batch = js_sys::Object::new();
+        for (seq, task) in chunk {
+            js_sys::Reflect::set(
+                &batch,
+                &wasm_bindgen::JsValue::from_str(&pending_key(*seq)),
+                &serde_wasm_bindgen::to_value(task)?,
+            )?;
+        }
+        storage.put_multiple_raw(batch).await?;
+    }
+    let index: Vec<(u64, i32)> = entries.iter().map(|(seq, t)| (*seq, t.priority)).collect();
+    put_priority_index(storage, &index).await?;
+    storage.put(PENDING_LEN_KEY, len + tasks.len()).await
+}
+
+/// Drop claimed `(seq, task)` entries from the queue.
+async fn remove_pending(storage: &Storage, taken: &[(u64, AgentTask)]) -> Result<()> {
+    let len = pending_len(storage).await?;
+    let mut keys = Vec::with_capacity(taken.len() * 2);
+    for (seq, task) in taken {
+        keys.push(pending_key(*seq));
+        keys.push(pending_priority_key(task.priority, *seq));
+    }
+    for chunk in keys.chunks(STORAGE_BATCH) {
+        storage.delete_multiple(chunk.to_vec()).await?;
+    }
+    storage
+        .put(PENDING_LEN_KEY, len.saturating_sub(taken.len()))
+        .await
+}
+
+/// Pending task count from `PENDING_LEN_KEY`.
+pub(crate) async fn pending_len(storage: &Storage) -> Result<usize> {
+    ensure_queue_layout(storage).await?;
+    Ok(storage
+        .get(PENDING_LEN_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0))
+}
+
+/// Scheduled task count from `SCHEDULED_LEN_KEY`.
+async fn scheduled_len(storage: &Storage) -> Result<usize> {
+    ensure_queue_layout(storage).await?;
+    Ok(storage
+        .get(SCHEDULED_LEN_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0))
+}
+
+async fn put_scheduled_entry(storage: &Storage, entry: ScheduledTask) -> Result<()> {
+    let seq = reserve_seqs(storage, 1).await?;
+    storage
+        .put(&scheduled_key(entry.due_at_ms, seq), entry)
+        .await
+}
+
+/// Hold `task` back until `due_at_ms`.
+async fn put_scheduled(storage: &Storage, due_at_ms: u64, task: AgentTask) -> Result<()> {
+    let len = scheduled_len(storage).await?;
+    put_scheduled_entry(storage, ScheduledTask { due_at_ms, task }).await?;
+    storage.put(SCHEDULED_LEN_KEY, len + 1).await
+}
+
+/// Scheduled tasks with their keys, soonest first. With `due_by_ms`, only
+/// those due by then.
+async fn list_scheduled(
+    storage: &Storage,
+    due_by_ms: Option<u64>,
+) -> Result<Vec<(String, ScheduledTask)>> {
+    ensure_queue_layout(storage).await?;
+    let end = due_by_ms.map(|ms| scheduled_key(ms.saturating_add(1), 0));
+    let mut options = ListOptions::new().prefix(SCHEDULED_PREFIX);
+    if let Some(end) = end.as_deref() {
+        options = options.end(end);
+    }
+    list_values(storage, options).await
+}
+
+/// Due time of the soonest scheduled task.
+async fn next_scheduled_due(storage: &Storage) -> Result<Option<u64>> {
+    let first: Vec<(String, ScheduledTask)> = list_values(
+        storage,
+        ListOptions::new().prefix(SCHEDULED_PREFIX).limit(1),
+    )
+    .await?;
+    Ok(first.first().map(|(_, s)| s.due_at_ms))
+}
+
+/// Drop the scheduled entries stored under `keys`.
+async fn remove_scheduled(storage: &Storage, keys: &[String]) -> Result<()> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+    let len = scheduled_len(storage).await?;
+    for chunk in keys.chunks(STORAGE_BATCH) {
+        storage.delete_multiple(chunk.to_vec()).await?;
+    }
+    storage
+        .put(SCHEDULED_LEN_KEY, len.saturating_sub(keys.len()))
+        .await
+}
+
+/// The pending tasks a claim chooses from: the [`CLAIM_WINDOW`] oldest and
+/// the [`CLAIM_WINDOW`] highest-priority ones, extended a page at a time
+/// through the rest of the queue by [`ClaimWindow::read_more`].
+struct ClaimWindow {
+    tasks: VecDeque<AgentTask>,
+    /// Seq of every task read so far, by task id, including claimed ones.
+    seqs: HashMap<String, u64>,
+    /// Last seq read from the front of the queue; `None` once the whole
+    /// queue has been read.
+    read_up_to: Option<u64>,
+}
+
+impl ClaimWindow {
+    async fn read(storage: &Storage) -> Result<Self> {
+        ensure_queue_layout(storage).await?;
+        let mut window = Self {
+            tasks: VecDeque::new(),
+            seqs: HashMap::new(),
+            read_up_to: Some(0),
+        };
+        window.read_page(storage, 0).await?;
+
+        let by_priority: Vec<(String, u64)> = list_values(
+            storage,
+            ListOptions::new()
+                .prefix(PENDING_PRIORITY_PREFIX)
+                .limit(CLAIM_WINDOW),
+        )
+        .await?;
+        let keys: Vec<String> = by_priority
+            .into_iter()
+            .filter(|(_, seq)| window.read_up_to.is_some_and(|up_to| *seq > up_to))
+            .map(|(_, seq)| pending_key(seq))
+            .collect();
+        if !keys.is_empty() {
+            let page = map_values(storage.get_multiple(keys).await?)?
+                .into_iter()
+                .filter_map(|(key, task)| Some((parse_pending_key(&key)?, task)))
+                .collect();
+            merge_claim_window(&mut window.tasks, &mut window.seqs, page);
+        }
+        Ok(window)
+    }
+
+    /// Read the next page of the queue from `from_seq`.
+    async fn read_page(&mut self, storage: &Storage, from_seq: u64) -> Result<()> {
+        let start = pending_key(from_seq);
+        let page: Vec<(u64, AgentTask)> = list_values(
+            storage,
+            ListOptions::new()
+                .prefix(PENDING_PREFIX)
+                .start(&start)
+                .limit(CLAIM_WINDOW),
+        )
+        .await?
+        .into_iter()
+        .filter_map(|(key, task)| Some((parse_pending_key(&key)?, task)))
+        .collect();
+        self.read_up_to = if page.len() < CLAIM_WINDOW {
+            None
+        } else {
+            page.last().map(|(seq, _)| *seq)
+        };
+        merge_claim_window(&mut self.tasks, &mut self.seqs, page);
+        Ok(())
+    }
+
+    /// Extend the window with the next page; `false` once nothing is left.
+    async fn read_more(&mut self, storage: &Storage) -> Result<bool> {
+        let Some(up_to) = self.read_up_to else {
+            return Ok(false);
+        };
+        self.read_page(storage, up_to + 1).await?;
+        Ok(true)
+    }
+}
+
+/// List `(key, value)` pairs; values that no longer deserialize are logged
+/// and skipped.
+async fn list_values<T: serde::de::DeserializeOwned>(
+    storage: &Storage,
+    options: ListOptions<'_>,
+) -> Result<Vec<(String, T)>> {
+    map_values(storage.list_with_options(options).await?)
+}
+
+/// `(key, value)` pairs of a `list` or `get_multiple` result; values that
+/// no longer deserialize are logged and skipped.
+pub(crate) fn map_values<T: serde::de::DeserializeOwned>(
+    map: js_sys::Map,
+) -> Result<Vec<(String, T)>> {
+    let mut values = Vec::with_capacity(map.size() as usize);
+    for entry in map.entries() {
+        let pair: js_sys::Array = entry?.into();
+        let Some(key) = pair.get(0).as_string() else {
+            continue;
+        };
+        match serde_wasm_bindgen::from_value::<T>(pair.get(1)) {
+            Ok(value) => values.push((key, value)),
+            Err(e) => worker::console_log!("task_do: skipping unreadable {}: {}", key, e),
+        }
+    }
+    Ok(values)
+}
+
+/// Move a store saved in the single-key layout to per-task keys.
+async fn migrate_legacy_dead_letter(storage: &Storage) -> Result<()> {
+    let legacy: Option<Vec<DeadLetterTask>> =
+        storage.get(LEGACY_DEAD_LETTER_KEY).await.ok().flatten();
+    if let Some(entries) = legacy {
+        put_dead_letter_entries(storage, &entries).await?;
+        storage.delete(LEGACY_DEAD_LETTER_KEY).await?;
+    }
+    Ok(())
+}
+
+async fn dead_letter_len(storage: &Storage) -> Result<usize> {
+    migrate_legacy_dead_letter(storage).await?;
+    Ok(storage
+        .get(DEAD_LETTER_LEN_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0))
+}
+
+async fn put_dead_letter_entries(storage: &Storage, entries: &[DeadLetterTask]) -> Result<()> {
+    let len: usize = storage
+        .get(DEAD_LETTER_LEN_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0);
+    // Two keys per entry.
+    for chunk in entries.chunks(STORAGE_BATCH / 2) {
+        let batch = js_sys::Object::new();
+        for entry in chunk {
+            let key = dead_letter_key(&entry.dead_lettered_at, &entry.task.id);
+            js_sys::Reflect::set(
+                &batch,
+                &wasm_bindgen::JsValue::from_str(&dead_letter_id_key(&entry.task.id)),
+                &wasm_bindgen::JsValue::from_str(&key),
+            )?;
+            js_sys::Reflect::set(
+                &batch,
+                &wasm_bindgen::JsValue::from_str(&key),
+                &serde_wasm_bindgen::to_value(entry)?,
+            )?;
+        }
+        storage.put_multiple_raw(batch).await?;
+    }
+    storage
+        .put(DEAD_LETTER_LEN_KEY, len.saturating_add(entries.len()))
+        .await
+}
+
+/// Add newly dead-lettered tasks, then evict the oldest entries beyond
+/// `MAX_DEAD_LETTER_TASKS`, logging every evicted task id. Callers write
+/// this before taking the tasks out of `active`, so a failed write never
+/// loses an exhausted task.
+async fn put_dead_letter(storage: &Storage, entries: &[DeadLetterTask]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    migrate_legacy_dead_letter(storage).await?;
+    put_dead_letter_entries(storage, entries).await?;
+
+    let len = dead_letter_len(storage).await?;
+    let excess = len.saturating_sub(MAX_DEAD_LETTER_TASKS);
+    if excess == 0 {
+        return Ok(());
+    }
+    let oldest: Vec<(String, DeadLetterTask)> = list_values(
+        storage,
+        ListOptions::new().prefix(DEAD_LETTER_PREFIX).limit(excess),
+    )
+    .await?;
+    let mut keys = Vec::with_capacity(oldest.len() * 2);
+    for (key, entry) in &oldest {
+        worker::console_log!(
+            "evicting dead-lettered task {} (store at capacity)",
+            entry.task.id
+        );
+        keys.push(key.clone());
+        keys.push(dead_letter_id_key(&entry.task.id));
+    }
+    for chunk in keys.chunks(STORAGE_BATCH) {
+        storage.delete_multiple(chunk.to_vec()).await?;
+    }
+    storage
+        .put(DEAD_LETTER_LEN_KEY, len.saturating_sub(oldest.len()))
+        .await
+}
+
+/// Look up `task_id` in the store without removing it.
+async fn get_dead_letter(storage: &Storage, task_id: &str) -> Result<Option<DeadLetterTask>> {
+    migrate_legacy_dead_letter(storage).await?;
+    let Some(key) = storage
+        .get::<String>(&dead_letter_id_key(task_id))
+        .await
+        .ok()
+        .flatten()
+    else {
+        return Ok(None);
+    };
+    Ok(storage.get(&key).await.ok().flatten())
+}
+
+/// Remove `task_id` from the store, returning its entry.
+async fn take_dead_letter(storage: &Storage, task_id: &str) -> Result<Option<DeadLetterTask>> {
+    let len = dead_letter_len(storage).await?;
+    let id_key = dead_letter_id_key(task_id);
+    let Some(key) = storage.get::<String>(&id_key).await.ok().flatten() else {
+        return Ok(None);
+    };
+    let entry: Option<DeadLetterTask> = storage.get(&key).await.ok().flatten();
+    storage.delete_multiple(vec![key, id_key]).await?;
+    if entry.is_some() {
+        storage
+            .put(DEAD_LETTER_LEN_KEY, len.saturating_sub(1))
+            .await?;
+    }
+    Ok(entry)
+}
+
+/// One `/dead-letter` page read straight from storage: lists newest first
+/// from the cursor, in batches, until `limit + 1` entries match `job_id`
+/// or the store runs out.
+async fn list_dead_letter_page(
+    storage: &Storage,
+    job_id: Option<&str>,
+    after: Option<&DeadLetterCursor>,
+    limit: usize,
+) -> Result<(Vec<DeadLetterTask>, Option<DeadLetterCursor>)> {
+    migrate_legacy_dead_letter(storage).await?;
+    let mut end = after.map(|c| dead_letter_key(&c.dead_lettered_at, &c.id));
+    let mut matching = Vec::new();
+    loop {
+        let mut options = ListOptions::new()
+            .prefix(DEAD_LETTER_PREFIX)
+            .reverse(true)
+            .limit(DEAD_LETTER_LIST_BATCH);
+        if let Some(end) = end.as_deref() {
+            options = options.end(end);
+        }
+        let batch: Vec<(String, DeadLetterTask)> = list_values(storage, options).await?;
+        let exhausted = batch.len() < DEAD_LETTER_LIST_BATCH;
+        for (key, entry) in batch {
+            end = Some(key);
+            if job_id.is_none_or(|job| entry.task.job_id == job) {
+                matching.push(entry);
+            }
+        }
+        if exhausted || matching.len() > limit {
+            break;
+        }
+    }
+    Ok(dead_letter_page(&matching, job_id, after, limit))
+}
+
+/// Pure helper: split a `notify_pending` list into (due_now, deferred)
+/// based on `now_ms`. Extracted for unit-testability.
+pub(crate) fn split_due_pending(
+    pending: &[PendingNotify],
+    now_ms: u64,
+) -> (Vec<PendingNotify>, Vec<PendingNotify>) {
+    let mut due = Vec::new();
+    let mut deferred = Vec::new();
+    for entry in pending {
+        if entry.next_attempt_at_ms <= now_ms {
+            due.push(entry.clone());
+        } else {
+            deferred.push(entry.clone());
+        }
+    }
+    (due, deferred)
+}
+
+/// Pure helper: percent-decode a `/complete/<task_id>` or
+/// `/fail/<task_id>` path tail into the canonical task id. Returns
+/// `None` for an empty segment, an invalid UTF-8 percent-encoding, or
+/// a decoded value that is empty after decoding.
+///
+/// crr2 MEDIUM finding (task_do.rs:87): the previous strip_prefix path
+/// kept the percent-encoded form (e.g. `task%2Dabc`) and used it as a
+/// hashmap key. The corresponding `/enqueue` path deserialises the
+/// task id from JSON, so the key in `active` was already in canonical
+/// form — meaning a percent-encoded `/complete/...` lookup silently
+/// missed and returned `404 task not found`. Decoding here makes the
+/// keying consistent regardless of caller encoding.
+///
+/// Extracted as a `pub(crate)` free function so it can be unit-tested
+/// without spinning up a Workers runtime.
+pub(crate) fn decode_task_id_segment(raw: &str) -> Option<String> {
+    if raw.is_empty() {
+        return None;
+    }
+    let decoded = percent_decode_str(raw).decode_utf8().ok()?;
+    let decoded = decoded.into_owned();
+    if decoded.is_empty() {
+        None
+    } else {
+        Some(decoded)
+    }
+}
+
+/// Pure helper: extract `task_id` from a DO request path of the form
+/// `/<route>/<task_id>` for the parameterised routes `/complete` and
+/// `/fail`. Returns `Some(task_id)` when the path matches the given
+/// route prefix and the task_id segment is non-empty; `None` otherwise.
+///
+/// Extracted as a test-only free function so the routing contract
+/// (mirroring `path.strip_prefix("/complete/")` in the live `fetch`
+/// handler) is unit-testable on host without spinning up a Workers
+/// runtime. This backs the regression test for the PR #132 routing bug
+/// where the old `/complete` static match arm never fired against the
+/// lib.rs caller's `/complete/<task_id>` path.
+#[cfg(test)]
+pub(crate) fn parse_task_id_from_path(path: &str, route: &str) -> Option<String> {
+    let prefix = format!("/{}/", route.trim_matches('/'));
+    let tail = path.strip_prefix(&prefix)?;
+    // Mirror the production decode step (crr2 MEDIUM finding) so this
+    // helper stays a faithful model of the live `fetch` routing.
+    decode_task_id_segment(tail)
+}
+
+/// Pure helper: upsert a PendingNotify into the in-memory list, keyed
+/// by `(target_name, play_task_id)`. If the key already exists we
+/// replace it (matters for retry re-enqueue with bumped attempt count).
+pub(crate) fn upsert_pending_notify(list: &mut Vec<PendingNotify>, entry: PendingNotify) {
+    if let Some(existing) = list
+        .iter_mut()
+        .find(|e| e.target_name == entry.target_name && e.play_task_id == entry.play_task_id)
+    {
+        *existing = entry;
+    } else {
+        list.push(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ── enqueue_decision (backpressure rule) ────────────────────
+
+    #[test]
+    fn enqueue_decision_accepts_when_below_capacity() {
+        assert_eq!(enqueue_decision(0, 10), EnqueueDecision::Accept);
+        assert_eq!(enqueue_decision(5, 10), EnqueueDecision::Accept);
+        assert_eq!(enqueue_decision(9, 10), EnqueueDecision::Accept);
+    }
+
+    #[test]
+    fn enqueue_decision_rejects_at_capacity() {
+        assert_eq!(enqueue_decision(10, 10), EnqueueDecision::Reject);
+        assert_eq!(enqueue_decision(11, 10), EnqueueDecision::Reject);
+        assert_eq!(enqueue_decision(usize::MAX, 10), EnqueueDecision::Reject);
+    }
+
+    #[test]
+    fn enqueue_decision_uses_configured_max_pending_tasks() {
         assert_eq!(
             enqueue_decision(MAX_PENDING_TASKS - 1, MAX_PENDING_TASKS),
             EnqueueDecision::Accept,
@@ -889,6 +2716,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn queue_depth_counts_scheduled_tasks_toward_the_cap() {
+        assert_eq!(
+            enqueue_decision(queue_depth(MAX_PENDING_TASKS - 1, 0), MAX_PENDING_TASKS),
+            EnqueueDecision::Accept,
+        );
+        assert_eq!(
+            enqueue_decision(queue_depth(MAX_PENDING_TASKS - 1, 1), MAX_PENDING_TASKS),
+            EnqueueDecision::Reject,
+        );
+        assert_eq!(queue_depth(usize::MAX, 1), usize::MAX);
+    }
+
     fn simulate_rejection_counter(prev: u64) -> u64 {
         prev.saturating_add(1)
     }
@@ -973,6 +2813,7 @@ mod tests {
                 play_task_id: "t1".to_string(),
                 attempts: 1,
                 next_attempt_at_ms: next_attempt_at(now, 1),
+                outcome: None,
             },
         );
         assert_eq!(list.len(), 1);
@@ -987,6 +2828,7 @@ mod tests {
                 play_task_id: "t1".to_string(),
                 attempts: 2,
                 next_attempt_at_ms: next_attempt_at(now, 2),
+                outcome: None,
             },
         );
         assert_eq!(list.len(), 1, "upsert keyed on (target,task), not appended");
@@ -1000,6 +2842,7 @@ mod tests {
                 play_task_id: "t2".to_string(),
                 attempts: 1,
                 next_attempt_at_ms: next_attempt_at(now, 1),
+                outcome: None,
             },
         );
         assert_eq!(list.len(), 2);
@@ -1025,18 +2868,21 @@ mod tests {
                 play_task_id: "t1".to_string(),
                 attempts: 1,
                 next_attempt_at_ms: now - 100, // due
+                outcome: None,
             },
             PendingNotify {
                 target_name: "j1".to_string(),
                 play_task_id: "t2".to_string(),
                 attempts: 1,
                 next_attempt_at_ms: now + 5_000, // deferred
+                outcome: None,
             },
             PendingNotify {
                 target_name: "j2".to_string(),
                 play_task_id: "t1".to_string(),
                 attempts: 2,
                 next_attempt_at_ms: now, // due (equality)
+                outcome: None,
             },
         ];
         let (due, deferred) = split_due_pending(&pending, now);
@@ -1199,6 +3045,10 @@ mod tests {
 
     // ── DO unit coverage (PR #142): queue / lease / complete helpers ──
 
+    fn any_agent() -> CapabilitySet {
+        CapabilitySet::new("a", &[])
+    }
+
     fn make_task(id: &str, task_type: &str) -> AgentTask {
         AgentTask {
             id: id.to_string(),
@@ -1219,6 +3069,14 @@ mod tests {
             completed_at: None,
             memory_context: None,
             tenant_id: Some("tenant-test".to_string()),
+            enqueued_at_ms: None,
+            failures: Vec::new(),
+            not_before: None,
+            cancel_requested: false,
+            requires: Vec::new(),
+            idempotency_key: None,
+            timeout_ms: None,
+            deadline_ms: None,
         }
     }
 
@@ -1267,6 +3125,140 @@ mod tests {
         assert_eq!(pending[0].id, "t1");
     }
 
+    fn make_job_task(id: &str, job_id: &str, priority: i32, enqueued_at_ms: u64) -> AgentTask {
+        let mut task = make_task(id, "build");
+        task.job_id = job_id.to_string();
+        task.priority = priority;
+        task.enqueued_at_ms = Some(enqueued_at_ms);
+        task
+    }
+
+    #[test]
+    fn claim_prefers_higher_priority_over_queue_order() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        enqueue_task(&mut pending, make_job_task("low", "job-1", 0, 0));
+        enqueue_task(&mut pending, make_job_task("high", "job-1", 5, 0));
+
+        let claimed = claim_next_task(&mut pending, &mut active, "a", &[], 0, "x".into());
+        assert_eq!(claimed.unwrap().id, "high");
+        assert_eq!(pending[0].id, "low");
+    }
+
+    #[test]
+    fn claim_breaks_priority_ties_by_queue_order() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        enqueue_task(&mut pending, make_job_task("first", "job-1", 2, 0));
+        enqueue_task(&mut pending, make_job_task("second", "job-1", 2, 0));
+
+        assert_eq!(
+            select_claim_index(&pending, &HashMap::new(), &any_agent(), 0),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn aging_lets_a_waiting_low_priority_task_overtake() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        // Queued at t=0 with priority 0; the fresh task has priority 3.
+        enqueue_task(&mut pending, make_job_task("old", "job-1", 0, 0));
+        let fresh_at = 10 * AGING_INTERVAL_MS;
+        enqueue_task(&mut pending, make_job_task("fresh", "job-2", 3, fresh_at));
+
+        let active = HashMap::new();
+        // Two intervals in, "old" has aged to 2 and still loses.
+        assert_eq!(
+            select_claim_index(&pending, &active, &any_agent(), 2 * AGING_INTERVAL_MS),
+            Some(1)
+        );
+        // After ten intervals it has aged past the fresh task's priority.
+        assert_eq!(
+            select_claim_index(&pending, &active, &any_agent(), fresh_at),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn aging_ignores_tasks_without_enqueue_time() {
+        let legacy = make_task("legacy", "build");
+        assert_eq!(effective_priority(&legacy, u64::MAX, 0), 0);
+    }
+
+    #[test]
+    fn fair_share_alternates_between_jobs_of_equal_priority() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        for i in 0..4 {
+            enqueue_task(
+                &mut pending,
+                make_job_task(&format!("big-{i}"), "big", 0, 0),
+            );
+        }
+        enqueue_task(&mut pending, make_job_task("small-0", "small", 0, 0));
+        enqueue_task(&mut pending, make_job_task("small-1", "small", 0, 0));
+
+        let order: Vec<String> = (0..4)
+            .map(|n| {
+                claim_next_task(
+                    &mut pending,
+                    &mut active,
+                    &format!("a{n}"),
+                    &[],
+                    0,
+                    "x".into(),
+                )
+                .unwrap()
+                .id
+            })
+            .collect();
+        assert_eq!(order, vec!["big-0", "small-0", "big-1", "small-1"]);
+    }
+
+    #[test]
+    fn fair_share_penalty_scales_with_held_leases() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        for i in 0..3 {
+            let mut leased = make_job_task(&format!("held-{i}"), "hot", 2, 0);
+            leased.status = "running".into();
+            active.insert(leased.id.clone(), leased);
+        }
+        enqueue_task(&mut pending, make_job_task("hot-next", "hot", 2, 0));
+        enqueue_task(&mut pending, make_job_task("cold", "cold", 0, 0));
+
+        // hot: 2 - 3 leases = -1, cold: 0 - 0 = 0.
+        assert_eq!(
+            select_claim_index(&pending, &active, &any_agent(), 0),
+            Some(1)
+        );
+        active.retain(|id, _| id == "held-0");
+        // hot: 2 - 1 = 1 beats cold again.
+        assert_eq!(
+            select_claim_index(&pending, &active, &any_agent(), 0),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn priority_selection_still_respects_caps() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut urgent = make_job_task("urgent", "job-1", 9, 0);
+        urgent.task_type = "deploy".into();
+        enqueue_task(&mut pending, urgent);
+        enqueue_task(&mut pending, make_job_task("routine", "job-1", 0, 0));
+
+        let caps = vec!["build".to_string()];
+        assert_eq!(
+            select_claim_index(
+                &pending,
+                &HashMap::new(),
+                &CapabilitySet::new("a", &caps),
+                0
+            ),
+            Some(1)
+        );
+    }
+
     #[test]
     fn expired_lease_reverts_task_to_pending_when_retries_remain() {
         let mut pending: VecDeque<AgentTask> = VecDeque::new();
@@ -1282,7 +3274,14 @@ mod tests {
             "1300".to_string(),
         );
 
-        let released = expire_leases(&mut active, &mut pending, 2_000, "1970-01-01T00:00:00Z");
+        let mut dead_letter = Vec::new();
+        let released = expire_leases(
+            &mut active,
+            &mut pending,
+            &mut dead_letter,
+            2_000,
+            "1970-01-01T00:00:00Z",
+        );
         assert_eq!(released, vec!["t1".to_string()]);
         assert!(active.is_empty());
         assert_eq!(pending.len(), 1);
@@ -1321,12 +3320,22 @@ mod tests {
     fn fail_requeues_when_retries_remain() {
         let mut pending: VecDeque<AgentTask> = VecDeque::new();
         let mut active: HashMap<String, AgentTask> = HashMap::new();
+        let mut dead_letter = Vec::new();
         active.insert("t1".to_string(), make_task("t1", "build"));
 
-        let failed = fail_task(&mut active, &mut pending, "t1", "boom", "ts");
+        let failed = fail_task(
+            &mut active,
+            &mut pending,
+            &mut dead_letter,
+            "t1",
+            "boom",
+            "ts",
+        );
         assert_eq!(failed.unwrap().retry_count, 1);
         assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].failures.len(), 1);
         assert!(active.is_empty());
+        assert!(dead_letter.is_empty());
     }
 
     #[test]
@@ -1338,8 +3347,699 @@ mod tests {
         task.max_retries = 3;
         active.insert("t1".to_string(), task);
 
-        let failed = fail_task(&mut active, &mut pending, "t1", "fatal", "tsfail");
+        let mut dead_letter = Vec::new();
+        let failed = fail_task(
+            &mut active,
+            &mut pending,
+            &mut dead_letter,
+            "t1",
+            "fatal",
+            "tsfail",
+        );
         assert_eq!(failed.unwrap().status, "failed");
         assert!(pending.is_empty());
+        assert_eq!(dead_letter.len(), 1);
+        assert_eq!(dead_letter[0].dead_lettered_at, "tsfail");
+    }
+
+    // ── Dead-letter store ─────────────────────────────────────
+
+    fn dead_lettered(id: &str, job_id: &str, at: &str) -> DeadLetterTask {
+        let mut task = make_task(id, "build");
+        task.job_id = job_id.to_string();
+        task.status = "failed".to_string();
+        DeadLetterTask {
+            task,
+            dead_lettered_at: at.to_string(),
+        }
+    }
+
+    #[test]
+    fn exhausted_task_carries_every_attempt_into_dead_letter() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        let mut dead_letter = Vec::new();
+        let mut task = make_task("t1", "build");
+        task.max_retries = 1;
+        enqueue_task(&mut pending, task);
+
+        for (agent, at) in [("agent-A", "ts1"), ("agent-B", "ts2")] {
+            claim_next_task(&mut pending, &mut active, agent, &[], 0, "lease".into()).unwrap();
+            fail_task(
+                &mut active,
+                &mut pending,
+                &mut dead_letter,
+                "t1",
+                "boom",
+                at,
+            );
+        }
+
+        assert!(pending.is_empty());
+        let failures = &dead_letter[0].task.failures;
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].attempt, 1);
+        assert_eq!(failures[0].agent_id.as_deref(), Some("agent-A"));
+        assert_eq!(failures[0].lease_expires_at.as_deref(), Some("lease"));
+        assert_eq!(failures[1].attempt, 2);
+        assert_eq!(failures[1].agent_id.as_deref(), Some("agent-B"));
+        assert_eq!(failures[1].failed_at, "ts2");
+    }
+
+    #[test]
+    fn expired_lease_with_no_retries_is_dead_lettered() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        let mut dead_letter = Vec::new();
+        let mut task = make_task("t1", "build");
+        task.max_retries = 0;
+        enqueue_task(&mut pending, task);
+        claim_next_task(&mut pending, &mut active, "agent-A", &[], 0, "1300".into());
+
+        expire_leases(&mut active, &mut pending, &mut dead_letter, 2_000, "ts");
+        assert!(pending.is_empty());
+        assert_eq!(dead_letter.len(), 1);
+        assert_eq!(dead_letter[0].task.failures[0].error, LEASE_EXPIRED_ERROR);
+    }
+
+    #[test]
+    fn attempt_past_its_timeout_is_released_despite_heartbeats() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        let mut dead_letter = Vec::new();
+        let mut task = make_task("t1", "build");
+        task.timeout_ms = Some(500);
+        enqueue_task(&mut pending, task);
+        let claimed = claim_next_task(
+            &mut pending,
+            &mut active,
+            "agent-A",
+            &[],
+            1_000,
+            "9000".into(),
+        )
+        .unwrap();
+        assert_eq!(claimed.deadline_ms, Some(1_500));
+
+        // The lease (9000) is still good; the deadline is not.
+        assert!(expire_leases(&mut active, &mut pending, &mut dead_letter, 1_499, "ts").is_empty());
+        assert_eq!(
+            expire_leases(&mut active, &mut pending, &mut dead_letter, 1_500, "ts"),
+            vec!["t1"],
+        );
+        assert_eq!(pending[0].failures[0].error, TIMED_OUT_ERROR);
+
+        // The retry gets a fresh deadline when claimed again.
+        let reclaimed = claim_next_task(
+            &mut pending,
+            &mut active,
+            "agent-B",
+            &[],
+            5_000,
+            "9000".into(),
+        )
+        .unwrap();
+        assert_eq!(reclaimed.deadline_ms, Some(5_500));
+    }
+
+    #[test]
+    fn lease_release_error_prefers_timeout_over_lease_expiry() {
+        let mut task = make_task("t1", "build");
+        assert_eq!(lease_release_error(&task, None, 10), None);
+        assert_eq!(lease_release_error(&task, Some(11), 10), None);
+        assert_eq!(
+            lease_release_error(&task, Some(10), 10),
+            Some(LEASE_EXPIRED_ERROR)
+        );
+        task.deadline_ms = Some(5);
+        assert_eq!(
+            lease_release_error(&task, Some(10), 10),
+            Some(TIMED_OUT_ERROR)
+        );
+    }
+
+    #[test]
+    fn play_task_outcome_strips_run_prefix_and_reports_last_error() {
+        let mut task = make_task("job-1-deploy-prod", "build");
+        let mut active = HashMap::from([(task.id.clone(), task.clone())]);
+        // The body `/mcp/task/:id/complete` and socket completions forward.
+        let body = serde_json::to_value(TaskCompleteRequest {
+            result: Some(serde_json::json!({"ok": true})),
+        })
+        .unwrap();
+        let completed = complete_task(&mut active, &task.id, Some(body), "ts").unwrap();
+        let outcome = play_task_outcome(&completed);
+        assert_eq!(outcome.task_id, "deploy-prod");
+        assert_eq!(outcome.status, "completed");
+        assert_eq!(outcome.result, Some(serde_json::json!({"ok": true})));
+        assert_eq!(outcome.error, None);
+
+        let mut without = completed.clone();
+        without.result = Some(serde_json::json!({"result": null}));
+        assert_eq!(play_task_outcome(&without).result, None);
+
+        let mut pending = VecDeque::new();
+        let mut dead_letter = Vec::new();
+        task.max_retries = 0;
+        let failed = retry_or_dead_letter(task, "exit 2", "ts", &mut pending, &mut dead_letter);
+        let outcome = play_task_outcome(&failed);
+        assert_eq!(outcome.status, "failed");
+        assert_eq!(outcome.result, None);
+        assert_eq!(outcome.error.as_deref(), Some("exit 2"));
+    }
+
+    #[test]
+    fn every_cancel_path_reports_a_failed_play_outcome() {
+        let cancelled_outcome = |task: &AgentTask| {
+            assert!(is_play_outcome(task), "{} not reported", task.status);
+            let outcome = play_task_outcome(task);
+            assert_eq!(outcome.task_id, "deploy");
+            assert_eq!(outcome.status, "failed");
+            assert_eq!(outcome.error.as_deref(), Some(CANCELLED_ERROR));
+            assert_eq!(outcome.result, None);
+        };
+
+        // Queued: `cancel` removes it at once.
+        let mut pending = VecDeque::from([make_task("job-1-deploy", "build")]);
+        let mut active = HashMap::new();
+        let out = cancel_tasks(&mut pending, &mut Vec::new(), &mut active, |_| true, "ts");
+        cancelled_outcome(&out.cancelled[0]);
+
+        // Leased: the holder's next heartbeat releases it.
+        let mut task = make_task("job-1-deploy", "build");
+        task.agent_id = Some("agent-1".to_string());
+        let mut active = HashMap::from([(task.id.clone(), task)]);
+        let out = cancel_tasks(
+            &mut VecDeque::new(),
+            &mut Vec::new(),
+            &mut active,
+            |_| true,
+            "ts",
+        );
+        assert!(out.cancelled.is_empty());
+        assert!(!is_play_outcome(&out.cancel_requested[0]));
+        let mut held = active.clone();
+        let task = heartbeat_task(&mut held, "job-1-deploy", "agent-1", "exp".into(), "ts");
+        cancelled_outcome(&task.unwrap());
+
+        // Leased: or the holder fails it, which never retries.
+        let mut pending = VecDeque::new();
+        let task = fail_task(
+            &mut active,
+            &mut pending,
+            &mut Vec::new(),
+            "job-1-deploy",
+            "x",
+            "ts",
+        );
+        cancelled_outcome(&task.unwrap());
+        assert!(pending.is_empty());
+
+        // A failed attempt with retries left is not an outcome yet.
+        let mut active = HashMap::from([("t".to_string(), make_task("t", "build"))]);
+        let retried = fail_task(&mut active, &mut pending, &mut Vec::new(), "t", "x", "ts");
+        assert!(!is_play_outcome(&retried.unwrap()));
+    }
+
+    #[test]
+    fn dead_letter_page_is_newest_first_and_filters_by_job() {
+        let store = vec![
+            dead_lettered("a", "job-1", "2026-01-01T00:00:00Z"),
+            dead_lettered("b", "job-2", "2026-01-02T00:00:00Z"),
+            dead_lettered("c", "job-1", "2026-01-03T00:00:00Z"),
+        ];
+
+        let (page, next) = dead_letter_page(&store, None, None, 2);
+        let ids: Vec<&str> = page.iter().map(|e| e.task.id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "b"]);
+        let (rest, next) = dead_letter_page(&store, None, next.as_ref(), 2);
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].task.id, "a");
+        assert!(next.is_none());
+
+        let (job1, _) = dead_letter_page(&store, Some("job-1"), None, 10);
+        let ids: Vec<&str> = job1.iter().map(|e| e.task.id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "a"]);
+    }
+
+    #[test]
+    fn requeue_resets_retry_budget_but_keeps_history() {
+        let mut entry = dead_lettered("t1", "job-1", "ts");
+        entry.task.retry_count = 3;
+        entry.task.completed_at = Some("ts".into());
+        entry.task.failures.push(TaskAttemptFailure {
+            attempt: 4,
+            agent_id: Some("agent-A".into()),
+            error: "boom".into(),
+            lease_expires_at: None,
+            failed_at: "ts".into(),
+        });
+
+        let task = requeued_task(entry, 5_000);
+        assert_eq!(task.status, "pending");
+        assert_eq!(task.retry_count, 0);
+        assert!(task.completed_at.is_none());
+        assert_eq!(task.enqueued_at_ms, Some(5_000));
+        assert_eq!(task.failures.len(), 1);
+    }
+
+    #[test]
+    fn dead_letter_keys_sort_like_the_page_order() {
+        let older = dead_letter_key("2026-01-01T00:00:00.000Z", "zz");
+        let newer = dead_letter_key("2026-01-02T00:00:00.000Z", "aa");
+        let same_time = dead_letter_key("2026-01-02T00:00:00.000Z", "ab");
+        assert!(older < newer && newer < same_time);
+        assert!(newer.starts_with(DEAD_LETTER_PREFIX));
+        for other in [
+            dead_letter_id_key("aa"),
+            DEAD_LETTER_LEN_KEY.to_string(),
+            LEGACY_DEAD_LETTER_KEY.to_string(),
+        ] {
+            assert!(!other.starts_with(DEAD_LETTER_PREFIX), "{other}");
+        }
+    }
+
+    // ── Delayed tasks ─────────────────────────────────────────
+
+    /// Insert `task` as the `scheduled:` keys list it: by due time, then
+    /// enqueue order.
+    fn schedule_task(scheduled: &mut Vec<ScheduledTask>, due_at_ms: u64, task: AgentTask) {
+        let idx = scheduled.partition_point(|s| s.due_at_ms <= due_at_ms);
+        scheduled.insert(idx, ScheduledTask { due_at_ms, task });
+    }
+
+    #[test]
+    fn scheduled_keys_sort_by_due_time_then_enqueue_order() {
+        let mut keys = vec![
+            scheduled_key(300, 1),
+            scheduled_key(100, 2),
+            scheduled_key(300, 3),
+            scheduled_key(10_000, 0),
+        ];
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                scheduled_key(100, 2),
+                scheduled_key(300, 1),
+                scheduled_key(300, 3),
+                scheduled_key(10_000, 0),
+            ]
+        );
+        // The list `end` used to read what is due by 300 stops before 301.
+        assert!(scheduled_key(300, u64::MAX) < scheduled_key(301, 0));
+        assert!(!SCHEDULED_LEN_KEY.starts_with(SCHEDULED_PREFIX));
+        assert!(!LEGACY_SCHEDULED_KEY.starts_with(SCHEDULED_PREFIX));
+    }
+
+    #[test]
+    fn promote_moves_only_due_tasks_and_ages_from_due_time() {
+        let mut scheduled = Vec::new();
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        schedule_task(&mut scheduled, 100, make_task("a", "build"));
+        schedule_task(&mut scheduled, 200, make_task("b", "build"));
+        schedule_task(&mut scheduled, 900, make_task("c", "build"));
+
+        assert!(promote_due_tasks(&mut scheduled, &mut pending, 50).is_empty());
+        let promoted = promote_due_tasks(&mut scheduled, &mut pending, 200);
+        assert_eq!(promoted, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(pending[0].enqueued_at_ms, Some(100));
+        assert_eq!(scheduled.len(), 1);
+    }
+
+    #[test]
+    fn delayed_task_is_not_claimable_until_promoted() {
+        let mut scheduled = Vec::new();
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        schedule_task(&mut scheduled, 5_000, make_task("t1", "build"));
+
+        assert!(claim_next_task(&mut pending, &mut active, "a", &[], 1_000, "l".into()).is_none());
+        promote_due_tasks(&mut scheduled, &mut pending, 5_000);
+        assert!(claim_next_task(&mut pending, &mut active, "a", &[], 5_000, "l".into()).is_some());
+    }
+
+    #[test]
+    fn next_alarm_prefers_the_sooner_of_sweep_and_due_task() {
+        assert_eq!(next_alarm_at(0, false, None), None);
+        assert_eq!(next_alarm_at(0, true, None), Some(LEASE_SWEEP_INTERVAL_MS));
+
+        assert_eq!(next_alarm_at(0, true, Some(10_000)), Some(10_000));
+        assert_eq!(next_alarm_at(0, false, Some(10_000)), Some(10_000));
+
+        // A task already overdue fires the alarm now, never in the past.
+        assert_eq!(next_alarm_at(20_000, false, Some(10_000)), Some(20_000));
+    }
+
+    #[test]
+    fn claim_honours_requirements_and_agent_pinning() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        let mut pinned = make_task("pinned", "build");
+        pinned.requires = vec!["agent:builder-7".into()];
+        let mut needs_rust = make_task("rust", "build");
+        needs_rust.requires = vec!["rust>=1.80".into()];
+        enqueue_task(&mut pending, pinned);
+        enqueue_task(&mut pending, needs_rust);
+
+        let old_rust = vec!["rust@1.75".to_string()];
+        assert!(claim_next_task(
+            &mut pending,
+            &mut active,
+            "builder-8",
+            &old_rust,
+            0,
+            "l".into()
+        )
+        .is_none());
+
+        let new_rust = vec!["rust@1.82".to_string()];
+        let claimed = claim_next_task(
+            &mut pending,
+            &mut active,
+            "builder-8",
+            &new_rust,
+            0,
+            "l".into(),
+        );
+        assert_eq!(claimed.map(|t| t.id), Some("rust".to_string()));
+        let claimed = claim_next_task(&mut pending, &mut active, "builder-7", &[], 0, "l".into());
+        assert_eq!(claimed.map(|t| t.id), Some("pinned".to_string()));
+    }
+
+    // ── cancellation ────────────────────────────────────────────
+
+    #[test]
+    fn cancel_removes_queued_tasks_and_flags_leased_ones() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut scheduled = Vec::new();
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        enqueue_task(&mut pending, make_task("queued", "build"));
+        let mut other = make_task("other-job", "build");
+        other.job_id = "job-2".into();
+        enqueue_task(&mut pending, other);
+        schedule_task(&mut scheduled, 10_000, make_task("delayed", "build"));
+        let mut leased = make_task("leased", "build");
+        leased.status = "running".into();
+        leased.agent_id = Some("agent-1".into());
+        active.insert(leased.id.clone(), leased);
+
+        let out = cancel_tasks(
+            &mut pending,
+            &mut scheduled,
+            &mut active,
+            |t| t.job_id == "job-1",
+            "2026-01-01T00:00:00Z",
+        );
+
+        let mut cancelled: Vec<_> = out.cancelled.iter().map(|t| t.id.as_str()).collect();
+        cancelled.sort();
+        assert_eq!(cancelled, vec!["delayed", "queued"]);
+        assert!(out.cancelled.iter().all(|t| t.status == "cancelled"));
+        assert_eq!(out.cancel_requested.len(), 1);
+        assert!(active["leased"].cancel_requested);
+        assert_eq!(pending.len(), 1, "other jobs are untouched");
+        assert!(scheduled.is_empty());
+    }
+
+    #[test]
+    fn heartbeat_after_cancel_releases_the_lease() {
+        let mut active: HashMap<String, AgentTask> = HashMap::new();
+        let mut task = make_task("t1", "build");
+        task.status = "running".into();
+        task.agent_id = Some("agent-1".into());
+        active.insert("t1".into(), task);
+
+        let hb = heartbeat_task(&mut active, "t1", "agent-1", "later".into(), "now").unwrap();
+        assert_eq!(hb.status, "running");
+        assert_eq!(hb.lease_expires_at.as_deref(), Some("later"));
+        assert!(heartbeat_task(&mut active, "t1", "agent-2", "later".into(), "now").is_none());
+
+        active.get_mut("t1").unwrap().cancel_requested = true;
+        let hb = heartbeat_task(&mut active, "t1", "agent-1", "later".into(), "now").unwrap();
+        assert_eq!(hb.status, "cancelled");
+        assert_eq!(hb.completed_at.as_deref(), Some("now"));
+        assert!(active.is_empty());
+    }
+
+    #[test]
+    fn cancelled_task_is_not_retried_on_failure() {
+        let mut pending: VecDeque<AgentTask> = VecDeque::new();
+        let mut dead_letter = Vec::new();
+        let mut task = make_task("t1", "build");
+        task.cancel_requested = true;
+
+        let out = retry_or_dead_letter(task, "stopped", "now", &mut pending, &mut dead_letter);
+        assert_eq!(out.status, "cancelled");
+        assert_eq!(out.failures.len(), 1);
+        assert!(pending.is_empty());
+        assert!(dead_letter.is_empty());
+    }
+
+    // ── long-poll / WebSocket claiming ──────────────────────────
+
+    #[test]
+    fn claim_wait_is_clamped_and_defaults_to_zero() {
+        assert_eq!(clamp_claim_wait_ms(None), 0);
+        assert_eq!(clamp_claim_wait_ms(Some("abc")), 0);
+        assert_eq!(clamp_claim_wait_ms(Some("5000")), 5_000);
+        assert_eq!(clamp_claim_wait_ms(Some("600000")), MAX_CLAIM_WAIT_MS);
+    }
+
+    #[test]
+    fn parse_caps_drops_empty_entries() {
+        assert!(parse_caps("").is_empty());
+        assert_eq!(parse_caps("rust,,wasm"), vec!["rust", "wasm"]);
+    }
+
+    #[test]
+    fn claim_waiter_resolves_only_after_notify() {
+        use futures_util::task::noop_waker;
+
+        let waiters = ClaimWaiters::default();
+        let seen = waiters.generation();
+        let waker = noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+
+        let mut changed = Box::pin(waiters.changed_since(seen));
+        assert!(changed.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(waiters.wakers.borrow().len(), 1);
+
+        waiters.notify();
+        assert!(waiters.wakers.borrow().is_empty());
+        assert!(changed.as_mut().poll(&mut cx).is_ready());
+
+        // A waiter that snapshots after the notify parks again.
+        let mut fresh = Box::pin(waiters.changed_since(waiters.generation()));
+        assert!(fresh.as_mut().poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn idempotency_window_defaults_and_parses() {
+        assert_eq!(idempotency_window_ms(None), 86_400_000);
+        assert_eq!(idempotency_window_ms(Some("nope")), 86_400_000);
+        assert_eq!(idempotency_window_ms(Some("60")), 60_000);
+        assert_eq!(idempotency_window_ms(Some("0")), 0);
+    }
+
+    #[test]
+    fn queue_snapshot_groups_depth_and_orders_leases() {
+        let mut pending = VecDeque::new();
+        for (id, task_type, priority, enqueued_at_ms) in [
+            ("p1", "build", 0, Some(4_000)),
+            ("p2", "build", 0, None),
+            ("p3", "test", 5, Some(7_000)),
+            ("p4", "build", 5, Some(9_000)),
+        ] {
+            let mut t = make_task(id, task_type);
+            t.priority = priority;
+            t.enqueued_at_ms = enqueued_at_ms;
+            pending.push_back(t);
+        }
+        let mut active = HashMap::new();
+        for (id, expires) in [
+            ("a1", "2026-01-01T00:05:00Z"),
+            ("a2", "2026-01-01T00:01:00Z"),
+        ] {
+            let mut t = make_task(id, "build");
+            t.agent_id = Some(format!("agent-{id}"));
+            t.lease_expires_at = Some(expires.to_string());
+            active.insert(id.to_string(), t);
+        }
+        let notify = vec![PendingNotify {
+            target_name: "t:play:r1".to_string(),
+            play_task_id: "build".to_string(),
+            attempts: 3,
+            next_attempt_at_ms: 12_000,
+            outcome: None,
+        }];
+
+        let snap = queue_snapshot(&pending, &[], &active, &notify, 7, 2, 10_000);
+        assert_eq!(snap.pending_total, 4);
+        assert_eq!(
+            snap.depth
+                .iter()
+                .map(|d| (d.task_type.as_str(), d.priority, d.count))
+                .collect::<Vec<_>>(),
+            vec![("build", 5, 1), ("test", 5, 1), ("build", 0, 2)]
+        );
+        assert_eq!(snap.oldest_pending_age_ms, Some(6_000));
+        assert_eq!(
+            snap.active
+                .iter()
+                .map(|l| l.task_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a2", "a1"]
+        );
+        assert_eq!(snap.active[0].agent_id.as_deref(), Some("agent-a2"));
+        assert_eq!(snap.rejected_total, 7);
+        assert_eq!(snap.dead_letter_total, 2);
+        assert_eq!(snap.pending_notifies[0].attempts, 3);
+    }
+
+    #[test]
+    fn queue_snapshot_of_empty_queue() {
+        let snap = queue_snapshot(&VecDeque::new(), &[], &HashMap::new(), &[], 0, 0, 1_000);
+        assert_eq!(snap.pending_total, 0);
+        assert!(snap.depth.is_empty());
+        assert_eq!(snap.oldest_pending_age_ms, None);
+        assert_eq!(snap.max_pending, MAX_PENDING_TASKS);
+    }
+
+    fn stored(ids: &[(&str, u64)]) -> Vec<(u64, AgentTask)> {
+        ids.iter()
+            .map(|(id, seq)| (*seq, make_task(id, "build")))
+            .collect()
+    }
+
+    #[test]
+    fn pending_keys_sort_in_queue_order() {
+        assert!(pending_key(9) < pending_key(10));
+        assert_eq!(parse_pending_key(&pending_key(42)), Some(42));
+        assert_eq!(parse_pending_key(LEGACY_PENDING_KEY), None);
+    }
+
+    #[test]
+    fn priority_index_lists_highest_priority_first_then_queue_order() {
+        let mut keys = vec![
+            pending_priority_key(0, 1),
+            pending_priority_key(5, 7),
+            pending_priority_key(-3, 0),
+            pending_priority_key(5, 2),
+            pending_priority_key(i32::MAX, 9),
+            pending_priority_key(i32::MIN, 3),
+        ];
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                pending_priority_key(i32::MAX, 9),
+                pending_priority_key(5, 2),
+                pending_priority_key(5, 7),
+                pending_priority_key(0, 1),
+                pending_priority_key(-3, 0),
+                pending_priority_key(i32::MIN, 3),
+            ]
+        );
+        for other in [PENDING_SEQ_KEY, PENDING_LEN_KEY, QUEUE_LAYOUT_KEY] {
+            assert!(!other.starts_with(PENDING_PRIORITY_PREFIX));
+        }
+        assert!(!pending_priority_key(0, 0).starts_with(PENDING_PREFIX));
+    }
+
+    #[test]
+    fn claim_window_merges_pages_in_queue_order_once() {
+        let mut tasks = VecDeque::new();
+        let mut seqs = HashMap::new();
+        merge_claim_window(
+            &mut tasks,
+            &mut seqs,
+            vec![(0, make_task("a", "build")), (1, make_task("b", "build"))],
+        );
+        // The priority index turned up a task deep in the queue.
+        merge_claim_window(&mut tasks, &mut seqs, vec![(9, make_task("z", "build"))]);
+        // "a" is claimed; the next page re-reads it and "z" from storage.
+        tasks.pop_front();
+        merge_claim_window(
+            &mut tasks,
+            &mut seqs,
+            vec![
+                (0, make_task("a", "build")),
+                (4, make_task("c", "build")),
+                (9, make_task("z", "build")),
+            ],
+        );
+        let ids: Vec<&str> = tasks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "c", "z"]);
+        assert_eq!(seqs.get("z"), Some(&9));
+    }
+
+    #[test]
+    fn pending_writes_touch_only_changed_tasks() {
+        let before = stored(&[("a", 0), ("b", 1), ("c", 2)]);
+
+        // Push to the back: one put.
+        let mut tasks: VecDeque<AgentTask> = before.iter().map(|(_, t)| t.clone()).collect();
+        tasks.push_back(make_task("d", "build"));
+        let w = plan_pending_writes(&before, &tasks, 3);
+        assert_eq!(w.puts.len(), 1);
+        assert_eq!((w.puts[0].0, w.puts[0].1.id.as_str()), (3, "d"));
+        assert!(w.deletes.is_empty());
+        assert_eq!(w.next_seq, 4);
+
+        // Claim from the middle: one delete.
+        tasks.remove(1);
+        let w = plan_pending_writes(&before, &tasks, 3);
+        assert_eq!(w.deletes, vec![1]);
+        assert_eq!(
+            w.puts
+                .iter()
+                .map(|(_, t)| t.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["d"]
+        );
+
+        // Nothing changed: nothing written.
+        let same: VecDeque<AgentTask> = before.iter().map(|(_, t)| t.clone()).collect();
+        assert_eq!(
+            plan_pending_writes(&before, &same, 3),
+            PendingWrites {
+                next_seq: 3,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn pending_writes_rewrite_tail_after_a_reorder_or_edit() {
+        let before = stored(&[("a", 0), ("b", 1), ("c", 2)]);
+
+        // `b` moved behind `c`: only `b` needs a new key.
+        let tasks: VecDeque<AgentTask> = ["a", "c", "b"]
+            .iter()
+            .map(|id| make_task(id, "build"))
+            .collect();
+        let w = plan_pending_writes(&before, &tasks, 3);
+        assert_eq!(
+            w.puts
+                .iter()
+                .map(|(s, t)| (*s, t.id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(3, "b")]
+        );
+        assert_eq!(w.deletes, vec![1]);
+
+        // `b` edited in place: it and everything after it are rewritten.
+        let mut tasks: VecDeque<AgentTask> = before.iter().map(|(_, t)| t.clone()).collect();
+        tasks[1].priority = 9;
+        let w = plan_pending_writes(&before, &tasks, 3);
+        assert_eq!(
+            w.puts
+                .iter()
+                .map(|(s, t)| (*s, t.id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(3, "b"), (4, "c")]
+        );
+        assert_eq!(w.deletes, vec![1, 2]);
     }
 }
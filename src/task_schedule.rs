@@ -0,0 +1,189 @@
+//! Recurring agent tasks defined by cron templates.
+//!
+//! `POST /v1/task-schedules` stores a [`models::CreateAgentTask`] template
+//! with a five-field UTC cron expression (see [`crate::cron`]). The
+//! worker's every-minute cron trigger calls [`run_due_schedules`], which
+//! enqueues one task per schedule whose `next_run_at` has passed and
+//! advances it to the next fire time after now. Fires missed while the
+//! trigger was not running collapse into a single task.
+//!
+//! A fire is enqueued up to a minute after its time. At most
+//! [`DUE_BATCH_LIMIT`] schedules fire per tick, so when more are due at
+//! once the rest slip to the following ticks, a minute per extra batch.
+
+use worker::*;
+
+use crate::cron::CronSchedule;
+use crate::{db, models, tenant_lifecycle};
+
+/// Cap on schedules materialized per tick so one backlog cannot run the
+/// handler past its CPU budget; the rest are picked up next tick.
+const DUE_BATCH_LIMIT: u32 = 100;
+
+/// Check a create request and return its first fire time after `now_ms`.
+pub fn validate(
+    body: &models::CreateTaskSchedule,
+    now_ms: u64,
+) -> std::result::Result<u64, String> {
+    if body.name.trim().is_empty() {
+        return Err("name must not be empty".into());
+    }
+    if body.task.not_before.is_some() {
+        return Err("task.not_before is not allowed in a schedule template".into());
+    }
+    crate::capability::validate_requirements(&body.task.requires)?;
+    let cron = CronSchedule::parse(&body.cron)?;
+    cron.next_after(now_ms)
+        .ok_or_else(|| format!("cron '{}' never fires", body.cron))
+}
+
+pub fn ms_to_iso(ms: u64) -> String {
+    js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(ms as f64))
+        .to_iso_string()
+        .as_string()
+        .unwrap_or_default()
+}
+
+/// Enqueue every due schedule. Per-schedule failures are logged and do not
+/// stop the rest of the batch.
+pub async fn run_due_schedules(env: &Env) -> Result<()> {
+    let d1 = env.d1("DB")?;
+    let now_ms = js_sys::Date::now() as u64;
+    let now = ms_to_iso(now_ms);
+    let due = db::list_due_task_schedules(&d1, &now, DUE_BATCH_LIMIT).await?;
+    for (tenant_id, schedule) in due {
+        if let Err(e) = run_schedule(env, &d1, &tenant_id, &schedule, now_ms, &now).await {
+            console_error!(
+                "task schedule {}/{} failed to run: {}",
+                tenant_id,
+                schedule.id,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn run_schedule(
+    env: &Env,
+    d1: &D1Database,
+    tenant_id: &str,
+    schedule: &models::TaskSchedule,
+    now_ms: u64,
+    now: &str,
+) -> Result<()> {
+    let next = CronSchedule::parse(&schedule.cron)
+        .ok()
+        .and_then(|cron| cron.next_after(now_ms));
+    let Some(next) = next else {
+        console_error!(
+            "task schedule {}/{} has no next fire time for '{}'; leaving it due",
+            tenant_id,
+            schedule.id,
+            schedule.cron
+        );
+        return Ok(());
+    };
+
+    // Claim this fire before enqueueing so overlapping ticks cannot both
+    // materialize it. A failed enqueue loses the fire, so it is recorded as
+    // the schedule's `last_error` rather than only logged.
+    let claimed = db::advance_task_schedule(
+        d1,
+        tenant_id,
+        &schedule.id,
+        &schedule.next_run_at,
+        &ms_to_iso(next),
+        now,
+    )
+    .await?;
+    if !claimed {
+        return Ok(());
+    }
+
+    // Suspended tenants keep their schedules but skip the fire.
+    let status = db::get_tenant_status(d1, tenant_id).await?;
+    if status.is_some_and(|s| {
+        tenant_lifecycle::TenantStatus::parse(&s) != tenant_lifecycle::TenantStatus::Active
+    }) {
+        return Ok(());
+    }
+
+    // Every fire is a new task; a template idempotency_key would collapse
+    // all fires inside the window into the first one.
+    let mut task = schedule.task.clone();
+    task.idempotency_key = None;
+    let error = match crate::enqueue_agent_task(env, tenant_id, &task).await {
+        Ok(Ok(created)) => {
+            return db::set_task_schedule_last_task(d1, tenant_id, &schedule.id, &created.id).await;
+        }
+        Ok(Err(resp)) => enqueue_rejected_error(resp.status_code(), now),
+        Err(e) => format!("enqueue failed at {now}: {e}"),
+    };
+    console_error!("task schedule {}/{}: {}", tenant_id, schedule.id, error);
+    db::set_task_schedule_error(d1, tenant_id, &schedule.id, &error).await
+}
+
+/// `last_error` for a fire whose enqueue was answered with `status`.
+fn enqueue_rejected_error(status: u16, now: &str) -> String {
+    match status {
+        429 => format!("enqueue rejected at {now}: task queue full"),
+        _ => format!("enqueue rejected at {now} with status {status}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(cron: &str) -> models::CreateTaskSchedule {
+        models::CreateTaskSchedule {
+            name: "nightly-dependency-audit".into(),
+            cron: cron.into(),
+            task: models::CreateAgentTask {
+                job_id: "dep-audit".into(),
+                task_type: "dependency_audit".into(),
+                priority: 0,
+                params: None,
+                graph_ref: None,
+                play_id: None,
+                parent_task_id: None,
+                max_retries: None,
+                not_before: None,
+                requires: Vec::new(),
+                idempotency_key: None,
+            },
+        }
+    }
+
+    #[test]
+    fn validate_returns_first_fire_time() {
+        // 2026-01-01T00:00:00Z
+        let now = 1_767_225_600_000;
+        assert_eq!(validate(&body("0 3 * * *"), now), Ok(now + 3 * 3_600_000));
+    }
+
+    #[test]
+    fn enqueue_rejection_names_a_full_queue() {
+        let now = "2026-01-01T03:00:00.000Z";
+        assert_eq!(
+            enqueue_rejected_error(429, now),
+            "enqueue rejected at 2026-01-01T03:00:00.000Z: task queue full"
+        );
+        assert!(enqueue_rejected_error(500, now).ends_with("with status 500"));
+    }
+
+    #[test]
+    fn validate_rejects_bad_templates() {
+        assert!(validate(&body("0 3 * *"), 0).is_err());
+        assert!(validate(&body("0 0 30 2 *"), 0).is_err());
+
+        let mut unnamed = body("0 3 * * *");
+        unnamed.name = "  ".into();
+        assert!(validate(&unnamed, 0).is_err());
+
+        let mut delayed = body("0 3 * * *");
+        delayed.task.not_before = Some("2026-01-01T00:00:00Z".into());
+        assert!(validate(&delayed, 0).is_err());
+    }
+}
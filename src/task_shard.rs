@@ -0,0 +1,473 @@
+//! Spreading one tenant's agent task queue over several `TaskLeaseManager`
+//! instances.
+//!
+//! `TASK_SHARDS` (default 1, at most 64) sets how many DO instances hold a
+//! tenant's queue. `TASK_SHARD_BY` picks the key: `task_type` (the
+//! default) keeps each type on one shard, so an agent that only takes some
+//! types only polls the shards holding them; `hash` spreads tasks by id,
+//! for tenants whose load is one hot type. Shard 0 is the instance named
+//! by the bare tenant id, so raising the shard count leaves existing queues
+//! where they are.
+//!
+//! Task-scoped calls (heartbeat, complete, fail, cancel, dead-letter
+//! requeue/discard) go to the shard the task should be on and fall back to
+//! the others on 404, which covers play tasks (no D1 row to read the type
+//! from) and tasks queued before the shard settings changed. Tenant-wide
+//! views (job cancel, queue snapshot, dead-letter listing) fan out to every
+//! shard and merge.
+//!
+//! A long-poll claim across several shards parks on shard 0's `/wait`;
+//! every other shard sends shard 0 a `/wake` when a task becomes
+//! claimable, and the claim sweeps its shards again.
+
+use crate::models::{AgentTask, CancelledTasks, DeadLetterTask, QueueSnapshot};
+use crate::pagination::DeadLetterCursor;
+use crate::{capability, db, task_do};
+use std::collections::HashMap;
+use worker::*;
+
+pub const TASK_SHARDS_VAR: &str = "TASK_SHARDS";
+pub const TASK_SHARD_BY_VAR: &str = "TASK_SHARD_BY";
+pub const MAX_TASK_SHARDS: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardBy {
+    TaskType,
+    TaskId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardConfig {
+    pub shards: u32,
+    pub by: ShardBy,
+}
+
+impl ShardConfig {
+    /// Missing or malformed values fall back to one shard keyed by type.
+    pub fn parse(shards: Option<&str>, by: Option<&str>) -> Self {
+        let shards = shards
+            .and_then(|s| s.trim().parse::<u32>().ok())
+            .unwrap_or(1)
+            .clamp(1, MAX_TASK_SHARDS);
+        let by = match by.map(str::trim) {
+            Some("hash") => ShardBy::TaskId,
+            _ => ShardBy::TaskType,
+        };
+        Self { shards, by }
+    }
+
+    pub fn from_env(env: &Env) -> Self {
+        let var = |name: &str| env.var(name).ok().map(|v| v.to_string());
+        Self::parse(
+            var(TASK_SHARDS_VAR).as_deref(),
+            var(TASK_SHARD_BY_VAR).as_deref(),
+        )
+    }
+
+    pub fn shard_for(&self, task_type: &str, task_id: &str) -> u32 {
+        let key = match self.by {
+            ShardBy::TaskType => task_type,
+            ShardBy::TaskId => task_id,
+        };
+        (fnv1a(key) % u64::from(self.shards)) as u32
+    }
+
+    pub fn all(&self) -> Vec<u32> {
+        (0..self.shards).collect()
+    }
+
+    /// `preferred` first, then every other shard in order.
+    pub fn search_order(&self, preferred: u32) -> Vec<u32> {
+        std::iter::once(preferred)
+            .chain((0..self.shards).filter(|s| *s != preferred))
+            .collect()
+    }
+
+    /// Shards an agent with `caps` should poll, rotated by `start` so
+    /// concurrent agents spread their first attempt across shards instead
+    /// of all draining shard 0. When sharding by type, an agent that names
+    /// its task types only visits the shards holding them.
+    pub fn claim_order(&self, caps: &[String], start: u32) -> Vec<u32> {
+        let types = capability::task_types(caps);
+        let mut shards: Vec<u32> = if self.by == ShardBy::TaskType && !types.is_empty() {
+            let mut s: Vec<u32> = types.iter().map(|t| self.shard_for(t, "")).collect();
+            s.sort_unstable();
+            s.dedup();
+            s
+        } else {
+            self.all()
+        };
+        let len = shards.len();
+        shards.rotate_left(start as usize % len);
+        shards
+    }
+}
+
+/// DO instance name for `shard` of a tenant's task queue.
+pub fn shard_name(tenant_id: &str, shard: u32) -> String {
+    if shard == 0 {
+        tenant_id.to_string()
+    } else {
+        format!("{tenant_id}:tasks:{shard}")
+    }
+}
+
+/// Stable across isolates and releases, unlike `DefaultHasher`.
+pub fn fnv1a(key: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in key.as_bytes() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Handle on one tenant's task queue shards.
+pub struct TaskShards {
+    namespace: ObjectNamespace,
+    tenant_id: String,
+    pub config: ShardConfig,
+}
+
+impl TaskShards {
+    pub fn new(env: &Env, tenant_id: &str) -> Result<Self> {
+        Ok(Self {
+            namespace: env.durable_object("TASK_LEASE_MANAGER")?,
+            tenant_id: tenant_id.to_string(),
+            config: ShardConfig::from_env(env),
+        })
+    }
+
+    pub fn id(&self, shard: u32) -> Result<ObjectId<'_>> {
+        self.namespace
+            .id_from_name(&shard_name(&self.tenant_id, shard))
+    }
+
+    pub fn stub(&self, shard: u32) -> Result<Stub> {
+        self.id(shard)?.get_stub()
+    }
+
+    pub fn stub_for_task(&self, task: &AgentTask) -> Result<Stub> {
+        self.stub(self.config.shard_for(&task.task_type, &task.id))
+    }
+
+    /// Send a task-scoped request to the shard holding `task_id`, trying
+    /// the expected shard first and the rest while they answer 404.
+    /// `make_req` is called once per attempt.
+    pub async fn fetch_for_task(
+        &self,
+        d1: &D1Database,
+        task_id: &str,
+        make_req: impl Fn() -> Result<Request>,
+    ) -> Result<Response> {
+        if self.config.shards == 1 {
+            return self.stub(0)?.fetch_with_request(make_req()?).await;
+        }
+        let preferred = match self.config.by {
+            ShardBy::TaskId => self.config.shard_for("", task_id),
+            ShardBy::TaskType => db::get_mcp_task_by_id(d1, &self.tenant_id, task_id)
+                .await?
+                .map_or(0, |t| self.config.shard_for(&t.task_type, task_id)),
+        };
+        let order = self.config.search_order(preferred);
+        let last = order.len() - 1;
+        for (i, shard) in order.into_iter().enumerate() {
+            let resp = self.stub(shard)?.fetch_with_request(make_req()?).await?;
+            if resp.status_code() != 404 || i == last {
+                return Ok(resp);
+            }
+        }
+        unreachable!("search_order always yields at least one shard")
+    }
+
+    /// Send the same request to every shard, in shard order.
+    pub async fn fetch_all(&self, make_req: impl Fn() -> Result<Request>) -> Result<Vec<Response>> {
+        let mut out = Vec::with_capacity(self.config.shards as usize);
+        for shard in self.config.all() {
+            out.push(self.stub(shard)?.fetch_with_request(make_req()?).await?);
+        }
+        Ok(out)
+    }
+}
+
+// ── Merging fanned-out responses ───────────────────────────────────────
+
+pub fn merge_cancelled(parts: Vec<CancelledTasks>) -> CancelledTasks {
+    parts
+        .into_iter()
+        .fold(CancelledTasks::default(), |mut acc, mut part| {
+            acc.cancelled.append(&mut part.cancelled);
+            acc.cancel_requested.append(&mut part.cancel_requested);
+            acc
+        })
+}
+
+/// Combine per-shard snapshots. Depth and leases are re-sorted the way a
+/// single shard orders them; `max_pending` is the tenant-wide capacity.
+pub fn merge_queue_snapshots(parts: Vec<QueueSnapshot>) -> QueueSnapshot {
+    let mut parts = parts.into_iter();
+    let Some(mut merged) = parts.next() else {
+        return QueueSnapshot {
+            pending_total: 0,
+            scheduled_total: 0,
+            max_pending: 0,
+            depth: Vec::new(),
+            oldest_pending_age_ms: None,
+            active: Vec::new(),
+            rejected_total: 0,
+            dead_letter_total: 0,
+            pending_notifies: Vec::new(),
+        };
+    };
+    for mut part in parts {
+        merged.pending_total += part.pending_total;
+        merged.scheduled_total += part.scheduled_total;
+        merged.max_pending += part.max_pending;
+        merged.depth.append(&mut part.depth);
+        merged.oldest_pending_age_ms = merged.oldest_pending_age_ms.max(part.oldest_pending_age_ms);
+        merged.active.append(&mut part.active);
+        merged.rejected_total += part.rejected_total;
+        merged.dead_letter_total += part.dead_letter_total;
+        merged.pending_notifies.append(&mut part.pending_notifies);
+    }
+
+    // The same (type, priority) can sit on several shards when hashing.
+    let mut counts: HashMap<(String, i32), usize> = HashMap::new();
+    for d in merged.depth.drain(..) {
+        *counts.entry((d.task_type, d.priority)).or_default() += d.count;
+    }
+    merged.depth = counts
+        .into_iter()
+        .map(|((task_type, priority), count)| crate::models::QueueDepth {
+            task_type,
+            priority,
+            count,
+        })
+        .collect();
+    merged.depth.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.task_type.cmp(&b.task_type))
+    });
+    merged.active.sort_by(|a, b| {
+        a.lease_expires_at
+            .cmp(&b.lease_expires_at)
+            .then_with(|| a.task_id.cmp(&b.task_id))
+    });
+    merged
+}
+
+/// Merge per-shard dead-letter pages fetched with the same cursor and
+/// `limit`. Each shard returned its newest `limit` entries past the
+/// cursor, so the newest `limit` of their union is the tenant-wide page.
+pub fn merge_dead_letter_pages(
+    pages: Vec<(Vec<DeadLetterTask>, bool)>,
+    limit: usize,
+) -> (Vec<DeadLetterTask>, Option<DeadLetterCursor>) {
+    let any_more = pages.iter().any(|(_, more)| *more);
+    let all: Vec<DeadLetterTask> = pages.into_iter().flat_map(|(tasks, _)| tasks).collect();
+    let (page, next) = task_do::dead_letter_page(&all, None, None, limit);
+    let next = next.or_else(|| {
+        any_more
+            .then(|| page.last())
+            .flatten()
+            .map(|entry| DeadLetterCursor {
+                dead_lettered_at: entry.dead_lettered_at.clone(),
+                id: entry.task.id.clone(),
+            })
+    });
+    (page, next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ActiveLease, QueueDepth};
+
+    fn caps(c: &[&str]) -> Vec<String> {
+        c.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn config_parses_with_safe_defaults() {
+        let d = ShardConfig::parse(None, None);
+        assert_eq!((d.shards, d.by), (1, ShardBy::TaskType));
+        assert_eq!(ShardConfig::parse(Some("0"), None).shards, 1);
+        assert_eq!(ShardConfig::parse(Some("x"), None).shards, 1);
+        assert_eq!(
+            ShardConfig::parse(Some("1000"), None).shards,
+            MAX_TASK_SHARDS
+        );
+        assert_eq!(
+            ShardConfig::parse(Some("8"), Some("hash")).by,
+            ShardBy::TaskId
+        );
+        assert_eq!(
+            ShardConfig::parse(Some("8"), Some("bogus")).by,
+            ShardBy::TaskType
+        );
+    }
+
+    #[test]
+    fn shard_zero_keeps_the_tenant_name() {
+        assert_eq!(shard_name("acme", 0), "acme");
+        assert_eq!(shard_name("acme", 3), "acme:tasks:3");
+    }
+
+    #[test]
+    fn placement_is_stable_and_in_range() {
+        let by_type = ShardConfig::parse(Some("8"), None);
+        assert_eq!(
+            by_type.shard_for("build", "t1"),
+            by_type.shard_for("build", "t2")
+        );
+        let by_id = ShardConfig::parse(Some("8"), Some("hash"));
+        let spread: std::collections::HashSet<u32> = (0..200)
+            .map(|i| by_id.shard_for("build", &format!("task-{i}")))
+            .collect();
+        assert!(spread.iter().all(|s| *s < 8));
+        assert!(spread.len() > 1);
+        assert_eq!(ShardConfig::parse(None, None).shard_for("x", "y"), 0);
+    }
+
+    #[test]
+    fn claim_order_rotates_and_narrows_by_task_type() {
+        let cfg = ShardConfig::parse(Some("4"), None);
+        assert_eq!(cfg.claim_order(&[], 0), vec![0, 1, 2, 3]);
+        assert_eq!(cfg.claim_order(&[], 6), vec![2, 3, 0, 1]);
+        // Only structured capabilities: every type, so every shard.
+        assert_eq!(cfg.claim_order(&caps(&["rust@1.82"]), 0).len(), 4);
+
+        let order = cfg.claim_order(&caps(&["build", "rust@1.82"]), 1);
+        assert_eq!(order, vec![cfg.shard_for("build", "")]);
+
+        // Hashing by id scatters every type, so the agent polls them all.
+        let hashed = ShardConfig::parse(Some("4"), Some("hash"));
+        assert_eq!(hashed.claim_order(&caps(&["build"]), 0).len(), 4);
+    }
+
+    #[test]
+    fn search_order_puts_preferred_first() {
+        let cfg = ShardConfig::parse(Some("4"), None);
+        assert_eq!(cfg.search_order(2), vec![2, 0, 1, 3]);
+    }
+
+    fn snapshot(
+        pending: usize,
+        depth: &[(&str, i32, usize)],
+        oldest: Option<u64>,
+    ) -> QueueSnapshot {
+        QueueSnapshot {
+            pending_total: pending,
+            scheduled_total: 1,
+            max_pending: 10,
+            depth: depth
+                .iter()
+                .map(|(t, p, c)| QueueDepth {
+                    task_type: t.to_string(),
+                    priority: *p,
+                    count: *c,
+                })
+                .collect(),
+            oldest_pending_age_ms: oldest,
+            active: vec![ActiveLease {
+                task_id: format!("lease-{pending}"),
+                job_id: "j".into(),
+                task_type: "build".into(),
+                agent_id: None,
+                lease_expires_at: Some(format!("2026-01-01T00:0{pending}:00Z")),
+                cancel_requested: false,
+            }],
+            rejected_total: 2,
+            dead_letter_total: 1,
+            pending_notifies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn queue_snapshots_sum_and_regroup() {
+        let merged = merge_queue_snapshots(vec![
+            snapshot(3, &[("build", 0, 3)], Some(500)),
+            snapshot(2, &[("build", 0, 1), ("test", 5, 1)], Some(900)),
+        ]);
+        assert_eq!(merged.pending_total, 5);
+        assert_eq!(merged.scheduled_total, 2);
+        assert_eq!(merged.max_pending, 20);
+        assert_eq!(merged.rejected_total, 4);
+        assert_eq!(merged.dead_letter_total, 2);
+        assert_eq!(merged.oldest_pending_age_ms, Some(900));
+        assert_eq!(
+            merged
+                .depth
+                .iter()
+                .map(|d| (d.task_type.as_str(), d.priority, d.count))
+                .collect::<Vec<_>>(),
+            vec![("test", 5, 1), ("build", 0, 4)]
+        );
+        assert_eq!(
+            merged
+                .active
+                .iter()
+                .map(|l| l.task_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["lease-2", "lease-3"]
+        );
+    }
+
+    fn dead(id: &str, at: &str) -> DeadLetterTask {
+        let task: AgentTask = serde_json::from_value(serde_json::json!({
+            "id": id, "job_id": "j", "task_type": "build", "priority": 0,
+            "status": "failed", "params": null, "result": null, "agent_id": null,
+            "graph_ref": null, "play_id": null, "parent_task_id": null,
+            "retry_count": 3, "max_retries": 3, "lease_expires_at": null,
+            "created_at": at, "completed_at": at
+        }))
+        .unwrap();
+        DeadLetterTask {
+            task,
+            dead_lettered_at: at.to_string(),
+        }
+    }
+
+    #[test]
+    fn dead_letter_pages_merge_newest_first() {
+        let (page, next) = merge_dead_letter_pages(
+            vec![
+                (
+                    vec![dead("a", "2026-01-03"), dead("b", "2026-01-01")],
+                    false,
+                ),
+                (vec![dead("c", "2026-01-02")], false),
+            ],
+            2,
+        );
+        assert_eq!(
+            page.iter().map(|e| e.task.id.as_str()).collect::<Vec<_>>(),
+            vec!["a", "c"]
+        );
+        assert_eq!(next.map(|c| c.id), Some("c".to_string()));
+
+        // A shard with more past its page keeps the listing going.
+        let (page, next) = merge_dead_letter_pages(vec![(vec![dead("a", "2026-01-03")], true)], 1);
+        assert_eq!(page.len(), 1);
+        assert_eq!(next.map(|c| c.id), Some("a".to_string()));
+
+        let (_, next) = merge_dead_letter_pages(vec![(vec![dead("a", "2026-01-03")], false)], 5);
+        assert!(next.is_none());
+    }
+
+    #[test]
+    fn cancelled_tasks_concatenate() {
+        let one = CancelledTasks {
+            cancelled: vec![dead("a", "x").task],
+            cancel_requested: Vec::new(),
+        };
+        let two = CancelledTasks {
+            cancelled: Vec::new(),
+            cancel_requested: vec![dead("b", "x").task],
+        };
+        let merged = merge_cancelled(vec![one, two]);
+        assert_eq!(merged.cancelled.len(), 1);
+        assert_eq!(merged.cancel_requested.len(), 1);
+    }
+}
@@ -1,22 +1,44 @@
 use worker::*;
 
+use std::collections::HashSet;
+
+use crate::auth;
+use crate::authz;
+use crate::secret_scan::ScanMode;
+use crate::tenant_security::{self, Permission, Resource, Role, TokenClaims};
+
 const TENANT_ID_HEADER: &str = "x-tenant-id";
 const TENANT_ROLE_HEADER: &str = "x-tenant-role";
 const TENANT_FED_HEADER: &str = "x-tenant-federation";
+const TENANT_SUBJECT_HEADER: &str = "x-tenant-subject";
+
+/// Plain var that re-enables the trusted `x-tenant-*` headers when no bearer
+/// token is presented. Only for local `wrangler dev` runs (set it in
+/// `.dev.vars`); deployed environments must leave it unset.
+const DEV_HEADERS_VAR: &str = "AUTH_DEV_HEADERS";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TenantRole {
     Viewer,
     Builder,
     Admin,
+    /// Machine principal (CI bots, agents) minted with the `system_service`
+    /// token role. Writes like a builder but never gets admin routes.
+    Service,
 }
 
 #[derive(Debug, Clone)]
 pub struct TenantContext {
     pub tenant_id: String,
     pub role: TenantRole,
-    #[allow(dead_code)]
+    /// Token opt-in for receiving federated partner data (WS8).
     pub federation_allowed: bool,
+    /// Permission scope from the token; narrows the role matrix, never widens it.
+    pub scoped_permissions: Option<HashSet<Permission>>,
+    /// Ingest secret handling, loaded from the tenant row at admission.
+    pub secret_scan_mode: ScanMode,
+    /// The token's `sub` claim: who holds it, beyond its tenant and role.
+    pub subject: Option<String>,
 }
 
 impl TenantContext {
@@ -37,13 +59,129 @@ impl TenantContext {
             TenantRole::Viewer => "viewer",
             TenantRole::Builder => "builder",
             TenantRole::Admin => "admin",
+            TenantRole::Service => "service",
         };
         format!("tenant:{}:{}", self.tenant_id, role)
     }
+
+    /// The `human:<sub>` actor deciding a play approval gate, or the error
+    /// code and message for the 403. Gates are for people: service tokens
+    /// are refused outright, and a token without a `sub` claim cannot say
+    /// who decided.
+    pub fn approver(&self) -> std::result::Result<String, (&'static str, &'static str)> {
+        if self.role == TenantRole::Service {
+            return Err((
+                "SERVICE_CANNOT_APPROVE",
+                "service tokens cannot decide approval tasks",
+            ));
+        }
+        match self.subject.as_deref() {
+            Some(subject) => Ok(format!("human:{subject}")),
+            None => Err((
+                "SUBJECT_REQUIRED",
+                "deciding an approval task requires a token with a sub claim",
+            )),
+        }
+    }
+
+    /// Whether the caller may claim, hold or heartbeat work as `agent_id`.
+    /// The id feeds the implicit `agent:<id>` capability that task pinning
+    /// and exclusion match on, so it must be the token's own `sub`, or a
+    /// registered agent whose owner (`registered_owner`) is that `sub`.
+    pub fn bind_agent(
+        &self,
+        agent_id: &str,
+        registered_owner: Option<&str>,
+    ) -> std::result::Result<(), (&'static str, &'static str)> {
+        let Some(subject) = self.subject.as_deref() else {
+            return Err((
+                "SUBJECT_REQUIRED",
+                "acting as an agent requires a token with a sub claim",
+            ));
+        };
+        if agent_id == subject || registered_owner == Some(subject) {
+            Ok(())
+        } else {
+            Err((
+                "AGENT_NOT_BOUND",
+                "agent_id must be the token subject or an agent it registered",
+            ))
+        }
+    }
+
+    /// Build the context from a verified bearer token.
+    pub fn from_token(token: &auth::VerifiedToken) -> Self {
+        Self {
+            tenant_id: token.claims.tenant_id.clone(),
+            role: role_from_claims(token.claims.role),
+            federation_allowed: token.federation_allowed,
+            scoped_permissions: token.claims.scoped_permissions.clone(),
+            secret_scan_mode: ScanMode::default(),
+            subject: token.subject.clone(),
+        }
+    }
+
+    /// Claims view used by `tenant_security::evaluate_authz`.
+    pub fn token_claims(&self) -> TokenClaims {
+        TokenClaims {
+            tenant_id: self.tenant_id.clone(),
+            role: match self.role {
+                TenantRole::Viewer => Role::Reader,
+                TenantRole::Builder => Role::Contributor,
+                TenantRole::Admin => Role::Admin,
+                TenantRole::Service => Role::SystemService,
+            },
+            scoped_permissions: self.scoped_permissions.clone(),
+        }
+    }
+}
+
+fn role_from_claims(role: Role) -> TenantRole {
+    match role {
+        Role::Reader => TenantRole::Viewer,
+        Role::Contributor => TenantRole::Builder,
+        Role::Admin => TenantRole::Admin,
+        Role::SystemService => TenantRole::Service,
+    }
+}
+
+/// Authenticate a request at the router edge.
+///
+/// A bearer token always wins: when `Authorization` is present it must
+/// verify, even in dev mode, so a bad token never silently degrades to the
+/// header path. Without a token the `x-tenant-*` headers are honoured only
+/// when `AUTH_DEV_HEADERS` is explicitly enabled.
+pub fn authenticate(req: &Request, env: &Env) -> Result<TenantContext> {
+    if let Some(value) = req.headers().get(auth::AUTHORIZATION_HEADER)? {
+        let token = auth::bearer_token(&value)
+            .ok_or_else(|| Error::RustError(auth::AuthError::MissingToken.to_string()))?;
+        let keys = auth::JwtKeys::from_env(env)?;
+        let now_secs = (js_sys::Date::now() / 1000.0) as u64;
+        let verified = auth::verify_token(token, &keys, now_secs)
+            .map_err(|err| Error::RustError(err.to_string()))?;
+        return Ok(TenantContext::from_token(&verified));
+    }
+
+    let dev_mode = env.var(DEV_HEADERS_VAR).ok().map(|v| v.to_string());
+    if dev_headers_enabled(dev_mode.as_deref()) {
+        return tenant_from_headers(req.headers());
+    }
+    Err(Error::RustError(auth::AuthError::MissingToken.to_string()))
 }
 
-pub fn tenant_from_request(req: &Request) -> Result<TenantContext> {
-    let headers = req.headers();
+fn dev_headers_enabled(value: Option<&str>) -> bool {
+    value.is_some_and(|v| v.trim().eq_ignore_ascii_case("true") || v.trim() == "1")
+}
+
+/// Resolve the tenant context that `fetch` authenticated and handed to the
+/// router. Handlers call this instead of re-reading request headers.
+pub fn verified(data: &Option<TenantContext>) -> Result<TenantContext> {
+    data.clone()
+        .ok_or_else(|| Error::RustError("missing tenant context".to_string()))
+}
+
+/// Dev-mode only: trust the `x-tenant-*` headers verbatim.
+fn tenant_from_headers(headers: &Headers) -> Result<TenantContext> {
     let tenant_id = headers
         .get(TENANT_ID_HEADER)?
         .filter(|v| !v.is_empty())
@@ -59,38 +197,33 @@ pub fn tenant_from_request(req: &Request) -> Result<TenantContext> {
         .get(TENANT_FED_HEADER)?
         .map(|v| v.eq_ignore_ascii_case("true"))
         .unwrap_or(false);
+    let subject = headers
+        .get(TENANT_SUBJECT_HEADER)?
+        .filter(|v| !v.trim().is_empty());
 
     Ok(TenantContext {
         tenant_id,
         role,
         federation_allowed,
+        scoped_permissions: None,
+        secret_scan_mode: ScanMode::default(),
+        subject,
     })
 }
 
+/// Authorize a request against the route table in `authz.rs`. The route's
+/// declared resource type and permission go through
+/// `tenant_security::evaluate_authz`, so token-scoped permissions and the
+/// per-resource role overrides apply uniformly.
 pub fn authorize(ctx: &TenantContext, method: Method, path: &str) -> Result<()> {
-    let is_read = matches!(method, Method::Get | Method::Head | Method::Options);
-
-    // Tenant provisioning and policy activation are admin-only.
-    if path.starts_with("/v1/tenants/") || path.starts_with("/v1/policies/activate") {
-        if ctx.role != TenantRole::Admin {
-            return Err(Error::RustError("admin role required".to_string()));
-        }
-        return Ok(());
-    }
-
-    // Policy rules writes are admin-only.
-    if path.starts_with("/v1/policies/rules") && !is_read && ctx.role != TenantRole::Admin {
-        return Err(Error::RustError(
-            "admin role required for policy rule mutation".to_string(),
-        ));
-    }
-
-    // Viewer is read-only everywhere else.
-    if ctx.role == TenantRole::Viewer && !is_read {
-        return Err(Error::RustError("viewer role is read-only".to_string()));
-    }
-
-    Ok(())
+    let requirement = authz::requirement_for(&method, path);
+    let resource = Resource {
+        tenant_id: ctx.tenant_id.clone(),
+        resource_type: requirement.resource_type.to_string(),
+        resource_id: requirement.resource_id,
+    };
+    tenant_security::evaluate_authz(&ctx.token_claims(), &resource, requirement.permission)
+        .map_err(|err| Error::RustError(format!("{err} on {}", requirement.resource_type)))
 }
 
 fn parse_role(v: &str) -> Result<TenantRole> {
@@ -98,8 +231,9 @@ fn parse_role(v: &str) -> Result<TenantRole> {
         "viewer" => Ok(TenantRole::Viewer),
         "builder" => Ok(TenantRole::Builder),
         "admin" => Ok(TenantRole::Admin),
+        "service" => Ok(TenantRole::Service),
         _ => Err(Error::RustError(
-            "invalid x-tenant-role (expected viewer|builder|admin)".to_string(),
+            "invalid x-tenant-role (expected viewer|builder|admin|service)".to_string(),
         )),
     }
 }
@@ -113,6 +247,9 @@ mod tests {
             tenant_id: "tenant-42".to_string(),
             role,
             federation_allowed: false,
+            scoped_permissions: None,
+            secret_scan_mode: ScanMode::default(),
+            subject: None,
         }
     }
 
@@ -139,6 +276,100 @@ mod tests {
         assert!(parse_role("root").is_err());
     }
 
+    #[test]
+    fn parse_role_accepts_service() {
+        assert_eq!(parse_role("service").unwrap(), TenantRole::Service);
+    }
+
+    // ── Token-derived context ──────────────────────────────────
+
+    #[test]
+    fn from_token_maps_claim_roles() {
+        use crate::tenant_security::TokenClaims;
+        let token = |role| auth::VerifiedToken {
+            claims: TokenClaims {
+                tenant_id: "acme".to_string(),
+                role,
+                scoped_permissions: None,
+            },
+            federation_allowed: true,
+            subject: Some("jane".to_string()),
+        };
+        let tc = TenantContext::from_token(&token(Role::Reader));
+        assert_eq!(tc.subject.as_deref(), Some("jane"));
+        assert_eq!(tc.tenant_id, "acme");
+        assert_eq!(tc.role, TenantRole::Viewer);
+        assert!(tc.federation_allowed);
+        assert_eq!(
+            TenantContext::from_token(&token(Role::Contributor)).role,
+            TenantRole::Builder
+        );
+        assert_eq!(
+            TenantContext::from_token(&token(Role::Admin)).role,
+            TenantRole::Admin
+        );
+        let svc = TenantContext::from_token(&token(Role::SystemService));
+        assert_eq!(svc.role, TenantRole::Service);
+        assert_eq!(svc.actor(), "tenant:acme:service");
+    }
+
+    #[test]
+    fn approver_needs_a_human_subject() {
+        let mut tc = ctx(TenantRole::Admin);
+        assert_eq!(tc.approver().unwrap_err().0, "SUBJECT_REQUIRED");
+        tc.subject = Some("jane".to_string());
+        assert_eq!(tc.approver().unwrap(), "human:jane");
+        tc.role = TenantRole::Service;
+        assert_eq!(tc.approver().unwrap_err().0, "SERVICE_CANNOT_APPROVE");
+    }
+
+    #[test]
+    fn agent_id_is_bound_to_the_token_subject() {
+        let mut tc = ctx(TenantRole::Service);
+        assert_eq!(
+            tc.bind_agent("builder-7", None).unwrap_err().0,
+            "SUBJECT_REQUIRED"
+        );
+        tc.subject = Some("builder-7".to_string());
+        assert!(tc.bind_agent("builder-7", None).is_ok());
+        // Another agent's id, unless this subject registered it.
+        assert_eq!(
+            tc.bind_agent("builder-8", None).unwrap_err().0,
+            "AGENT_NOT_BOUND"
+        );
+        assert!(tc.bind_agent("builder-8", Some("someone-else")).is_err());
+        assert!(tc.bind_agent("agt-123", Some("builder-7")).is_ok());
+    }
+
+    #[test]
+    fn dev_headers_require_explicit_opt_in() {
+        assert!(!dev_headers_enabled(None));
+        assert!(!dev_headers_enabled(Some("")));
+        assert!(!dev_headers_enabled(Some("false")));
+        assert!(!dev_headers_enabled(Some("yes")));
+        assert!(dev_headers_enabled(Some("true")));
+        assert!(dev_headers_enabled(Some("TRUE")));
+        assert!(dev_headers_enabled(Some("1")));
+    }
+
+    #[test]
+    fn verified_requires_route_data() {
+        assert!(verified(&None).is_err());
+        let tc = verified(&Some(ctx(TenantRole::Builder))).unwrap();
+        assert_eq!(tc.tenant_id, "tenant-42");
+    }
+
+    #[test]
+    fn service_cannot_access_admin_paths_but_can_write() {
+        assert!(authorize(
+            &ctx(TenantRole::Service),
+            Method::Post,
+            "/v1/tenants/provision"
+        )
+        .is_err());
+        assert!(authorize(&ctx(TenantRole::Service), Method::Post, "/v1/events").is_ok());
+    }
+
     // ── TenantContext::r2_prefix ───────────────────────────────
 
     #[test]
@@ -153,6 +384,9 @@ mod tests {
             tenant_id: "org-abc".to_string(),
             role: TenantRole::Admin,
             federation_allowed: true,
+            scoped_permissions: None,
+            secret_scan_mode: ScanMode::default(),
+            subject: None,
         };
         assert_eq!(tc.r2_prefix(), "tenants/org-abc/");
     }
@@ -223,6 +457,15 @@ mod tests {
         .is_err());
     }
 
+    #[test]
+    fn only_admins_change_tenant_settings() {
+        let path = "/v1/tenants/org-abc/settings";
+        assert!(authorize(&ctx(TenantRole::Admin), Method::Put, path).is_ok());
+        for role in [TenantRole::Builder, TenantRole::Service, TenantRole::Viewer] {
+            assert!(authorize(&ctx(role), Method::Put, path).is_err());
+        }
+    }
+
     #[test]
     fn admin_can_activate_policy() {
         assert!(authorize(
@@ -295,4 +538,47 @@ mod tests {
     fn builder_can_read_general_paths() {
         assert!(authorize(&ctx(TenantRole::Builder), Method::Get, "/v1/artifacts").is_ok());
     }
+
+    // ── authorize: per-resource service account rules ──────────
+
+    #[test]
+    fn service_can_write_events_but_not_policy_rules_or_checkpoints() {
+        let svc = ctx(TenantRole::Service);
+        assert!(authorize(&svc, Method::Post, "/v1/events").is_ok());
+        assert!(authorize(&svc, Method::Post, "/v1/graph-events").is_ok());
+        assert!(authorize(&svc, Method::Post, "/v1/integrations/aivcs/events").is_ok());
+        assert!(authorize(&svc, Method::Post, "/v1/policies/rules").is_err());
+        assert!(authorize(&svc, Method::Patch, "/v1/policies/rules/r-1").is_err());
+        assert!(authorize(&svc, Method::Post, "/v1/checkpoints").is_err());
+        assert!(authorize(&svc, Method::Delete, "/v1/checkpoints/cp-1").is_err());
+        // Reads stay open.
+        assert!(authorize(&svc, Method::Get, "/v1/checkpoints/cp-1").is_ok());
+        assert!(authorize(&svc, Method::Get, "/v1/policies/rules").is_ok());
+    }
+
+    #[test]
+    fn builder_can_write_checkpoints() {
+        assert!(authorize(&ctx(TenantRole::Builder), Method::Post, "/v1/checkpoints").is_ok());
+    }
+
+    #[test]
+    fn viewer_can_run_read_only_post_routes() {
+        // Declared as reads even though the query travels in a POST body.
+        assert!(authorize(
+            &ctx(TenantRole::Viewer),
+            Method::Post,
+            "/v1/memory/retrieve"
+        )
+        .is_ok());
+        assert!(authorize(&ctx(TenantRole::Viewer), Method::Post, "/v1/memory/gc").is_err());
+    }
+
+    #[test]
+    fn scoped_token_permissions_narrow_the_role() {
+        let mut tc = ctx(TenantRole::Admin);
+        tc.scoped_permissions = Some([Permission::Read].into_iter().collect());
+        assert!(authorize(&tc, Method::Get, "/v1/runs").is_ok());
+        assert!(authorize(&tc, Method::Post, "/v1/runs").is_err());
+        assert!(authorize(&tc, Method::Post, "/v1/tenants/provision").is_err());
+    }
 }
@@ -0,0 +1,764 @@
+//! WS8: tenant lifecycle — suspend, resume, export and hard-delete.
+//!
+//! `tenants.status` drives edge admission: a suspended tenant only admits
+//! its admins (so they can export and resume), and a deleted tenant keeps a
+//! tombstone row that rejects everything except the `/v1/tenants/` admin
+//! subtree. Export and hard-delete walk [`TENANT_SCOPED_TABLES`] plus the
+//! tenant's R2 prefix (`TenantContext::r2_prefix`) and
+//! [`legacy_r2_prefixes`]. Hard-delete also empties the tenant's Durable
+//! Objects and Vectorize entries, found through the D1 rows that name them.
+//!
+//! `tenant_scoped_tables_cover_migrations` keeps the table list in
+//! lock-step with `migrations/`: a new table with a `tenant_id` column
+//! fails the test suite until it is listed here. `tenants` (the tombstone),
+//! `tenant_deletion_reports` (the evidence) and the append-only `audit_log`
+//! are deliberately excluded.
+
+use sha2::{Digest, Sha256};
+use worker::*;
+
+use crate::db;
+use crate::models;
+use crate::tenant::TenantRole;
+
+/// Every D1 table partitioned by `tenant_id`. Order is the hard-delete
+/// order: dependents before the rows they point at.
+pub const TENANT_SCOPED_TABLES: &[&str] = &[
+    // Provenance + gold layer (WS3)
+    "events",
+    "events_bronze",
+    "events_silver",
+    "relationships",
+    "task_dependencies",
+    "run_summaries",
+    "verification_evidence",
+    "telemetry_snapshots",
+    "reasoning_traces",
+    // Memory (WS5)
+    "memory_retrieval_feedback",
+    "memory_retrieval_queries",
+    "memory_index",
+    "memory",
+    // Policy (WS4)
+    "policy_decisions",
+    "policy_escalations",
+    "policy_rate_limit_counters",
+    "policy_rules",
+    // AIVCS projections
+    "review_comment",
+    "review_thread_resolution",
+    "file_anchor",
+    "review_thread",
+    "human_decision",
+    "ci_check_run",
+    "branch",
+    "gold_aivcs_review_queue",
+    "change_set",
+    // Orchestration
+    "checkpoints",
+    "task_schedules",
+    "task_idempotency_keys",
+    "mcp_tasks",
+    "agents",
+    "play_definitions",
+    "play_definition_versions",
+    "play_runs",
+    "integrations",
+    "gemini_batch_jobs",
+    "tenant_federation",
+    "secret_scan_results",
+    "tenant_data_keys",
+    // WS2 domain model
+    "tool_calls",
+    "artifacts",
+    "plans",
+    "releases",
+    "tasks",
+    "runs",
+];
+
+/// Archives live outside `tenants/` so a hard-delete never removes the
+/// export taken just before it.
+const EXPORT_ROOT: &str = "tenant-exports";
+
+/// R2 `delete_multiple` accepts at most 1000 keys per call.
+const R2_DELETE_BATCH: usize = 1000;
+
+/// Ids per Vectorize `deleteByIds` call.
+const VECTOR_DELETE_BATCH: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TenantStatus {
+    Active,
+    Suspended,
+    Deleted,
+}
+
+impl TenantStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TenantStatus::Active => "active",
+            TenantStatus::Suspended => "suspended",
+            TenantStatus::Deleted => "deleted",
+        }
+    }
+
+    /// Unknown values are treated as active, matching the column default.
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "suspended" => TenantStatus::Suspended,
+            "deleted" => TenantStatus::Deleted,
+            _ => TenantStatus::Active,
+        }
+    }
+}
+
+/// Edge admission for a tenant in `status`. Returns the error code and
+/// message for the 403 when the request must be rejected.
+pub fn admission(
+    status: TenantStatus,
+    role: TenantRole,
+    path: &str,
+) -> std::result::Result<(), (&'static str, &'static str)> {
+    let is_admin = role == TenantRole::Admin;
+    match status {
+        TenantStatus::Active => Ok(()),
+        TenantStatus::Suspended if is_admin => Ok(()),
+        TenantStatus::Suspended => Err(("TENANT_SUSPENDED", "tenant is suspended")),
+        TenantStatus::Deleted if is_admin && path.starts_with("/v1/tenants/") => Ok(()),
+        TenantStatus::Deleted => Err(("TENANT_DELETED", "tenant has been deleted")),
+    }
+}
+
+/// Tenant admin routes act on the `:tenant_id` in the path. The admin role
+/// is scoped to the caller's own verified tenant, so any other tenant in
+/// the path is rejected with a 403 (code, message).
+pub fn authorize_path_tenant(
+    verified_tenant: &str,
+    path_tenant: &str,
+) -> std::result::Result<(), (&'static str, &'static str)> {
+    if verified_tenant == path_tenant {
+        Ok(())
+    } else {
+        Err((
+            "TENANT_MISMATCH",
+            "tenant admin routes only act on the caller's own tenant",
+        ))
+    }
+}
+
+/// `POST /v1/tenants/provision` names its tenant in the body rather than
+/// the path; the same rule applies, so an admin can only provision (and
+/// re-provision the limits of) their own tenant.
+pub fn authorize_provision(
+    verified_tenant: &str,
+    body_tenant: &str,
+) -> std::result::Result<(), (&'static str, &'static str)> {
+    if verified_tenant == body_tenant {
+        Ok(())
+    } else {
+        Err((
+            "TENANT_MISMATCH",
+            "tenants can only be provisioned by their own admins",
+        ))
+    }
+}
+
+/// Root prefix of one export archive.
+pub fn export_prefix(tenant_id: &str, export_id: &str) -> String {
+    format!("{EXPORT_ROOT}/{tenant_id}/{export_id}/")
+}
+
+pub fn tenant_prefix(tenant_id: &str) -> String {
+    format!("tenants/{tenant_id}/")
+}
+
+/// First path segments of the R2 layouts outside `tenants/`. A tenant id
+/// equal to one of them would make its legacy prefixes overlap another
+/// tenant's keys (e.g. `checkpoints/artifacts/`).
+const R2_LAYOUT_ROOTS: &[&str] = &[
+    "tenants",
+    "checkpoints",
+    "artifacts",
+    "policies",
+    EXPORT_ROOT,
+];
+
+/// R2 prefixes holding a tenant's objects besides [`tenant_prefix`], as
+/// `(walk, skipped)`. The oxidizedgraph ingest writes checkpoint state
+/// under `checkpoints/{tenant}/` and the AIVCS ingest writes artifact
+/// metadata under `{tenant}/artifacts/`; both predate `r2_prefix`. Those
+/// that would overlap another layout are returned as skipped instead.
+pub fn legacy_r2_prefixes(tenant_id: &str) -> (Vec<String>, Vec<String>) {
+    let prefixes = vec![
+        format!("checkpoints/{tenant_id}/"),
+        format!("{tenant_id}/artifacts/"),
+    ];
+    if R2_LAYOUT_ROOTS.contains(&tenant_id) {
+        (Vec::new(), prefixes)
+    } else {
+        (prefixes, Vec::new())
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Digest of a deletion report: SHA-256 over its JSON with `digest` blanked.
+pub fn report_digest(report: &models::TenantDeletionReport) -> String {
+    let mut unsigned = report.clone();
+    unsigned.digest.clear();
+    let bytes = serde_json::to_vec(&unsigned).unwrap_or_default();
+    sha256_hex(&bytes)
+}
+
+/// Every object key (and size) under `prefix`, following list cursors.
+async fn list_prefix(bucket: &Bucket, prefix: &str) -> Result<Vec<(String, u64)>> {
+    let mut out = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut list = bucket.list().prefix(prefix);
+        if let Some(c) = cursor.take() {
+            list = list.cursor(c);
+        }
+        let page = list.execute().await?;
+        out.extend(page.objects().iter().map(|o| (o.key(), o.size())));
+        match page.cursor() {
+            Some(c) if page.truncated() => cursor = Some(c),
+            _ => break,
+        }
+    }
+    Ok(out)
+}
+
+/// R2 objects copied per export request. An export larger than this is
+/// finished by further `POST /v1/tenants/:tenant_id/export/:export_id`
+/// calls, each resuming from the `progress.json` the last one left.
+pub const EXPORT_OBJECTS_PER_STEP: usize = 200;
+
+/// Resume point of an export that has not copied every object yet, kept
+/// at the archive root as `progress.json` until `manifest.json` replaces
+/// it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ExportProgress {
+    pub manifest: models::TenantExportManifest,
+    /// Index into [`export_sources`] of the prefix being copied.
+    pub source: usize,
+    /// R2 list cursor within that prefix.
+    pub cursor: Option<String>,
+}
+
+/// R2 prefixes an export copies, in order, as `(prefix, legacy)`.
+pub fn export_sources(tenant_id: &str) -> Vec<(String, bool)> {
+    let (legacy, _) = legacy_r2_prefixes(tenant_id);
+    std::iter::once((tenant_prefix(tenant_id), false))
+        .chain(legacy.into_iter().map(|prefix| (prefix, true)))
+        .collect()
+}
+
+/// Archive key for `source_key`, listed under `source_prefix`. Legacy keys
+/// keep their full path under a separate root so they can never collide
+/// with a key from the current layout.
+pub fn archive_key(
+    archive_prefix: &str,
+    source_prefix: &str,
+    legacy: bool,
+    source_key: &str,
+) -> String {
+    if legacy {
+        format!("{archive_prefix}r2-legacy/{source_key}")
+    } else {
+        let relative = source_key.strip_prefix(source_prefix).unwrap_or(source_key);
+        format!("{archive_prefix}r2/{relative}")
+    }
+}
+
+fn progress_key(archive_prefix: &str) -> String {
+    format!("{archive_prefix}progress.json")
+}
+
+fn manifest_key(archive_prefix: &str) -> String {
+    format!("{archive_prefix}manifest.json")
+}
+
+/// Start a new archive under [`export_prefix`]: copy every tenant-scoped
+/// D1 row, then the first [`EXPORT_OBJECTS_PER_STEP`] R2 objects. The
+/// returned manifest has `complete: false` while objects remain.
+pub async fn start_export(
+    d1: &D1Database,
+    bucket: &Bucket,
+    tenant_id: &str,
+    export_id: &str,
+) -> Result<models::TenantExportManifest> {
+    let archive_prefix = export_prefix(tenant_id, export_id);
+
+    let mut tables = Vec::with_capacity(TENANT_SCOPED_TABLES.len());
+    for table in TENANT_SCOPED_TABLES {
+        let rows = db::export_tenant_rows(d1, table, tenant_id).await?;
+        let body = serde_json::to_vec(&rows)?;
+        let key = format!("{archive_prefix}d1/{table}.json");
+        let sha256 = sha256_hex(&body);
+        bucket.put(&key, body).execute().await?;
+        tables.push(models::ExportedTable {
+            table: table.to_string(),
+            rows: rows.len(),
+            key,
+            sha256,
+        });
+    }
+
+    let (_, skipped_prefixes) = legacy_r2_prefixes(tenant_id);
+    let progress = ExportProgress {
+        manifest: models::TenantExportManifest {
+            tenant_id: tenant_id.to_string(),
+            export_id: export_id.to_string(),
+            archive_prefix,
+            created_at: db::now_iso(),
+            tables,
+            objects: Vec::new(),
+            skipped_prefixes,
+            complete: false,
+        },
+        source: 0,
+        cursor: None,
+    };
+    copy_export_objects(bucket, progress).await
+}
+
+/// Continue `export_id`: copy the next [`EXPORT_OBJECTS_PER_STEP`] objects,
+/// or return the finished manifest. `None` if there is no such export.
+pub async fn resume_export(
+    bucket: &Bucket,
+    tenant_id: &str,
+    export_id: &str,
+) -> Result<Option<models::TenantExportManifest>> {
+    let archive_prefix = export_prefix(tenant_id, export_id);
+    if let Some(bytes) = crate::storage::get_blob(bucket, &progress_key(&archive_prefix)).await? {
+        let progress: ExportProgress = serde_json::from_slice(&bytes)?;
+        return copy_export_objects(bucket, progress).await.map(Some);
+    }
+    match crate::storage::get_blob(bucket, &manifest_key(&archive_prefix)).await? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Copy up to [`EXPORT_OBJECTS_PER_STEP`] objects from where `progress`
+/// left off, then save the new resume point, or the manifest once every
+/// source is done.
+async fn copy_export_objects(
+    bucket: &Bucket,
+    mut progress: ExportProgress,
+) -> Result<models::TenantExportManifest> {
+    let sources = export_sources(&progress.manifest.tenant_id);
+    let archive_prefix = progress.manifest.archive_prefix.clone();
+    let mut copied = 0;
+    while copied < EXPORT_OBJECTS_PER_STEP {
+        let Some((source_prefix, legacy)) = sources.get(progress.source) else {
+            break;
+        };
+        let mut list = bucket
+            .list()
+            .prefix(source_prefix.clone())
+            .limit((EXPORT_OBJECTS_PER_STEP - copied) as u32);
+        if let Some(c) = progress.cursor.take() {
+            list = list.cursor(c);
+        }
+        let page = list.execute().await?;
+        for object in page.objects() {
+            let source_key = object.key();
+            copied += 1;
+            let Some(bytes) = crate::storage::get_blob(bucket, &source_key).await? else {
+                continue;
+            };
+            let archive_key = archive_key(&archive_prefix, source_prefix, *legacy, &source_key);
+            let encrypted = crate::envelope::is_sealed(&bytes);
+            bucket.put(&archive_key, bytes).execute().await?;
+            progress.manifest.objects.push(models::ExportedObject {
+                source_key,
+                archive_key,
+                size: object.size(),
+                encrypted,
+            });
+        }
+        match page.cursor() {
+            Some(c) if page.truncated() => progress.cursor = Some(c),
+            _ => progress.source += 1,
+        }
+    }
+
+    if progress.source < sources.len() {
+        bucket
+            .put(
+                progress_key(&archive_prefix),
+                serde_json::to_vec(&progress)?,
+            )
+            .execute()
+            .await?;
+        return Ok(progress.manifest);
+    }
+    progress.manifest.complete = true;
+    bucket
+        .put(
+            manifest_key(&archive_prefix),
+            serde_json::to_vec(&progress.manifest)?,
+        )
+        .execute()
+        .await?;
+    bucket.delete(progress_key(&archive_prefix)).await?;
+    Ok(progress.manifest)
+}
+
+/// Empty one Durable Object through its `/purge` route.
+async fn purge_durable_object(stub: Stub) -> Result<()> {
+    let resp = stub
+        .fetch_with_request(Request::new("https://do/purge", Method::Post)?)
+        .await?;
+    if resp.status_code() >= 300 {
+        return Err(Error::RustError(format!(
+            "durable object purge failed with status {}",
+            resp.status_code()
+        )));
+    }
+    Ok(())
+}
+
+/// Empty every Durable Object the tenant owns: each task queue shard, its
+/// rate limiter, and the `PlayManager` and `ThreadManager` of every run and
+/// thread found in D1. Must run before the D1 rows go, since they are the
+/// only index of those instance names. Returns the number purged.
+async fn purge_durable_objects(env: &Env, d1: &D1Database, tenant_id: &str) -> Result<usize> {
+    let mut purged = 0;
+    let shards = crate::task_shard::TaskShards::new(env, tenant_id)?;
+    for shard in shards.config.all() {
+        purge_durable_object(shards.stub(shard)?).await?;
+        purged += 1;
+    }
+    if let Some(stub) = crate::rate_limiter_stub(env, tenant_id) {
+        purge_durable_object(stub).await?;
+        purged += 1;
+    }
+    let plays = env.durable_object("PLAY_MANAGER")?;
+    for run_id in db::tenant_play_run_ids(d1, tenant_id).await? {
+        let name = crate::play_do_name(tenant_id, &run_id);
+        purge_durable_object(plays.id_from_name(&name)?.get_stub()?).await?;
+        purged += 1;
+    }
+    let threads = env.durable_object("THREAD_MANAGER")?;
+    for thread_id in db::tenant_thread_ids(d1, tenant_id).await? {
+        let name = crate::thread_do_name(tenant_id, &thread_id);
+        purge_durable_object(threads.id_from_name(&name)?.get_stub()?).await?;
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+/// Remove the Vectorize entries of the tenant's memory items. Skipped, like
+/// indexing itself, when the index binding is absent. Returns the number
+/// of ids submitted.
+async fn delete_memory_vectors(env: &Env, d1: &D1Database, tenant_id: &str) -> Result<usize> {
+    let Ok(index) = crate::vector_index::SemanticIndex::new(env) else {
+        return Ok(0);
+    };
+    let ids = db::tenant_memory_ids(d1, tenant_id).await?;
+    for chunk in ids.chunks(VECTOR_DELETE_BATCH) {
+        index.delete_by_ids(chunk).await?;
+    }
+    Ok(ids.len())
+}
+
+/// Purge every tenant-scoped Durable Object, Vectorize entry, D1 row and
+/// R2 object, then re-count the rows and objects to build a digest-sealed
+/// [`models::TenantDeletionReport`]. The caller persists the report and
+/// flips the tenant to `deleted`. Any failure before the D1 rows go leaves
+/// them in place, so retrying the delete finds the same instances again.
+pub async fn hard_delete_tenant(
+    env: &Env,
+    d1: &D1Database,
+    bucket: &Bucket,
+    tenant_id: &str,
+    report_id: &str,
+) -> Result<models::TenantDeletionReport> {
+    let durable_objects_purged = purge_durable_objects(env, d1, tenant_id).await?;
+    let vectors_deleted = delete_memory_vectors(env, d1, tenant_id).await?;
+
+    let mut tables = Vec::with_capacity(TENANT_SCOPED_TABLES.len());
+    for table in TENANT_SCOPED_TABLES {
+        let rows_deleted = db::delete_tenant_rows(d1, table, tenant_id).await?;
+        let rows_remaining = db::count_tenant_rows(d1, table, tenant_id).await?;
+        tables.push(models::TableDeletion {
+            table: table.to_string(),
+            rows_deleted,
+            rows_remaining,
+        });
+    }
+
+    let r2_prefix = tenant_prefix(tenant_id);
+    let (legacy_prefixes, skipped_prefixes) = legacy_r2_prefixes(tenant_id);
+    let prefixes: Vec<&String> = std::iter::once(&r2_prefix)
+        .chain(&legacy_prefixes)
+        .collect();
+    let mut keys = Vec::new();
+    for prefix in &prefixes {
+        keys.extend(
+            list_prefix(bucket, prefix)
+                .await?
+                .into_iter()
+                .map(|(key, _)| key),
+        );
+    }
+    for chunk in keys.chunks(R2_DELETE_BATCH) {
+        bucket.delete_multiple(chunk.to_vec()).await?;
+    }
+    let mut objects_remaining = 0;
+    for prefix in &prefixes {
+        objects_remaining += list_prefix(bucket, prefix).await?.len();
+    }
+
+    Ok(seal_report(models::TenantDeletionReport {
+        tenant_id: tenant_id.to_string(),
+        report_id: report_id.to_string(),
+        deleted_at: db::now_iso(),
+        tables,
+        r2_prefix,
+        legacy_prefixes,
+        skipped_prefixes,
+        objects_deleted: keys.len().saturating_sub(objects_remaining),
+        objects_remaining,
+        durable_objects_purged,
+        vectors_deleted,
+        verified: false,
+        digest: String::new(),
+    }))
+}
+
+/// Fill in `verified` and `digest` from the report's own counts.
+fn seal_report(mut report: models::TenantDeletionReport) -> models::TenantDeletionReport {
+    report.verified = report.objects_remaining == 0
+        && report.skipped_prefixes.is_empty()
+        && report.tables.iter().all(|t| t.rows_remaining == 0);
+    report.digest = report_digest(&report);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tables carrying `tenant_id` that a hard-delete deliberately keeps.
+    const RETAINED_TABLES: &[&str] = &[
+        "tenants",
+        "tenant_deletion_reports",
+        "audit_log",
+        "audit_append_failures",
+    ];
+
+    fn report(rows_remaining: usize, objects_remaining: usize) -> models::TenantDeletionReport {
+        models::TenantDeletionReport {
+            tenant_id: "acme".to_string(),
+            report_id: "r-1".to_string(),
+            deleted_at: "2026-01-01T00:00:00.000Z".to_string(),
+            tables: vec![models::TableDeletion {
+                table: "runs".to_string(),
+                rows_deleted: 3,
+                rows_remaining,
+            }],
+            r2_prefix: "tenants/acme/".to_string(),
+            legacy_prefixes: Vec::new(),
+            skipped_prefixes: Vec::new(),
+            objects_deleted: 2,
+            objects_remaining,
+            durable_objects_purged: 0,
+            vectors_deleted: 0,
+            verified: false,
+            digest: String::new(),
+        }
+    }
+
+    /// Tables whose CREATE (or a later ALTER ... ADD COLUMN) gives them a
+    /// `tenant_id` column, scanned from the migration files.
+    fn migration_tenant_tables() -> Vec<String> {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/migrations");
+        let mut tables = Vec::new();
+        for entry in std::fs::read_dir(dir).expect("migrations dir") {
+            let sql = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            let mut current: Option<String> = None;
+            for line in sql.lines() {
+                let line = line.trim();
+                if let Some(rest) = line.strip_prefix("CREATE TABLE") {
+                    let name = rest.trim_start_matches(" IF NOT EXISTS").trim();
+                    current = name.split_whitespace().next().map(str::to_string);
+                } else if let Some(rest) = line.strip_prefix("ALTER TABLE") {
+                    if line.contains("ADD COLUMN tenant_id") {
+                        tables.push(rest.split_whitespace().next().unwrap().to_string());
+                    }
+                } else if line.starts_with(')') {
+                    current = None;
+                }
+                if line.starts_with("tenant_id ") {
+                    if let Some(table) = &current {
+                        tables.push(table.clone());
+                    }
+                }
+            }
+        }
+        tables.sort();
+        tables.dedup();
+        tables
+    }
+
+    #[test]
+    fn tenant_scoped_tables_cover_migrations() {
+        let tables = migration_tenant_tables();
+        assert!(tables.len() > 30, "migration scan found too few tables");
+        for table in tables {
+            assert!(
+                TENANT_SCOPED_TABLES.contains(&table.as_str())
+                    || RETAINED_TABLES.contains(&table.as_str()),
+                "table {table} has a tenant_id column but is not in TENANT_SCOPED_TABLES"
+            );
+        }
+    }
+
+    #[test]
+    fn tenant_scoped_tables_have_no_duplicates_or_retained_tables() {
+        for (i, a) in TENANT_SCOPED_TABLES.iter().enumerate() {
+            assert!(!TENANT_SCOPED_TABLES[i + 1..].contains(a), "duplicate {a}");
+            assert!(!RETAINED_TABLES.contains(a), "{a} must survive a delete");
+        }
+    }
+
+    #[test]
+    fn status_parse_round_trips_and_defaults_to_active() {
+        for s in [
+            TenantStatus::Active,
+            TenantStatus::Suspended,
+            TenantStatus::Deleted,
+        ] {
+            assert_eq!(TenantStatus::parse(s.as_str()), s);
+        }
+        assert_eq!(TenantStatus::parse("SUSPENDED"), TenantStatus::Suspended);
+        assert_eq!(TenantStatus::parse("provisioning"), TenantStatus::Active);
+    }
+
+    #[test]
+    fn suspended_tenant_admits_only_admins() {
+        let s = TenantStatus::Suspended;
+        assert!(admission(s, TenantRole::Admin, "/v1/runs").is_ok());
+        for role in [TenantRole::Viewer, TenantRole::Builder, TenantRole::Service] {
+            assert_eq!(
+                admission(s, role, "/v1/runs").unwrap_err().0,
+                "TENANT_SUSPENDED"
+            );
+        }
+    }
+
+    #[test]
+    fn deleted_tenant_admits_admins_on_tenant_routes_only() {
+        let d = TenantStatus::Deleted;
+        assert!(admission(d, TenantRole::Admin, "/v1/tenants/acme/deletion-report").is_ok());
+        assert_eq!(
+            admission(d, TenantRole::Admin, "/v1/runs").unwrap_err().0,
+            "TENANT_DELETED"
+        );
+        assert!(admission(d, TenantRole::Builder, "/v1/tenants/acme/resume").is_err());
+        assert!(admission(TenantStatus::Active, TenantRole::Viewer, "/v1/runs").is_ok());
+    }
+
+    #[test]
+    fn tenant_admin_routes_are_scoped_to_the_callers_tenant() {
+        assert!(authorize_path_tenant("acme", "acme").is_ok());
+        assert_eq!(
+            authorize_path_tenant("acme", "globex").unwrap_err().0,
+            "TENANT_MISMATCH"
+        );
+        assert!(authorize_path_tenant("acme", "ACME").is_err());
+    }
+
+    #[test]
+    fn provision_rejects_another_tenants_id() {
+        assert!(authorize_provision("acme", "acme").is_ok());
+        assert_eq!(
+            authorize_provision("acme", "globex").unwrap_err().0,
+            "TENANT_MISMATCH"
+        );
+        assert!(authorize_provision("acme", "acme ").is_err());
+    }
+
+    #[test]
+    fn export_archive_lives_outside_tenant_prefix() {
+        let archive = export_prefix("acme", "e-1");
+        assert_eq!(archive, "tenant-exports/acme/e-1/");
+        assert!(!archive.starts_with(&tenant_prefix("acme")));
+    }
+
+    #[test]
+    fn export_copies_current_layout_then_legacy_prefixes() {
+        let sources = export_sources("acme");
+        assert_eq!(sources[0], ("tenants/acme/".to_string(), false));
+        assert!(sources[1..].iter().all(|(_, legacy)| *legacy));
+        assert_eq!(export_sources("checkpoints").len(), 1);
+    }
+
+    #[test]
+    fn archive_keys_separate_current_and_legacy_layouts() {
+        let archive = export_prefix("acme", "e-1");
+        assert_eq!(
+            archive_key(&archive, "tenants/acme/", false, "tenants/acme/blobs/a"),
+            "tenant-exports/acme/e-1/r2/blobs/a"
+        );
+        assert_eq!(
+            archive_key(&archive, "checkpoints/acme/", true, "checkpoints/acme/t/1"),
+            "tenant-exports/acme/e-1/r2-legacy/checkpoints/acme/t/1"
+        );
+    }
+
+    #[test]
+    fn seal_report_verifies_only_when_everything_is_gone() {
+        assert!(seal_report(report(0, 0)).verified);
+        assert!(!seal_report(report(1, 0)).verified);
+        assert!(!seal_report(report(0, 1)).verified);
+    }
+
+    #[test]
+    fn skipped_legacy_prefix_keeps_report_unverified() {
+        let mut r = report(0, 0);
+        r.skipped_prefixes = vec!["checkpoints/artifacts/".to_string()];
+        assert!(!seal_report(r).verified);
+    }
+
+    #[test]
+    fn legacy_prefixes_cover_ingest_layouts_unless_ambiguous() {
+        let (walk, skipped) = legacy_r2_prefixes("acme");
+        assert_eq!(walk, vec!["checkpoints/acme/", "acme/artifacts/"]);
+        assert!(skipped.is_empty());
+        for root in R2_LAYOUT_ROOTS {
+            let (walk, skipped) = legacy_r2_prefixes(root);
+            assert!(walk.is_empty());
+            assert_eq!(skipped.len(), 2);
+        }
+    }
+
+    #[test]
+    fn report_digest_is_stable_for_reports_without_legacy_fields() {
+        let sealed = seal_report(report(0, 0));
+        let json = serde_json::to_value(&sealed).unwrap();
+        assert!(json.get("legacy_prefixes").is_none());
+        assert!(json.get("durable_objects_purged").is_none());
+        assert!(json.get("vectors_deleted").is_none());
+        let stored: models::TenantDeletionReport = serde_json::from_value(json).unwrap();
+        assert_eq!(report_digest(&stored), sealed.digest);
+    }
+
+    #[test]
+    fn report_digest_detects_tampering() {
+        let sealed = seal_report(report(0, 0));
+        assert_eq!(sealed.digest.len(), 64);
+        assert_eq!(report_digest(&sealed), sealed.digest);
+
+        let mut tampered = sealed.clone();
+        tampered.tables[0].rows_deleted = 0;
+        assert_ne!(report_digest(&tampered), sealed.digest);
+    }
+}
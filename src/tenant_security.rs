@@ -156,6 +156,22 @@ impl Default for AuthzPolicy {
                 .collect(),
         );
 
+        // Per-resource overrides. Governance and agent state are read-only
+        // for service accounts (CI bots ingest events and check runs but
+        // must not rewrite policy or checkpoints, nor record human
+        // decisions such as play approvals); contributors can read policy
+        // rules but only admins mutate them.
+        for resource_type in ["policy_rule", "checkpoint", "human_decision"] {
+            rules.insert(
+                (Role::SystemService, resource_type.into()),
+                [Permission::Read].into_iter().collect(),
+            );
+        }
+        rules.insert(
+            (Role::Contributor, "policy_rule".into()),
+            [Permission::Read].into_iter().collect(),
+        );
+
         Self { rules }
     }
 }
@@ -184,12 +200,15 @@ impl AuthzPolicy {
     }
 }
 
-/// Token claims extracted from request authentication.
-#[derive(Debug, Clone)]
+/// Token claims extracted from request authentication (see `auth.rs` for
+/// the signed JWT wire format).
+#[derive(Debug, Clone, Deserialize)]
 pub struct TokenClaims {
     pub tenant_id: String,
     pub role: Role,
-    /// Optional explicit permission overrides from the token.
+    /// Optional permission scope from the token. It narrows the role's
+    /// permissions (the two are intersected) and can never widen them.
+    #[serde(default)]
     pub scoped_permissions: Option<HashSet<Permission>>,
 }
 
@@ -232,7 +251,8 @@ impl fmt::Display for AuthzError {
 /// Evaluate authorization: pure in-memory, no I/O.  Target: <1ms.
 ///
 /// 1. Checks tenant boundary (token tenant must match resource tenant).
-/// 2. Checks role-permission matrix for the requested action.
+/// 2. Checks role-permission matrix for the requested action, narrowed to
+///    the token's scoped permissions when it carries any.
 pub fn evaluate_authz(
     claims: &TokenClaims,
     resource: &Resource,
@@ -246,14 +266,17 @@ pub fn evaluate_authz(
         });
     }
 
-    // 2. Permission check — prefer token-scoped overrides, else policy.
-    let effective = match &claims.scoped_permissions {
-        Some(scoped) => scoped.clone(),
-        None => AuthzPolicy::default()
-            .permissions_for_role_resource(&claims.role, &resource.resource_type),
-    };
+    // 2. Permission check — the role matrix, intersected with the token's
+    //    scope so a scoped token is never granted more than its role.
+    let granted = AuthzPolicy::default()
+        .permissions_for_role_resource(&claims.role, &resource.resource_type)
+        .contains(&action);
+    let in_scope = claims
+        .scoped_permissions
+        .as_ref()
+        .is_none_or(|scoped| scoped.contains(&action));
 
-    if effective.contains(&action) {
+    if granted && in_scope {
         Ok(())
     } else {
         Err(AuthzError::PermissionDenied {
@@ -265,8 +288,10 @@ pub fn evaluate_authz(
 
 // ── 3. Rate Limiting ───────────────────────────────────────────────
 
-/// Per-tenant rate-limit configuration.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Per-tenant rate-limit configuration. Persisted by the `TenantRateLimiter`
+/// DO; fields missing from a stored/provisioned config take the defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TenantRateLimitConfig {
     pub requests_per_minute: u64,
     pub burst_limit: u64,
@@ -283,11 +308,21 @@ impl Default for TenantRateLimitConfig {
     }
 }
 
-/// Sliding-window counter kept in Worker memory for the Worker lifetime.
-#[derive(Debug, Clone)]
+/// Length of one rate-limit window.
+const RATE_WINDOW_MS: u64 = 60_000;
+
+/// Fixed-window counter: a start and a count, so the state the
+/// `TenantRateLimiter` DO persists on every check stays the same size
+/// however busy the tenant is. Windows are aligned to the minute, so a
+/// tenant can land up to twice the effective limit across one boundary.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RateLimitState {
-    /// Timestamps (epoch millis) of requests within the current window.
-    window_hits: Vec<u64>,
+    /// Epoch-ms start of the window `window_count` belongs to.
+    #[serde(default)]
+    window_start_ms: u64,
+    /// Requests admitted in that window.
+    #[serde(default)]
+    window_count: u64,
     /// Consecutive failure count for circuit breaker.
     consecutive_failures: u32,
     /// If set, circuit is open until this epoch-ms timestamp.
@@ -297,7 +332,8 @@ pub struct RateLimitState {
 impl RateLimitState {
     pub fn new() -> Self {
         Self {
-            window_hits: Vec::new(),
+            window_start_ms: 0,
+            window_count: 0,
             consecutive_failures: 0,
             circuit_open_until: None,
         }
@@ -320,7 +356,28 @@ impl RateLimitState {
 
     /// Number of requests in the current window.
     pub fn window_count(&self) -> usize {
-        self.window_hits.len()
+        self.window_count as usize
+    }
+
+    /// Epoch-ms until which the circuit is open, if tripped.
+    pub fn circuit_open_until(&self) -> Option<u64> {
+        self.circuit_open_until
+    }
+
+    /// Milliseconds until a request rejected by [`check_rate_limit`] could
+    /// succeed: the remaining cooldown for an open circuit, or the time until
+    /// a full window starts over.
+    pub fn retry_after_ms(&self, config: &TenantRateLimitConfig, now_ms: u64) -> u64 {
+        if let Some(until) = self.circuit_open_until {
+            if now_ms < until {
+                return until - now_ms;
+            }
+        }
+        let effective_limit = config.requests_per_minute + config.burst_limit;
+        if self.window_count < effective_limit {
+            return 0;
+        }
+        (self.window_start_ms + RATE_WINDOW_MS).saturating_sub(now_ms)
     }
 }
 
@@ -358,7 +415,7 @@ const CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
 /// Cooldown period when circuit breaker trips (30 seconds).
 const CIRCUIT_COOLDOWN_MS: u64 = 30_000;
 
-/// Check rate limit using a sliding window.
+/// Check rate limit using a fixed one-minute window.
 ///
 /// `now_ms` is the current epoch-millisecond timestamp (caller provides
 /// for testability and WASM compatibility — avoids `std::time::Instant`
@@ -392,13 +449,16 @@ pub fn check_rate_limit(
         });
     }
 
-    // Sliding window: remove entries older than 60 seconds.
-    let window_start = now_ms.saturating_sub(60_000);
-    state.window_hits.retain(|&ts| ts >= window_start);
+    // Start a new window once the current one has ended.
+    let window_start = now_ms - now_ms % RATE_WINDOW_MS;
+    if state.window_start_ms != window_start {
+        state.window_start_ms = window_start;
+        state.window_count = 0;
+    }
 
     // Check limit (allow burst up to burst_limit above sustained rate).
     let effective_limit = config.requests_per_minute + config.burst_limit;
-    if state.window_hits.len() as u64 >= effective_limit {
+    if state.window_count >= effective_limit {
         return Err(RateLimitError::Exceeded {
             tenant_id: tenant_id.to_string(),
             limit: config.requests_per_minute,
@@ -406,7 +466,7 @@ pub fn check_rate_limit(
     }
 
     // Record this request.
-    state.window_hits.push(now_ms);
+    state.window_count += 1;
     Ok(())
 }
 
@@ -731,6 +791,62 @@ mod tests {
         assert!(evaluate_authz(&claims, &res, Permission::Admin).is_err());
     }
 
+    #[test]
+    fn system_service_is_read_only_on_policy_rules_and_checkpoints() {
+        let claims = TokenClaims {
+            tenant_id: "t1".into(),
+            role: Role::SystemService,
+            scoped_permissions: None,
+        };
+        let res = |resource_type: &str| Resource {
+            tenant_id: "t1".into(),
+            resource_type: resource_type.into(),
+            resource_id: "*".into(),
+        };
+        assert!(evaluate_authz(&claims, &res("event"), Permission::Write).is_ok());
+        assert!(evaluate_authz(&claims, &res("policy_rule"), Permission::Read).is_ok());
+        assert!(evaluate_authz(&claims, &res("policy_rule"), Permission::Write).is_err());
+        assert!(evaluate_authz(&claims, &res("checkpoint"), Permission::Write).is_err());
+        assert!(evaluate_authz(&claims, &res("human_decision"), Permission::Write).is_err());
+    }
+
+    #[test]
+    fn scoped_permissions_never_widen_a_role() {
+        let res = |resource_type: &str| Resource {
+            tenant_id: "t1".into(),
+            resource_type: resource_type.into(),
+            resource_id: "*".into(),
+        };
+        let scoped = |role, perms: &[Permission]| TokenClaims {
+            tenant_id: "t1".into(),
+            role,
+            scoped_permissions: Some(perms.iter().copied().collect()),
+        };
+
+        let viewer = scoped(Role::Reader, &[Permission::Admin, Permission::Write]);
+        assert!(evaluate_authz(&viewer, &res("tenant"), Permission::Admin).is_err());
+        assert!(evaluate_authz(&viewer, &res("run"), Permission::Write).is_err());
+
+        // The `auth.rs` example scope on a service token still cannot
+        // write what the role matrix keeps read-only.
+        let service = scoped(Role::SystemService, &[Permission::Read, Permission::Write]);
+        assert!(evaluate_authz(&service, &res("event"), Permission::Write).is_ok());
+        for resource_type in ["policy_rule", "checkpoint", "human_decision"] {
+            assert!(evaluate_authz(&service, &res(resource_type), Permission::Read).is_ok());
+            assert!(evaluate_authz(&service, &res(resource_type), Permission::Write).is_err());
+        }
+    }
+
+    #[test]
+    fn contributor_cannot_write_policy_rules() {
+        let policy = AuthzPolicy::default();
+        let perms = policy.permissions_for_role_resource(&Role::Contributor, "policy_rule");
+        assert!(perms.contains(&Permission::Read));
+        assert!(!perms.contains(&Permission::Write));
+        let run_perms = policy.permissions_for_role_resource(&Role::Contributor, "run");
+        assert!(run_perms.contains(&Permission::Write));
+    }
+
     #[test]
     fn cross_tenant_authz_denied() {
         let claims = TokenClaims {
@@ -748,8 +864,8 @@ mod tests {
     }
 
     #[test]
-    fn scoped_permissions_override_role_defaults() {
-        // Reader role, but token grants Write explicitly.
+    fn scoped_permissions_intersect_role_defaults() {
+        // Reader role, token scope names only Write.
         let claims = TokenClaims {
             tenant_id: "t1".into(),
             role: Role::Reader,
@@ -760,9 +876,9 @@ mod tests {
             resource_type: "run".into(),
             resource_id: "r1".into(),
         };
-        // Write allowed via scope override.
-        assert!(evaluate_authz(&claims, &res, Permission::Write).is_ok());
-        // Read NOT in scope override — denied.
+        // Write is in scope but not granted to the role — denied.
+        assert!(evaluate_authz(&claims, &res, Permission::Write).is_err());
+        // Read is granted to the role but not in scope — denied.
         assert!(evaluate_authz(&claims, &res, Permission::Read).is_err());
     }
 
@@ -841,7 +957,7 @@ mod tests {
     }
 
     #[test]
-    fn rate_limit_window_slides() {
+    fn rate_limit_window_resets() {
         let config = TenantRateLimitConfig {
             requests_per_minute: 2,
             burst_limit: 0,
@@ -855,8 +971,9 @@ mod tests {
         assert!(check_rate_limit("t1", &config, &mut state, base + 1).is_ok());
         assert!(check_rate_limit("t1", &config, &mut state, base + 2).is_err());
 
-        // Advance past 60s window — old entries expire.
-        assert!(check_rate_limit("t1", &config, &mut state, base + 61_000).is_ok());
+        // The next minute starts a fresh window.
+        assert!(check_rate_limit("t1", &config, &mut state, base + 20_000).is_ok());
+        assert_eq!(state.window_count(), 1);
     }
 
     #[test]
@@ -913,6 +1030,65 @@ mod tests {
         assert!(check_rate_limit("t1", &config, &mut state, during_cooldown).is_err());
     }
 
+    #[test]
+    fn retry_after_runs_to_the_end_of_the_window() {
+        let config = TenantRateLimitConfig {
+            requests_per_minute: 2,
+            burst_limit: 0,
+            quota_bytes: 1024,
+        };
+        let mut state = RateLimitState::new();
+        let base = 100_000u64;
+        assert_eq!(state.retry_after_ms(&config, base), 0);
+        check_rate_limit("t1", &config, &mut state, base).unwrap();
+        check_rate_limit("t1", &config, &mut state, base + 5_000).unwrap();
+        assert!(check_rate_limit("t1", &config, &mut state, base + 10_000).is_err());
+        // The window holding base (60s..120s) ends at 120s.
+        assert_eq!(state.retry_after_ms(&config, base + 10_000), 10_000);
+    }
+
+    #[test]
+    fn retry_after_reports_circuit_cooldown() {
+        let config = TenantRateLimitConfig::default();
+        let mut state = RateLimitState::new();
+        for _ in 0..CIRCUIT_BREAKER_THRESHOLD {
+            state.record_failure();
+        }
+        let now = 200_000u64;
+        let _ = check_rate_limit("t1", &config, &mut state, now);
+        assert_eq!(state.circuit_open_until(), Some(now + CIRCUIT_COOLDOWN_MS));
+        assert_eq!(
+            state.retry_after_ms(&config, now + 1_000),
+            CIRCUIT_COOLDOWN_MS - 1_000
+        );
+    }
+
+    #[test]
+    fn rate_limit_state_round_trips_through_json() {
+        let config = TenantRateLimitConfig::default();
+        let mut state = RateLimitState::new();
+        check_rate_limit("t1", &config, &mut state, 1_000).unwrap();
+        state.record_failure();
+        let json = serde_json::to_string(&state).unwrap();
+        let back: RateLimitState = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.window_count(), 1);
+        assert_eq!(back.consecutive_failures(), 1);
+
+        // State persisted by the old per-request timestamp log.
+        let legacy: RateLimitState =
+            serde_json::from_str(r#"{"window_hits":[1,2,3],"consecutive_failures":2}"#).unwrap();
+        assert_eq!(legacy.window_count(), 0);
+        assert_eq!(legacy.consecutive_failures(), 2);
+
+        let partial: TenantRateLimitConfig =
+            serde_json::from_str(r#"{"requests_per_minute": 600}"#).unwrap();
+        assert_eq!(partial.requests_per_minute, 600);
+        assert_eq!(
+            partial.burst_limit,
+            TenantRateLimitConfig::default().burst_limit
+        );
+    }
+
     #[test]
     fn success_resets_failure_counter() {
         let mut state = RateLimitState::new();
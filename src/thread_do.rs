@@ -118,10 +118,11 @@ impl DurableObject for ThreadManager {
                     .get("x-checkpoint-id")?
                     .ok_or_else(|| Error::RustError("missing x-checkpoint-id header".into()))?;
                 let now = js_sys::Date::now() as u64;
-                let created_at = js_sys::Date::new(&serde_wasm_bindgen::to_value(&now).unwrap_or_default())
-                    .to_iso_string()
-                    .as_string()
-                    .unwrap_or_default();
+                let created_at =
+                    js_sys::Date::new(&serde_wasm_bindgen::to_value(&now).unwrap_or_default())
+                        .to_iso_string()
+                        .as_string()
+                        .unwrap_or_default();
 
                 // PR #132 crr finding (thread_do.rs:44/50): the size was
                 // measured but never compared against the 128 KB limit,
@@ -225,6 +226,10 @@ impl DurableObject for ThreadManager {
                     .unwrap_or_default();
                 Response::from_json(&history)
             }
+            (Method::Post, "/purge") => {
+                self.state.storage().delete_all().await?;
+                Response::empty().map(|r| r.with_status(204))
+            }
             _ => Response::error("not found", 404),
         }
     }
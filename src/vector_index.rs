@@ -76,6 +76,31 @@ impl SemanticIndex {
         Ok(())
     }
 
+    /// Remove vectors by id; unknown ids are ignored.
+    pub async fn delete_by_ids(&self, ids: &[String]) -> Result<()> {
+        let js_ids =
+            serde_wasm_bindgen::to_value(ids).map_err(|e| Error::RustError(e.to_string()))?;
+
+        let delete_fn = js_sys::Reflect::get(&self.index, &JsValue::from_str("deleteByIds"))
+            .map_err(|e| Error::RustError(format!("failed to get deleteByIds method: {:?}", e)))?;
+        let delete_fn: js_sys::Function = delete_fn
+            .dyn_into()
+            .map_err(|_| Error::RustError("deleteByIds is not a function".into()))?;
+
+        let promise = delete_fn
+            .call1(&self.index, &js_ids)
+            .map_err(|e| Error::RustError(format!("failed to call deleteByIds: {:?}", e)))?;
+        let promise: js_sys::Promise = promise
+            .dyn_into()
+            .map_err(|_| Error::RustError("deleteByIds did not return a promise".into()))?;
+
+        wasm_bindgen_futures::JsFuture::from(promise)
+            .await
+            .map_err(|e| Error::RustError(format!("deleteByIds promise failed: {:?}", e)))?;
+
+        Ok(())
+    }
+
     pub async fn query(&self, vector: Vec<f32>, top_k: usize) -> Result<serde_json::Value> {
         // Convert vector to JsValue (which will be a JS array of numbers)
         let js_vector =